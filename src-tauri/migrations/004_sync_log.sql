@@ -0,0 +1,208 @@
+-- CaT4G Migration: Sync Log
+-- Records every local change for the Supabase sync (src-tauri/src/sync)
+
+-- 同期待ちの変更（行ごとに最新の1件）。削除は deleted = 1 の墓標として残す
+CREATE TABLE IF NOT EXISTS sync_log (
+    table_name TEXT NOT NULL,
+    row_key TEXT NOT NULL,
+    deleted INTEGER NOT NULL DEFAULT 0,
+    changed_at TEXT NOT NULL,
+    PRIMARY KEY (table_name, row_key)
+);
+
+-- 同期の状態（テーブルごとのカーソル、リモート変更の適用中フラグ）
+CREATE TABLE IF NOT EXISTS sync_state (
+    key TEXT PRIMARY KEY,
+    value TEXT NOT NULL
+);
+
+-- リモートの変更を書き込む間は sync_state.applying_remote を立てて記録を止める
+
+CREATE TRIGGER IF NOT EXISTS sync_artists_insert AFTER INSERT ON artists
+WHEN NOT EXISTS (SELECT 1 FROM sync_state WHERE key = 'applying_remote')
+BEGIN
+    INSERT OR REPLACE INTO sync_log (table_name, row_key, deleted, changed_at)
+    VALUES ('artists', json_object('id', NEW.id), 0, strftime('%Y-%m-%dT%H:%M:%fZ', 'now'));
+END;
+
+CREATE TRIGGER IF NOT EXISTS sync_artists_update AFTER UPDATE ON artists
+WHEN NOT EXISTS (SELECT 1 FROM sync_state WHERE key = 'applying_remote')
+BEGIN
+    INSERT OR REPLACE INTO sync_log (table_name, row_key, deleted, changed_at)
+    VALUES ('artists', json_object('id', NEW.id), 0, strftime('%Y-%m-%dT%H:%M:%fZ', 'now'));
+END;
+
+CREATE TRIGGER IF NOT EXISTS sync_artists_delete AFTER DELETE ON artists
+WHEN NOT EXISTS (SELECT 1 FROM sync_state WHERE key = 'applying_remote')
+BEGIN
+    INSERT OR REPLACE INTO sync_log (table_name, row_key, deleted, changed_at)
+    VALUES ('artists', json_object('id', OLD.id), 1, strftime('%Y-%m-%dT%H:%M:%fZ', 'now'));
+END;
+
+CREATE TRIGGER IF NOT EXISTS sync_songs_insert AFTER INSERT ON songs
+WHEN NOT EXISTS (SELECT 1 FROM sync_state WHERE key = 'applying_remote')
+BEGIN
+    INSERT OR REPLACE INTO sync_log (table_name, row_key, deleted, changed_at)
+    VALUES ('songs', json_object('id', NEW.id), 0, strftime('%Y-%m-%dT%H:%M:%fZ', 'now'));
+END;
+
+CREATE TRIGGER IF NOT EXISTS sync_songs_update AFTER UPDATE ON songs
+WHEN NOT EXISTS (SELECT 1 FROM sync_state WHERE key = 'applying_remote')
+BEGIN
+    INSERT OR REPLACE INTO sync_log (table_name, row_key, deleted, changed_at)
+    VALUES ('songs', json_object('id', NEW.id), 0, strftime('%Y-%m-%dT%H:%M:%fZ', 'now'));
+END;
+
+CREATE TRIGGER IF NOT EXISTS sync_songs_delete AFTER DELETE ON songs
+WHEN NOT EXISTS (SELECT 1 FROM sync_state WHERE key = 'applying_remote')
+BEGIN
+    INSERT OR REPLACE INTO sync_log (table_name, row_key, deleted, changed_at)
+    VALUES ('songs', json_object('id', OLD.id), 1, strftime('%Y-%m-%dT%H:%M:%fZ', 'now'));
+END;
+
+CREATE TRIGGER IF NOT EXISTS sync_sections_insert AFTER INSERT ON sections
+WHEN NOT EXISTS (SELECT 1 FROM sync_state WHERE key = 'applying_remote')
+BEGIN
+    INSERT OR REPLACE INTO sync_log (table_name, row_key, deleted, changed_at)
+    VALUES ('sections', json_object('id', NEW.id), 0, strftime('%Y-%m-%dT%H:%M:%fZ', 'now'));
+END;
+
+CREATE TRIGGER IF NOT EXISTS sync_sections_update AFTER UPDATE ON sections
+WHEN NOT EXISTS (SELECT 1 FROM sync_state WHERE key = 'applying_remote')
+BEGIN
+    INSERT OR REPLACE INTO sync_log (table_name, row_key, deleted, changed_at)
+    VALUES ('sections', json_object('id', NEW.id), 0, strftime('%Y-%m-%dT%H:%M:%fZ', 'now'));
+END;
+
+CREATE TRIGGER IF NOT EXISTS sync_sections_delete AFTER DELETE ON sections
+WHEN NOT EXISTS (SELECT 1 FROM sync_state WHERE key = 'applying_remote')
+BEGIN
+    INSERT OR REPLACE INTO sync_log (table_name, row_key, deleted, changed_at)
+    VALUES ('sections', json_object('id', OLD.id), 1, strftime('%Y-%m-%dT%H:%M:%fZ', 'now'));
+END;
+
+CREATE TRIGGER IF NOT EXISTS sync_lines_insert AFTER INSERT ON lines
+WHEN NOT EXISTS (SELECT 1 FROM sync_state WHERE key = 'applying_remote')
+BEGIN
+    INSERT OR REPLACE INTO sync_log (table_name, row_key, deleted, changed_at)
+    VALUES ('lines', json_object('id', NEW.id), 0, strftime('%Y-%m-%dT%H:%M:%fZ', 'now'));
+END;
+
+CREATE TRIGGER IF NOT EXISTS sync_lines_update AFTER UPDATE ON lines
+WHEN NOT EXISTS (SELECT 1 FROM sync_state WHERE key = 'applying_remote')
+BEGIN
+    INSERT OR REPLACE INTO sync_log (table_name, row_key, deleted, changed_at)
+    VALUES ('lines', json_object('id', NEW.id), 0, strftime('%Y-%m-%dT%H:%M:%fZ', 'now'));
+END;
+
+CREATE TRIGGER IF NOT EXISTS sync_lines_delete AFTER DELETE ON lines
+WHEN NOT EXISTS (SELECT 1 FROM sync_state WHERE key = 'applying_remote')
+BEGIN
+    INSERT OR REPLACE INTO sync_log (table_name, row_key, deleted, changed_at)
+    VALUES ('lines', json_object('id', OLD.id), 1, strftime('%Y-%m-%dT%H:%M:%fZ', 'now'));
+END;
+
+CREATE TRIGGER IF NOT EXISTS sync_tags_insert AFTER INSERT ON tags
+WHEN NOT EXISTS (SELECT 1 FROM sync_state WHERE key = 'applying_remote')
+BEGIN
+    INSERT OR REPLACE INTO sync_log (table_name, row_key, deleted, changed_at)
+    VALUES ('tags', json_object('id', NEW.id), 0, strftime('%Y-%m-%dT%H:%M:%fZ', 'now'));
+END;
+
+CREATE TRIGGER IF NOT EXISTS sync_tags_update AFTER UPDATE ON tags
+WHEN NOT EXISTS (SELECT 1 FROM sync_state WHERE key = 'applying_remote')
+BEGIN
+    INSERT OR REPLACE INTO sync_log (table_name, row_key, deleted, changed_at)
+    VALUES ('tags', json_object('id', NEW.id), 0, strftime('%Y-%m-%dT%H:%M:%fZ', 'now'));
+END;
+
+CREATE TRIGGER IF NOT EXISTS sync_tags_delete AFTER DELETE ON tags
+WHEN NOT EXISTS (SELECT 1 FROM sync_state WHERE key = 'applying_remote')
+BEGIN
+    INSERT OR REPLACE INTO sync_log (table_name, row_key, deleted, changed_at)
+    VALUES ('tags', json_object('id', OLD.id), 1, strftime('%Y-%m-%dT%H:%M:%fZ', 'now'));
+END;
+
+CREATE TRIGGER IF NOT EXISTS sync_song_tags_insert AFTER INSERT ON song_tags
+WHEN NOT EXISTS (SELECT 1 FROM sync_state WHERE key = 'applying_remote')
+BEGIN
+    INSERT OR REPLACE INTO sync_log (table_name, row_key, deleted, changed_at)
+    VALUES ('song_tags', json_object('song_id', NEW.song_id, 'tag_id', NEW.tag_id), 0, strftime('%Y-%m-%dT%H:%M:%fZ', 'now'));
+END;
+
+CREATE TRIGGER IF NOT EXISTS sync_song_tags_update AFTER UPDATE ON song_tags
+WHEN NOT EXISTS (SELECT 1 FROM sync_state WHERE key = 'applying_remote')
+BEGIN
+    INSERT OR REPLACE INTO sync_log (table_name, row_key, deleted, changed_at)
+    VALUES ('song_tags', json_object('song_id', NEW.song_id, 'tag_id', NEW.tag_id), 0, strftime('%Y-%m-%dT%H:%M:%fZ', 'now'));
+END;
+
+CREATE TRIGGER IF NOT EXISTS sync_song_tags_delete AFTER DELETE ON song_tags
+WHEN NOT EXISTS (SELECT 1 FROM sync_state WHERE key = 'applying_remote')
+BEGIN
+    INSERT OR REPLACE INTO sync_log (table_name, row_key, deleted, changed_at)
+    VALUES ('song_tags', json_object('song_id', OLD.song_id, 'tag_id', OLD.tag_id), 1, strftime('%Y-%m-%dT%H:%M:%fZ', 'now'));
+END;
+
+CREATE TRIGGER IF NOT EXISTS sync_playlists_insert AFTER INSERT ON playlists
+WHEN NOT EXISTS (SELECT 1 FROM sync_state WHERE key = 'applying_remote')
+BEGIN
+    INSERT OR REPLACE INTO sync_log (table_name, row_key, deleted, changed_at)
+    VALUES ('playlists', json_object('id', NEW.id), 0, strftime('%Y-%m-%dT%H:%M:%fZ', 'now'));
+END;
+
+CREATE TRIGGER IF NOT EXISTS sync_playlists_update AFTER UPDATE ON playlists
+WHEN NOT EXISTS (SELECT 1 FROM sync_state WHERE key = 'applying_remote')
+BEGIN
+    INSERT OR REPLACE INTO sync_log (table_name, row_key, deleted, changed_at)
+    VALUES ('playlists', json_object('id', NEW.id), 0, strftime('%Y-%m-%dT%H:%M:%fZ', 'now'));
+END;
+
+CREATE TRIGGER IF NOT EXISTS sync_playlists_delete AFTER DELETE ON playlists
+WHEN NOT EXISTS (SELECT 1 FROM sync_state WHERE key = 'applying_remote')
+BEGIN
+    INSERT OR REPLACE INTO sync_log (table_name, row_key, deleted, changed_at)
+    VALUES ('playlists', json_object('id', OLD.id), 1, strftime('%Y-%m-%dT%H:%M:%fZ', 'now'));
+END;
+
+CREATE TRIGGER IF NOT EXISTS sync_playlist_songs_insert AFTER INSERT ON playlist_songs
+WHEN NOT EXISTS (SELECT 1 FROM sync_state WHERE key = 'applying_remote')
+BEGIN
+    INSERT OR REPLACE INTO sync_log (table_name, row_key, deleted, changed_at)
+    VALUES ('playlist_songs', json_object('playlist_id', NEW.playlist_id, 'song_id', NEW.song_id), 0, strftime('%Y-%m-%dT%H:%M:%fZ', 'now'));
+END;
+
+CREATE TRIGGER IF NOT EXISTS sync_playlist_songs_update AFTER UPDATE ON playlist_songs
+WHEN NOT EXISTS (SELECT 1 FROM sync_state WHERE key = 'applying_remote')
+BEGIN
+    INSERT OR REPLACE INTO sync_log (table_name, row_key, deleted, changed_at)
+    VALUES ('playlist_songs', json_object('playlist_id', NEW.playlist_id, 'song_id', NEW.song_id), 0, strftime('%Y-%m-%dT%H:%M:%fZ', 'now'));
+END;
+
+CREATE TRIGGER IF NOT EXISTS sync_playlist_songs_delete AFTER DELETE ON playlist_songs
+WHEN NOT EXISTS (SELECT 1 FROM sync_state WHERE key = 'applying_remote')
+BEGIN
+    INSERT OR REPLACE INTO sync_log (table_name, row_key, deleted, changed_at)
+    VALUES ('playlist_songs', json_object('playlist_id', OLD.playlist_id, 'song_id', OLD.song_id), 1, strftime('%Y-%m-%dT%H:%M:%fZ', 'now'));
+END;
+
+CREATE TRIGGER IF NOT EXISTS sync_chord_preferences_insert AFTER INSERT ON chord_preferences
+WHEN NOT EXISTS (SELECT 1 FROM sync_state WHERE key = 'applying_remote')
+BEGIN
+    INSERT OR REPLACE INTO sync_log (table_name, row_key, deleted, changed_at)
+    VALUES ('chord_preferences', json_object('id', NEW.id), 0, strftime('%Y-%m-%dT%H:%M:%fZ', 'now'));
+END;
+
+CREATE TRIGGER IF NOT EXISTS sync_chord_preferences_update AFTER UPDATE ON chord_preferences
+WHEN NOT EXISTS (SELECT 1 FROM sync_state WHERE key = 'applying_remote')
+BEGIN
+    INSERT OR REPLACE INTO sync_log (table_name, row_key, deleted, changed_at)
+    VALUES ('chord_preferences', json_object('id', NEW.id), 0, strftime('%Y-%m-%dT%H:%M:%fZ', 'now'));
+END;
+
+CREATE TRIGGER IF NOT EXISTS sync_chord_preferences_delete AFTER DELETE ON chord_preferences
+WHEN NOT EXISTS (SELECT 1 FROM sync_state WHERE key = 'applying_remote')
+BEGIN
+    INSERT OR REPLACE INTO sync_log (table_name, row_key, deleted, changed_at)
+    VALUES ('chord_preferences', json_object('id', OLD.id), 1, strftime('%Y-%m-%dT%H:%M:%fZ', 'now'));
+END;
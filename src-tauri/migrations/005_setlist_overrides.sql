@@ -0,0 +1,20 @@
+-- CaT4G Migration: Setlist Overrides
+-- Adds per-entry performance settings and set breaks to playlist_songs
+
+-- 本番での演奏キー（null = 曲のキー）
+ALTER TABLE playlist_songs ADD COLUMN key_override TEXT DEFAULT NULL;
+
+-- 本番でのカポ位置（null = 曲のカポ）
+ALTER TABLE playlist_songs ADD COLUMN capo_override INTEGER DEFAULT NULL;
+
+-- 本番でのBPM（null = 曲のBPM）
+ALTER TABLE playlist_songs ADD COLUMN bpm_override INTEGER DEFAULT NULL;
+
+-- この曲だけのメモ（null = 曲のメモ）
+ALTER TABLE playlist_songs ADD COLUMN notes TEXT DEFAULT NULL;
+
+-- この曲から新しいセットを始める場合のセット名（空文字 = 名前なし）
+ALTER TABLE playlist_songs ADD COLUMN set_break TEXT DEFAULT NULL;
+
+-- セット前の休憩時間（分）
+ALTER TABLE playlist_songs ADD COLUMN break_minutes INTEGER DEFAULT NULL;
@@ -0,0 +1,19 @@
+-- CaT4G Migration: Practice Log
+-- Adds practice sessions and performance dates for setlists
+
+-- 練習記録（開始・終了時刻、到達テンポ、自己評価 1-5、メモ）
+CREATE TABLE IF NOT EXISTS practice_sessions (
+    id TEXT PRIMARY KEY,
+    song_id TEXT NOT NULL REFERENCES songs(id) ON DELETE CASCADE,
+    started_at TEXT NOT NULL,
+    ended_at TEXT NOT NULL,
+    tempo INTEGER DEFAULT NULL,
+    rating INTEGER DEFAULT NULL CHECK (rating BETWEEN 1 AND 5),
+    notes TEXT DEFAULT NULL,
+    created_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+CREATE INDEX IF NOT EXISTS idx_practice_sessions_song ON practice_sessions(song_id, started_at);
+
+-- 本番の日付（YYYY-MM-DD、null = 予定なし）
+ALTER TABLE playlists ADD COLUMN performance_date TEXT DEFAULT NULL;
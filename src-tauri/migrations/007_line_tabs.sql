@@ -0,0 +1,5 @@
+-- CaT4G Migration: Line Tabs
+-- Stores tablature as its own line, where it sat among the lyric lines
+
+-- タブ譜の行（改行区切り）。タブ譜の行は歌詞・コードを持たない
+ALTER TABLE lines ADD COLUMN tab TEXT DEFAULT NULL;
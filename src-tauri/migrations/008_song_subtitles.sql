@@ -0,0 +1,7 @@
+-- CaT4G Migration: Song Subtitles
+-- Keeps what normalization splits off a title ("Live ver.", drama/CM tie-ins)
+
+-- サブタイトル（"Live ver." など）
+ALTER TABLE songs ADD COLUMN subtitle TEXT DEFAULT NULL;
+-- タイアップ（ドラマ・映画・CM など）
+ALTER TABLE songs ADD COLUMN tie_in TEXT DEFAULT NULL;
@@ -0,0 +1,138 @@
+//! Render a chord sheet for a specific instrument and tuning
+//!
+//! - Guitar in a uniformly lowered tuning (half-step down etc.): chord names
+//!   are relabelled as the shapes the player fingers
+//! - Instruments that do not use a capo (ukulele, bass): chords are moved to
+//!   sounding pitch and the capo is dropped
+//! - Bass: every chord becomes its bass note
+
+use crate::parsers::FetchedChordSheet;
+use crate::theory::chord::Chord;
+use crate::theory::instrument::Instrument;
+use crate::theory::voicing::{find_voicings, Voicing};
+use crate::theory::{note_name, transpose_chord_name, transpose_sheet};
+use serde::{Deserialize, Serialize};
+
+/// Voicings offered per chord
+const VOICINGS_PER_CHORD: usize = 3;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArrangedSheet {
+    pub instrument: Instrument,
+    pub sheet: FetchedChordSheet,
+    /// Semitones applied to the original chord names
+    pub transpose: i32,
+    pub voicings: Vec<ChordVoicings>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChordVoicings {
+    /// Chord name as it appears in the arranged sheet
+    pub chord: String,
+    pub voicings: Vec<Voicing>,
+}
+
+pub fn arrange_sheet(sheet: &FetchedChordSheet, instrument: &Instrument) -> ArrangedSheet {
+    let capo = sheet.capo.unwrap_or(0);
+    let capo_shift = if instrument.uses_capo { 0 } else { capo };
+    // Tuned down a semitone, an E shape sounds Eb: read shapes one semitone up
+    let shape_shift = -instrument.uniform_offset().unwrap_or(0);
+
+    // Sounding pitch relative to the nut (or capo, if the instrument keeps it)
+    let sounding = transpose_sheet(sheet, capo_shift);
+
+    let mut voicings: Vec<ChordVoicings> = Vec::new();
+    let mut arranged = sounding.clone();
+    arranged.key = sheet.key.clone();
+    if !instrument.uses_capo {
+        arranged.capo = None;
+    }
+
+    for (section, arranged_section) in sounding.sections.iter().zip(&mut arranged.sections) {
+        for (line, arranged_line) in section.lines.iter().zip(&mut arranged_section.lines) {
+            for (chord, arranged_chord) in line.chords.iter().zip(&mut arranged_line.chords) {
+                let Some(parsed) = Chord::parse(&chord.chord) else {
+                    continue;
+                };
+
+                arranged_chord.chord = if instrument.root_only {
+                    note_name(parsed.bass_note(), parsed.prefer_flat).to_string()
+                } else {
+                    transpose_chord_name(&chord.chord, shape_shift)
+                };
+
+                if !voicings.iter().any(|v| v.chord == arranged_chord.chord) {
+                    voicings.push(ChordVoicings {
+                        chord: arranged_chord.chord.clone(),
+                        voicings: find_voicings(&parsed, instrument, VOICINGS_PER_CHORD),
+                    });
+                }
+            }
+        }
+    }
+
+    ArrangedSheet {
+        instrument: instrument.clone(),
+        sheet: arranged,
+        transpose: capo_shift + shape_shift,
+        voicings,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::sheet_with;
+
+    fn chord_names(sheet: &FetchedChordSheet) -> Vec<String> {
+        sheet.sections[0].lines[0]
+            .chords
+            .iter()
+            .map(|c| c.chord.clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_standard_guitar_is_unchanged() {
+        let mut sheet = sheet_with(&["G", "D/F#", "Em"]);
+        sheet.capo = Some(2);
+        let result = arrange_sheet(&sheet, &Instrument::standard_guitar());
+        assert_eq!(chord_names(&result.sheet), vec!["G", "D/F#", "Em"]);
+        assert_eq!(result.sheet.capo, Some(2));
+        assert_eq!(result.transpose, 0);
+        assert_eq!(result.voicings.len(), 3);
+    }
+
+    #[test]
+    fn test_half_step_down_relabels_shapes() {
+        let sheet = sheet_with(&["Eb", "Ab"]);
+        let instrument = Instrument::by_id("half-step-down").unwrap();
+        let result = arrange_sheet(&sheet, &instrument);
+        assert_eq!(chord_names(&result.sheet), vec!["E", "A"]);
+        // The E shape on Eb tuning is the familiar open E
+        assert_eq!(
+            result.voicings[0].voicings[0].frets,
+            vec![Some(0), Some(0), Some(1), Some(2), Some(2), Some(0)]
+        );
+    }
+
+    #[test]
+    fn test_ukulele_drops_capo() {
+        let mut sheet = sheet_with(&["G", "C"]);
+        sheet.capo = Some(2);
+        sheet.key = Some("A".to_string());
+        let instrument = Instrument::by_id("ukulele").unwrap();
+        let result = arrange_sheet(&sheet, &instrument);
+        assert_eq!(chord_names(&result.sheet), vec!["A", "D"]);
+        assert_eq!(result.sheet.capo, None);
+        assert_eq!(result.sheet.key, Some("A".to_string()));
+    }
+
+    #[test]
+    fn test_bass_uses_root_notes() {
+        let sheet = sheet_with(&["Am7", "F/C", "G"]);
+        let instrument = Instrument::by_id("bass").unwrap();
+        let result = arrange_sheet(&sheet, &instrument);
+        assert_eq!(chord_names(&result.sheet), vec!["A", "C", "G"]);
+    }
+}
@@ -0,0 +1,223 @@
+//! Capo suggestion engine
+//!
+//! For each capo position the chord set is re-expressed as the shapes that
+//! keep the sounding key, then scored by the easiest voicing of each shape.
+//! On a tuning moved down as a whole the shapes are named as fretted, the way
+//! `arrange` names them.
+//! Chords are weighted by how often they appear, so a rare barre chord costs
+//! less than one on every line.
+
+use crate::parsers::FetchedChordSheet;
+use crate::theory::chord::Chord;
+use crate::theory::instrument::Instrument;
+use crate::theory::transpose_chord_name;
+use crate::theory::voicing::{find_voicings, VoicingDifficulty};
+use serde::{Deserialize, Serialize};
+
+/// Capo positions considered (0 = no capo)
+const MAX_CAPO: i32 = 9;
+
+/// Score assigned to a shape with no playable voicing
+const UNPLAYABLE_SCORE: u32 = 150;
+
+/// Extra cost per capo fret (high capos thin out the sound)
+const CAPO_FRET_PENALTY: u32 = 2;
+
+/// Discount for shapes that ring open strings
+const OPEN_SHAPE_BONUS: u32 = 10;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CapoSuggestion {
+    pub capo: i32,
+    /// Semitones applied to the sheet's chord names for this capo
+    pub transpose: i32,
+    /// Key of the shapes at this capo (the sounding key does not change)
+    pub shape_key: Option<String>,
+    /// Weighted average effort; lower is easier
+    pub score: u32,
+    pub open_chords: usize,
+    pub barre_chords: usize,
+    pub chords: Vec<CapoChord>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CapoChord {
+    pub original: String,
+    pub shape: String,
+    pub occurrences: usize,
+    pub difficulty: Option<VoicingDifficulty>,
+}
+
+/// Rank capo positions 0-9 for a sheet, easiest first
+pub fn suggest_capo(sheet: &FetchedChordSheet, instrument: &Instrument) -> Vec<CapoSuggestion> {
+    let current_capo = sheet.capo.unwrap_or(0);
+    // Tuned down a semitone, an E shape sounds Eb: name shapes one semitone up
+    let shape_shift = -instrument.uniform_offset().unwrap_or(0);
+
+    // Distinct chords with their occurrence counts, in order of appearance
+    let mut chord_counts: Vec<(String, usize)> = Vec::new();
+    for chord in sheet
+        .sections
+        .iter()
+        .flat_map(|s| &s.lines)
+        .flat_map(|l| &l.chords)
+    {
+        if Chord::parse(&chord.chord).is_none() {
+            continue;
+        }
+        match chord_counts
+            .iter_mut()
+            .find(|(name, _)| *name == chord.chord)
+        {
+            Some((_, count)) => *count += 1,
+            None => chord_counts.push((chord.chord.clone(), 1)),
+        }
+    }
+
+    let total: usize = chord_counts.iter().map(|(_, count)| count).sum();
+    let mut suggestions: Vec<CapoSuggestion> = (0..=MAX_CAPO)
+        .map(|capo| {
+            // Pitch above the capo, and the shape fretted for it
+            let sounding = current_capo - capo;
+            let transpose = sounding + shape_shift;
+            let mut weighted = 0u32;
+            let mut open_chords = 0;
+            let mut barre_chords = 0;
+            let mut chords = Vec::new();
+
+            for (name, occurrences) in &chord_counts {
+                let shape = transpose_chord_name(name, transpose);
+                // Voiced at pitch on the instrument's own strings, which frets the shape
+                let best = Chord::parse(&transpose_chord_name(name, sounding))
+                    .and_then(|c| find_voicings(&c, instrument, 1).into_iter().next());
+
+                let chord_score = match &best {
+                    Some(voicing) if voicing.is_open() => {
+                        open_chords += 1;
+                        voicing.score.saturating_sub(OPEN_SHAPE_BONUS)
+                    }
+                    Some(voicing) => {
+                        if voicing.is_barre() {
+                            barre_chords += 1;
+                        }
+                        voicing.score
+                    }
+                    None => UNPLAYABLE_SCORE,
+                };
+                weighted += chord_score * *occurrences as u32;
+
+                chords.push(CapoChord {
+                    original: name.clone(),
+                    shape,
+                    occurrences: *occurrences,
+                    difficulty: best.map(|v| v.difficulty),
+                });
+            }
+
+            let average = if total > 0 {
+                weighted / total as u32
+            } else {
+                0
+            };
+            CapoSuggestion {
+                capo,
+                transpose,
+                shape_key: sheet
+                    .key
+                    .as_deref()
+                    .map(|key| transpose_chord_name(key, transpose)),
+                score: average + capo as u32 * CAPO_FRET_PENALTY,
+                open_chords,
+                barre_chords,
+                chords,
+            }
+        })
+        .collect();
+
+    suggestions.sort_by_key(|s| (s.score, s.capo));
+    suggestions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::{FetchedChord, FetchedLine, FetchedSection};
+
+    fn sheet_with(chords: &[&str], capo: Option<i32>) -> FetchedChordSheet {
+        let mut sheet = FetchedChordSheet::new(String::new());
+        sheet.capo = capo;
+        let mut section = FetchedSection::new("Main");
+        section.lines.push(FetchedLine::with_chords(
+            "",
+            chords
+                .iter()
+                .enumerate()
+                .map(|(i, c)| FetchedChord::new(c, i as i32 * 4))
+                .collect(),
+        ));
+        sheet.sections.push(section);
+        sheet
+    }
+
+    #[test]
+    fn test_barre_progression_prefers_open_shapes() {
+        // Ab Fm Db Eb (no capo) is G Em C D with capo 1
+        let sheet = sheet_with(&["Ab", "Fm", "Db", "Eb"], None);
+        let suggestions = suggest_capo(&sheet, &Instrument::standard_guitar());
+        assert_eq!(suggestions.len(), 10);
+        assert_eq!(suggestions[0].capo, 1);
+        let shapes: Vec<&str> = suggestions[0]
+            .chords
+            .iter()
+            .map(|c| c.shape.as_str())
+            .collect();
+        assert_eq!(shapes, vec!["G", "Em", "C", "D"]);
+        assert_eq!(suggestions[0].barre_chords, 0);
+    }
+
+    #[test]
+    fn test_preserves_sounding_key_with_existing_capo() {
+        // G shapes with capo 2 sound in A; at capo 0 they become A shapes
+        let mut sheet = sheet_with(&["G", "C", "D"], Some(2));
+        sheet.key = Some("G".to_string());
+        let suggestions = suggest_capo(&sheet, &Instrument::standard_guitar());
+        let no_capo = suggestions.iter().find(|s| s.capo == 0).unwrap();
+        assert_eq!(no_capo.transpose, 2);
+        assert_eq!(no_capo.shape_key, Some("A".to_string()));
+        let shapes: Vec<&str> = no_capo.chords.iter().map(|c| c.shape.as_str()).collect();
+        assert_eq!(shapes, vec!["A", "D", "E"]);
+    }
+
+    #[test]
+    fn test_half_step_down_names_fretted_shapes() {
+        // Eb Ab Bb Cm is D G A Bm with capo 1, or capo 2 tuned down a semitone
+        let sheet = sheet_with(&["Eb", "Ab", "Bb", "Cm"], None);
+        let shapes = |suggestion: &CapoSuggestion| {
+            suggestion
+                .chords
+                .iter()
+                .map(|c| c.shape.clone())
+                .collect::<Vec<_>>()
+        };
+        let standard = suggest_capo(&sheet, &Instrument::standard_guitar());
+        assert_eq!(standard[0].capo, 1);
+        assert_eq!(shapes(&standard[0]), vec!["D", "G", "A", "Bm"]);
+
+        let half_step_down = Instrument::by_id("half-step-down").unwrap();
+        let suggestions = suggest_capo(&sheet, &half_step_down);
+        assert_eq!(suggestions[0].capo, 2);
+        assert_eq!(suggestions[0].transpose, -1);
+        assert_eq!(shapes(&suggestions[0]), vec!["D", "G", "A", "Bm"]);
+        let no_capo = suggestions.iter().find(|s| s.capo == 0).unwrap();
+        assert_eq!(shapes(no_capo), vec!["E", "A", "B", "C#m"]);
+    }
+
+    #[test]
+    fn test_occurrences_are_counted() {
+        let sheet = sheet_with(&["C", "G", "C", "N.C."], None);
+        let suggestions = suggest_capo(&sheet, &Instrument::standard_guitar());
+        let c = &suggestions[0].chords[0];
+        assert_eq!(c.occurrences, 2);
+        assert_eq!(suggestions[0].chords.len(), 2);
+    }
+}
@@ -0,0 +1,308 @@
+//! Song difficulty rating
+//!
+//! Each factor adds points; the total maps onto a 1-5 score, which in turn
+//! maps onto the `songs.difficulty` levels (1-2 beginner, 3 intermediate,
+//! 4-5 advanced). Chords are judged on a guitar in the sheet's tuning
+//! (standard when it names no guitar tuning). Under a capo the written chords
+//! are the shapes played above it, so they are judged as if the capo were the
+//! nut; a chord with no open-position shape among its easier voicings counts
+//! as a barre chord.
+
+pub use crate::parsers::DifficultyLevel;
+use crate::parsers::{tab, FetchedChordSheet};
+use crate::theory::chord::{Chord, Quality};
+use crate::theory::instrument::{Instrument, InstrumentFamily};
+use crate::theory::voicing::find_voicings;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::sync::LazyLock;
+
+/// Voicings searched for an open-position shape
+const OPEN_SHAPE_SEARCH: usize = 10;
+
+/// Section names that mark tab or lead-guitar parts
+static SOLO_SECTION_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)solo|tab|riff|ソロ|リフ|タブ").unwrap());
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DifficultyFactor {
+    DistinctChords,
+    BarreChords,
+    ChangeDensity,
+    Extensions,
+    TabSections,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DifficultyReason {
+    pub factor: DifficultyFactor,
+    pub points: u8,
+    pub detail: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DifficultyRating {
+    /// 1 (easiest) to 5
+    pub score: u8,
+    pub level: DifficultyLevel,
+    /// Factors that raised the score, largest first
+    pub reasons: Vec<DifficultyReason>,
+}
+
+/// A sheet as returned to the frontend: the sheet's fields plus `difficulty`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RatedSheet {
+    #[serde(flatten)]
+    pub sheet: FetchedChordSheet,
+    pub difficulty: DifficultyRating,
+}
+
+impl RatedSheet {
+    pub fn new(sheet: FetchedChordSheet) -> Self {
+        let difficulty = rate_difficulty(&sheet);
+        Self { sheet, difficulty }
+    }
+}
+
+pub fn rate_difficulty(sheet: &FetchedChordSheet) -> DifficultyRating {
+    let guitar = sheet
+        .tuning
+        .as_deref()
+        .and_then(Instrument::by_id)
+        .filter(|instrument| instrument.family == InstrumentFamily::Guitar)
+        .unwrap_or_else(Instrument::standard_guitar);
+    let mut reasons = Vec::new();
+
+    let mut distinct: Vec<&str> = Vec::new();
+    for chord in sheet
+        .sections
+        .iter()
+        .flat_map(|s| &s.lines)
+        .flat_map(|l| &l.chords)
+    {
+        if !distinct.contains(&chord.chord.as_str()) && Chord::parse(&chord.chord).is_some() {
+            distinct.push(&chord.chord);
+        }
+    }
+    let parsed: Vec<(&str, Chord)> = distinct
+        .iter()
+        .filter_map(|name| Chord::parse(name).map(|chord| (*name, chord)))
+        .collect();
+
+    let points = match distinct.len() {
+        0..=4 => 0,
+        5..=7 => 1,
+        8..=11 => 2,
+        _ => 3,
+    };
+    if points > 0 {
+        reasons.push(DifficultyReason {
+            factor: DifficultyFactor::DistinctChords,
+            points,
+            detail: format!("{} distinct chords", distinct.len()),
+        });
+    }
+
+    let barres: Vec<&str> = parsed
+        .iter()
+        .filter(|(_, chord)| {
+            !find_voicings(chord, &guitar, OPEN_SHAPE_SEARCH)
+                .iter()
+                .any(|voicing| voicing.is_open() && !voicing.is_barre())
+        })
+        .map(|(name, _)| *name)
+        .collect();
+    let points = match barres.len() {
+        0 => 0,
+        1..=2 => 1,
+        _ => 2,
+    };
+    if points > 0 {
+        reasons.push(DifficultyReason {
+            factor: DifficultyFactor::BarreChords,
+            points,
+            detail: format!("Barre chords: {}", barres.join(", ")),
+        });
+    }
+
+    let chord_lines: Vec<usize> = sheet
+        .sections
+        .iter()
+        .flat_map(|s| &s.lines)
+        .map(|l| l.chords.len())
+        .filter(|&count| count > 0)
+        .collect();
+    if !chord_lines.is_empty() {
+        let average = chord_lines.iter().sum::<usize>() as f32 / chord_lines.len() as f32;
+        let points = if average > 3.5 {
+            2
+        } else if average > 2.0 {
+            1
+        } else {
+            0
+        };
+        if points > 0 {
+            reasons.push(DifficultyReason {
+                factor: DifficultyFactor::ChangeDensity,
+                points,
+                detail: format!("{:.1} chord changes per line", average),
+            });
+        }
+    }
+
+    let unusual: Vec<&str> = parsed
+        .iter()
+        .filter(|(_, chord)| {
+            chord.has_tensions()
+                || !chord.alterations.is_empty()
+                || matches!(chord.quality, Quality::Diminished | Quality::Augmented)
+        })
+        .map(|(name, _)| *name)
+        .collect();
+    let points = match unusual.len() {
+        0 => 0,
+        1..=3 => 1,
+        _ => 2,
+    };
+    if points > 0 {
+        reasons.push(DifficultyReason {
+            factor: DifficultyFactor::Extensions,
+            points,
+            detail: format!("Extended or altered chords: {}", unusual.join(", ")),
+        });
+    }
+
+    let tab_sections: Vec<&str> = sheet
+        .sections
+        .iter()
+        .filter(|s| {
+            SOLO_SECTION_RE.is_match(&s.name)
+                || s.lines
+                    .iter()
+                    .any(|l| l.tab.is_some() || tab::is_tab_line(&l.lyrics))
+        })
+        .map(|s| s.name.as_str())
+        .collect();
+    if !tab_sections.is_empty() {
+        reasons.push(DifficultyReason {
+            factor: DifficultyFactor::TabSections,
+            points: 1,
+            detail: format!("Tab or solo sections: {}", tab_sections.join(", ")),
+        });
+    }
+
+    let total: u8 = reasons.iter().map(|r| r.points).sum();
+    let score = (1 + total / 2).min(5);
+    reasons.sort_by_key(|r| std::cmp::Reverse(r.points));
+
+    DifficultyRating {
+        score,
+        level: match score {
+            1..=2 => DifficultyLevel::Beginner,
+            3 => DifficultyLevel::Intermediate,
+            _ => DifficultyLevel::Advanced,
+        },
+        reasons,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::{FetchedChord, FetchedLine, FetchedSection};
+
+    fn sheet_with(lines: &[&[&str]]) -> FetchedChordSheet {
+        let mut sheet = FetchedChordSheet::new(String::new());
+        let mut section = FetchedSection::new("Aメロ");
+        for chords in lines {
+            section.lines.push(FetchedLine::with_chords(
+                "歌詞",
+                chords
+                    .iter()
+                    .enumerate()
+                    .map(|(i, c)| FetchedChord::new(c, i as i32 * 2))
+                    .collect(),
+            ));
+        }
+        sheet.sections.push(section);
+        sheet
+    }
+
+    #[test]
+    fn test_open_chords_are_beginner() {
+        let sheet = sheet_with(&[&["G", "D"], &["Em", "C"], &["G", "D"]]);
+        let rating = rate_difficulty(&sheet);
+        assert_eq!(rating.score, 1);
+        assert_eq!(rating.level, DifficultyLevel::Beginner);
+        assert!(rating.reasons.is_empty());
+    }
+
+    #[test]
+    fn test_barre_chords_are_reported() {
+        let sheet = sheet_with(&[&["F", "Fm"], &["G", "C"]]);
+        let rating = rate_difficulty(&sheet);
+        assert_eq!(rating.reasons[0].factor, DifficultyFactor::BarreChords);
+        assert_eq!(rating.reasons[0].detail, "Barre chords: F, Fm");
+    }
+
+    #[test]
+    fn test_barres_follow_the_tuning() {
+        let mut sheet = sheet_with(&[&["Eb", "Ab", "Db"]]);
+        let rating = rate_difficulty(&sheet);
+        assert!(rating
+            .reasons
+            .iter()
+            .any(|r| r.factor == DifficultyFactor::BarreChords));
+
+        // Tuned down a half step these are the open E, A and D shapes
+        sheet.tuning = Some("half-step-down".to_string());
+        let rating = rate_difficulty(&sheet);
+        assert!(!rating
+            .reasons
+            .iter()
+            .any(|r| r.factor == DifficultyFactor::BarreChords));
+    }
+
+    #[test]
+    fn test_busy_jazzy_sheet_is_advanced() {
+        let sheet = sheet_with(&[
+            &["FM7", "Em7", "A7", "Dm7", "G7"],
+            &["Bbm6", "Bm7-5", "E7(b9)", "Am9", "Abdim7"],
+            &["Gm7", "C13", "F#m7", "B7", "Caug"],
+        ]);
+        let rating = rate_difficulty(&sheet);
+        assert_eq!(rating.score, 5);
+        assert_eq!(rating.level, DifficultyLevel::Advanced);
+        assert_eq!(rating.reasons[0].factor, DifficultyFactor::DistinctChords);
+        assert!(rating
+            .reasons
+            .iter()
+            .any(|r| r.factor == DifficultyFactor::BarreChords));
+        assert!(rating
+            .reasons
+            .iter()
+            .any(|r| r.factor == DifficultyFactor::ChangeDensity));
+    }
+
+    #[test]
+    fn test_tab_section_is_a_reason() {
+        let mut sheet = sheet_with(&[&["C", "G"]]);
+        let mut solo = FetchedSection::new("間奏");
+        solo.lines.push(FetchedLine::new("e|--3--5--7--|"));
+        sheet.sections.push(solo);
+        let rating = rate_difficulty(&sheet);
+        assert_eq!(rating.reasons.len(), 1);
+        assert_eq!(rating.reasons[0].factor, DifficultyFactor::TabSections);
+        assert_eq!(rating.score, 1);
+    }
+
+    #[test]
+    fn test_rated_sheet_keeps_the_sheet_shape() {
+        let mut sheet = sheet_with(&[&["G", "D"]]);
+        sheet.title = Some("Song".to_string());
+        let json = serde_json::to_value(RatedSheet::new(sheet)).unwrap();
+        assert_eq!(json["title"], "Song");
+        assert_eq!(json["difficulty"]["level"], "beginner");
+    }
+}
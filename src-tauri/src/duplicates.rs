@@ -0,0 +1,445 @@
+//! Finding the same song imported more than once
+//!
+//! The same song arrives from several sites (U-Fret, 楽器.me, ChordWiki) and as
+//! hand-made copies, with titles that differ only in width, kana, brackets or
+//! a drama tie-in. Songs are first grouped by a folded title or opening lyric,
+//! then each candidate pair is scored on title, artist, a lyric fingerprint
+//! and a key-independent chord progression. Pairs above the threshold are
+//! joined into clusters, and a cluster can be merged into one song.
+
+use crate::library::LibrarySong;
+use crate::normalize::{fold, letters};
+use crate::parsers::FetchedChordSheet;
+use crate::revisions::lcs;
+use crate::theory::chord::Chord;
+use crate::theory::pitch_class;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// Score from which two songs count as the same
+pub const DEFAULT_THRESHOLD: f64 = 0.75;
+
+/// Chords compared per song; the opening of a song is enough to tell it apart
+const MAX_PROGRESSION: usize = 64;
+
+/// Lyric shingle length, in characters
+const SHINGLE: usize = 3;
+
+/// Title with tie-ins and brackets removed, folded and stripped to letters:
+/// 「栄光の架橋」(NHKアテネ五輪テーマソング) and 栄光の架橋 give the same key
+pub fn title_key(title: &str) -> String {
+    let folded = fold(title);
+    let mut out = String::new();
+    let mut depth = 0usize;
+    for c in folded.chars() {
+        match c {
+            '(' | '[' | '【' | '〔' | '<' | '〈' | '《' => depth += 1,
+            ')' | ']' | '】' | '〕' | '>' | '〉' | '》' => depth = depth.saturating_sub(1),
+            _ if depth == 0 && c.is_alphanumeric() => out.push(c),
+            _ => {}
+        }
+    }
+    if out.is_empty() {
+        // A title that is all brackets keeps its content
+        letters(&folded)
+    } else {
+        out
+    }
+}
+
+/// Artist folded and stripped to letters
+pub fn artist_key(artist: &str) -> String {
+    letters(artist)
+}
+
+/// What is compared of each song, worked out once
+struct Fingerprint {
+    title: String,
+    artist: String,
+    first_line: Option<String>,
+    shingles: HashSet<String>,
+    progression: Vec<String>,
+}
+
+impl Fingerprint {
+    fn new(sheet: &FetchedChordSheet) -> Self {
+        let lines: Vec<String> = sheet
+            .sections
+            .iter()
+            .flat_map(|s| &s.lines)
+            .map(|l| letters(&l.lyrics))
+            .filter(|l| !l.is_empty())
+            .collect();
+        let lyrics: Vec<char> = lines.concat().chars().collect();
+        let shingles = lyrics
+            .windows(SHINGLE)
+            .map(|w| w.iter().collect())
+            .collect();
+
+        Self {
+            title: sheet.title.as_deref().map(title_key).unwrap_or_default(),
+            artist: sheet.artist.as_deref().map(artist_key).unwrap_or_default(),
+            first_line: lines.into_iter().find(|l| l.chars().count() >= 4),
+            shingles,
+            progression: progression(sheet),
+        }
+    }
+}
+
+/// Chord progression as steps between roots plus chord type, so the same
+/// song in another key or with a capo still matches
+fn progression(sheet: &FetchedChordSheet) -> Vec<String> {
+    let mut steps = Vec::new();
+    let mut previous: Option<(u8, String)> = None;
+    let chords = sheet
+        .sections
+        .iter()
+        .flat_map(|s| &s.lines)
+        .flat_map(|l| &l.chords)
+        .filter_map(|c| Chord::parse(&c.chord));
+    for chord in chords {
+        let kind = format!("{:?}{:?}", chord.quality, chord.seventh);
+        let step = match &previous {
+            Some((root, last)) if *root == chord.root && *last == kind => continue,
+            Some((root, _)) => pitch_class(chord.root as i32 - *root as i32),
+            None => 0,
+        };
+        steps.push(format!("{}{}", step, kind));
+        previous = Some((chord.root, kind));
+        if steps.len() == MAX_PROGRESSION {
+            break;
+        }
+    }
+    steps
+}
+
+/// Share of a and b in their longest common subsequence
+fn sequence_similarity(a: &[String], b: &[String]) -> f64 {
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+    2.0 * lcs(a, b).len() as f64 / (a.len() + b.len()) as f64
+}
+
+/// Dice coefficient over character bigrams, for near-identical titles
+fn text_similarity(a: &str, b: &str) -> f64 {
+    if a == b {
+        return 1.0;
+    }
+    let bigrams = |s: &str| {
+        let chars: Vec<char> = s.chars().collect();
+        chars
+            .windows(2)
+            .map(|w| (w[0], w[1]))
+            .collect::<HashSet<_>>()
+    };
+    let (a, b) = (bigrams(a), bigrams(b));
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+    2.0 * a.intersection(&b).count() as f64 / (a.len() + b.len()) as f64
+}
+
+fn jaccard(a: &HashSet<String>, b: &HashSet<String>) -> f64 {
+    let union = a.union(b).count();
+    if union == 0 {
+        return 0.0;
+    }
+    a.intersection(b).count() as f64 / union as f64
+}
+
+/// How alike two songs are, signal by signal
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DuplicateMatch {
+    pub song_id: String,
+    pub other_id: String,
+    /// Weighted over the signals both songs have, 0.0 to 1.0
+    pub score: f64,
+    pub title: f64,
+    /// None when either song has no artist
+    pub artist: Option<f64>,
+    /// None when either song has no lyrics
+    pub lyrics: Option<f64>,
+    /// None when either song has no chords
+    pub chords: Option<f64>,
+}
+
+fn compare(songs: &[LibrarySong], prints: &[Fingerprint], i: usize, j: usize) -> DuplicateMatch {
+    let (a, b) = (&prints[i], &prints[j]);
+    let title = text_similarity(&a.title, &b.title);
+    let artist = (!a.artist.is_empty() && !b.artist.is_empty())
+        .then(|| text_similarity(&a.artist, &b.artist));
+    let lyrics = (!a.shingles.is_empty() && !b.shingles.is_empty())
+        .then(|| jaccard(&a.shingles, &b.shingles));
+    let chords = (!a.progression.is_empty() && !b.progression.is_empty())
+        .then(|| sequence_similarity(&a.progression, &b.progression));
+
+    // Lyrics say the most: a hand-made copy may be titled in another script
+    // and often lacks the artist
+    let signals = [
+        (Some(title), 0.2),
+        (artist, 0.1),
+        (lyrics, 0.5),
+        (chords, 0.2),
+    ];
+    let (sum, weight) = signals
+        .iter()
+        .filter_map(|&(value, weight)| value.map(|v| (v * weight, weight)))
+        .fold((0.0, 0.0), |(s, w), (v, weight)| (s + v, w + weight));
+
+    DuplicateMatch {
+        song_id: songs[i].id.clone(),
+        other_id: songs[j].id.clone(),
+        score: sum / weight,
+        title,
+        artist,
+        lyrics,
+        chords,
+    }
+}
+
+/// Songs found to be the same, best-matching pairs first
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DuplicateCluster {
+    pub song_ids: Vec<String>,
+    /// Weakest link holding the cluster together
+    pub score: f64,
+    pub matches: Vec<DuplicateMatch>,
+}
+
+fn find_root(parents: &mut [usize], i: usize) -> usize {
+    let mut root = i;
+    while parents[root] != root {
+        root = parents[root];
+    }
+    parents[i] = root;
+    root
+}
+
+/// Group the library's songs into clusters of likely duplicates
+pub fn find_duplicates(songs: &[LibrarySong], threshold: f64) -> Vec<DuplicateCluster> {
+    let prints: Vec<Fingerprint> = songs.iter().map(|s| Fingerprint::new(&s.sheet)).collect();
+
+    // Only songs sharing a title or an opening lyric are compared
+    let mut blocks: HashMap<String, Vec<usize>> = HashMap::new();
+    for (i, print) in prints.iter().enumerate() {
+        if !print.title.is_empty() {
+            blocks
+                .entry(format!("t:{}", print.title))
+                .or_default()
+                .push(i);
+        }
+        if let Some(line) = &print.first_line {
+            blocks.entry(format!("l:{}", line)).or_default().push(i);
+        }
+    }
+    let mut pairs: Vec<(usize, usize)> = blocks
+        .values()
+        .flat_map(|members| {
+            members
+                .iter()
+                .enumerate()
+                .flat_map(move |(n, &a)| members[n + 1..].iter().map(move |&b| (a, b)))
+        })
+        .collect();
+    pairs.sort_unstable();
+    pairs.dedup();
+
+    let mut parents: Vec<usize> = (0..songs.len()).collect();
+    let mut matches = Vec::new();
+    for (a, b) in pairs {
+        let found = compare(songs, &prints, a, b);
+        if found.score < threshold {
+            continue;
+        }
+        let (root_a, root_b) = (find_root(&mut parents, a), find_root(&mut parents, b));
+        parents[root_a.max(root_b)] = root_a.min(root_b);
+        matches.push((a, found));
+    }
+
+    let mut groups: BTreeMap<usize, Vec<String>> = BTreeMap::new();
+    for (i, song) in songs.iter().enumerate() {
+        groups
+            .entry(find_root(&mut parents, i))
+            .or_default()
+            .push(song.id.clone());
+    }
+    let mut clusters: BTreeMap<usize, DuplicateCluster> = groups
+        .into_iter()
+        .filter(|(_, song_ids)| song_ids.len() > 1)
+        .map(|(root, song_ids)| {
+            let cluster = DuplicateCluster {
+                song_ids,
+                score: 1.0,
+                matches: Vec::new(),
+            };
+            (root, cluster)
+        })
+        .collect();
+    for (a, found) in matches {
+        let cluster = clusters
+            .get_mut(&find_root(&mut parents, a))
+            .expect("matched songs are clustered");
+        cluster.score = cluster.score.min(found.score);
+        cluster.matches.push(found);
+    }
+
+    let mut clusters: Vec<DuplicateCluster> = clusters.into_values().collect();
+    for cluster in &mut clusters {
+        cluster.matches.sort_by(|a, b| b.score.total_cmp(&a.score));
+    }
+    clusters.sort_by(|a, b| b.score.total_cmp(&a.score));
+    clusters
+}
+
+/// A cluster merged into the song kept
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MergedSong {
+    pub song: LibrarySong,
+    /// Songs to delete once their playlist entries point at the kept song
+    pub removed_ids: Vec<String>,
+}
+
+/// Merge duplicates into `keep`: its sheet and settings stay, while play
+/// counts add up, tags and notes are combined and missing details are
+/// filled in from the others
+pub fn merge_songs(keep: LibrarySong, others: Vec<LibrarySong>) -> MergedSong {
+    let mut song = keep;
+    let mut removed_ids = Vec::new();
+    for other in others {
+        if other.id == song.id {
+            continue;
+        }
+        song.play_count += other.play_count;
+        song.is_favorite |= other.is_favorite;
+        for tag in other.tag_ids {
+            if !song.tag_ids.contains(&tag) {
+                song.tag_ids.push(tag);
+            }
+        }
+        if let Some(notes) = other.notes.filter(|n| !n.trim().is_empty()) {
+            song.notes = match song.notes.take().filter(|n| !n.trim().is_empty()) {
+                Some(mine) if mine.contains(notes.trim()) => Some(mine),
+                Some(mine) => Some(format!("{}\n\n{}", mine, notes)),
+                None => Some(notes),
+            };
+        }
+        // ISO timestamps compare as strings
+        song.created_at = match (song.created_at.take(), other.created_at) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        song.bpm = song.bpm.or(other.bpm);
+        song.time_signature = song.time_signature.or(other.time_signature);
+        let sheet = &mut song.sheet;
+        sheet.title = sheet.title.take().or(other.sheet.title);
+        sheet.artist = sheet.artist.take().or(other.sheet.artist);
+        sheet.key = sheet.key.take().or(other.sheet.key);
+        sheet.capo = sheet.capo.or(other.sheet.capo);
+        removed_ids.push(other.id);
+    }
+    MergedSong { song, removed_ids }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::{FetchedChord, FetchedLine, FetchedSection};
+
+    fn song(id: &str, title: &str, artist: Option<&str>, lines: &[(&str, &[&str])]) -> LibrarySong {
+        let mut sheet = FetchedChordSheet::new(format!("https://example.com/{}", id));
+        sheet.title = Some(title.to_string());
+        sheet.artist = artist.map(str::to_string);
+        let mut section = FetchedSection::new("Verse");
+        section.lines = lines
+            .iter()
+            .map(|(lyrics, chords)| {
+                let chords = chords
+                    .iter()
+                    .enumerate()
+                    .map(|(i, c)| FetchedChord::new(c, i as i32 * 4))
+                    .collect();
+                FetchedLine::with_chords(lyrics, chords)
+            })
+            .collect();
+        sheet.sections = vec![section];
+        serde_json::from_value(serde_json::json!({ "id": id, "sheet": sheet })).unwrap()
+    }
+
+    #[test]
+    fn test_keys() {
+        assert_eq!(
+            title_key("「栄光の架橋」(NHKアテネ五輪テーマソング)"),
+            title_key("栄光の架橋")
+        );
+        assert_eq!(title_key("Lemon (ドラマ「アンナチュラル」主題歌)"), "lemon");
+        assert_eq!(title_key("【MV】"), "mv");
+        assert_eq!(artist_key("米津 玄師"), artist_key("米津玄師"));
+    }
+
+    #[test]
+    fn test_find_duplicates() {
+        let verse: &[(&str, &[&str])] = &[
+            ("夢ならばどれほどよかったでしょう", &["C", "G", "Am"]),
+            ("未だにあなたのことを夢にみる", &["F", "G", "C"]),
+        ];
+        // The same song a whole step up, as another site might have it
+        let transposed: &[(&str, &[&str])] = &[
+            ("夢ならばどれほどよかったでしょう", &["D", "A", "Bm"]),
+            ("未だにあなたのことを夢にみる", &["G", "A", "D"]),
+        ];
+        let songs = vec![
+            song("ufret", "Lemon", Some("米津玄師"), verse),
+            song(
+                "gakki",
+                "Ｌｅｍｏｎ (ドラマ「アンナチュラル」主題歌)",
+                Some("米津 玄師"),
+                transposed,
+            ),
+            song("manual", "レモン", None, verse),
+            song(
+                "other",
+                "Lemon",
+                Some("Someone Else"),
+                &[("Completely different words here", &["E", "B"])],
+            ),
+        ];
+
+        let clusters = find_duplicates(&songs, DEFAULT_THRESHOLD);
+        assert_eq!(clusters.len(), 1);
+        let mut ids = clusters[0].song_ids.clone();
+        ids.sort();
+        assert_eq!(ids, vec!["gakki", "manual", "ufret"]);
+        assert!(clusters[0].score >= DEFAULT_THRESHOLD);
+        let best = &clusters[0].matches[0];
+        assert_eq!(best.chords, Some(1.0));
+        assert_eq!(best.lyrics, Some(1.0));
+    }
+
+    #[test]
+    fn test_merge_songs() {
+        let mut keep = song("a", "Lemon", None, &[]);
+        keep.play_count = 3;
+        keep.tag_ids = vec!["t1".to_string()];
+        keep.created_at = Some("2024-05-01T00:00:00Z".to_string());
+        let mut other = song("b", "Lemon", Some("米津玄師"), &[]);
+        other.play_count = 2;
+        other.is_favorite = true;
+        other.tag_ids = vec!["t1".to_string(), "t2".to_string()];
+        other.notes = Some("Capo 2 live".to_string());
+        other.created_at = Some("2023-01-01T00:00:00Z".to_string());
+        other.bpm = Some(87);
+
+        let merged = merge_songs(keep, vec![other]);
+        assert_eq!(merged.removed_ids, vec!["b"]);
+        let song = merged.song;
+        assert_eq!(song.id, "a");
+        assert_eq!(song.play_count, 5);
+        assert!(song.is_favorite);
+        assert_eq!(song.tag_ids, vec!["t1", "t2"]);
+        assert_eq!(song.notes.as_deref(), Some("Capo 2 live"));
+        assert_eq!(song.created_at.as_deref(), Some("2023-01-01T00:00:00Z"));
+        assert_eq!(song.bpm, Some(87));
+        assert_eq!(song.sheet.artist.as_deref(), Some("米津玄師"));
+    }
+}
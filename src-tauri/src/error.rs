@@ -26,3 +26,141 @@ impl From<FetchError> for String {
         error.to_string()
     }
 }
+
+#[derive(Error, Debug)]
+pub enum ExportError {
+    #[error("Font error: {0}")]
+    Font(String),
+
+    #[error("Render error: {0}")]
+    Render(String),
+
+    #[error("Invalid setlist: {0}")]
+    Setlist(String),
+
+    #[error("Failed to write file: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+impl From<ExportError> for String {
+    fn from(error: ExportError) -> Self {
+        error.to_string()
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum ImportError {
+    #[error("Unsupported file format: {0}")]
+    UnsupportedFormat(String),
+
+    #[error("Invalid file: {0}")]
+    Invalid(String),
+
+    #[error("Failed to read file: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+impl From<ImportError> for String {
+    fn from(error: ImportError) -> Self {
+        error.to_string()
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum ArchiveError {
+    #[error("Invalid library archive: {0}")]
+    Invalid(String),
+
+    #[error("Library archive version {0} is newer than this app supports ({1})")]
+    UnsupportedVersion(u32, u32),
+
+    #[error("Library archive is corrupt: {0} does not match its checksum")]
+    Corrupt(String),
+
+    #[error("Failed to access library archive: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+impl From<ArchiveError> for String {
+    fn from(error: ArchiveError) -> Self {
+        error.to_string()
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum RevisionError {
+    #[error("Revision not found: {0}")]
+    NotFound(String),
+
+    #[error("Invalid revision history: {0}")]
+    Invalid(String),
+
+    #[error("Failed to access revision history: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+impl From<RevisionError> for String {
+    fn from(error: RevisionError) -> Self {
+        error.to_string()
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum SyncError {
+    #[error("Sync request failed: {0}")]
+    Http(#[from] tauri_plugin_http::reqwest::Error),
+
+    #[error("Sync server returned {status}: {body}")]
+    Server { status: u16, body: String },
+
+    #[error("Invalid sync row: {0}")]
+    Invalid(String),
+
+    #[error("Not signed in")]
+    SignedOut,
+
+    #[error("The app did not hand over the library to sync in time")]
+    NoInput,
+}
+
+impl From<SyncError> for String {
+    fn from(error: SyncError) -> Self {
+        error.to_string()
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum QueueError {
+    #[error("Operation not found: {0}")]
+    NotFound(String),
+
+    #[error("Invalid operation queue: {0}")]
+    Invalid(String),
+
+    #[error("Failed to access operation queue: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+impl From<QueueError> for String {
+    fn from(error: QueueError) -> Self {
+        error.to_string()
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum PracticeError {
+    #[error("Invalid practice session: {0}")]
+    InvalidSession(String),
+
+    #[error("Invalid date: {0}")]
+    InvalidDate(String),
+
+    #[error("Invalid UTC offset: {0} minutes")]
+    UtcOffset(i32),
+}
+
+impl From<PracticeError> for String {
+    fn from(error: PracticeError) -> Self {
+        error.to_string()
+    }
+}
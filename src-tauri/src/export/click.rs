@@ -0,0 +1,303 @@
+//! Click tracks rendered to WAV
+//!
+//! The same sine clicks as the webview metronome (`useMetronome.ts`), laid out
+//! over the song structure: count-in bars, then every section at its own tempo
+//! and meter, repeated `repeat_count` times. Optional cues beep on the beat
+//! before each section starts.
+
+use super::timing::{section_beats, TimeSignature};
+use super::ExportSong;
+use crate::error::ExportError;
+use serde::{Deserialize, Serialize};
+use std::f32::consts::TAU;
+use std::io::Cursor;
+use std::path::Path;
+
+const DEFAULT_BPM: u32 = 120;
+const MIN_BPM: u32 = 40;
+const MAX_BPM: u32 = 240;
+
+const ACCENT_FREQUENCY: f32 = 1000.0;
+const NORMAL_FREQUENCY: f32 = 800.0;
+const CLICK_DURATION: f32 = 0.1;
+const CUE_FREQUENCY: f32 = 1500.0;
+const CUE_BEEP_DURATION: f32 = 0.05;
+const CUE_BEEP_GAP: f32 = 0.08;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClickOptions {
+    /// Bars of clicks before the first section
+    pub count_in_bars: u32,
+    /// Higher, louder click on the first beat of each bar
+    pub accent_downbeat: bool,
+    /// Two short beeps on the beat before each section
+    pub section_cues: bool,
+    /// Master volume, 0.0 to 1.0
+    pub volume: f32,
+    pub sample_rate: u32,
+}
+
+impl Default for ClickOptions {
+    fn default() -> Self {
+        Self {
+            count_in_bars: 1,
+            accent_downbeat: true,
+            section_cues: false,
+            volume: 0.8,
+            sample_rate: 44_100,
+        }
+    }
+}
+
+/// A run of bars at one tempo and meter
+#[derive(Debug, Clone, PartialEq)]
+struct Segment {
+    bpm: u32,
+    time: TimeSignature,
+    beats: usize,
+    /// Cue on the beat before this segment
+    cue: bool,
+}
+
+impl Segment {
+    fn seconds_per_beat(&self) -> f32 {
+        60.0 / self.bpm as f32 * self.time.beat_in_quarters() as f32
+    }
+}
+
+fn segments(song: &ExportSong, options: &ClickOptions) -> Vec<Segment> {
+    let song_bpm = song.bpm.unwrap_or(DEFAULT_BPM);
+    let song_time = TimeSignature::parse(song.time_signature.as_deref());
+
+    let mut segments = Vec::new();
+    for section in &song.sheet.sections {
+        let time = match section.time_signature.as_deref() {
+            Some(text) => TimeSignature::parse(Some(text)),
+            None => song_time,
+        };
+        let beats = section_beats(section, &time);
+        if beats == 0 {
+            continue;
+        }
+        for _ in 0..section.repeat_count.unwrap_or(1).max(1) {
+            segments.push(Segment {
+                bpm: section
+                    .bpm_override
+                    .unwrap_or(song_bpm)
+                    .clamp(MIN_BPM, MAX_BPM),
+                time,
+                beats,
+                cue: options.section_cues,
+            });
+        }
+    }
+
+    if options.count_in_bars > 0 {
+        // Count in at the first section's tempo and meter
+        let (bpm, time) = segments
+            .first()
+            .map_or((song_bpm.clamp(MIN_BPM, MAX_BPM), song_time), |s| {
+                (s.bpm, s.time)
+            });
+        segments.insert(
+            0,
+            Segment {
+                bpm,
+                time,
+                beats: options.count_in_bars as usize * time.beats as usize,
+                cue: false,
+            },
+        );
+    }
+    segments
+}
+
+/// Mix a decaying sine into `buffer` starting at `start` seconds
+fn tone(
+    buffer: &mut Vec<f32>,
+    sample_rate: u32,
+    start: f32,
+    frequency: f32,
+    gain: f32,
+    duration: f32,
+) {
+    let rate = sample_rate as f32;
+    let first = (start * rate).round() as usize;
+    let length = (duration * rate) as usize;
+    if buffer.len() < first + length {
+        buffer.resize(first + length, 0.0);
+    }
+    // Exponential decay to 0.001 over the duration, as the webview gain ramp does
+    let decay = (0.001f32 / gain.max(0.001)).ln() / length as f32;
+    for (i, sample) in buffer[first..first + length].iter_mut().enumerate() {
+        let t = i as f32 / rate;
+        *sample += gain * (decay * i as f32).exp() * (TAU * frequency * t).sin();
+    }
+}
+
+/// Render a click track for a song as 16-bit mono WAV bytes
+pub fn render_click_track(
+    song: &ExportSong,
+    options: &ClickOptions,
+) -> Result<Vec<u8>, ExportError> {
+    let volume = options.volume.clamp(0.0, 1.0);
+    let sample_rate = options.sample_rate.clamp(8_000, 192_000);
+    let mut samples: Vec<f32> = Vec::new();
+    let mut clock = 0.0f32;
+    let mut last_beat = 0.0f32;
+
+    for segment in segments(song, options) {
+        let beat_length = segment.seconds_per_beat();
+        if segment.cue && clock > 0.0 {
+            let cue_start = (clock - last_beat).max(0.0);
+            for beep in 0..2 {
+                let start = cue_start + beep as f32 * (CUE_BEEP_DURATION + CUE_BEEP_GAP);
+                tone(
+                    &mut samples,
+                    sample_rate,
+                    start,
+                    CUE_FREQUENCY,
+                    volume * 0.6,
+                    CUE_BEEP_DURATION,
+                );
+            }
+        }
+        for beat in 0..segment.beats {
+            let accent = options.accent_downbeat && beat % segment.time.beats as usize == 0;
+            let (frequency, gain) = if accent {
+                (ACCENT_FREQUENCY, volume)
+            } else {
+                (NORMAL_FREQUENCY, volume * 0.7)
+            };
+            tone(
+                &mut samples,
+                sample_rate,
+                clock,
+                frequency,
+                gain,
+                CLICK_DURATION,
+            );
+            clock += beat_length;
+        }
+        last_beat = beat_length;
+    }
+    samples.resize((clock * sample_rate as f32).round() as usize, 0.0);
+
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut cursor = Cursor::new(Vec::new());
+    let to_render_error = |e: hound::Error| ExportError::Render(e.to_string());
+    let mut writer = hound::WavWriter::new(&mut cursor, spec).map_err(to_render_error)?;
+    for sample in samples {
+        let value = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+        writer.write_sample(value).map_err(to_render_error)?;
+    }
+    writer.finalize().map_err(to_render_error)?;
+    Ok(cursor.into_inner())
+}
+
+/// Render a click track and write it to `path`
+pub fn export_click_track(
+    song: &ExportSong,
+    options: &ClickOptions,
+    path: &Path,
+) -> Result<(), ExportError> {
+    let bytes = render_click_track(song, options)?;
+    std::fs::write(path, bytes)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::{FetchedChord, FetchedChordSheet, FetchedLine, FetchedSection};
+
+    /// Two sections of one 4/4 bar each; the chorus can change tempo
+    fn song(chorus_bpm: Option<u32>) -> ExportSong {
+        let mut sheet = FetchedChordSheet::new(String::new());
+        for name in ["Verse", "Chorus"] {
+            let mut section = FetchedSection::new(name);
+            section.lines.push(FetchedLine::with_chords(
+                "歌詞",
+                vec![FetchedChord::new("C", 0), FetchedChord::new("G", 1)],
+            ));
+            sheet.sections.push(section);
+        }
+        sheet.sections[1].bpm_override = chorus_bpm;
+        ExportSong {
+            sheet,
+            bpm: Some(120),
+            time_signature: None,
+            notes: None,
+        }
+    }
+
+    fn samples(bytes: Vec<u8>) -> Vec<i16> {
+        let mut reader = hound::WavReader::new(Cursor::new(bytes)).unwrap();
+        assert_eq!(reader.spec().channels, 1);
+        reader.samples::<i16>().map(Result::unwrap).collect()
+    }
+
+    fn peak(samples: &[i16], from: f32, to: f32) -> i16 {
+        samples[(from * 44_100.0) as usize..(to * 44_100.0) as usize]
+            .iter()
+            .map(|s| s.saturating_abs())
+            .max()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_count_in_and_length() {
+        let wav = samples(render_click_track(&song(None), &ClickOptions::default()).unwrap());
+        // One count-in bar plus two bars at 120 BPM
+        assert_eq!(wav.len(), 12 * 22_050);
+        // Accented downbeat is louder than beat two
+        assert!(peak(&wav, 0.0, 0.1) > peak(&wav, 0.5, 0.6));
+
+        let options = ClickOptions {
+            count_in_bars: 0,
+            ..ClickOptions::default()
+        };
+        let wav = samples(render_click_track(&song(None), &options).unwrap());
+        assert_eq!(wav.len(), 8 * 22_050);
+    }
+
+    #[test]
+    fn test_section_tempo_and_repeats() {
+        let options = ClickOptions {
+            count_in_bars: 0,
+            ..ClickOptions::default()
+        };
+        let mut song = song(Some(60));
+        song.sheet.sections[1].repeat_count = Some(2);
+        let wav = samples(render_click_track(&song, &options).unwrap());
+        // 2 s verse, then two 4 s passes of the chorus
+        assert_eq!(wav.len(), 10 * 44_100);
+        // Chorus clicks fall every second: silence between them
+        assert_eq!(peak(&wav, 2.5, 2.9), 0);
+        assert!(peak(&wav, 3.0, 3.05) > 0);
+    }
+
+    #[test]
+    fn test_section_cues() {
+        let options = ClickOptions {
+            count_in_bars: 0,
+            section_cues: true,
+            ..ClickOptions::default()
+        };
+        let cued = samples(render_click_track(&song(None), &options).unwrap());
+        let options = ClickOptions {
+            section_cues: false,
+            ..options
+        };
+        let plain = samples(render_click_track(&song(None), &options).unwrap());
+        // The cue lands on the last verse beat (1.5 s), after its click has decayed
+        assert_eq!(peak(&plain, 1.62, 1.7), 0);
+        assert!(peak(&cued, 1.62, 1.7) > 0);
+    }
+}
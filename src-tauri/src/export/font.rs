@@ -0,0 +1,472 @@
+//! Font loading for PDF export
+//!
+//! The PDF writer embeds TrueType (`glyf`) fonts only, and Japanese system
+//! fonts mostly ship as collections (`.ttc`), so the first face of a
+//! collection is copied out into a standalone font before embedding.
+//!
+//! A Japanese font runs to megabytes, nearly all of it outlines for glyphs a
+//! songbook never shows, so only the glyphs in use keep their outlines. Glyph
+//! ids are left as they are, which keeps the character map and widths the
+//! PDF writer reads valid.
+
+use crate::error::ExportError;
+use std::collections::BTreeSet;
+use std::path::Path;
+
+/// Japanese-capable system fonts, tried in order
+const SYSTEM_FONTS: &[&str] = &[
+    // macOS
+    "/System/Library/Fonts/ヒラギノ角ゴシック W3.ttc",
+    "/System/Library/Fonts/Hiragino Sans GB.ttc",
+    "/System/Library/Fonts/Supplemental/Arial Unicode.ttf",
+    "/Library/Fonts/Arial Unicode.ttf",
+    // Windows
+    "C:\\Windows\\Fonts\\YuGothM.ttc",
+    "C:\\Windows\\Fonts\\meiryo.ttc",
+    "C:\\Windows\\Fonts\\msgothic.ttc",
+    // Linux
+    "/usr/share/fonts/opentype/ipafont-gothic/ipag.ttf",
+    "/usr/share/fonts/truetype/fonts-japanese-gothic.ttf",
+    "/usr/share/fonts/truetype/takao-gothic/TakaoGothic.ttf",
+];
+
+/// Tables kept in a subset: those the PDF writer reads and those a viewer
+/// needs to draw TrueType outlines; layout and bitmap tables are dropped
+const SUBSET_TABLES: &[&[u8; 4]] = &[
+    b"OS/2", b"cmap", b"cvt ", b"fpgm", b"glyf", b"head", b"hhea", b"hmtx", b"loca", b"maxp",
+    b"name", b"post", b"prep",
+];
+
+/// Sum the whole font comes to once `head`'s checksum adjustment is set
+const FONT_CHECKSUM: u32 = 0xB1B0_AFBA;
+
+/// A font ready to embed, with the metrics needed for layout
+pub struct EmbedFont {
+    pub data: Vec<u8>,
+    units_per_em: f32,
+    advances: Vec<(char, u16)>,
+    default_advance: u16,
+}
+
+impl EmbedFont {
+    /// Load `path`, or the first usable Japanese system font
+    pub fn load(path: Option<&str>) -> Result<Self, ExportError> {
+        if let Some(path) = path {
+            return Self::from_file(Path::new(path));
+        }
+        SYSTEM_FONTS
+            .iter()
+            .map(Path::new)
+            .filter(|path| path.exists())
+            .find_map(|path| Self::from_file(path).ok())
+            .ok_or_else(|| ExportError::Font("No Japanese TrueType font found".to_string()))
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, ExportError> {
+        let data = std::fs::read(path)?;
+        Self::from_bytes(data).map_err(|e| ExportError::Font(format!("{}: {}", path.display(), e)))
+    }
+
+    pub fn from_bytes(data: Vec<u8>) -> Result<Self, String> {
+        let data = if data.starts_with(b"ttcf") {
+            extract_collection_face(&data, 0).ok_or("Invalid font collection")?
+        } else {
+            data
+        };
+
+        let face = ttf_parser::Face::parse(&data, 0).map_err(|e| e.to_string())?;
+        if face.tables().glyf.is_none() {
+            return Err("Only TrueType outline fonts can be embedded".to_string());
+        }
+
+        // Cache advances for ASCII and the kana/full-width ranges that dominate lyrics
+        let ranges = [
+            '\u{20}'..='\u{7e}',
+            '\u{3000}'..='\u{30ff}',
+            '\u{ff00}'..='\u{ffef}',
+        ];
+        let advances = ranges
+            .into_iter()
+            .flatten()
+            .filter_map(|c| {
+                let advance = face.glyph_hor_advance(face.glyph_index(c)?)?;
+                Some((c, advance))
+            })
+            .collect();
+        let units_per_em = face.units_per_em();
+
+        Ok(Self {
+            data,
+            units_per_em: units_per_em as f32,
+            advances,
+            default_advance: units_per_em / 2,
+        })
+    }
+
+    /// The font to embed for `text`; a font whose tables can't be read is
+    /// embedded whole
+    pub fn subset(&self, text: impl IntoIterator<Item = char>) -> Vec<u8> {
+        let Ok(face) = ttf_parser::Face::parse(&self.data, 0) else {
+            return self.data.clone();
+        };
+        let glyphs = text
+            .into_iter()
+            .filter_map(|c| face.glyph_index(c))
+            .map(|id| id.0)
+            .collect();
+        subset_glyphs(&self.data, &glyphs).unwrap_or_else(|| self.data.clone())
+    }
+
+    fn advance(&self, c: char) -> u16 {
+        if let Ok(index) = self.advances.binary_search_by_key(&c, |&(ch, _)| ch) {
+            return self.advances[index].1;
+        }
+        // CJK ideographs and anything uncached
+        if c > '\u{2e80}' {
+            return self.units_per_em as u16;
+        }
+        self.default_advance
+    }
+}
+
+/// Text width in millimetres at `size` points
+pub trait Measure {
+    fn text_width(&self, text: &str, size: f32) -> f32;
+}
+
+impl Measure for EmbedFont {
+    fn text_width(&self, text: &str, size: f32) -> f32 {
+        let units: u32 = text.chars().map(|c| self.advance(c) as u32).sum();
+        units as f32 / self.units_per_em * size * PT_TO_MM
+    }
+}
+
+pub const PT_TO_MM: f32 = 0.352_778;
+
+/// Font-independent widths: half an em for ASCII, a full em otherwise
+pub struct ApproxMeasure;
+
+impl Measure for ApproxMeasure {
+    fn text_width(&self, text: &str, size: f32) -> f32 {
+        let ems: f32 = text
+            .chars()
+            .map(|c| if c.is_ascii() { 0.5 } else { 1.0 })
+            .sum();
+        ems * size * PT_TO_MM
+    }
+}
+
+fn read_u16(data: &[u8], at: usize) -> Option<u16> {
+    data.get(at..at + 2)
+        .map(|b| u16::from_be_bytes([b[0], b[1]]))
+}
+
+fn read_u32(data: &[u8], at: usize) -> Option<u32> {
+    data.get(at..at + 4)
+        .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+fn checksum(data: &[u8]) -> u32 {
+    data.chunks(4).fold(0u32, |sum, chunk| {
+        let mut word = [0; 4];
+        word[..chunk.len()].copy_from_slice(chunk);
+        sum.wrapping_add(u32::from_be_bytes(word))
+    })
+}
+
+/// Glyphs a composite glyph is drawn from; none for a simple glyph
+fn components(glyph: &[u8]) -> Option<Vec<u16>> {
+    const ARGS_ARE_WORDS: u16 = 0x0001;
+    const HAS_SCALE: u16 = 0x0008;
+    const MORE_COMPONENTS: u16 = 0x0020;
+    const HAS_XY_SCALE: u16 = 0x0040;
+    const HAS_2X2: u16 = 0x0080;
+
+    let mut components = Vec::new();
+    // An empty glyph has no header; a simple one has a contour count >= 0
+    if glyph.is_empty() || read_u16(glyph, 0)? as i16 >= 0 {
+        return Some(components);
+    }
+    let mut at = 10;
+    loop {
+        let flags = read_u16(glyph, at)?;
+        components.push(read_u16(glyph, at + 2)?);
+        at += if flags & ARGS_ARE_WORDS != 0 { 8 } else { 6 };
+        at += if flags & HAS_SCALE != 0 {
+            2
+        } else if flags & HAS_XY_SCALE != 0 {
+            4
+        } else if flags & HAS_2X2 != 0 {
+            8
+        } else {
+            0
+        };
+        if flags & MORE_COMPONENTS == 0 {
+            return Some(components);
+        }
+    }
+}
+
+/// Lay tables out as an sfnt font, sorted by tag, and set `head`'s checksum
+/// adjustment
+fn write_sfnt(version: u32, mut tables: Vec<([u8; 4], Vec<u8>)>) -> Vec<u8> {
+    tables.sort_by_key(|(tag, _)| *tag);
+    let count = tables.len() as u16;
+    let power = if count == 0 {
+        0
+    } else {
+        15 - count.leading_zeros() as u16
+    };
+    let search_range = (1u16 << power) * 16;
+
+    let mut output = version.to_be_bytes().to_vec();
+    for field in [count, search_range, power, count * 16 - search_range] {
+        output.extend_from_slice(&field.to_be_bytes());
+    }
+    let mut offset = 12 + tables.len() * 16;
+    let mut head_offset = None;
+    for (tag, table) in &tables {
+        if tag == b"head" {
+            head_offset = Some(offset);
+        }
+        output.extend_from_slice(tag);
+        output.extend_from_slice(&checksum(table).to_be_bytes());
+        output.extend_from_slice(&(offset as u32).to_be_bytes());
+        output.extend_from_slice(&(table.len() as u32).to_be_bytes());
+        offset += table.len().next_multiple_of(4);
+    }
+    for (_, table) in &tables {
+        output.extend_from_slice(table);
+        output.resize(output.len().next_multiple_of(4), 0);
+    }
+    if let Some(head) = head_offset {
+        let adjustment = FONT_CHECKSUM.wrapping_sub(checksum(&output));
+        output[head + 8..head + 12].copy_from_slice(&adjustment.to_be_bytes());
+    }
+    output
+}
+
+/// Copy a standalone TrueType font keeping the outlines of `used` glyphs (and
+/// of the glyphs they are built from) and `.notdef`; every other glyph is
+/// left empty under its id
+pub fn subset_glyphs(data: &[u8], used: &BTreeSet<u16>) -> Option<Vec<u8>> {
+    let num_tables = read_u16(data, 4)? as usize;
+    let mut tables = Vec::new();
+    for i in 0..num_tables {
+        let record = 12 + i * 16;
+        let tag: [u8; 4] = data.get(record..record + 4)?.try_into().ok()?;
+        let offset = read_u32(data, record + 8)? as usize;
+        let length = read_u32(data, record + 12)? as usize;
+        tables.push((tag, data.get(offset..offset.checked_add(length)?)?));
+    }
+    let table = |tag: &[u8; 4]| tables.iter().find(|(t, _)| t == tag).map(|(_, t)| *t);
+    let head = table(b"head")?;
+    let glyf = table(b"glyf")?;
+    let loca = table(b"loca")?;
+    let num_glyphs = read_u16(table(b"maxp")?, 4)? as usize;
+    let long_loca = read_u16(head, 50)? != 0;
+    let glyph = |id: usize| -> Option<&[u8]> {
+        let (start, end) = if long_loca {
+            (
+                read_u32(loca, id * 4)? as usize,
+                read_u32(loca, id * 4 + 4)? as usize,
+            )
+        } else {
+            (
+                read_u16(loca, id * 2)? as usize * 2,
+                read_u16(loca, id * 2 + 2)? as usize * 2,
+            )
+        };
+        glyf.get(start..end)
+    };
+
+    let mut keep: BTreeSet<u16> = used
+        .iter()
+        .copied()
+        .filter(|&id| (id as usize) < num_glyphs)
+        .chain([0])
+        .collect();
+    let mut pending: Vec<u16> = keep.iter().copied().collect();
+    while let Some(id) = pending.pop() {
+        for component in components(glyph(id as usize)?)? {
+            if (component as usize) < num_glyphs && keep.insert(component) {
+                pending.push(component);
+            }
+        }
+    }
+
+    let mut new_glyf = Vec::new();
+    let mut new_loca = Vec::with_capacity((num_glyphs + 1) * 4);
+    for id in 0..num_glyphs {
+        new_loca.extend_from_slice(&(new_glyf.len() as u32).to_be_bytes());
+        if keep.contains(&(id as u16)) {
+            new_glyf.extend_from_slice(glyph(id)?);
+            new_glyf.resize(new_glyf.len().next_multiple_of(4), 0);
+        }
+    }
+    new_loca.extend_from_slice(&(new_glyf.len() as u32).to_be_bytes());
+
+    let mut new_head = head.to_vec();
+    // Long offsets in the new loca
+    new_head
+        .get_mut(50..52)?
+        .copy_from_slice(&1u16.to_be_bytes());
+    // Summed with the adjustment zeroed, then set
+    new_head.get_mut(8..12)?.fill(0);
+
+    let mut output = Vec::new();
+    for (tag, table) in &tables {
+        let table = match tag {
+            b"glyf" => std::mem::take(&mut new_glyf),
+            b"loca" => std::mem::take(&mut new_loca),
+            b"head" => std::mem::take(&mut new_head),
+            // Version 3 drops the glyph names, keeping the header
+            b"post" if table.len() >= 32 => {
+                let mut post = table[..32].to_vec();
+                post[..4].copy_from_slice(&0x0003_0000u32.to_be_bytes());
+                post
+            }
+            _ if SUBSET_TABLES.contains(&tag) => table.to_vec(),
+            _ => continue,
+        };
+        output.push((*tag, table));
+    }
+    Some(write_sfnt(read_u32(data, 0)?, output))
+}
+
+/// Copy one face of a TrueType collection into a standalone font
+pub fn extract_collection_face(data: &[u8], index: u32) -> Option<Vec<u8>> {
+    let read_u32 = |at: usize| -> Option<u32> {
+        data.get(at..at + 4)
+            .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    };
+    let read_u16 = |at: usize| -> Option<u16> {
+        data.get(at..at + 2)
+            .map(|b| u16::from_be_bytes([b[0], b[1]]))
+    };
+
+    let num_fonts = read_u32(8)?;
+    if index >= num_fonts {
+        return None;
+    }
+    let font_offset = read_u32(12 + index as usize * 4)? as usize;
+    let num_tables = read_u16(font_offset + 4)? as usize;
+    let header_len = 12 + num_tables * 16;
+
+    let mut output = data.get(font_offset..font_offset + 12)?.to_vec();
+    let mut tables = Vec::new();
+    let mut next_offset = header_len;
+    for i in 0..num_tables {
+        let record = font_offset + 12 + i * 16;
+        let tag_and_checksum = data.get(record..record + 8)?;
+        let offset = read_u32(record + 8)? as usize;
+        let length = read_u32(record + 12)? as usize;
+        let table = data.get(offset..offset + length)?;
+
+        output.extend_from_slice(tag_and_checksum);
+        output.extend_from_slice(&(next_offset as u32).to_be_bytes());
+        output.extend_from_slice(&(length as u32).to_be_bytes());
+        tables.push(table);
+        next_offset += length.next_multiple_of(4);
+    }
+    for table in tables {
+        output.extend_from_slice(table);
+        output.resize(output.len().next_multiple_of(4), 0);
+    }
+    Some(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A one-table sfnt wrapped in a collection header
+    fn collection() -> (Vec<u8>, Vec<u8>) {
+        let table = b"abcdef".to_vec();
+        let mut font = vec![0, 1, 0, 0, 0, 1, 0, 16, 0, 0, 0, 0];
+        font.extend_from_slice(b"name");
+        font.extend_from_slice(&[0; 4]);
+        font.extend_from_slice(&48u32.to_be_bytes());
+        font.extend_from_slice(&6u32.to_be_bytes());
+
+        let mut ttc = b"ttcf".to_vec();
+        ttc.extend_from_slice(&[0, 1, 0, 0]);
+        ttc.extend_from_slice(&1u32.to_be_bytes());
+        ttc.extend_from_slice(&16u32.to_be_bytes());
+        ttc.extend_from_slice(&font);
+        ttc.extend_from_slice(&[0; 4]);
+        ttc.extend_from_slice(&table);
+        (ttc, table)
+    }
+
+    /// Glyphs: .notdef, "A", "B", and a composite of "B"; short loca offsets
+    fn font() -> Vec<u8> {
+        let simple = |fill: u8| {
+            let mut glyph = vec![0, 1];
+            glyph.resize(12, fill);
+            glyph
+        };
+        // One component, glyph 2, with byte offsets and no scale
+        let composite = vec![0xff, 0xff, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 0, 0];
+        let glyphs = [simple(0), simple(0xa), simple(0xb), composite];
+
+        let mut glyf = Vec::new();
+        let mut loca = Vec::new();
+        for glyph in &glyphs {
+            loca.extend_from_slice(&(glyf.len() as u16 / 2).to_be_bytes());
+            glyf.extend_from_slice(glyph);
+        }
+        loca.extend_from_slice(&(glyf.len() as u16 / 2).to_be_bytes());
+        let mut maxp = 0x0000_5000u32.to_be_bytes().to_vec();
+        maxp.extend_from_slice(&4u16.to_be_bytes());
+        let mut post = 0x0002_0000u32.to_be_bytes().to_vec();
+        post.resize(48, 1);
+
+        write_sfnt(
+            0x0001_0000,
+            vec![
+                (*b"head", vec![0; 54]),
+                (*b"maxp", maxp),
+                (*b"loca", loca),
+                (*b"glyf", glyf),
+                (*b"post", post),
+                (*b"GSUB", vec![1; 8]),
+            ],
+        )
+    }
+
+    #[test]
+    fn test_subset_glyphs() {
+        let font = font();
+        let subset = subset_glyphs(&font, &BTreeSet::from([3])).unwrap();
+        let face = ttf_parser::RawFace::parse(&subset, 0).unwrap();
+        let table = |tag: &[u8; 4]| face.table(ttf_parser::Tag::from_bytes(tag));
+
+        // Long offsets; "A" is emptied, the composite keeps "B" it is built from
+        assert_eq!(read_u16(table(b"head").unwrap(), 50), Some(1));
+        let loca = table(b"loca").unwrap();
+        let offsets: Vec<u32> = (0..5).map(|i| read_u32(loca, i * 4).unwrap()).collect();
+        assert_eq!(offsets, vec![0, 12, 12, 24, 40]);
+        let glyf = table(b"glyf").unwrap();
+        assert_eq!(glyf[14], 0xb);
+
+        assert!(table(b"GSUB").is_none());
+        let post = table(b"post").unwrap();
+        assert_eq!(post.len(), 32);
+        assert_eq!(read_u32(post, 0), Some(0x0003_0000));
+        assert_eq!(checksum(&subset), FONT_CHECKSUM);
+        assert!(subset.len() < font.len());
+    }
+
+    #[test]
+    fn test_extract_collection_face() {
+        let (ttc, table) = collection();
+        let font = extract_collection_face(&ttc, 0).unwrap();
+        assert_eq!(&font[..4], &[0, 1, 0, 0]);
+        // Table moved right after the 28-byte header, padded to 4 bytes
+        assert_eq!(&font[20..24], &28u32.to_be_bytes());
+        assert_eq!(&font[28..34], table.as_slice());
+        assert_eq!(font.len(), 36);
+        assert!(extract_collection_face(&ttc, 1).is_none());
+    }
+}
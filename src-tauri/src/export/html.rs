@@ -0,0 +1,453 @@
+//! Self-contained HTML and SVG export
+//!
+//! The HTML output is a single file with inline CSS: chords sit above the
+//! lyrics as `<ruby>` annotations on the character they fall on, so they stay
+//! aligned whatever font the reader has. The SVG option reuses the page layout
+//! of the PDF export with approximate glyph widths.
+
+use super::font::{ApproxMeasure, PT_TO_MM};
+use super::layout::{layout, Op, PageSetup, Tone};
+use super::view::SheetView;
+use super::{escape, ExportSong};
+use crate::parsers::{FetchedChordSheet, FetchedLine};
+use crate::theory::chord::Chord;
+use crate::theory::instrument::Instrument;
+use crate::theory::voicing::{find_voicings, Voicing};
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+
+const FONT_FAMILY: &str =
+    "'Hiragino Sans', 'Hiragino Kaku Gothic ProN', 'Yu Gothic', Meiryo, 'Noto Sans JP', sans-serif";
+
+const STYLE: &str = "\
+body { font-family: FONTS; margin: 2rem auto; max-width: 48rem; padding: 0 1rem; color: #000; }
+h1 { font-size: 1.6rem; margin: 0; }
+.artist { color: #666; margin: .25rem 0; }
+.meta { color: #666; font-size: .85rem; margin: .25rem 0 1rem; }
+.diagrams { display: flex; flex-wrap: wrap; gap: .5rem; margin-bottom: 1rem; }
+.part { break-inside: avoid; margin-bottom: 1.2rem; }
+.part h2 { font-size: .95rem; color: #666; border-bottom: 1px solid #ccc; display: inline-block; margin: 0 0 .3rem; }
+.line { margin: 0; line-height: 2.6; white-space: pre-wrap; }
+.line.empty { line-height: 1; min-height: .6em; }
+.tab { font-family: ui-monospace, Menlo, Consolas, monospace; font-size: .85rem; line-height: 1.3; margin: .3rem 0 0; overflow-x: auto; }
+ruby { ruby-position: over; }
+rt { font-size: .8em; font-weight: bold; color: #1a4dbf; ruby-align: start; text-align: left; padding-right: .3em; }
+";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HtmlFormat {
+    /// One HTML document
+    #[default]
+    Html,
+    /// One SVG document per page
+    SvgPages,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HtmlOptions {
+    #[serde(default)]
+    pub format: HtmlFormat,
+    /// Add an SVG diagram for every chord used (HTML only)
+    #[serde(default)]
+    pub chord_diagrams: bool,
+    /// Tuning id for the diagrams; standard guitar by default
+    #[serde(default)]
+    pub tuning: Option<String>,
+    /// Two-column pages (SVG only)
+    #[serde(default)]
+    pub two_columns: bool,
+    #[serde(default)]
+    pub view: SheetView,
+    /// Song time signature (songs.time_signature), for the chord chart view
+    #[serde(default)]
+    pub time_signature: Option<String>,
+}
+
+/// Render a sheet as one HTML document, or as one SVG document per page
+pub fn export_html(
+    sheet: &FetchedChordSheet,
+    options: &HtmlOptions,
+    instrument: &Instrument,
+) -> Vec<String> {
+    let song = ExportSong {
+        sheet: options.view.apply(sheet, options.time_signature.as_deref()),
+        bpm: None,
+        time_signature: options.time_signature.clone(),
+        notes: None,
+    };
+    match options.format {
+        HtmlFormat::Html => vec![render_html(
+            &song,
+            options.chord_diagrams.then_some(instrument),
+        )],
+        HtmlFormat::SvgPages => render_svg_pages(&song, options.two_columns),
+    }
+}
+
+fn render_html(song: &ExportSong, diagrams: Option<&Instrument>) -> String {
+    let sheet = &song.sheet;
+    let mut html = String::new();
+    let _ = write!(
+        html,
+        "<!DOCTYPE html>\n<html lang=\"ja\">\n<head>\n<meta charset=\"utf-8\">\n\
+         <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n\
+         <title>{}</title>\n<style>\n{}</style>\n</head>\n<body>\n<article class=\"song\">\n<header>\n<h1>{}</h1>\n",
+        escape(song.title()),
+        STYLE.replace("FONTS", FONT_FAMILY),
+        escape(song.title()),
+    );
+    if let Some(artist) = sheet.artist.as_deref().filter(|a| !a.is_empty()) {
+        let _ = writeln!(html, "<p class=\"artist\">{}</p>", escape(artist));
+    }
+    let meta = song.meta_line();
+    if !meta.is_empty() {
+        let _ = writeln!(html, "<p class=\"meta\">{}</p>", escape(&meta));
+    }
+    html.push_str("</header>\n");
+
+    if let Some(instrument) = diagrams {
+        html.push_str("<section class=\"diagrams\">\n");
+        let mut seen: Vec<&str> = Vec::new();
+        for chord in sheet
+            .sections
+            .iter()
+            .flat_map(|s| &s.lines)
+            .flat_map(|l| &l.chords)
+        {
+            if seen.contains(&chord.chord.as_str()) {
+                continue;
+            }
+            seen.push(&chord.chord);
+            let voicing = Chord::parse(&chord.chord)
+                .and_then(|parsed| find_voicings(&parsed, instrument, 1).into_iter().next());
+            if let Some(voicing) = voicing {
+                html.push_str(&chord_diagram_svg(&chord.chord, &voicing));
+                html.push('\n');
+            }
+        }
+        html.push_str("</section>\n");
+    }
+
+    for section in &sheet.sections {
+        html.push_str("<section class=\"part\">\n");
+        if !section.name.trim().is_empty() {
+            let _ = writeln!(html, "<h2>{}</h2>", escape(&section.name));
+        }
+        for line in &section.lines {
+            match &line.tab {
+                Some(tab) => {
+                    let _ = writeln!(
+                        html,
+                        "<pre class=\"tab\">{}</pre>",
+                        escape(&tab.lines.join("\n"))
+                    );
+                }
+                None => {
+                    html.push_str(&line_html(line));
+                    html.push('\n');
+                }
+            }
+        }
+        html.push_str("</section>\n");
+    }
+    html.push_str("</article>\n</body>\n</html>\n");
+    html
+}
+
+/// One lyric line with each chord as ruby text over the character it falls on
+fn line_html(line: &FetchedLine) -> String {
+    if line.lyrics.trim().is_empty() && line.chords.is_empty() {
+        return "<p class=\"line empty\"></p>".to_string();
+    }
+
+    let chars: Vec<char> = line.lyrics.chars().collect();
+    let mut chords: Vec<_> = line.chords.iter().collect();
+    chords.sort_by_key(|c| c.position);
+
+    let mut html = String::from("<p class=\"line\">");
+    let mut cursor = 0;
+    for chord in chords {
+        let position = (chord.position.max(0) as usize).max(cursor);
+        let end = position.min(chars.len());
+        html.push_str(&escape(&chars[cursor..end].iter().collect::<String>()));
+        cursor = end;
+
+        // Chords past the end of the lyrics (or sharing a character) get a blank base
+        let base = if cursor == position && cursor < chars.len() {
+            cursor += 1;
+            escape(&chars[cursor - 1].to_string())
+        } else {
+            "\u{3000}".to_string()
+        };
+        let _ = write!(
+            html,
+            "<ruby>{}<rt>{}</rt></ruby>",
+            base,
+            escape(&chord.chord)
+        );
+    }
+    html.push_str(&escape(&chars[cursor..].iter().collect::<String>()));
+    html.push_str("</p>");
+    html
+}
+
+/// Fretboard diagram for one voicing, low string on the left
+pub fn chord_diagram_svg(name: &str, voicing: &Voicing) -> String {
+    const STRING_GAP: f32 = 14.0;
+    const FRET_GAP: f32 = 16.0;
+    const FRETS_SHOWN: u8 = 4;
+    const LEFT: f32 = 12.0;
+    const TOP: f32 = 26.0;
+
+    let strings = voicing.frets.len();
+    // Display order: lowest string first
+    let frets: Vec<Option<u8>> = voicing.frets.iter().rev().copied().collect();
+    let highest = frets.iter().flatten().copied().max().unwrap_or(0);
+    let start = if highest <= FRETS_SHOWN {
+        1
+    } else {
+        voicing.base_fret
+    };
+
+    let grid_width = STRING_GAP * (strings.saturating_sub(1)) as f32;
+    let width = grid_width + LEFT * 2.0 + 14.0;
+    let height = TOP + FRET_GAP * FRETS_SHOWN as f32 + 8.0;
+    let x = |index: usize| LEFT + index as f32 * STRING_GAP;
+    let y = |fret: u8| TOP + (fret - start) as f32 * FRET_GAP + FRET_GAP / 2.0;
+
+    let mut svg = String::new();
+    let _ = write!(
+        svg,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" class=\"diagram\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\" \
+         font-family=\"sans-serif\"><title>{name}</title>\
+         <text x=\"{cx}\" y=\"11\" font-size=\"11\" font-weight=\"bold\" text-anchor=\"middle\">{name}</text>",
+        w = width,
+        h = height,
+        cx = LEFT + grid_width / 2.0,
+        name = escape(name),
+    );
+
+    for fret in 0..=FRETS_SHOWN {
+        let line_y = TOP + fret as f32 * FRET_GAP;
+        let stroke = if fret == 0 && start == 1 { 3 } else { 1 };
+        let _ = write!(
+            svg,
+            "<line x1=\"{}\" y1=\"{y}\" x2=\"{}\" y2=\"{y}\" stroke=\"#333\" stroke-width=\"{}\"/>",
+            LEFT,
+            LEFT + grid_width,
+            stroke,
+            y = line_y,
+        );
+    }
+    if start > 1 {
+        let _ = write!(
+            svg,
+            "<text x=\"{}\" y=\"{}\" font-size=\"8\">{}fr</text>",
+            LEFT + grid_width + 3.0,
+            TOP + FRET_GAP / 2.0 + 3.0,
+            start,
+        );
+    }
+    for index in 0..strings {
+        let _ = write!(
+            svg,
+            "<line x1=\"{x}\" y1=\"{}\" x2=\"{x}\" y2=\"{}\" stroke=\"#333\"/>",
+            TOP,
+            TOP + FRET_GAP * FRETS_SHOWN as f32,
+            x = x(index),
+        );
+    }
+
+    if let Some(barre) = voicing.barre_at {
+        let covered: Vec<usize> = (0..strings)
+            .filter(|&i| frets[i].is_some_and(|f| f >= barre))
+            .collect();
+        if let (Some(&first), Some(&last)) = (covered.first(), covered.last()) {
+            let _ = write!(
+                svg,
+                "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"10\" rx=\"5\" fill=\"#222\"/>",
+                x(first) - 5.0,
+                y(barre) - 5.0,
+                x(last) - x(first) + 10.0,
+            );
+        }
+    }
+    for (index, fret) in frets.iter().enumerate() {
+        match fret {
+            None => {
+                let _ = write!(
+                    svg,
+                    "<text x=\"{}\" y=\"{}\" font-size=\"9\" text-anchor=\"middle\">×</text>",
+                    x(index),
+                    TOP - 4.0,
+                );
+            }
+            Some(0) => {
+                let _ = write!(
+                    svg,
+                    "<circle cx=\"{}\" cy=\"{}\" r=\"3.5\" fill=\"none\" stroke=\"#333\"/>",
+                    x(index),
+                    TOP - 7.0,
+                );
+            }
+            Some(fret) if Some(*fret) == voicing.barre_at => {}
+            Some(fret) => {
+                let _ = write!(
+                    svg,
+                    "<circle cx=\"{}\" cy=\"{}\" r=\"5\" fill=\"#222\"/>",
+                    x(index),
+                    y(*fret),
+                );
+            }
+        }
+    }
+    svg.push_str("</svg>");
+    svg
+}
+
+fn render_svg_pages(song: &ExportSong, two_columns: bool) -> Vec<String> {
+    let setup = PageSetup {
+        two_columns,
+        ..PageSetup::default()
+    };
+    let (width, height) = setup.page_size.dimensions();
+    let document = layout(std::slice::from_ref(song), &setup, &ApproxMeasure);
+
+    document
+        .pages
+        .iter()
+        .map(|page| {
+            let mut svg = String::new();
+            let _ = write!(
+                svg,
+                "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}mm\" height=\"{h}mm\" viewBox=\"0 0 {w} {h}\">\n\
+                 <rect width=\"100%\" height=\"100%\" fill=\"#fff\"/>\n<g font-family=\"{}\" xml:space=\"preserve\">\n",
+                escape(FONT_FAMILY),
+                w = width,
+                h = height,
+            );
+            for op in &page.ops {
+                match op {
+                    Op::Text {
+                        x,
+                        y,
+                        size,
+                        tone,
+                        text,
+                    } => {
+                        let _ = writeln!(
+                            svg,
+                            "<text x=\"{:.2}\" y=\"{:.2}\" font-size=\"{:.2}\" fill=\"{}\">{}</text>",
+                            x,
+                            y,
+                            size * PT_TO_MM,
+                            tone_color(*tone),
+                            escape(text),
+                        );
+                    }
+                    Op::Rule { x1, x2, y } => {
+                        let _ = writeln!(
+                            svg,
+                            "<line x1=\"{:.2}\" y1=\"{y:.2}\" x2=\"{:.2}\" y2=\"{y:.2}\" stroke=\"#666\" stroke-width=\"0.18\"/>",
+                            x1,
+                            x2,
+                            y = y,
+                        );
+                    }
+                }
+            }
+            svg.push_str("</g>\n</svg>\n");
+            svg
+        })
+        .collect()
+}
+
+fn tone_color(tone: Tone) -> &'static str {
+    match tone {
+        Tone::Text => "#000",
+        Tone::Chord => "#1a4dbf",
+        Tone::Muted => "#666",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::{FetchedChord, FetchedSection};
+
+    fn sheet() -> FetchedChordSheet {
+        let mut sheet = FetchedChordSheet::new(String::new());
+        sheet.title = Some("夜に<駆ける>".to_string());
+        sheet.key = Some("Eb".to_string());
+        let mut section = FetchedSection::new("Aメロ");
+        section.lines.push(FetchedLine::with_chords(
+            "沈むように",
+            vec![FetchedChord::new("F", 0), FetchedChord::new("Gm7", 2)],
+        ));
+        section.lines.push(FetchedLine::with_chords(
+            "",
+            vec![FetchedChord::new("C", 0), FetchedChord::new("D", 4)],
+        ));
+        sheet.sections.push(section);
+        sheet
+    }
+
+    #[test]
+    fn test_ruby_chords() {
+        let html = export_html(
+            &sheet(),
+            &HtmlOptions::default(),
+            &Instrument::standard_guitar(),
+        )
+        .remove(0);
+        assert!(html.contains("<title>夜に&lt;駆ける&gt;</title>"));
+        assert!(html.contains("<p class=\"meta\">Key: Eb</p>"));
+        assert!(html.contains(
+            "<p class=\"line\"><ruby>沈<rt>F</rt></ruby>む<ruby>よ<rt>Gm7</rt></ruby>うに</p>"
+        ));
+        // Chord-only line: blank bases
+        assert!(html.contains("<ruby>\u{3000}<rt>C</rt></ruby><ruby>\u{3000}<rt>D</rt></ruby>"));
+        assert!(!html.contains("class=\"diagrams\""));
+    }
+
+    #[test]
+    fn test_chord_diagrams() {
+        let options = HtmlOptions {
+            chord_diagrams: true,
+            ..HtmlOptions::default()
+        };
+        let html = export_html(&sheet(), &options, &Instrument::standard_guitar()).remove(0);
+        assert_eq!(html.matches("class=\"diagram\"").count(), 4);
+        assert!(html.contains("<title>Gm7</title>"));
+    }
+
+    #[test]
+    fn test_barre_diagram() {
+        let voicing =
+            Voicing::from_frets(vec![Some(1), Some(1), Some(2), Some(3), Some(3), Some(1)])
+                .unwrap();
+        let svg = chord_diagram_svg("F", &voicing);
+        assert!(svg.contains("<rect"));
+        // Barre covers all six strings; three more dots above it
+        assert_eq!(svg.matches("<circle").count(), 3);
+        assert!(!svg.contains("fr</text>"));
+
+        let high = Voicing::from_frets(vec![Some(5), Some(5), Some(6), Some(7), Some(7), Some(5)])
+            .unwrap();
+        assert!(chord_diagram_svg("A", &high).contains(">5fr</text>"));
+    }
+
+    #[test]
+    fn test_svg_pages() {
+        let options = HtmlOptions {
+            format: HtmlFormat::SvgPages,
+            ..HtmlOptions::default()
+        };
+        let pages = export_html(&sheet(), &options, &Instrument::standard_guitar());
+        assert_eq!(pages.len(), 1);
+        assert!(pages[0].starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"210mm\""));
+        assert!(pages[0].contains(">沈むように</text>"));
+        assert!(pages[0].contains("fill=\"#1a4dbf\">Gm7</text>"));
+    }
+}
@@ -0,0 +1,650 @@
+//! Page layout shared by the PDF and SVG exports
+//!
+//! Layout runs against a `Measure`, so the PDF path measures with the
+//! embedded font and the SVG path with an approximation. Each song starts on
+//! a new page with a full-width header; its sections are then flowed into one
+//! or two columns. A section moves to the next column instead of breaking,
+//! unless it is taller than a whole column. The table of contents is laid out
+//! last, once the song page numbers are known.
+
+use super::font::{Measure, PT_TO_MM};
+use super::ExportSong;
+use crate::parsers::{FetchedLine, FetchedSection};
+use serde::{Deserialize, Serialize};
+
+const MARGIN: f32 = 15.0;
+const COLUMN_GAP: f32 = 8.0;
+/// Space kept free at the bottom of each page for the page number
+const FOOTER: f32 = 8.0;
+const LINE_SPACING: f32 = 1.3;
+/// Chord names relative to the lyric size
+const CHORD_SCALE: f32 = 0.85;
+const TITLE_SCALE: f32 = 1.7;
+
+/// Lyric size in points when none is given
+pub const DEFAULT_FONT_SIZE: f32 = 11.0;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PageSize {
+    #[default]
+    A4,
+    Letter,
+}
+
+impl PageSize {
+    /// Width and height in millimetres
+    pub fn dimensions(self) -> (f32, f32) {
+        match self {
+            PageSize::A4 => (210.0, 297.0),
+            PageSize::Letter => (215.9, 279.4),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PageSetup {
+    pub page_size: PageSize,
+    /// Lyric size in points
+    pub font_size: f32,
+    pub two_columns: bool,
+    /// Contents page title; None for no contents page
+    pub contents: Option<String>,
+}
+
+impl Default for PageSetup {
+    fn default() -> Self {
+        Self {
+            page_size: PageSize::default(),
+            font_size: DEFAULT_FONT_SIZE,
+            two_columns: false,
+            contents: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tone {
+    Text,
+    Chord,
+    Muted,
+}
+
+/// Drawing operation; `y` is measured from the top of the page (or row)
+#[derive(Debug, Clone, PartialEq)]
+pub enum Op {
+    /// `y` is the text baseline
+    Text {
+        x: f32,
+        y: f32,
+        size: f32,
+        tone: Tone,
+        text: String,
+    },
+    Rule {
+        x1: f32,
+        x2: f32,
+        y: f32,
+    },
+}
+
+impl Op {
+    fn offset(&self, dx: f32, dy: f32) -> Op {
+        match self {
+            Op::Text {
+                x,
+                y,
+                size,
+                tone,
+                text,
+            } => Op::Text {
+                x: x + dx,
+                y: y + dy,
+                size: *size,
+                tone: *tone,
+                text: text.clone(),
+            },
+            Op::Rule { x1, x2, y } => Op::Rule {
+                x1: x1 + dx,
+                x2: x2 + dx,
+                y: y + dy,
+            },
+        }
+    }
+}
+
+/// One unbreakable line of output (a chord line and its lyric line share a row)
+struct Row {
+    height: f32,
+    ops: Vec<Op>,
+}
+
+#[derive(Debug, Default)]
+pub struct Page {
+    pub ops: Vec<Op>,
+}
+
+/// Laid-out document, ready to render
+pub struct Document {
+    pub pages: Vec<Page>,
+    /// Song titles and the page index each starts on
+    pub bookmarks: Vec<(String, usize)>,
+}
+
+struct Layout<'a, M: Measure> {
+    measure: &'a M,
+    size: f32,
+    page_width: f32,
+    page_height: f32,
+    two_columns: bool,
+    pages: Vec<Page>,
+    column: usize,
+    column_top: f32,
+    y: f32,
+}
+
+impl<'a, M: Measure> Layout<'a, M> {
+    fn new(measure: &'a M, setup: &PageSetup, two_columns: bool) -> Self {
+        let (page_width, page_height) = setup.page_size.dimensions();
+        Self {
+            measure,
+            size: setup.font_size,
+            page_width,
+            page_height,
+            two_columns,
+            pages: Vec::new(),
+            column: 0,
+            column_top: MARGIN,
+            y: MARGIN,
+        }
+    }
+
+    fn content_width(&self) -> f32 {
+        self.page_width - 2.0 * MARGIN
+    }
+
+    fn column_width(&self) -> f32 {
+        if self.two_columns {
+            (self.content_width() - COLUMN_GAP) / 2.0
+        } else {
+            self.content_width()
+        }
+    }
+
+    fn column_x(&self) -> f32 {
+        MARGIN + self.column as f32 * (self.column_width() + COLUMN_GAP)
+    }
+
+    fn bottom(&self) -> f32 {
+        self.page_height - MARGIN - FOOTER
+    }
+
+    fn new_page(&mut self) {
+        self.pages.push(Page::default());
+        self.column = 0;
+        self.column_top = MARGIN;
+        self.y = MARGIN;
+    }
+
+    fn next_column(&mut self) {
+        if self.two_columns && self.column == 0 {
+            self.column = 1;
+            self.y = self.column_top;
+        } else {
+            self.new_page();
+        }
+    }
+
+    fn emit(&mut self, row: &Row, x: f32) {
+        let y = self.y;
+        let page = self.pages.last_mut().expect("layout starts with a page");
+        page.ops.extend(row.ops.iter().map(|op| op.offset(x, y)));
+        self.y += row.height;
+    }
+
+    /// Rows spanning both columns at the top of the current page
+    fn place_header(&mut self, rows: &[Row]) {
+        for row in rows {
+            self.emit(row, MARGIN);
+        }
+        self.column_top = self.y;
+    }
+
+    /// Rows flowed into the columns; `keep_together` moves the whole block
+    /// to the next column when it fits there but not here
+    fn place_block(&mut self, rows: &[Row], gap_before: f32, keep_together: bool) {
+        if self.y > self.column_top {
+            self.y += gap_before;
+        }
+        let height: f32 = rows.iter().map(|row| row.height).sum();
+        let fits_column = height <= self.bottom() - self.column_top;
+        if keep_together && fits_column && self.y + height > self.bottom() {
+            self.next_column();
+        }
+        for row in rows {
+            if self.y + row.height > self.bottom() && self.y > self.column_top {
+                self.next_column();
+            }
+            let x = self.column_x();
+            self.emit(row, x);
+        }
+    }
+
+    fn text_row(&self, text: &str, size: f32, tone: Tone) -> Row {
+        Row {
+            height: size * PT_TO_MM * LINE_SPACING,
+            ops: vec![Op::Text {
+                x: 0.0,
+                y: size * PT_TO_MM,
+                size,
+                tone,
+                text: text.to_string(),
+            }],
+        }
+    }
+
+    /// Lay out one song from a fresh page; returns its first page index
+    fn song(&mut self, song: &ExportSong) -> usize {
+        self.new_page();
+        let start = self.pages.len() - 1;
+
+        let mut header = vec![self.text_row(song.title(), self.size * TITLE_SCALE, Tone::Text)];
+        if let Some(artist) = song.sheet.artist.as_deref().filter(|a| !a.is_empty()) {
+            header.push(self.text_row(artist, self.size, Tone::Muted));
+        }
+        let meta = song.meta_line();
+        if !meta.is_empty() {
+            header.push(self.text_row(&meta, self.size * CHORD_SCALE, Tone::Muted));
+        }
+        let notes = song.notes.as_deref().unwrap_or("");
+        for line in notes.lines().filter(|l| !l.trim().is_empty()) {
+            header.push(self.text_row(line, self.size * CHORD_SCALE, Tone::Muted));
+        }
+        header.push(Row {
+            height: self.size * PT_TO_MM,
+            ops: vec![Op::Rule {
+                x1: 0.0,
+                x2: self.content_width(),
+                y: self.size * PT_TO_MM * 0.4,
+            }],
+        });
+        self.place_header(&header);
+
+        for section in &song.sheet.sections {
+            let rows = self.section_rows(section);
+            self.place_block(&rows, self.size * PT_TO_MM, true);
+        }
+        start
+    }
+
+    fn section_rows(&self, section: &FetchedSection) -> Vec<Row> {
+        let mut rows = Vec::new();
+        if !section.name.trim().is_empty() {
+            let size = self.size * 0.9;
+            let mut heading = self.text_row(&section.name, size, Tone::Muted);
+            let underline = self.measure.text_width(&section.name, size);
+            heading.ops.push(Op::Rule {
+                x1: 0.0,
+                x2: underline,
+                y: size * PT_TO_MM * 1.15,
+            });
+            rows.push(heading);
+        }
+        let width = self.column_width();
+        for line in &section.lines {
+            match &line.tab {
+                Some(tab) => rows.extend(self.tab_rows(&tab.lines, width)),
+                None => rows.extend(self.line_rows(line, width)),
+            }
+        }
+        rows
+    }
+
+    /// Tab rows on a fixed character grid so the strings stay aligned in a
+    /// proportional font; shrunk to fit the column when needed
+    fn tab_rows(&self, lines: &[String], width: f32) -> Vec<Row> {
+        let longest = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+        let mut size = self.size * CHORD_SCALE;
+        let widest = |size: f32| {
+            "0123456789-|"
+                .chars()
+                .map(|c| self.measure.text_width(c.encode_utf8(&mut [0; 4]), size))
+                .fold(0.0f32, f32::max)
+        };
+        let mut cell = widest(size);
+        if longest > 0 && cell * longest as f32 > width {
+            size *= width / (cell * longest as f32);
+            cell = widest(size);
+        }
+
+        lines
+            .iter()
+            .map(|line| Row {
+                height: size * PT_TO_MM * LINE_SPACING,
+                ops: line
+                    .chars()
+                    .enumerate()
+                    .filter(|(_, c)| !c.is_whitespace())
+                    .map(|(i, c)| Op::Text {
+                        x: i as f32 * cell,
+                        y: size * PT_TO_MM,
+                        size,
+                        tone: Tone::Text,
+                        text: c.to_string(),
+                    })
+                    .collect(),
+            })
+            .collect()
+    }
+
+    /// Chords above lyrics, aligned by character position; long lyrics wrap
+    fn line_rows(&self, line: &FetchedLine, width: f32) -> Vec<Row> {
+        let lyric_size = self.size;
+        let chord_size = self.size * CHORD_SCALE;
+        let chars: Vec<char> = line.lyrics.chars().collect();
+        let char_width = |c: char| {
+            self.measure
+                .text_width(c.encode_utf8(&mut [0; 4]), lyric_size)
+        };
+
+        // Split the lyrics into runs that fit the column
+        let mut segments = Vec::new();
+        let mut start = 0;
+        let mut run = 0.0;
+        for (i, &c) in chars.iter().enumerate() {
+            let w = char_width(c);
+            if run + w > width && i > start {
+                segments.push(start..i);
+                start = i;
+                run = 0.0;
+            }
+            run += w;
+        }
+        segments.push(start..chars.len());
+
+        let pad = self.measure.text_width(" ", lyric_size);
+        let gap = self.measure.text_width(" ", chord_size);
+        let last = segments.len() - 1;
+        segments
+            .into_iter()
+            .enumerate()
+            .map(|(index, range)| {
+                let lyrics: String = chars[range.clone()].iter().collect();
+                let mut ops = Vec::new();
+                let mut height = 0.0;
+
+                let mut min_x = 0.0f32;
+                for chord in &line.chords {
+                    let position = chord.position.max(0) as usize;
+                    let inside =
+                        range.contains(&position) || (index == last && position >= range.end);
+                    if !inside {
+                        continue;
+                    }
+                    let offset = position - range.start;
+                    let x = if offset <= range.len() {
+                        self.measure.text_width(
+                            &chars[range.start..range.start + offset]
+                                .iter()
+                                .collect::<String>(),
+                            lyric_size,
+                        )
+                    } else {
+                        self.measure.text_width(&lyrics, lyric_size)
+                            + (offset - range.len()) as f32 * pad
+                    };
+                    let x = x.max(min_x);
+                    min_x = x + self.measure.text_width(&chord.chord, chord_size) + gap;
+                    ops.push(Op::Text {
+                        x,
+                        y: chord_size * PT_TO_MM,
+                        size: chord_size,
+                        tone: Tone::Chord,
+                        text: chord.chord.clone(),
+                    });
+                }
+                if !ops.is_empty() {
+                    height += chord_size * PT_TO_MM * LINE_SPACING;
+                }
+
+                if !lyrics.trim().is_empty() {
+                    ops.push(Op::Text {
+                        x: 0.0,
+                        y: height + lyric_size * PT_TO_MM,
+                        size: lyric_size,
+                        tone: Tone::Text,
+                        text: lyrics,
+                    });
+                    height += lyric_size * PT_TO_MM * LINE_SPACING;
+                }
+
+                if ops.is_empty() {
+                    height = lyric_size * PT_TO_MM * 0.6;
+                }
+                Row { height, ops }
+            })
+            .collect()
+    }
+
+    /// Contents pages; `first_song_page` is the page number (1-based) that
+    /// the first body page will get
+    fn contents(
+        &mut self,
+        title: &str,
+        songs: &[ExportSong],
+        starts: &[usize],
+        first_song_page: usize,
+    ) {
+        self.new_page();
+        let heading = self.text_row(title, self.size * TITLE_SCALE, Tone::Text);
+        self.place_header(&[heading]);
+
+        let width = self.column_width();
+        for (song, start) in songs.iter().zip(starts) {
+            let number = (first_song_page + start).to_string();
+            let number_width = self.measure.text_width(&number, self.size);
+
+            let mut label = song.title().to_string();
+            if let Some(artist) = song.sheet.artist.as_deref().filter(|a| !a.is_empty()) {
+                label = format!("{} - {}", label, artist);
+            }
+            let available = width - number_width - self.measure.text_width("  ", self.size);
+            if self.measure.text_width(&label, self.size) > available {
+                while !label.is_empty()
+                    && self.measure.text_width(&format!("{}…", label), self.size) > available
+                {
+                    label.pop();
+                }
+                label.push('…');
+            }
+
+            let mut row = self.text_row(&label, self.size, Tone::Text);
+            row.ops.push(Op::Text {
+                x: width - number_width,
+                y: self.size * PT_TO_MM,
+                size: self.size,
+                tone: Tone::Muted,
+                text: number,
+            });
+            self.place_block(&[row], 0.0, false);
+        }
+    }
+}
+
+/// Lay out songs into pages, with a contents page first if requested
+pub fn layout<M: Measure>(songs: &[ExportSong], setup: &PageSetup, measure: &M) -> Document {
+    let mut body = Layout::new(measure, setup, setup.two_columns);
+    let starts: Vec<usize> = songs.iter().map(|song| body.song(song)).collect();
+
+    let mut pages = Vec::new();
+    let mut offset = 0;
+    if let Some(title) = setup.contents.as_deref() {
+        // Contents length does not depend on the page numbers it lists
+        let mut probe = Layout::new(measure, setup, false);
+        probe.contents(title, songs, &starts, 1);
+        offset = probe.pages.len();
+
+        let mut contents = Layout::new(measure, setup, false);
+        contents.contents(title, songs, &starts, offset + 1);
+        pages = contents.pages;
+    }
+    pages.extend(body.pages);
+
+    // Page numbers
+    let (page_width, page_height) = setup.page_size.dimensions();
+    let size = setup.font_size * CHORD_SCALE;
+    for (index, page) in pages.iter_mut().enumerate() {
+        let number = (index + 1).to_string();
+        page.ops.push(Op::Text {
+            x: (page_width - measure.text_width(&number, size)) / 2.0,
+            y: page_height - MARGIN,
+            size,
+            tone: Tone::Muted,
+            text: number,
+        });
+    }
+
+    let bookmarks = songs
+        .iter()
+        .zip(&starts)
+        .map(|(song, start)| (song.title().to_string(), start + offset))
+        .collect();
+    Document { pages, bookmarks }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::font::ApproxMeasure;
+    use crate::parsers::{FetchedChord, FetchedChordSheet};
+
+    fn song(title: &str, sections: usize, lines: usize) -> ExportSong {
+        let mut sheet = FetchedChordSheet::new(String::new());
+        sheet.title = Some(title.to_string());
+        sheet.key = Some("G".to_string());
+        for s in 0..sections {
+            let mut section = FetchedSection::new(&format!("Section {}", s + 1));
+            for _ in 0..lines {
+                section.lines.push(FetchedLine::with_chords(
+                    "あいうえお",
+                    vec![FetchedChord::new("G", 0), FetchedChord::new("D", 3)],
+                ));
+            }
+            sheet.sections.push(section);
+        }
+        ExportSong {
+            sheet,
+            bpm: Some(120),
+            time_signature: None,
+            notes: None,
+        }
+    }
+
+    fn texts(page: &Page) -> Vec<(&str, f32, f32)> {
+        page.ops
+            .iter()
+            .filter_map(|op| match op {
+                Op::Text { x, y, text, .. } => Some((text.as_str(), *x, *y)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn find(document: &Document, text: &str) -> Vec<(usize, f32, f32)> {
+        document
+            .pages
+            .iter()
+            .enumerate()
+            .flat_map(|(index, page)| {
+                texts(page)
+                    .into_iter()
+                    .filter(|(t, _, _)| *t == text)
+                    .map(move |(_, x, y)| (index, x, y))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_chords_align_with_lyrics() {
+        let document = layout(&[song("Song", 1, 1)], &PageSetup::default(), &ApproxMeasure);
+        let lyrics = find(&document, "あいうえお")[0];
+        let d = find(&document, "D")[0];
+        // "あいう" is three full-width characters at 11pt
+        let expected = lyrics.1 + 3.0 * 11.0 * PT_TO_MM;
+        assert!((d.1 - expected).abs() < 0.01);
+        assert!(d.2 < lyrics.2);
+        assert!(find(&document, "Key: G / BPM: 120").len() == 1);
+    }
+
+    #[test]
+    fn test_long_lines_wrap() {
+        let mut sheet = FetchedChordSheet::new(String::new());
+        let mut section = FetchedSection::new("");
+        section.lines.push(FetchedLine::with_chords(
+            &"あ".repeat(60),
+            vec![FetchedChord::new("C", 0), FetchedChord::new("Am", 55)],
+        ));
+        sheet.sections.push(section);
+        let song = ExportSong {
+            sheet,
+            bpm: None,
+            time_signature: None,
+            notes: None,
+        };
+        let document = layout(&[song], &PageSetup::default(), &ApproxMeasure);
+        let c = find(&document, "C")[0];
+        let am = find(&document, "Am")[0];
+        // The second chord lands on the wrapped row, back near the margin
+        assert!(am.2 > c.2);
+        assert!(am.1 < MARGIN + 100.0);
+    }
+
+    #[test]
+    fn test_sections_are_kept_together() {
+        // Each section is ~95mm tall, so only two fit on a page
+        let document = layout(&[song("Song", 3, 9)], &PageSetup::default(), &ApproxMeasure);
+        assert_eq!(document.pages.len(), 2);
+        let third = find(&document, "Section 3")[0];
+        assert_eq!(third.0, 1);
+        assert!((third.2 - MARGIN - 0.9 * 11.0 * PT_TO_MM).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_two_columns() {
+        let setup = PageSetup {
+            two_columns: true,
+            ..PageSetup::default()
+        };
+        let document = layout(&[song("Song", 3, 9)], &setup, &ApproxMeasure);
+        assert_eq!(document.pages.len(), 1);
+        let first = find(&document, "Section 1")[0];
+        let third = find(&document, "Section 3")[0];
+        assert!(third.1 > first.1 + 80.0);
+        assert!((third.2 - first.2).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_table_of_contents() {
+        let setup = PageSetup {
+            contents: Some("Live 2026".to_string()),
+            ..PageSetup::default()
+        };
+        let songs = [song("One", 1, 1), song("Two", 3, 9), song("Three", 1, 1)];
+        let document = layout(&songs, &setup, &ApproxMeasure);
+        // Contents, One, Two (2 pages), Three
+        assert_eq!(document.pages.len(), 5);
+        let contents = texts(&document.pages[0]);
+        assert_eq!(contents[0].0, "Live 2026");
+        assert_eq!(contents[2].0, "2");
+        assert_eq!(contents[4].0, "3");
+        assert_eq!(contents[6].0, "5");
+        assert_eq!(
+            document.bookmarks,
+            vec![
+                ("One".to_string(), 1),
+                ("Two".to_string(), 2),
+                ("Three".to_string(), 4)
+            ]
+        );
+    }
+}
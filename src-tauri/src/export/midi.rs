@@ -0,0 +1,535 @@
+//! Standard MIDI backing tracks
+//!
+//! Chords are timed like the lead sheet (see `timing`) and played at sounding
+//! pitch: each shape is voiced on a standard guitar and raised by the capo.
+//! Track 0 carries tempo, meter and section markers; guitar, bass and drums
+//! follow on channels 1, 2 and 10.
+
+use super::timing::{played_lines, time_line, TimeSignature};
+use super::ExportSong;
+use crate::error::ExportError;
+use crate::theory::chord::Chord;
+use crate::theory::instrument::Instrument;
+use crate::theory::voicing::find_voicings;
+use midly::num::{u15, u24, u28, u4, u7};
+use midly::{Format, Header, MetaMessage, MidiMessage, Smf, Timing, TrackEvent, TrackEventKind};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+
+const TICKS_PER_QUARTER: u32 = 480;
+pub const DEFAULT_BPM: u32 = 120;
+
+const GUITAR_CHANNEL: u8 = 0;
+const BASS_CHANNEL: u8 = 1;
+const DRUM_CHANNEL: u8 = 9;
+/// General MIDI programs (zero-based): steel acoustic guitar, fingered bass
+const GUITAR_PROGRAM: u8 = 25;
+const BASS_PROGRAM: u8 = 33;
+
+const KICK: u8 = 36;
+const SNARE: u8 = 38;
+const CLOSED_HAT: u8 = 42;
+const CRASH: u8 = 49;
+
+/// Ticks between the strings of a strum
+const STRUM_SPREAD: u32 = 12;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MidiStyle {
+    /// Down strums on the beat, up strums on off-beats
+    #[default]
+    Strum,
+    /// Broken chords in eighth notes, bass string first
+    Arpeggio,
+}
+
+/// A stretch of beats under one chord (None = rest)
+struct Span<'a> {
+    start: u32,
+    beats: u32,
+    chord: Option<&'a str>,
+}
+
+/// Flatten the sheet into chord spans, playing each section `repeat_count` times
+fn spans<'a>(
+    song: &'a ExportSong,
+    time: &TimeSignature,
+) -> (Vec<Span<'a>>, Vec<(u32, &'a str)>, u32) {
+    let mut spans = Vec::new();
+    let mut markers = Vec::new();
+    let mut beat = 0;
+    let mut previous: Option<&str> = None;
+
+    for section in &song.sheet.sections {
+        for _ in 0..section.repeat_count.unwrap_or(1).max(1) {
+            markers.push((beat, section.name.trim()));
+            for line in played_lines(section) {
+                let timed = time_line(line, time);
+                let total = timed.total_beats(time) as u32;
+                // Leading beats before the first chord, or a whole lyric line, keep the last chord
+                let first = timed.chords.first().map_or(total, |c| c.start as u32);
+                if first > 0 {
+                    spans.push(Span {
+                        start: beat,
+                        beats: first,
+                        chord: previous,
+                    });
+                }
+                for chord in timed.chords.iter().filter(|c| c.beats > 0) {
+                    let name = Some(chord.name).filter(|_| Chord::parse(chord.name).is_some());
+                    spans.push(Span {
+                        start: beat + chord.start as u32,
+                        beats: chord.beats as u32,
+                        chord: name,
+                    });
+                    previous = name;
+                }
+                beat += total;
+            }
+        }
+    }
+    (spans, markers, beat)
+}
+
+/// Collects absolute-time events, then writes them as a delta-timed track
+#[derive(Default)]
+struct TrackBuilder<'a> {
+    events: Vec<(u32, TrackEventKind<'a>)>,
+}
+
+impl<'a> TrackBuilder<'a> {
+    fn meta(&mut self, tick: u32, message: MetaMessage<'a>) {
+        self.events.push((tick, TrackEventKind::Meta(message)));
+    }
+
+    fn program(&mut self, channel: u8, program: u8) {
+        let message = MidiMessage::ProgramChange {
+            program: u7::new(program),
+        };
+        self.events.push((0, midi(channel, message)));
+    }
+
+    fn note(&mut self, channel: u8, key: u8, velocity: u8, start: u32, length: u32) {
+        let (key, vel) = (u7::new(key.min(127)), u7::new(velocity.min(127)));
+        self.events
+            .push((start, midi(channel, MidiMessage::NoteOn { key, vel })));
+        let off = MidiMessage::NoteOff {
+            key,
+            vel: u7::new(0),
+        };
+        self.events
+            .push((start + length.max(1), midi(channel, off)));
+    }
+
+    fn build(mut self, name: &'a str) -> Vec<TrackEvent<'a>> {
+        // Note-offs go before note-ons on the same tick so repeated notes retrigger
+        self.events.sort_by_key(|(tick, kind)| {
+            let is_on = matches!(
+                kind,
+                TrackEventKind::Midi {
+                    message: MidiMessage::NoteOn { .. },
+                    ..
+                }
+            );
+            (*tick, is_on)
+        });
+        let mut track = vec![TrackEvent {
+            delta: u28::new(0),
+            kind: TrackEventKind::Meta(MetaMessage::TrackName(name.as_bytes())),
+        }];
+        let mut last = 0;
+        for (tick, kind) in self.events {
+            track.push(TrackEvent {
+                delta: u28::new(tick - last),
+                kind,
+            });
+            last = tick;
+        }
+        track.push(TrackEvent {
+            delta: u28::new(0),
+            kind: TrackEventKind::Meta(MetaMessage::EndOfTrack),
+        });
+        track
+    }
+}
+
+fn midi(channel: u8, message: MidiMessage) -> TrackEventKind<'static> {
+    TrackEventKind::Midi {
+        channel: u4::new(channel),
+        message,
+    }
+}
+
+/// Sounding guitar pitches for a chord shape, lowest first
+fn guitar_notes(
+    name: &str,
+    capo: u8,
+    guitar: &Instrument,
+    cache: &mut HashMap<String, Vec<u8>>,
+) -> Vec<u8> {
+    if let Some(notes) = cache.get(name) {
+        return notes.clone();
+    }
+    let Some(chord) = Chord::parse(name) else {
+        return Vec::new();
+    };
+    let mut notes: Vec<u8> = match find_voicings(&chord, guitar, 1).first() {
+        Some(voicing) => voicing
+            .frets
+            .iter()
+            .zip(&guitar.strings)
+            .filter_map(|(fret, open)| fret.map(|fret| open + fret + capo))
+            .collect(),
+        // Close position above the bass note
+        None => {
+            let root = 48 + chord.root + capo;
+            let mut notes = vec![36 + (chord.bass_note() + capo) % 12 + 12];
+            notes.extend(chord.tones().iter().map(|t| root + t.interval));
+            notes
+        }
+    };
+    notes.sort_unstable();
+    notes.dedup();
+    cache.insert(name.to_string(), notes.clone());
+    notes
+}
+
+/// Bass root in the E1..D#2 octave
+fn bass_key(name: &str, capo: u8) -> Option<u8> {
+    let chord = Chord::parse(name)?;
+    Some(28 + (chord.bass_note() + capo + 8) % 12)
+}
+
+fn strum(
+    track: &mut TrackBuilder,
+    notes: &[u8],
+    start: u32,
+    length: u32,
+    down: bool,
+    velocity: u8,
+) {
+    // Up strums catch only the top strings
+    let strings: Vec<u8> = if down {
+        notes.to_vec()
+    } else {
+        notes.iter().rev().take(4).copied().collect()
+    };
+    for (index, &key) in strings.iter().enumerate() {
+        let offset = index as u32 * STRUM_SPREAD;
+        if offset < length {
+            track.note(
+                GUITAR_CHANNEL,
+                key,
+                velocity,
+                start + offset,
+                length - offset,
+            );
+        }
+    }
+}
+
+fn play_guitar(
+    track: &mut TrackBuilder,
+    style: MidiStyle,
+    notes: &[u8],
+    span: &Span,
+    time: &TimeSignature,
+    beat_ticks: u32,
+) {
+    let start = span.start * beat_ticks;
+    let end = (span.start + span.beats) * beat_ticks;
+    // Quarter-note beats split into eighths; eighth-note beats are already eighths
+    let subdivisions = if time.beat_type <= 4 { 2 } else { 1 };
+    let step = beat_ticks / subdivisions;
+
+    match style {
+        MidiStyle::Strum => {
+            for beat in span.start..span.start + span.beats {
+                let tick = beat * beat_ticks;
+                let accent = if beat % time.beats == 0 { 100 } else { 88 };
+                let up_follows = subdivisions == 2 && beat % 2 == 1;
+                let down_length = if up_follows { step } else { beat_ticks };
+                strum(
+                    track,
+                    notes,
+                    tick,
+                    down_length.min(end - tick),
+                    true,
+                    accent,
+                );
+                if up_follows {
+                    strum(track, notes, tick + step, step, false, 70);
+                }
+            }
+        }
+        MidiStyle::Arpeggio => {
+            let (bass, upper) = notes.split_first().unwrap_or((&0, &[]));
+            let mut tick = start;
+            let mut index = 0;
+            while tick < end {
+                let key = match (index, upper.len()) {
+                    (0, _) | (_, 0) => *bass,
+                    (i, n) => upper[(i - 1) % n],
+                };
+                let velocity = if index == 0 { 90 } else { 72 };
+                track.note(
+                    GUITAR_CHANNEL,
+                    key,
+                    velocity,
+                    tick,
+                    (step * 2).min(end - tick),
+                );
+                tick += step;
+                index += 1;
+            }
+        }
+    }
+}
+
+fn play_bass(
+    track: &mut TrackBuilder,
+    key: u8,
+    span: &Span,
+    time: &TimeSignature,
+    beat_ticks: u32,
+) {
+    let half_bar = (time.beats >= 4 && time.beats.is_multiple_of(2)).then_some(time.beats / 2);
+    let hits: Vec<u32> = (span.start..span.start + span.beats)
+        .filter(|&beat| {
+            beat == span.start
+                || beat.is_multiple_of(time.beats)
+                || Some(beat % time.beats) == half_bar
+        })
+        .collect();
+    let end = span.start + span.beats;
+    for (index, &beat) in hits.iter().enumerate() {
+        let next = hits.get(index + 1).copied().unwrap_or(end);
+        let velocity = if beat.is_multiple_of(time.beats) {
+            96
+        } else {
+            84
+        };
+        track.note(
+            BASS_CHANNEL,
+            key,
+            velocity,
+            beat * beat_ticks,
+            (next - beat) * beat_ticks - 10,
+        );
+    }
+}
+
+/// Kick and snare on the main pulses, closed hats on every beat and off-beat
+fn play_drums(
+    track: &mut TrackBuilder,
+    total_beats: u32,
+    markers: &[(u32, &str)],
+    time: &TimeSignature,
+    beat_ticks: u32,
+) {
+    let compound = time.beat_type == 8 && time.beats.is_multiple_of(3);
+    let length = beat_ticks / 4;
+    for beat in 0..total_beats {
+        let tick = beat * beat_ticks;
+        let position = beat % time.beats;
+        let hit = if compound {
+            // Dotted-quarter pulses: kick on the first of each pair, snare on the second
+            position
+                .is_multiple_of(3)
+                .then_some(if (position / 3).is_multiple_of(2) {
+                    KICK
+                } else {
+                    SNARE
+                })
+        } else if time.beats == 3 {
+            Some(if position == 0 { KICK } else { SNARE })
+        } else {
+            Some(if position.is_multiple_of(2) {
+                KICK
+            } else {
+                SNARE
+            })
+        };
+        if let Some(key) = hit {
+            track.note(
+                DRUM_CHANNEL,
+                key,
+                if position == 0 { 110 } else { 96 },
+                tick,
+                length,
+            );
+        }
+        track.note(DRUM_CHANNEL, CLOSED_HAT, 70, tick, length);
+        if time.beat_type <= 4 {
+            track.note(DRUM_CHANNEL, CLOSED_HAT, 55, tick + beat_ticks / 2, length);
+        }
+    }
+    for &(beat, _) in markers.iter().filter(|(beat, _)| *beat < total_beats) {
+        track.note(DRUM_CHANNEL, CRASH, 100, beat * beat_ticks, beat_ticks);
+    }
+}
+
+/// Render a backing track for a song as Standard MIDI File bytes
+pub fn render_midi(song: &ExportSong, style: MidiStyle) -> Result<Vec<u8>, ExportError> {
+    let time = TimeSignature::parse(song.time_signature.as_deref());
+    let bpm = song.bpm.unwrap_or(DEFAULT_BPM).clamp(20, 400);
+    let capo = song.sheet.capo.unwrap_or(0).clamp(0, 12) as u8;
+    let beat_ticks = (TICKS_PER_QUARTER as f64 * time.beat_in_quarters()) as u32;
+    let (spans, markers, total_beats) = spans(song, &time);
+
+    let mut conductor = TrackBuilder::default();
+    conductor.meta(0, MetaMessage::Tempo(u24::new(60_000_000 / bpm)));
+    conductor.meta(
+        0,
+        MetaMessage::TimeSignature(
+            time.beats as u8,
+            time.beat_type.trailing_zeros() as u8,
+            24,
+            8,
+        ),
+    );
+    for &(beat, name) in &markers {
+        conductor.meta(beat * beat_ticks, MetaMessage::Marker(name.as_bytes()));
+    }
+    // Keeps the conductor track as long as the music
+    conductor.meta(total_beats * beat_ticks, MetaMessage::Text(b""));
+
+    let guitar_instrument = Instrument::standard_guitar();
+    let mut voicings = HashMap::new();
+    let mut guitar = TrackBuilder::default();
+    let mut bass = TrackBuilder::default();
+    guitar.program(GUITAR_CHANNEL, GUITAR_PROGRAM);
+    bass.program(BASS_CHANNEL, BASS_PROGRAM);
+    for span in &spans {
+        let Some(name) = span.chord else { continue };
+        let notes = guitar_notes(name, capo, &guitar_instrument, &mut voicings);
+        if !notes.is_empty() {
+            play_guitar(&mut guitar, style, &notes, span, &time, beat_ticks);
+        }
+        if let Some(key) = bass_key(name, capo) {
+            play_bass(&mut bass, key, span, &time, beat_ticks);
+        }
+    }
+
+    let mut drums = TrackBuilder::default();
+    play_drums(&mut drums, total_beats, &markers, &time, beat_ticks);
+
+    let mut smf = Smf::new(Header::new(
+        Format::Parallel,
+        Timing::Metrical(u15::new(TICKS_PER_QUARTER as u16)),
+    ));
+    smf.tracks.push(conductor.build(song.title()));
+    smf.tracks.push(guitar.build("Guitar"));
+    smf.tracks.push(bass.build("Bass"));
+    smf.tracks.push(drums.build("Drums"));
+
+    let mut bytes = Vec::new();
+    smf.write_std(&mut bytes)?;
+    Ok(bytes)
+}
+
+/// Render a backing track and write it to `path`
+pub fn export_midi(song: &ExportSong, style: MidiStyle, path: &Path) -> Result<(), ExportError> {
+    let bytes = render_midi(song, style)?;
+    std::fs::write(path, bytes)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::{FetchedChord, FetchedChordSheet, FetchedLine, FetchedSection};
+
+    fn song(capo: i32, repeat_count: Option<u32>, time_signature: &str) -> ExportSong {
+        let mut sheet = FetchedChordSheet::new(String::new());
+        sheet.title = Some("Song".to_string());
+        sheet.capo = Some(capo);
+        let mut section = FetchedSection::new("Verse");
+        section.repeat_count = repeat_count;
+        section.lines.push(FetchedLine::with_chords(
+            "歌詞",
+            vec![FetchedChord::new("G", 0), FetchedChord::new("D/F#", 1)],
+        ));
+        sheet.sections.push(section);
+        ExportSong {
+            sheet,
+            bpm: Some(90),
+            time_signature: Some(time_signature.to_string()),
+            notes: None,
+        }
+    }
+
+    /// (tick, channel, key) of every note-on in a track
+    fn note_ons(track: &[TrackEvent]) -> Vec<(u32, u8, u8)> {
+        let mut tick = 0;
+        let mut notes = Vec::new();
+        for event in track {
+            tick += event.delta.as_int();
+            if let TrackEventKind::Midi {
+                channel,
+                message: MidiMessage::NoteOn { key, .. },
+            } = event.kind
+            {
+                notes.push((tick, channel.as_int(), key.as_int()));
+            }
+        }
+        notes
+    }
+
+    #[test]
+    fn test_midi_structure() {
+        let bytes = render_midi(&song(0, None, "4/4"), MidiStyle::Strum).unwrap();
+        let smf = Smf::parse(&bytes).unwrap();
+        assert_eq!(smf.header.format, Format::Parallel);
+        assert_eq!(smf.tracks.len(), 4);
+        let conductor: Vec<_> = smf.tracks[0].iter().map(|e| e.kind).collect();
+        assert!(conductor.contains(&TrackEventKind::Meta(MetaMessage::Tempo(u24::new(666_666)))));
+        assert!(
+            conductor.contains(&TrackEventKind::Meta(MetaMessage::TimeSignature(
+                4, 2, 24, 8
+            )))
+        );
+        assert!(conductor.contains(&TrackEventKind::Meta(MetaMessage::Marker(b"Verse"))));
+
+        assert!(note_ons(&smf.tracks[3])
+            .iter()
+            .all(|&(_, channel, _)| channel == 9));
+        // G for two beats, then D/F# with F# in the bass
+        let bass = note_ons(&smf.tracks[2]);
+        assert_eq!(bass, vec![(0, 1, 31), (960, 1, 30)]);
+    }
+
+    #[test]
+    fn test_capo_raises_sounding_pitch() {
+        let lowest = |capo| {
+            let bytes = render_midi(&song(capo, None, "4/4"), MidiStyle::Strum).unwrap();
+            let smf = Smf::parse(&bytes).unwrap();
+            note_ons(&smf.tracks[1])
+                .iter()
+                .filter(|n| n.0 == 0)
+                .map(|n| n.2)
+                .min()
+                .unwrap()
+        };
+        assert_eq!(lowest(2), lowest(0) + 2);
+    }
+
+    #[test]
+    fn test_repeats_and_meter() {
+        let once = Smf::parse(&render_midi(&song(0, None, "3/4"), MidiStyle::Arpeggio).unwrap())
+            .unwrap()
+            .tracks[2]
+            .len();
+        let bytes = render_midi(&song(0, Some(2), "3/4"), MidiStyle::Arpeggio).unwrap();
+        let smf = Smf::parse(&bytes).unwrap();
+        let bass = note_ons(&smf.tracks[2]);
+        // One 3/4 bar per pass: G on beat 1, D/F# from beat 2 (480 ticks per beat)
+        assert_eq!(
+            bass.iter().map(|n| n.0).collect::<Vec<_>>(),
+            vec![0, 480, 1440, 1920]
+        );
+        assert!(smf.tracks[2].len() > once);
+    }
+}
@@ -0,0 +1,71 @@
+//! Song exports (PDF, HTML, SVG, text, MusicXML, MIDI, click-track WAV)
+//!
+//! Exporters take one or many `ExportSong`s so a single song and a whole
+//! playlist go through the same path. PDF, HTML and text take a `SheetView`
+//! for lyrics-only or chord-chart output.
+
+pub mod click;
+pub mod font;
+pub mod html;
+pub mod layout;
+pub mod midi;
+pub mod musicxml;
+pub mod pdf;
+pub mod text;
+pub mod timing;
+pub mod view;
+
+use crate::parsers::FetchedChordSheet;
+use serde::{Deserialize, Serialize};
+
+/// A sheet plus the song metadata that is not part of the parsed sheet
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportSong {
+    pub sheet: FetchedChordSheet,
+    #[serde(default)]
+    pub bpm: Option<u32>,
+    /// "4/4", "3/4", "6/8" (songs.time_signature)
+    #[serde(default)]
+    pub time_signature: Option<String>,
+    /// Performance notes printed under the header (songs.notes, or a
+    /// setlist entry's notes)
+    #[serde(default)]
+    pub notes: Option<String>,
+}
+
+impl ExportSong {
+    pub fn title(&self) -> &str {
+        self.sheet.title.as_deref().unwrap_or("Untitled")
+    }
+
+    /// "Key: G / Capo: 2 / BPM: 120", omitting unknown values
+    pub fn meta_line(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(key) = &self.sheet.key {
+            parts.push(format!("Key: {}", key));
+        }
+        if let Some(capo) = self.sheet.capo.filter(|&capo| capo > 0) {
+            parts.push(format!("Capo: {}", capo));
+        }
+        if let Some(bpm) = self.bpm {
+            parts.push(format!("BPM: {}", bpm));
+        }
+        parts.join(" / ")
+    }
+}
+
+/// Escape text for HTML/XML content and attribute values
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
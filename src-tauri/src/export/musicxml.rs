@@ -0,0 +1,589 @@
+//! MusicXML lead-sheet export
+//!
+//! Sheets have no rhythm, so lines get their bars from `timing` and chords are
+//! spread evenly across them. Every beat is a slash note;
+//! a chord's `<harmony>` precedes the beat it starts on (offset within the beat
+//! when several chords share it), and the lyrics between two chords are sung
+//! on that beat.
+
+use super::timing::{played_lines, time_line, TimeSignature};
+use super::{escape, ExportSong};
+use crate::parsers::{FetchedLine, FetchedSection};
+use crate::theory::chord::{Chord, Quality, Seventh};
+use crate::theory::key::Key;
+use std::fmt::Write;
+
+/// Fifths for each major tonic pitch class (flat spellings for 1 and 6 are fixed up below)
+const MAJOR_FIFTHS: [i32; 12] = [0, -5, 2, -3, 4, -1, 6, 1, -4, 3, -2, 5];
+
+/// Parts a beat can be split into for chords that share it (1, 2, 3, 4 or 6 chords split evenly)
+const BEAT_SPLIT: u32 = 12;
+
+/// Divisions per quarter note, so that one beat is a whole number of divisions
+fn divisions(time: &TimeSignature) -> u32 {
+    (time.beat_type / 4).max(1) * BEAT_SPLIT
+}
+
+fn beat_duration(time: &TimeSignature) -> u32 {
+    divisions(time) * 4 / time.beat_type
+}
+
+fn note_type(time: &TimeSignature) -> &'static str {
+    match time.beat_type {
+        2 => "half",
+        8 => "eighth",
+        _ => "quarter",
+    }
+}
+
+/// Metronome mark in the beat the time signature counts: (beat unit, dotted, per minute)
+///
+/// `bpm` is in quarter notes, as everywhere else; compound meters count dotted quarters.
+fn metronome(time: &TimeSignature, bpm: u32) -> (&'static str, bool, u32) {
+    if time.beat_type == 8 && time.beats.is_multiple_of(3) {
+        return ("quarter", true, (bpm as f64 / 1.5).round() as u32);
+    }
+    let per_minute = (bpm as f64 / time.beat_in_quarters()).round() as u32;
+    (note_type(time), false, per_minute)
+}
+
+/// One beat of a bar
+#[derive(Debug, Default)]
+struct Beat {
+    /// Chords starting on this beat, in order
+    chords: Vec<String>,
+    lyric: Option<(String, &'static str)>,
+}
+
+/// Render a song as a MusicXML 4.0 partwise score
+pub fn export_musicxml(song: &ExportSong) -> String {
+    let sheet = &song.sheet;
+    let time = TimeSignature::parse(song.time_signature.as_deref());
+
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"no\"?>\n\
+         <!DOCTYPE score-partwise PUBLIC \"-//Recordare//DTD MusicXML 4.0 Partwise//EN\" \
+         \"http://www.musicxml.org/dtds/partwise.dtd\">\n<score-partwise version=\"4.0\">\n",
+    );
+    let _ = writeln!(
+        xml,
+        "  <work><work-title>{}</work-title></work>",
+        escape(song.title())
+    );
+    xml.push_str("  <identification>\n");
+    if let Some(artist) = sheet.artist.as_deref().filter(|a| !a.is_empty()) {
+        let _ = writeln!(
+            xml,
+            "    <creator type=\"composer\">{}</creator>",
+            escape(artist)
+        );
+    }
+    xml.push_str(
+        "    <encoding><software>CaT4G</software></encoding>\n  </identification>\n  <part-list>\n    \
+         <score-part id=\"P1\"><part-name>Lead Sheet</part-name></score-part>\n  </part-list>\n  <part id=\"P1\">\n",
+    );
+
+    let mut bars: Vec<(Option<&str>, Vec<Beat>)> = Vec::new();
+    for section in &sheet.sections {
+        let mut rehearsal = Some(section.name.trim()).filter(|name| !name.is_empty());
+        for bar in section_bars(section, &time) {
+            bars.push((rehearsal.take(), bar));
+        }
+    }
+    if bars.is_empty() {
+        bars.push((None, beats_for(&FetchedLine::new(""), &time).remove(0)));
+    }
+
+    let last = bars.len() - 1;
+    for (index, (rehearsal, beats)) in bars.iter().enumerate() {
+        let _ = writeln!(xml, "    <measure number=\"{}\">", index + 1);
+        if index == 0 {
+            xml.push_str(&attributes(song, &time));
+            if let Some(bpm) = song.bpm {
+                let (unit, dotted, per_minute) = metronome(&time, bpm);
+                let dot = if dotted { "<beat-unit-dot/>" } else { "" };
+                let _ = writeln!(
+                    xml,
+                    "      <direction placement=\"above\"><direction-type><metronome>\
+                     <beat-unit>{unit}</beat-unit>{dot}<per-minute>{per_minute}</per-minute></metronome>\
+                     </direction-type><sound tempo=\"{bpm}\"/></direction>"
+                );
+            }
+        }
+        if let Some(name) = rehearsal {
+            let _ = writeln!(
+                xml,
+                "      <direction placement=\"above\"><direction-type><rehearsal>{}</rehearsal>\
+                 </direction-type></direction>",
+                escape(name)
+            );
+        }
+        for beat in beats {
+            let count = beat.chords.len() as u32;
+            for (index, chord) in beat.chords.iter().enumerate() {
+                let offset = beat_duration(&time) * index as u32 / count;
+                xml.push_str(&harmony(chord, offset));
+            }
+            xml.push_str(&note(beat, &time));
+        }
+        if index == last {
+            xml.push_str(
+                "      <barline location=\"right\"><bar-style>light-heavy</bar-style></barline>\n",
+            );
+        }
+        xml.push_str("    </measure>\n");
+    }
+    xml.push_str("  </part>\n</score-partwise>\n");
+    xml
+}
+
+fn attributes(song: &ExportSong, time: &TimeSignature) -> String {
+    let mut xml = String::new();
+    let _ = write!(
+        xml,
+        "      <attributes>\n        <divisions>{}</divisions>\n",
+        divisions(time)
+    );
+    if let Some(key) = song.sheet.key.as_deref().and_then(Key::parse) {
+        let _ = writeln!(
+            xml,
+            "        <key><fifths>{}</fifths><mode>{}</mode></key>",
+            key_fifths(&key),
+            if key.minor { "minor" } else { "major" }
+        );
+    }
+    let _ = write!(
+        xml,
+        "        <time><beats>{}</beats><beat-type>{}</beat-type></time>\n        \
+         <clef><sign>G</sign><line>2</line></clef>\n      </attributes>\n",
+        time.beats, time.beat_type
+    );
+    xml
+}
+
+fn key_fifths(key: &Key) -> i32 {
+    let major = if key.minor {
+        (key.tonic + 3) % 12
+    } else {
+        key.tonic
+    };
+    match (major, key.prefer_flat) {
+        (6, true) => -6,
+        (1, false) => 7,
+        _ => MAJOR_FIFTHS[major as usize],
+    }
+}
+
+fn section_bars(section: &FetchedSection, time: &TimeSignature) -> Vec<Vec<Beat>> {
+    played_lines(section)
+        .flat_map(|line| beats_for(line, time))
+        .collect()
+}
+
+/// Spread a line's chords evenly over its bars; lyrics follow the chords
+fn beats_for(line: &FetchedLine, time: &TimeSignature) -> Vec<Vec<Beat>> {
+    let timed = time_line(line, time);
+    let bar_count = timed.bars;
+    let beats_per_bar = time.beats as usize;
+    let mut beats: Vec<Beat> = (0..timed.total_beats(time))
+        .map(|_| Beat::default())
+        .collect();
+
+    for chord in &timed.chords {
+        beats[chord.start].chords.push(chord.name.to_string());
+    }
+
+    // Lyric segments run from one chord to the next; text before the first chord joins it
+    let chars: Vec<char> = line.lyrics.chars().collect();
+    let mut bounds: Vec<usize> = timed
+        .chords
+        .iter()
+        .map(|c| c.position.min(chars.len()))
+        .collect();
+    if bounds.is_empty() {
+        bounds.push(0);
+    }
+    bounds[0] = 0;
+    bounds.push(chars.len());
+    let segments: Vec<String> = bounds
+        .windows(2)
+        .map(|w| chars[w[0]..w[1].max(w[0])].iter().collect())
+        .collect();
+
+    for (index, segment) in segments.iter().enumerate() {
+        let text = segment.trim();
+        if text.is_empty() {
+            continue;
+        }
+        let joins_previous = index > 0 && joined(&segments[index - 1], segment);
+        let joins_next = segments
+            .get(index + 1)
+            .is_some_and(|next| joined(segment, next));
+        let syllabic = match (joins_previous, joins_next) {
+            (false, false) => "single",
+            (false, true) => "begin",
+            (true, true) => "middle",
+            (true, false) => "end",
+        };
+        let beat = timed.chords.get(index).map_or(0, |c| c.start);
+        // Chords sharing a beat share its lyric too
+        let lyric = match beats[beat].lyric.take() {
+            Some((earlier, earlier_syllabic)) => {
+                let separator = if joins_previous { "" } else { " " };
+                let syllabic = match (matches!(earlier_syllabic, "middle" | "end"), joins_next) {
+                    (false, false) => "single",
+                    (false, true) => "begin",
+                    (true, true) => "middle",
+                    (true, false) => "end",
+                };
+                (format!("{}{}{}", earlier, separator, text), syllabic)
+            }
+            None => (text.to_string(), syllabic),
+        };
+        beats[beat].lyric = Some(lyric);
+    }
+
+    let mut bars = Vec::with_capacity(bar_count);
+    let mut beats = beats.into_iter();
+    for _ in 0..bar_count {
+        bars.push(beats.by_ref().take(beats_per_bar).collect());
+    }
+    bars
+}
+
+/// Whether a Latin word continues across a chord boundary
+fn joined(before: &str, after: &str) -> bool {
+    let end = before.chars().last();
+    let start = after.chars().next();
+    matches!((end, start), (Some(a), Some(b)) if a.is_ascii_alphabetic() && b.is_ascii_alphabetic())
+}
+
+fn note(beat: &Beat, time: &TimeSignature) -> String {
+    let mut xml = format!(
+        "      <note><pitch><step>B</step><octave>4</octave></pitch><duration>{}</duration>\
+         <voice>1</voice><type>{}</type><stem>none</stem><notehead>slash</notehead>",
+        beat_duration(time),
+        note_type(time)
+    );
+    if let Some((text, syllabic)) = &beat.lyric {
+        let _ = write!(
+            xml,
+            "<lyric number=\"1\"><syllabic>{}</syllabic><text>{}</text></lyric>",
+            syllabic,
+            escape(text)
+        );
+    }
+    xml.push_str("</note>\n");
+    xml
+}
+
+/// `<harmony>` for a chord name, `offset` divisions into its beat; unparseable
+/// names (N.C.) become kind "none"
+fn harmony(name: &str, offset: u32) -> String {
+    let offset = if offset > 0 {
+        format!("<offset>{}</offset>", offset)
+    } else {
+        String::new()
+    };
+    let Some(chord) = Chord::parse(name) else {
+        return format!(
+            "      <harmony><root><root-step>C</root-step></root><kind text=\"{}\">none</kind>{}</harmony>\n",
+            escape(name),
+            offset
+        );
+    };
+
+    let mut xml = String::from("      <harmony>");
+    let (step, alter) = step_and_alter(chord.root_name());
+    let _ = write!(xml, "<root><root-step>{}</root-step>", step);
+    if alter != 0 {
+        let _ = write!(xml, "<root-alter>{}</root-alter>", alter);
+    }
+    xml.push_str("</root>");
+
+    let (kind, degrees) = kind_and_degrees(&chord);
+    let _ = write!(
+        xml,
+        "<kind text=\"{}\">{}</kind>",
+        escape(&chord.suffix),
+        kind
+    );
+
+    if let Some(bass) = chord.bass_name() {
+        let (step, alter) = step_and_alter(bass);
+        let _ = write!(xml, "<bass><bass-step>{}</bass-step>", step);
+        if alter != 0 {
+            let _ = write!(xml, "<bass-alter>{}</bass-alter>", alter);
+        }
+        xml.push_str("</bass>");
+    }
+    for (value, alter, kind) in degrees {
+        let _ = write!(
+            xml,
+            "<degree><degree-value>{}</degree-value><degree-alter>{}</degree-alter>\
+             <degree-type>{}</degree-type></degree>",
+            value, alter, kind
+        );
+    }
+    xml.push_str(&offset);
+    xml.push_str("</harmony>\n");
+    xml
+}
+
+fn step_and_alter(name: &str) -> (char, i32) {
+    let step = name.chars().next().unwrap_or('C');
+    let alter = match name.chars().nth(1) {
+        Some('#' | '♯') => 1,
+        Some('b' | '♭') => -1,
+        _ => 0,
+    };
+    (step, alter)
+}
+
+/// MusicXML kind plus any degrees it cannot express
+fn kind_and_degrees(chord: &Chord) -> (&'static str, Vec<(u8, i8, &'static str)>) {
+    let mut degrees: Vec<(u8, i8, &'static str)> = Vec::new();
+    let extended = |seventh: &'static str, ninth, eleventh, thirteenth| match chord.extension {
+        Some(9) => ninth,
+        Some(11) => eleventh,
+        Some(13) => thirteenth,
+        _ => seventh,
+    };
+
+    let half_diminished = chord.is_half_diminished();
+    let kind = match (chord.quality, chord.seventh) {
+        (Quality::Power, _) => "power",
+        _ if half_diminished => "half-diminished",
+        (Quality::Major, None) if chord.sixth => "major-sixth",
+        (Quality::Major, None) => "major",
+        (Quality::Major, Some(Seventh::Minor)) => extended(
+            "dominant",
+            "dominant-ninth",
+            "dominant-11th",
+            "dominant-13th",
+        ),
+        (Quality::Major, Some(_)) => {
+            extended("major-seventh", "major-ninth", "major-11th", "major-13th")
+        }
+        (Quality::Minor, None) if chord.sixth => "minor-sixth",
+        (Quality::Minor, None) => "minor",
+        (Quality::Minor, Some(Seventh::Major)) => "major-minor",
+        (Quality::Minor, Some(_)) => {
+            extended("minor-seventh", "minor-ninth", "minor-11th", "minor-13th")
+        }
+        (Quality::Diminished, Some(Seventh::Diminished)) => "diminished-seventh",
+        (Quality::Diminished, Some(_)) => "half-diminished",
+        (Quality::Diminished, None) => "diminished",
+        (Quality::Augmented, Some(Seventh::Minor)) => "augmented-seventh",
+        (Quality::Augmented, _) => "augmented",
+        (Quality::Sus2, _) => "suspended-second",
+        (Quality::Sus4, _) => "suspended-fourth",
+    };
+
+    // Sevenths the kind does not include
+    match (chord.quality, chord.seventh) {
+        (Quality::Sus2 | Quality::Sus4, Some(Seventh::Minor)) => degrees.push((7, -1, "add")),
+        (Quality::Sus2 | Quality::Sus4, Some(Seventh::Major)) => degrees.push((7, 0, "add")),
+        (Quality::Augmented, Some(Seventh::Major)) => degrees.push((7, 0, "add")),
+        _ => {}
+    }
+    if chord.sixth
+        && (chord.seventh.is_some() || !matches!(chord.quality, Quality::Major | Quality::Minor))
+    {
+        degrees.push((6, 0, "add"));
+    }
+    for &added in &chord.added {
+        degrees.push((added, 0, "add"));
+    }
+    for &(degree, shift) in &chord.alterations {
+        if half_diminished && degree == 5 {
+            continue;
+        }
+        let kind = if degree == 5 || chord.extension.is_some_and(|e| e >= degree) {
+            "alter"
+        } else {
+            "add"
+        };
+        degrees.push((degree, shift, kind));
+    }
+    for &omitted in &chord.omitted {
+        degrees.push((omitted, 0, "subtract"));
+    }
+    (kind, degrees)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::{FetchedChord, FetchedChordSheet};
+
+    fn kind(name: &str) -> (&'static str, Vec<(u8, i8, &'static str)>) {
+        kind_and_degrees(&Chord::parse(name).unwrap())
+    }
+
+    #[test]
+    fn test_harmony_kinds() {
+        assert_eq!(kind("C").0, "major");
+        assert_eq!(kind("Am7").0, "minor-seventh");
+        assert_eq!(kind("G7").0, "dominant");
+        assert_eq!(kind("G9").0, "dominant-ninth");
+        assert_eq!(kind("FM7").0, "major-seventh");
+        assert_eq!(kind("Bm7-5"), ("half-diminished", vec![]));
+        assert_eq!(kind("Cdim7").0, "diminished-seventh");
+        assert_eq!(kind("E5").0, "power");
+        assert_eq!(kind("Dsus4").0, "suspended-fourth");
+        assert_eq!(kind("G7sus4"), ("suspended-fourth", vec![(7, -1, "add")]));
+        assert_eq!(kind("Cadd9"), ("major", vec![(9, 0, "add")]));
+        assert_eq!(kind("E7(b9)"), ("dominant", vec![(9, -1, "add")]));
+    }
+
+    #[test]
+    fn test_harmony_element() {
+        assert_eq!(
+            harmony("F#m7/E", 0),
+            "      <harmony><root><root-step>F</root-step><root-alter>1</root-alter></root>\
+             <kind text=\"m7\">minor-seventh</kind><bass><bass-step>E</bass-step></bass></harmony>\n"
+        );
+        assert!(harmony("N.C.", 0).contains("<kind text=\"N.C.\">none</kind>"));
+    }
+
+    #[test]
+    fn test_key_fifths() {
+        assert_eq!(key_fifths(&Key::parse("Eb").unwrap()), -3);
+        assert_eq!(key_fifths(&Key::parse("F#m").unwrap()), 3);
+        assert_eq!(key_fifths(&Key::parse("Gb").unwrap()), -6);
+        assert_eq!(key_fifths(&Key::parse("F#").unwrap()), 6);
+        assert_eq!(key_fifths(&Key::parse("Dm").unwrap()), -1);
+    }
+
+    #[test]
+    fn test_chords_spread_across_bars() {
+        let time = TimeSignature::parse(None);
+        let line = FetchedLine::with_chords(
+            "沈むように溶けてゆくように",
+            vec![
+                FetchedChord::new("F", 0),
+                FetchedChord::new("G", 3),
+                FetchedChord::new("Em", 5),
+                FetchedChord::new("Am", 9),
+            ],
+        );
+        let bars = beats_for(&line, &time);
+        assert_eq!(bars.len(), 2);
+        assert_eq!(bars[0][0].chords, ["F"]);
+        assert_eq!(bars[0][2].chords, ["G"]);
+        assert_eq!(bars[1][0].chords, ["Em"]);
+        assert_eq!(bars[1][2].chords, ["Am"]);
+        assert_eq!(bars[0][0].lyric, Some(("沈むよ".to_string(), "single")));
+        assert_eq!(bars[1][2].lyric, Some(("くように".to_string(), "single")));
+
+        let three = TimeSignature::parse(Some("3/4"));
+        let bars = beats_for(&line, &three);
+        assert_eq!(bars.len(), 2);
+        assert_eq!(bars[0].len(), 3);
+        // Starts at beats 0, 1, 3, 4 of six
+        assert_eq!(bars[0][1].chords, ["G"]);
+        assert_eq!(bars[1][1].chords, ["Am"]);
+    }
+
+    #[test]
+    fn test_chords_sharing_a_beat() {
+        let mut crowded = FetchedLine::with_chords(
+            "あいうえお",
+            ["C", "G", "Am", "F", "E"]
+                .iter()
+                .enumerate()
+                .map(|(i, chord)| FetchedChord::new(chord, i as i32))
+                .collect(),
+        );
+        crowded.measures = Some(1);
+        let bars = beats_for(&crowded, &TimeSignature::parse(None));
+        assert_eq!(bars.len(), 1);
+        let chords: Vec<&str> = bars[0]
+            .iter()
+            .flat_map(|b| &b.chords)
+            .map(String::as_str)
+            .collect();
+        assert_eq!(chords, ["C", "G", "Am", "F", "E"]);
+        let shared = bars[0].iter().find(|b| b.chords.len() == 2).unwrap();
+        assert_eq!(shared.lyric.as_ref().unwrap().0.chars().count(), 3);
+
+        let mut sheet = FetchedChordSheet::new(String::new());
+        let mut section = FetchedSection::new("");
+        section.lines.push(crowded);
+        sheet.sections.push(section);
+        let song = ExportSong {
+            sheet,
+            bpm: None,
+            time_signature: None,
+            notes: None,
+        };
+        let xml = export_musicxml(&song);
+        assert_eq!(xml.matches("<harmony>").count(), 5);
+        assert_eq!(xml.matches("<offset>6</offset>").count(), 1);
+    }
+
+    #[test]
+    fn test_metronome_counts_the_meters_beat() {
+        assert_eq!(
+            metronome(&TimeSignature::parse(None), 120),
+            ("quarter", false, 120)
+        );
+        assert_eq!(
+            metronome(&TimeSignature::parse(Some("6/8")), 90),
+            ("quarter", true, 60)
+        );
+        assert_eq!(
+            metronome(&TimeSignature::parse(Some("2/2")), 120),
+            ("half", false, 60)
+        );
+        assert_eq!(
+            metronome(&TimeSignature::parse(Some("5/8")), 60),
+            ("eighth", false, 120)
+        );
+    }
+
+    #[test]
+    fn test_split_words_are_syllables() {
+        let line = FetchedLine::with_chords(
+            "Yesterday all",
+            vec![FetchedChord::new("F", 0), FetchedChord::new("Em7", 3)],
+        );
+        let bars = beats_for(&line, &TimeSignature::parse(None));
+        assert_eq!(bars[0][0].lyric, Some(("Yes".to_string(), "begin")));
+        assert_eq!(bars[0][2].lyric, Some(("terday all".to_string(), "end")));
+    }
+
+    #[test]
+    fn test_export_document() {
+        let mut sheet = FetchedChordSheet::new(String::new());
+        sheet.title = Some("Song".to_string());
+        sheet.artist = Some("Artist".to_string());
+        sheet.key = Some("Eb".to_string());
+        for name in ["Intro", "Aメロ"] {
+            let mut section = FetchedSection::new(name);
+            section.lines.push(FetchedLine::with_chords(
+                "歌詞",
+                vec![FetchedChord::new("Eb", 0), FetchedChord::new("Bb/D", 1)],
+            ));
+            sheet.sections.push(section);
+        }
+        let song = ExportSong {
+            sheet,
+            bpm: Some(96),
+            time_signature: Some("3/4".to_string()),
+            notes: None,
+        };
+        let xml = export_musicxml(&song);
+        assert!(xml.contains("<work-title>Song</work-title>"));
+        assert!(xml.contains("<key><fifths>-3</fifths><mode>major</mode></key>"));
+        assert!(xml.contains("<time><beats>3</beats><beat-type>4</beat-type></time>"));
+        assert!(xml.contains("<per-minute>96</per-minute>"));
+        assert!(xml.contains("<rehearsal>Aメロ</rehearsal>"));
+        assert_eq!(xml.matches("<measure ").count(), 2);
+        assert_eq!(xml.matches("<note>").count(), 6);
+        assert!(xml.contains("<bass><bass-step>D</bass-step></bass>"));
+        assert!(xml.contains("<text>歌</text>"));
+        assert!(xml.ends_with("</score-partwise>\n"));
+    }
+}
@@ -0,0 +1,155 @@
+//! PDF songbook export
+//!
+//! Pages come from the shared layout, measured with the embedded font; the
+//! font is cut down to the glyphs on the pages before it is embedded.
+
+use super::font::EmbedFont;
+use super::layout::{layout, Op, PageSetup, PageSize, Tone, DEFAULT_FONT_SIZE};
+use super::view::SheetView;
+use super::ExportSong;
+use crate::error::ExportError;
+use printpdf::{Color, Line, Mm, PdfDocument, Point, Rgb};
+use serde::{Deserialize, Serialize};
+use std::io::{BufWriter, Cursor};
+use std::path::Path;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PdfOptions {
+    /// Songbook title, shown on the contents page
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub page_size: PageSize,
+    #[serde(default)]
+    pub two_columns: bool,
+    /// Contents page listing every song (only used for more than one song)
+    #[serde(default)]
+    pub table_of_contents: bool,
+    /// TrueType font to embed; defaults to a Japanese system font
+    #[serde(default)]
+    pub font_path: Option<String>,
+    /// Lyric size in points
+    #[serde(default = "default_font_size")]
+    pub font_size: f32,
+    #[serde(default)]
+    pub view: SheetView,
+}
+
+fn default_font_size() -> f32 {
+    DEFAULT_FONT_SIZE
+}
+
+impl PdfOptions {
+    fn page_setup(&self, song_count: usize) -> PageSetup {
+        PageSetup {
+            page_size: self.page_size,
+            font_size: self.font_size,
+            two_columns: self.two_columns,
+            contents: (self.table_of_contents && song_count > 1)
+                .then(|| self.title.clone().unwrap_or_else(|| "Contents".to_string())),
+        }
+    }
+}
+
+fn tone_color(tone: Tone) -> Color {
+    let (r, g, b) = match tone {
+        Tone::Text => (0.0, 0.0, 0.0),
+        Tone::Chord => (0.1, 0.3, 0.75),
+        Tone::Muted => (0.4, 0.4, 0.4),
+    };
+    Color::Rgb(Rgb::new(r, g, b, None))
+}
+
+/// Render songs into PDF bytes
+pub fn render_pdf(songs: &[ExportSong], options: &PdfOptions) -> Result<Vec<u8>, ExportError> {
+    let font = EmbedFont::load(options.font_path.as_deref())?;
+    let songs: Vec<ExportSong> = songs
+        .iter()
+        .map(|song| ExportSong {
+            sheet: options
+                .view
+                .apply(&song.sheet, song.time_signature.as_deref()),
+            ..song.clone()
+        })
+        .collect();
+    let document = layout(&songs, &options.page_setup(songs.len()), &font);
+
+    let (width, height) = options.page_size.dimensions();
+    let title = match (options.title.as_deref(), songs.as_slice()) {
+        (Some(title), _) => title.to_string(),
+        (None, [song]) => song.title().to_string(),
+        (None, _) => "Songbook".to_string(),
+    };
+    let (doc, first_page, first_layer) = PdfDocument::new(title, Mm(width), Mm(height), "Layer 1");
+    let text = document
+        .pages
+        .iter()
+        .flat_map(|page| &page.ops)
+        .flat_map(|op| match op {
+            Op::Text { text, .. } => text.chars().collect(),
+            Op::Rule { .. } => Vec::new(),
+        });
+    let pdf_font = doc
+        .add_external_font(Cursor::new(font.subset(text)))
+        .map_err(|e| ExportError::Render(e.to_string()))?;
+
+    let mut page_indices = Vec::new();
+    for (index, page) in document.pages.iter().enumerate() {
+        let (page_index, layer_index) = if index == 0 {
+            (first_page, first_layer)
+        } else {
+            doc.add_page(Mm(width), Mm(height), "Layer 1")
+        };
+        page_indices.push(page_index);
+        let layer = doc.get_page(page_index).get_layer(layer_index);
+
+        for op in &page.ops {
+            match op {
+                Op::Text {
+                    x,
+                    y,
+                    size,
+                    tone,
+                    text,
+                } => {
+                    layer.set_fill_color(tone_color(*tone));
+                    layer.use_text(text.clone(), *size, Mm(*x), Mm(height - y), &pdf_font);
+                }
+                Op::Rule { x1, x2, y } => {
+                    layer.set_outline_color(tone_color(Tone::Muted));
+                    layer.set_outline_thickness(0.5);
+                    layer.add_line(Line {
+                        points: vec![
+                            (Point::new(Mm(*x1), Mm(height - y)), false),
+                            (Point::new(Mm(*x2), Mm(height - y)), false),
+                        ],
+                        is_closed: false,
+                    });
+                }
+            }
+        }
+    }
+    for (title, page) in document.bookmarks {
+        if let Some(&page_index) = page_indices.get(page) {
+            doc.add_bookmark(title, page_index);
+        }
+    }
+
+    let mut buffer = BufWriter::new(Vec::new());
+    doc.save(&mut buffer)
+        .map_err(|e| ExportError::Render(e.to_string()))?;
+    buffer
+        .into_inner()
+        .map_err(|e| ExportError::Io(e.into_error()))
+}
+
+/// Render songs and write the PDF to `path`
+pub fn export_pdf(
+    songs: &[ExportSong],
+    options: &PdfOptions,
+    path: &Path,
+) -> Result<(), ExportError> {
+    let bytes = render_pdf(songs, options)?;
+    std::fs::write(path, bytes)?;
+    Ok(())
+}
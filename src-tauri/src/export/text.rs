@@ -0,0 +1,129 @@
+//! Plain-text export
+//!
+//! Chords sit on their own row above the lyrics, aligned for a monospace font
+//! in which full-width characters take two columns.
+
+use super::ExportSong;
+use crate::parsers::FetchedLine;
+
+fn columns(c: char) -> usize {
+    if c.is_ascii() {
+        1
+    } else {
+        2
+    }
+}
+
+/// Chord row plus lyric row (the chord row is omitted when there are no chords)
+fn line_text(line: &FetchedLine) -> Vec<String> {
+    let lyrics = line.lyrics.trim_end();
+    if line.chords.is_empty() {
+        return vec![lyrics.to_string()];
+    }
+
+    // Display column where each character starts
+    let mut starts = Vec::with_capacity(lyrics.len() + 1);
+    let mut column = 0;
+    for c in lyrics.chars() {
+        starts.push(column);
+        column += columns(c);
+    }
+    starts.push(column);
+
+    let mut chords: Vec<_> = line.chords.iter().collect();
+    chords.sort_by_key(|c| c.position);
+    let mut row = String::new();
+    let mut width = 0;
+    for chord in chords {
+        let index = (chord.position.max(0) as usize).min(starts.len() - 1);
+        let target = if width == 0 {
+            starts[index]
+        } else {
+            starts[index].max(width + 1)
+        };
+        row.push_str(&" ".repeat(target - width));
+        row.push_str(&chord.chord);
+        width = target + chord.chord.chars().map(columns).sum::<usize>();
+    }
+
+    let mut rows = vec![row];
+    if !lyrics.trim().is_empty() {
+        rows.push(lyrics.to_string());
+    }
+    rows
+}
+
+/// Render a song as plain text with chords above the lyrics
+pub fn export_text(song: &ExportSong) -> String {
+    let sheet = &song.sheet;
+    let mut rows = vec![song.title().to_string()];
+    if let Some(artist) = sheet.artist.as_deref().filter(|a| !a.is_empty()) {
+        rows.push(artist.to_string());
+    }
+    let meta = song.meta_line();
+    if !meta.is_empty() {
+        rows.push(meta);
+    }
+    if let Some(notes) = song.notes.as_deref().filter(|n| !n.trim().is_empty()) {
+        rows.push(notes.trim().to_string());
+    }
+
+    for section in &sheet.sections {
+        rows.push(String::new());
+        if !section.name.trim().is_empty() {
+            rows.push(format!("[{}]", section.name.trim()));
+        }
+        for line in &section.lines {
+            match &line.tab {
+                Some(tab) => rows.extend(tab.lines.iter().cloned()),
+                None => rows.extend(line_text(line)),
+            }
+        }
+    }
+    let mut text = rows.join("\n");
+    text.push('\n');
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::{FetchedChord, FetchedChordSheet, FetchedSection};
+
+    #[test]
+    fn test_chords_above_lyrics() {
+        let line = FetchedLine::with_chords(
+            "君の名前を",
+            vec![FetchedChord::new("Am", 0), FetchedChord::new("F", 2)],
+        );
+        assert_eq!(line_text(&line), vec!["Am  F", "君の名前を"]);
+
+        // Crowded chords keep a space between them
+        let line = FetchedLine::with_chords(
+            "I love",
+            vec![FetchedChord::new("Cmaj7", 0), FetchedChord::new("G/B", 2)],
+        );
+        assert_eq!(line_text(&line), vec!["Cmaj7 G/B", "I love"]);
+    }
+
+    #[test]
+    fn test_export_text() {
+        let mut sheet = FetchedChordSheet::new(String::new());
+        sheet.title = Some("Song".to_string());
+        sheet.capo = Some(2);
+        let mut section = FetchedSection::new("Aメロ");
+        section.lines.push(FetchedLine::with_chords(
+            "",
+            vec![FetchedChord::new("C", 0)],
+        ));
+        section.lines.push(FetchedLine::new("歌詞"));
+        sheet.sections.push(section);
+        let song = ExportSong {
+            sheet,
+            bpm: None,
+            time_signature: None,
+            notes: None,
+        };
+        assert_eq!(export_text(&song), "Song\nCapo: 2\n\n[Aメロ]\nC\n歌詞\n");
+    }
+}
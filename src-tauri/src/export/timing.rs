@@ -0,0 +1,160 @@
+//! Bar timing for sheets without rhythm
+//!
+//! A line spans its stored `measures`, or one bar per two chords when it has
+//! none, and its chords are spread evenly across those beats.
+
+use crate::parsers::{FetchedLine, FetchedSection};
+
+/// Chords per bar before a line gets another bar
+const CHORDS_PER_BAR: usize = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeSignature {
+    pub beats: u32,
+    pub beat_type: u32,
+}
+
+impl Default for TimeSignature {
+    fn default() -> Self {
+        Self {
+            beats: 4,
+            beat_type: 4,
+        }
+    }
+}
+
+impl TimeSignature {
+    /// Parse "3/4"; anything unusable falls back to 4/4
+    pub fn parse(text: Option<&str>) -> Self {
+        text.and_then(|t| t.split_once('/'))
+            .and_then(|(beats, beat_type)| {
+                let beats = beats.trim().parse().ok().filter(|&b| b > 0)?;
+                let beat_type = beat_type
+                    .trim()
+                    .parse()
+                    .ok()
+                    .filter(|b| [2, 4, 8].contains(b))?;
+                Some(Self { beats, beat_type })
+            })
+            .unwrap_or_default()
+    }
+
+    /// Beat length in quarter notes
+    pub fn beat_in_quarters(&self) -> f64 {
+        4.0 / self.beat_type as f64
+    }
+}
+
+/// A chord placed on a beat of its line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimedChord<'a> {
+    pub name: &'a str,
+    /// Character offset in the lyrics
+    pub position: usize,
+    /// First beat, counted from the start of the line
+    pub start: usize,
+    /// Beats until the next chord; 0 when more chords than beats share a beat
+    pub beats: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimedLine<'a> {
+    pub bars: usize,
+    pub chords: Vec<TimedChord<'a>>,
+}
+
+impl TimedLine<'_> {
+    pub fn total_beats(&self, time: &TimeSignature) -> usize {
+        self.bars * time.beats as usize
+    }
+}
+
+/// Place a line's chords, in lyric order, on its beats
+pub fn time_line<'a>(line: &'a FetchedLine, time: &TimeSignature) -> TimedLine<'a> {
+    let mut chords: Vec<_> = line.chords.iter().collect();
+    chords.sort_by_key(|c| c.position);
+
+    let bars = match line.measures {
+        Some(measures) if measures > 0 => measures as usize,
+        _ => chords.len().div_ceil(CHORDS_PER_BAR).max(1),
+    };
+    let total = bars * time.beats as usize;
+    let starts: Vec<usize> = (0..chords.len())
+        .map(|i| i * total / chords.len())
+        .collect();
+
+    let chords = chords
+        .iter()
+        .enumerate()
+        .map(|(i, chord)| {
+            let end = starts.get(i + 1).copied().unwrap_or(total);
+            TimedChord {
+                name: &chord.chord,
+                position: chord.position.max(0) as usize,
+                start: starts[i],
+                beats: end - starts[i],
+            }
+        })
+        .collect();
+    TimedLine { bars, chords }
+}
+
+/// Lines that take up time; blank spacer lines do not
+pub fn played_lines(section: &FetchedSection) -> impl Iterator<Item = &FetchedLine> {
+    section
+        .lines
+        .iter()
+        .filter(|line| !line.chords.is_empty() || !line.lyrics.trim().is_empty())
+}
+
+/// Beats in one pass through a section
+pub fn section_beats(section: &FetchedSection, time: &TimeSignature) -> usize {
+    played_lines(section)
+        .map(|line| time_line(line, time).total_beats(time))
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::FetchedChord;
+
+    #[test]
+    fn test_parse_time_signature() {
+        assert_eq!(
+            TimeSignature::parse(Some("6/8")),
+            TimeSignature {
+                beats: 6,
+                beat_type: 8
+            }
+        );
+        assert_eq!(TimeSignature::parse(Some("3/5")), TimeSignature::default());
+        assert_eq!(TimeSignature::parse(None), TimeSignature::default());
+    }
+
+    #[test]
+    fn test_stored_measures_win() {
+        let mut line = FetchedLine::with_chords(
+            "",
+            vec![
+                FetchedChord::new("G", 4),
+                FetchedChord::new("C", 0),
+                FetchedChord::new("D", 8),
+            ],
+        );
+        let time = TimeSignature::default();
+        let timed = time_line(&line, &time);
+        assert_eq!(timed.bars, 2);
+        let starts: Vec<_> = timed
+            .chords
+            .iter()
+            .map(|c| (c.name, c.start, c.beats))
+            .collect();
+        assert_eq!(starts, vec![("C", 0, 2), ("G", 2, 3), ("D", 5, 3)]);
+
+        line.measures = Some(1);
+        let timed = time_line(&line, &time);
+        assert_eq!(timed.total_beats(&time), 4);
+        assert_eq!(timed.chords[2].start, 2);
+    }
+}
@@ -0,0 +1,200 @@
+//! Part-specific views of a sheet
+//!
+//! Singers get the lyrics alone; players get a compact chord chart with one
+//! `|` cell per bar. Both views are ordinary `FetchedChordSheet`s, so every
+//! exporter renders them without special cases.
+
+use super::timing::{played_lines, time_line, TimeSignature};
+use crate::parsers::{FetchedChordSheet, FetchedLine, FetchedSection};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SheetView {
+    /// Chords over lyrics, as imported
+    #[default]
+    Full,
+    LyricsOnly,
+    ChordChart,
+}
+
+impl SheetView {
+    /// `time_signature` is the song's ("3/4"), which chord chart bars follow
+    pub fn apply(
+        self,
+        sheet: &FetchedChordSheet,
+        time_signature: Option<&str>,
+    ) -> FetchedChordSheet {
+        match self {
+            SheetView::Full => sheet.clone(),
+            SheetView::LyricsOnly => lyrics_only(sheet),
+            SheetView::ChordChart => chord_chart(sheet, TimeSignature::parse(time_signature)),
+        }
+    }
+}
+
+/// "Chorus ×2" for sections played more than once
+fn section_label(section: &FetchedSection) -> String {
+    match section.repeat_count {
+        Some(count) if count > 1 => format!("{} ×{}", section.name.trim(), count),
+        _ => section.name.clone(),
+    }
+}
+
+/// Copy a section's settings with new lines
+fn with_lines(section: &FetchedSection, lines: Vec<FetchedLine>) -> FetchedSection {
+    FetchedSection {
+        name: section_label(section),
+        lines,
+        ..section.clone()
+    }
+}
+
+/// Lyrics without chords; the padding left for chord names is closed up and
+/// sections with no words (intros, solos) are dropped
+pub fn lyrics_only(sheet: &FetchedChordSheet) -> FetchedChordSheet {
+    let mut view = FetchedChordSheet {
+        sections: Vec::new(),
+        ..sheet.clone()
+    };
+    for section in &sheet.sections {
+        let lines: Vec<FetchedLine> = section
+            .lines
+            .iter()
+            .map(|line| line.lyrics.split_whitespace().collect::<Vec<_>>().join(" "))
+            .filter(|lyrics| !lyrics.is_empty())
+            .map(|lyrics| FetchedLine::new(&lyrics))
+            .collect();
+        if !lines.is_empty() {
+            view.sections.push(with_lines(section, lines));
+        }
+    }
+    view
+}
+
+/// One chart row per line ("| C G | Am % |"), identical consecutive rows
+/// folded into "×n"; lines without chords are dropped. Bars follow
+/// `song_time` unless the section has its own time signature.
+pub fn chord_chart(sheet: &FetchedChordSheet, song_time: TimeSignature) -> FetchedChordSheet {
+    let mut view = FetchedChordSheet {
+        sections: Vec::new(),
+        ..sheet.clone()
+    };
+    for section in &sheet.sections {
+        let time = match section.time_signature.as_deref() {
+            Some(text) => TimeSignature::parse(Some(text)),
+            None => song_time,
+        };
+        let mut rows: Vec<(String, usize)> = Vec::new();
+        for line in played_lines(section).filter(|line| !line.chords.is_empty()) {
+            let timed = time_line(line, &time);
+            let mut bars: Vec<Vec<&str>> = vec![Vec::new(); timed.bars];
+            for chord in &timed.chords {
+                bars[chord.start / time.beats as usize].push(chord.name);
+            }
+            let cells: Vec<String> = bars
+                .iter()
+                .map(|bar| {
+                    if bar.is_empty() {
+                        "%".to_string()
+                    } else {
+                        bar.join(" ")
+                    }
+                })
+                .collect();
+            let row = format!("| {} |", cells.join(" | "));
+            match rows.last_mut() {
+                Some((last, count)) if *last == row => *count += 1,
+                _ => rows.push((row, 1)),
+            }
+        }
+        if rows.is_empty() {
+            continue;
+        }
+        let lines = rows
+            .into_iter()
+            .map(|(row, count)| match count {
+                1 => FetchedLine::new(&row),
+                _ => FetchedLine::new(&format!("{} ×{}", row, count)),
+            })
+            .collect();
+        view.sections.push(with_lines(section, lines));
+    }
+    view
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::FetchedChord;
+
+    fn sheet() -> FetchedChordSheet {
+        let mut sheet = FetchedChordSheet::new(String::new());
+        let mut intro = FetchedSection::new("Intro");
+        for _ in 0..4 {
+            intro.lines.push(FetchedLine::with_chords(
+                "",
+                vec![FetchedChord::new("C", 0), FetchedChord::new("G", 0)],
+            ));
+        }
+        let mut chorus = FetchedSection::new("サビ");
+        chorus.repeat_count = Some(2);
+        chorus.lines.push(FetchedLine::with_chords(
+            "君の    名前を",
+            vec![FetchedChord::new("Am", 0), FetchedChord::new("F", 6)],
+        ));
+        let mut long = FetchedLine::with_chords("呼ぶ", vec![FetchedChord::new("G", 0)]);
+        long.measures = Some(2);
+        chorus.lines.push(long);
+        chorus.lines.push(FetchedLine::new(""));
+        sheet.sections = vec![intro, chorus];
+        sheet
+    }
+
+    #[test]
+    fn test_lyrics_only() {
+        let view = lyrics_only(&sheet());
+        assert_eq!(view.sections.len(), 1);
+        assert_eq!(view.sections[0].name, "サビ ×2");
+        let lyrics: Vec<_> = view.sections[0]
+            .lines
+            .iter()
+            .map(|l| l.lyrics.as_str())
+            .collect();
+        assert_eq!(lyrics, vec!["君の 名前を", "呼ぶ"]);
+        assert!(view.sections[0].lines.iter().all(|l| l.chords.is_empty()));
+    }
+
+    #[test]
+    fn test_chord_chart() {
+        let view = chord_chart(&sheet(), TimeSignature::default());
+        let rows: Vec<Vec<&str>> = view
+            .sections
+            .iter()
+            .map(|s| s.lines.iter().map(|l| l.lyrics.as_str()).collect())
+            .collect();
+        assert_eq!(rows[0], vec!["| C G | ×4"]);
+        assert_eq!(rows[1], vec!["| Am F |", "| G | % |"]);
+        assert_eq!(view.sections[1].name, "サビ ×2");
+        assert_eq!(
+            SheetView::Full.apply(&sheet(), None).sections[0]
+                .lines
+                .len(),
+            4
+        );
+    }
+
+    #[test]
+    fn test_views_leave_out_the_tab() {
+        let mut sheet = sheet();
+        sheet.sections[1].push_tab_line("e|--0--|");
+        let no_tab = |view: FetchedChordSheet| {
+            view.sections
+                .iter()
+                .flat_map(|s| &s.lines)
+                .all(|l| l.tab.is_none())
+        };
+        assert!(no_tab(lyrics_only(&sheet)));
+        assert!(no_tab(chord_chart(&sheet, TimeSignature::default())));
+    }
+}
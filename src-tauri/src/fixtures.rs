@@ -0,0 +1,36 @@
+//! Chord sheets for unit tests
+
+use crate::parsers::{FetchedChord, FetchedChordSheet, FetchedLine, FetchedSection};
+
+/// A line with chords at the given character positions
+pub fn line(lyrics: &str, chords: &[(&str, i32)]) -> FetchedLine {
+    FetchedLine::with_chords(
+        lyrics,
+        chords
+            .iter()
+            .map(|(chord, position)| FetchedChord::new(chord, *position))
+            .collect(),
+    )
+}
+
+/// A sheet with one "Main" section holding a chord-only line per entry,
+/// chords four characters apart
+pub fn sheet_of(lines: &[&[&str]]) -> FetchedChordSheet {
+    let mut sheet = FetchedChordSheet::new(String::new());
+    let mut section = FetchedSection::new("Main");
+    for chords in lines {
+        let positioned: Vec<_> = chords
+            .iter()
+            .enumerate()
+            .map(|(i, chord)| (*chord, i as i32 * 4))
+            .collect();
+        section.lines.push(line("", &positioned));
+    }
+    sheet.sections.push(section);
+    sheet
+}
+
+/// A sheet with a single line of chords
+pub fn sheet_with(chords: &[&str]) -> FetchedChordSheet {
+    sheet_of(&[chords])
+}
@@ -0,0 +1,1522 @@
+//! Guitar Pro 3, 4 and 5 files (.gp3, .gp4, .gp5)
+//!
+//! Only what a chord sheet needs is kept: song information, tempo, time
+//! signatures, markers, chord diagrams on beats and the lyrics. Everything
+//! else (notes, effects, mixer settings) is read past. Markers become
+//! sections; each lyric syllable lands on the next sounding beat of the
+//! lyrics track, and a chord takes the position of the syllable sung when it
+//! starts.
+
+use super::ImportedSong;
+use crate::error::ImportError;
+use crate::parsers::{FetchedChord, FetchedChordSheet, FetchedLine, FetchedSection};
+use crate::theory::instrument::Instrument;
+
+/// Ticks per quarter note
+const QUARTER: u32 = 960;
+
+/// Bars a line of chords (or of lyrics without line breaks) runs before wrapping
+const BARS_PER_LINE: usize = 4;
+
+/// Major keys by number of sharps (negative: flats)
+const KEYS: [&str; 15] = [
+    "Cb", "Gb", "Db", "Ab", "Eb", "Bb", "F", "C", "G", "D", "A", "E", "B", "F#", "C#",
+];
+
+/// Strings are stored in the writer's code page; Japanese files are usually Shift_JIS
+fn decode(bytes: &[u8]) -> String {
+    if let Ok(text) = std::str::from_utf8(bytes) {
+        return text.to_string();
+    }
+    let (text, _, errors) = encoding_rs::SHIFT_JIS.decode(bytes);
+    if !errors {
+        return text.into_owned();
+    }
+    encoding_rs::WINDOWS_1252.decode(bytes).0.into_owned()
+}
+
+/// "FICHIER GUITAR PRO v5.10" -> 510
+fn parse_version(text: &str) -> Option<u32> {
+    let (major, minor) = text.strip_prefix("FICHIER GUITAR PRO v")?.split_once('.')?;
+    let major: u32 = major.parse().ok()?;
+    let minor: u32 = minor.get(..2)?.parse().ok()?;
+    (3..=5).contains(&major).then_some(major * 100 + minor)
+}
+
+/// Arithmetic on file values only overflows in a corrupt file
+fn checked<T>(value: Option<T>, what: &str) -> Result<T, ImportError> {
+    value.ok_or_else(|| ImportError::Invalid(format!("{} out of range", what)))
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    version: u32,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, count: usize) -> Result<&'a [u8], ImportError> {
+        let end = self
+            .pos
+            .checked_add(count)
+            .filter(|&end| end <= self.data.len());
+        let end = end.ok_or_else(|| {
+            ImportError::Invalid(format!("Unexpected end of file at byte {}", self.pos))
+        })?;
+        let bytes = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn skip(&mut self, count: usize) -> Result<(), ImportError> {
+        self.take(count).map(|_| ())
+    }
+
+    fn u8(&mut self) -> Result<u8, ImportError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn i8(&mut self) -> Result<i8, ImportError> {
+        Ok(self.u8()? as i8)
+    }
+
+    fn i16(&mut self) -> Result<i16, ImportError> {
+        let bytes = self.take(2)?;
+        Ok(i16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn i32(&mut self) -> Result<i32, ImportError> {
+        let bytes = self.take(4)?;
+        Ok(i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    /// Count as an int, rejecting negative or absurd values from a corrupt file
+    fn count(&mut self) -> Result<usize, ImportError> {
+        let value = self.i32()?;
+        usize::try_from(value)
+            .ok()
+            .filter(|&count| count <= self.data.len())
+            .ok_or_else(|| {
+                ImportError::Invalid(format!("Bad count {} at byte {}", value, self.pos - 4))
+            })
+    }
+
+    /// Length byte, then a fixed `size` bytes of which the first `length` are text
+    fn byte_string(&mut self, size: usize) -> Result<String, ImportError> {
+        let length = self.u8()? as usize;
+        let bytes = self.take(size)?;
+        Ok(decode(&bytes[..length.min(size)]))
+    }
+
+    /// Int holding the size (length + 1), then a byte-length string of that size
+    fn int_byte_string(&mut self) -> Result<String, ImportError> {
+        let size = self.i32()?;
+        let length = self.u8()? as usize;
+        let size = if size > 0 { size as usize - 1 } else { length };
+        let bytes = self.take(size)?;
+        Ok(decode(&bytes[..length.min(size)]))
+    }
+
+    /// Int length, then the text
+    fn int_string(&mut self) -> Result<String, ImportError> {
+        let length = self.count()?;
+        Ok(decode(self.take(length)?))
+    }
+}
+
+struct MeasureHeader {
+    numerator: u32,
+    denominator: u32,
+    marker: Option<String>,
+}
+
+impl MeasureHeader {
+    fn ticks(&self) -> u32 {
+        self.numerator * QUARTER * 4 / self.denominator.max(1)
+    }
+
+    fn time_signature(&self) -> String {
+        format!("{}/{}", self.numerator, self.denominator)
+    }
+}
+
+struct Track {
+    /// Open MIDI pitch of each string, 1st string first
+    strings: Vec<u8>,
+    capo: i32,
+}
+
+struct Beat {
+    /// Ticks from the start of the measure
+    start: u32,
+    /// Rests and empty beats take no syllable
+    rest: bool,
+    chord: Option<String>,
+    tempo: Option<u32>,
+}
+
+struct Document {
+    title: String,
+    artist: String,
+    tempo: u32,
+    /// Sharps (positive) or flats (negative) in the key signature
+    key: i32,
+    lyrics_track: Option<usize>,
+    /// Lyric lines with the measure each starts at
+    lyrics: Vec<(usize, String)>,
+    headers: Vec<MeasureHeader>,
+    tracks: Vec<Track>,
+    /// First-voice beats, indexed by measure then track
+    beats: Vec<Vec<Vec<Beat>>>,
+}
+
+/// Duration in ticks of a beat
+fn duration(value: i8, dotted: bool, tuplet: i32) -> u32 {
+    let mut ticks = match value {
+        -2 => QUARTER * 4,
+        -1 => QUARTER * 2,
+        0..=6 => QUARTER >> value as u32,
+        _ => QUARTER,
+    };
+    if dotted {
+        ticks += ticks / 2;
+    }
+    // n notes in the time of the next lower power of two
+    let (enters, times) = match tuplet {
+        3 => (3, 2),
+        5..=7 => (tuplet as u32, 4),
+        9..=13 => (tuplet as u32, 8),
+        _ => (1, 1),
+    };
+    ticks * times / enters
+}
+
+impl Reader<'_> {
+    fn document(&mut self) -> Result<Document, ImportError> {
+        let version_text = self.byte_string(30)?;
+        self.version = parse_version(&version_text)
+            .ok_or_else(|| ImportError::UnsupportedFormat(format!("'{}'", version_text)))?;
+        let version = self.version;
+
+        let title = self.int_byte_string()?;
+        let _subtitle = self.int_byte_string()?;
+        let artist = self.int_byte_string()?;
+        // Album, words, music (GP5), copyright, tab author, instructions
+        let info_fields = if version >= 500 { 6 } else { 5 };
+        for _ in 0..info_fields {
+            self.int_byte_string()?;
+        }
+        for _ in 0..self.count()? {
+            self.int_byte_string()?;
+        }
+
+        if version < 500 {
+            // Triplet feel
+            self.skip(1)?;
+        }
+
+        let mut lyrics_track = None;
+        let mut lyrics = Vec::new();
+        if version >= 400 {
+            let track = checked(self.i32()?.checked_sub(1), "Lyrics track")?;
+            lyrics_track = usize::try_from(track).ok();
+            for _ in 0..5 {
+                let start = checked(self.i32()?.checked_sub(1), "Lyrics measure")?;
+                let text = self.int_string()?;
+                if !text.trim().is_empty() {
+                    lyrics.push((usize::try_from(start).unwrap_or(0), text));
+                }
+            }
+        }
+
+        if version >= 510 {
+            // Master volume, effect and equalizer
+            self.skip(19)?;
+        }
+        if version >= 500 {
+            // Page size and margins, then the header/footer formats
+            self.skip(30)?;
+            for _ in 0..10 {
+                self.int_byte_string()?;
+            }
+            // Tempo name
+            self.int_byte_string()?;
+        }
+        let tempo = self.i32()?.clamp(1, 999) as u32;
+        if version >= 510 {
+            // Hide tempo
+            self.skip(1)?;
+        }
+        let key = if version >= 500 {
+            // A signed byte, then 3 unused bytes
+            let key = self.i8()?;
+            self.skip(3)?;
+            key as i32
+        } else {
+            self.i32()?
+        };
+        if version >= 400 {
+            // Octave
+            self.skip(1)?;
+        }
+        // 64 MIDI channels
+        self.skip(64 * 12)?;
+        if version >= 500 {
+            // Coda/segno directions, then 4 unknown bytes
+            self.skip(42)?;
+        }
+
+        let measure_count = self.count()?;
+        let track_count = self.count()?;
+
+        let mut headers: Vec<MeasureHeader> = Vec::with_capacity(measure_count);
+        for index in 0..measure_count {
+            let header = self.measure_header(headers.last(), index)?;
+            headers.push(header);
+        }
+
+        let mut tracks = Vec::with_capacity(track_count);
+        for index in 0..track_count {
+            tracks.push(self.track(index)?);
+        }
+        if version >= 500 {
+            self.skip(if version == 500 { 2 } else { 1 })?;
+        }
+
+        let mut beats = Vec::with_capacity(measure_count);
+        for _ in 0..measure_count {
+            let mut measure = Vec::with_capacity(track_count);
+            for track in &tracks {
+                let voices = if version >= 500 { 2 } else { 1 };
+                let mut first_voice = Vec::new();
+                for voice in 0..voices {
+                    let mut start = 0;
+                    for _ in 0..self.count()? {
+                        let (beat, ticks) = self.beat(track.strings.len(), start)?;
+                        start = checked(start.checked_add(ticks), "Measure length")?;
+                        if voice == 0 {
+                            first_voice.push(beat);
+                        }
+                    }
+                }
+                if version >= 500 {
+                    // Line break
+                    self.skip(1)?;
+                }
+                measure.push(first_voice);
+            }
+            beats.push(measure);
+        }
+
+        Ok(Document {
+            title,
+            artist,
+            tempo,
+            key,
+            lyrics_track,
+            lyrics,
+            headers,
+            tracks,
+            beats,
+        })
+    }
+
+    fn measure_header(
+        &mut self,
+        previous: Option<&MeasureHeader>,
+        index: usize,
+    ) -> Result<MeasureHeader, ImportError> {
+        let version = self.version;
+        if version >= 500 && index > 0 {
+            self.skip(1)?;
+        }
+        let flags = self.u8()?;
+        let mut header = MeasureHeader {
+            numerator: previous.map_or(4, |p| p.numerator),
+            denominator: previous.map_or(4, |p| p.denominator),
+            marker: None,
+        };
+        if flags & 0x01 != 0 {
+            header.numerator = self.u8()?.max(1) as u32;
+        }
+        if flags & 0x02 != 0 {
+            header.denominator = self.u8()?.max(1) as u32;
+        }
+        if flags & 0x08 != 0 {
+            // Repeat close
+            self.skip(1)?;
+        }
+        if version < 500 && flags & 0x10 != 0 {
+            // Alternate ending
+            self.skip(1)?;
+        }
+        if flags & 0x20 != 0 {
+            let name = self.int_byte_string()?;
+            // Colour
+            self.skip(4)?;
+            header.marker = Some(name.trim().to_string()).filter(|n| !n.is_empty());
+        }
+        if flags & 0x40 != 0 {
+            // Key signature
+            self.skip(2)?;
+        }
+        if version >= 500 {
+            if flags & 0x10 != 0 {
+                // Alternate ending
+                self.skip(1)?;
+            }
+            if flags & 0x03 != 0 {
+                // Beaming
+                self.skip(4)?;
+            }
+            if flags & 0x10 == 0 {
+                self.skip(1)?;
+            }
+            // Triplet feel
+            self.skip(1)?;
+        }
+        Ok(header)
+    }
+
+    fn track(&mut self, index: usize) -> Result<Track, ImportError> {
+        let version = self.version;
+        if version >= 500 && (index == 0 || version == 500) {
+            self.skip(1)?;
+        }
+        // Flags and name
+        self.skip(1)?;
+        self.byte_string(40)?;
+        let string_count = self.count()?.min(7);
+        let mut strings = Vec::with_capacity(string_count);
+        for string in 0..7 {
+            let pitch = self.i32()?;
+            if string < string_count {
+                strings.push(pitch.clamp(0, 127) as u8);
+            }
+        }
+        // Port, channel, effect channel, fret count
+        self.skip(16)?;
+        let capo = self.i32()?;
+        // Colour
+        self.skip(4)?;
+        if version >= 500 {
+            // Display flags, accentuation, bank and RSE settings
+            self.skip(if version > 500 { 45 } else { 44 })?;
+            if version > 500 {
+                // Equalizer, then the RSE effect name and category
+                self.skip(4)?;
+                self.int_byte_string()?;
+                self.int_byte_string()?;
+            }
+        }
+        Ok(Track { strings, capo })
+    }
+
+    /// One beat and its length in ticks
+    fn beat(&mut self, string_count: usize, start: u32) -> Result<(Beat, u32), ImportError> {
+        let flags = self.u8()?;
+        let mut rest = false;
+        if flags & 0x40 != 0 {
+            // 0 empty, 1 normal, 2 rest
+            rest = self.u8()? != 1;
+        }
+        let value = self.i8()?;
+        let tuplet = if flags & 0x20 != 0 { self.i32()? } else { 1 };
+        let ticks = duration(value, flags & 0x01 != 0, tuplet);
+
+        let chord = if flags & 0x02 != 0 {
+            Some(self.chord()?).filter(|name| !name.is_empty())
+        } else {
+            None
+        };
+        if flags & 0x04 != 0 {
+            // Free text
+            self.int_byte_string()?;
+        }
+        if flags & 0x08 != 0 {
+            self.beat_effects()?;
+        }
+        let tempo = if flags & 0x10 != 0 {
+            self.mix_table()?
+        } else {
+            None
+        };
+        let notes = self.notes(string_count)?;
+        if self.version >= 500 {
+            let display = self.i16()?;
+            if display & 0x0800 != 0 {
+                self.skip(1)?;
+            }
+        }
+
+        let beat = Beat {
+            start,
+            rest: rest || notes == 0,
+            chord,
+            tempo,
+        };
+        Ok((beat, ticks))
+    }
+
+    /// A chord diagram; only its name is kept
+    fn chord(&mut self) -> Result<String, ImportError> {
+        let version = self.version;
+        let name = if version >= 500 {
+            self.skip(17)?;
+            let name = self.byte_string(21)?;
+            // Fifth/ninth/eleventh, first fret, 7 frets, barres, fingering
+            self.skip(4 + 4 + 28 + 6 + 26)?;
+            name
+        } else if self.u8()? != 0 {
+            if version >= 400 {
+                self.skip(16)?;
+                let name = self.byte_string(21)?;
+                self.skip(4 + 4 + 28 + 6 + 26)?;
+                name
+            } else {
+                self.skip(25)?;
+                let name = self.byte_string(34)?;
+                // First fret, 6 frets, barres and omissions
+                self.skip(4 + 24 + 36)?;
+                name
+            }
+        } else {
+            let name = self.int_byte_string()?;
+            let first_fret = self.i32()?;
+            if first_fret != 0 {
+                self.skip(if version >= 406 { 7 * 4 } else { 6 * 4 })?;
+            }
+            name
+        };
+        Ok(name.trim().to_string())
+    }
+
+    fn bend(&mut self) -> Result<(), ImportError> {
+        self.skip(5)?;
+        let points = self.count()?;
+        self.skip(points * 9)
+    }
+
+    fn beat_effects(&mut self) -> Result<(), ImportError> {
+        if self.version < 400 {
+            let flags = self.u8()?;
+            if flags & 0x20 != 0 {
+                // Slap/pop or tremolo bar, with its value
+                self.skip(5)?;
+            }
+            if flags & 0x40 != 0 {
+                // Stroke
+                self.skip(2)?;
+            }
+            return Ok(());
+        }
+
+        let flags1 = self.u8()?;
+        let flags2 = self.u8()?;
+        if flags1 & 0x20 != 0 {
+            self.skip(1)?;
+        }
+        if flags2 & 0x04 != 0 {
+            self.bend()?;
+        }
+        if flags1 & 0x40 != 0 {
+            self.skip(2)?;
+        }
+        if flags2 & 0x02 != 0 {
+            // Pick stroke
+            self.skip(1)?;
+        }
+        Ok(())
+    }
+
+    /// Mixer change; returns the new tempo, if any
+    fn mix_table(&mut self) -> Result<Option<u32>, ImportError> {
+        let version = self.version;
+        // Instrument
+        self.skip(1)?;
+        if version >= 500 {
+            // RSE instrument
+            self.skip(16)?;
+        }
+        // Volume, balance, chorus, reverb, phaser, tremolo
+        let mut changed = 0;
+        for _ in 0..6 {
+            if self.i8()? >= 0 {
+                changed += 1;
+            }
+        }
+        if version >= 500 {
+            // Tempo name
+            self.int_byte_string()?;
+        }
+        let tempo = self.i32()?;
+        // Transition duration of each change
+        self.skip(changed)?;
+        if tempo >= 0 {
+            self.skip(if version > 500 { 2 } else { 1 })?;
+        }
+        if version >= 400 {
+            // Apply-to-all-tracks flags
+            self.skip(1)?;
+        }
+        if version >= 500 {
+            // Wah
+            self.skip(1)?;
+            if version > 500 {
+                self.int_byte_string()?;
+                self.int_byte_string()?;
+            }
+        }
+        Ok((tempo > 0).then_some(tempo as u32))
+    }
+
+    /// Notes of a beat; returns how many there were
+    fn notes(&mut self, string_count: usize) -> Result<usize, ImportError> {
+        let strings = self.u8()?;
+        let mut count = 0;
+        for string in 1..=string_count {
+            if strings & (1 << (7 - string)) != 0 {
+                self.note()?;
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
+    fn note(&mut self) -> Result<(), ImportError> {
+        let version = self.version;
+        let flags = self.u8()?;
+        if flags & 0x20 != 0 {
+            // Normal, tie or dead
+            self.skip(1)?;
+        }
+        if version < 500 && flags & 0x01 != 0 {
+            // Independent duration and tuplet
+            self.skip(2)?;
+        }
+        if flags & 0x10 != 0 {
+            // Velocity
+            self.skip(1)?;
+        }
+        if flags & 0x20 != 0 {
+            // Fret
+            self.skip(1)?;
+        }
+        if flags & 0x80 != 0 {
+            // Fingering
+            self.skip(2)?;
+        }
+        if version >= 500 {
+            if flags & 0x01 != 0 {
+                // Duration percent (f64)
+                self.skip(8)?;
+            }
+            self.skip(1)?;
+        }
+        if flags & 0x08 != 0 {
+            self.note_effects()?;
+        }
+        Ok(())
+    }
+
+    fn note_effects(&mut self) -> Result<(), ImportError> {
+        let version = self.version;
+        if version < 400 {
+            let flags = self.u8()?;
+            if flags & 0x01 != 0 {
+                self.bend()?;
+            }
+            if flags & 0x10 != 0 {
+                // Grace note
+                self.skip(4)?;
+            }
+            return Ok(());
+        }
+
+        let flags1 = self.u8()?;
+        let flags2 = self.u8()?;
+        if flags1 & 0x01 != 0 {
+            self.bend()?;
+        }
+        if flags1 & 0x10 != 0 {
+            self.skip(if version >= 500 { 5 } else { 4 })?;
+        }
+        if flags2 & 0x04 != 0 {
+            // Tremolo picking
+            self.skip(1)?;
+        }
+        if flags2 & 0x08 != 0 {
+            // Slide
+            self.skip(1)?;
+        }
+        if flags2 & 0x10 != 0 {
+            // Harmonic; GP5 artificial and tapped harmonics carry extra bytes
+            let kind = self.i8()?;
+            if version >= 500 {
+                match kind {
+                    2 => self.skip(3)?,
+                    3 => self.skip(1)?,
+                    _ => {}
+                }
+            }
+        }
+        if flags2 & 0x20 != 0 {
+            // Trill
+            self.skip(2)?;
+        }
+        Ok(())
+    }
+}
+
+struct Syllable {
+    text: String,
+    /// Continues the previous syllable's word ("Hel-lo")
+    joined: bool,
+    /// First syllable after a line break in the lyrics
+    new_line: bool,
+}
+
+/// Split Guitar Pro lyrics into one syllable per beat
+///
+/// Spaces and line breaks separate words and "-" separates syllables; "+"
+/// keeps two words on one beat and "[...]" is a comment.
+fn syllables(text: &str) -> Vec<Syllable> {
+    let mut syllables = Vec::new();
+    let mut current = String::new();
+    let mut joined = false;
+    let mut new_line = false;
+    let mut comment = false;
+
+    for c in text.chars().chain(std::iter::once(' ')) {
+        if comment {
+            comment = c != ']';
+            continue;
+        }
+        match c {
+            '[' => comment = true,
+            '+' => current.push(' '),
+            '-' | ' ' | '\t' | '\r' | '\n' => {
+                if !current.is_empty() {
+                    syllables.push(Syllable {
+                        text: std::mem::take(&mut current),
+                        joined,
+                        new_line,
+                    });
+                    joined = c == '-';
+                    new_line = false;
+                }
+                if c == '\r' || c == '\n' {
+                    new_line = !syllables.is_empty();
+                    joined = false;
+                }
+            }
+            _ => current.push(c),
+        }
+    }
+    syllables
+}
+
+/// Full-width scripts are written without spaces between words
+fn is_wide(c: char) -> bool {
+    c >= '\u{2E80}'
+}
+
+#[derive(Default)]
+struct Line {
+    start: u32,
+    lyrics: String,
+    chords: Vec<FetchedChord>,
+    /// Character position of the latest syllable
+    syllable: Option<usize>,
+    /// Next free column in a line of chords only
+    column: usize,
+    /// Bars started in this line
+    bars: usize,
+    /// Bars in a row without lyrics since the last syllable
+    idle: usize,
+}
+
+/// Collects lines and sections measure by measure
+struct SheetBuilder<'a> {
+    measure_starts: &'a [u32],
+    /// Wrap lyric lines by bar count, for lyrics without line breaks
+    wrap_lyrics: bool,
+    sections: Vec<FetchedSection>,
+    section: FetchedSection,
+    section_start: u32,
+    /// Finished lines of the current section with their start tick
+    lines: Vec<(u32, FetchedLine)>,
+    line: Line,
+    singing: bool,
+}
+
+impl SheetBuilder<'_> {
+    fn break_line(&mut self, at: u32) {
+        let line = std::mem::replace(
+            &mut self.line,
+            Line {
+                start: at,
+                ..Line::default()
+            },
+        );
+        if !line.lyrics.is_empty() || !line.chords.is_empty() {
+            self.lines.push((
+                line.start,
+                FetchedLine::with_chords(&line.lyrics, line.chords),
+            ));
+        }
+    }
+
+    /// Close the current section at `end`, counting the bars each line spans
+    fn end_section(&mut self, end: u32) {
+        self.break_line(end);
+        let lines = std::mem::take(&mut self.lines);
+        let starts: Vec<u32> = lines.iter().map(|(start, _)| *start).collect();
+        for (index, (start, mut line)) in lines.into_iter().enumerate() {
+            let from = if index == 0 {
+                self.section_start
+            } else {
+                start
+            };
+            let to = starts.get(index + 1).copied().unwrap_or(end);
+            let bars = self
+                .measure_starts
+                .iter()
+                .filter(|&&tick| tick >= from && tick < to)
+                .count();
+            line.measures = (bars > 0).then_some(bars as u32);
+            self.section.lines.push(line);
+        }
+        let section = std::mem::replace(&mut self.section, FetchedSection::new(""));
+        if !section.lines.is_empty() || self.sections.is_empty() {
+            self.sections.push(section);
+        }
+    }
+
+    fn start_section(&mut self, section: FetchedSection, start: u32) {
+        if start > 0 {
+            self.end_section(start);
+            // An empty first section only holds the place of the default one
+            if self.sections.len() == 1 && self.sections[0].lines.is_empty() {
+                self.sections.clear();
+            }
+        }
+        self.section = section;
+        self.section_start = start;
+        self.line = Line {
+            start,
+            ..Line::default()
+        };
+    }
+
+    fn start_measure(&mut self, start: u32, sung: bool) {
+        let line = &mut self.line;
+        let split = if !line.lyrics.is_empty() {
+            if sung {
+                line.idle = 0;
+                self.wrap_lyrics && line.bars >= BARS_PER_LINE
+            } else {
+                // The bar after the words still belongs to the line; a longer
+                // break starts a line of chords
+                line.idle += 1;
+                line.idle >= 2
+            }
+        } else {
+            sung || line.bars >= BARS_PER_LINE
+        };
+        if split {
+            self.break_line(start);
+        }
+        self.line.bars += 1;
+        self.singing = sung;
+    }
+
+    fn syllable(&mut self, tick: u32, syllable: &Syllable) {
+        if syllable.new_line && !self.line.lyrics.is_empty() {
+            self.break_line(tick);
+        }
+        let lyrics = &mut self.line.lyrics;
+        let spaced = !syllable.joined
+            && lyrics
+                .chars()
+                .last()
+                .is_some_and(|last| !(is_wide(last) && syllable.text.starts_with(is_wide)));
+        if spaced {
+            lyrics.push(' ');
+        }
+        self.line.syllable = Some(lyrics.chars().count());
+        lyrics.push_str(&syllable.text);
+    }
+
+    fn chord(&mut self, tick: u32, name: &str) {
+        let line = &mut self.line;
+        let position = if !self.singing && !line.lyrics.is_empty() {
+            // Between lines of lyrics: after the words
+            line.lyrics.chars().count()
+        } else if self.singing {
+            line.syllable.unwrap_or(0)
+        } else {
+            // Chords only: two columns per beat
+            let column = ((tick.saturating_sub(line.start)) / QUARTER * 2) as usize;
+            let column = column.max(line.column);
+            line.column = column + name.chars().count() + 1;
+            column
+        };
+        line.chords.push(FetchedChord::new(name, position as i32));
+    }
+}
+
+impl Document {
+    fn has_chords(&self, track: usize) -> bool {
+        self.beats
+            .iter()
+            .any(|measure| measure[track].iter().any(|beat| beat.chord.is_some()))
+    }
+
+    /// Syllables placed on the lyrics track's sounding beats, per measure
+    ///
+    /// Lyric lines that overlap (a second verse under the first) do not
+    /// replace syllables already placed.
+    fn place_lyrics(&self, measure_starts: &[u32]) -> Vec<Vec<(u32, Syllable)>> {
+        let mut placed: Vec<Vec<(usize, u32, Syllable)>> =
+            (0..self.headers.len()).map(|_| Vec::new()).collect();
+        let track = self.lyrics_track.filter(|&t| t < self.tracks.len());
+        let lyrics = if track.is_some() {
+            &self.lyrics[..]
+        } else {
+            &[]
+        };
+
+        for (first_measure, text) in lyrics {
+            let mut syllables = syllables(text).into_iter();
+            'measures: for measure in *first_measure..self.headers.len() {
+                for (index, beat) in self.beats[measure][track.unwrap_or(0)].iter().enumerate() {
+                    if beat.rest {
+                        continue;
+                    }
+                    let Some(syllable) = syllables.next() else {
+                        break 'measures;
+                    };
+                    if placed[measure].iter().all(|(taken, _, _)| *taken != index) {
+                        let tick = measure_starts[measure].saturating_add(beat.start);
+                        placed[measure].push((index, tick, syllable));
+                    }
+                }
+            }
+        }
+
+        placed
+            .into_iter()
+            .map(|mut measure| {
+                measure.sort_by_key(|(_, tick, _)| *tick);
+                measure.into_iter().map(|(_, tick, s)| (tick, s)).collect()
+            })
+            .collect()
+    }
+
+    fn to_song(&self) -> Result<ImportedSong, ImportError> {
+        let mut measure_starts = Vec::with_capacity(self.headers.len() + 1);
+        let mut tick: u32 = 0;
+        for header in &self.headers {
+            measure_starts.push(tick);
+            tick = checked(tick.checked_add(header.ticks()), "Song length")?;
+        }
+        let end = tick;
+
+        let lyrics = self.place_lyrics(&measure_starts);
+        let chord_track = self
+            .lyrics_track
+            .filter(|&t| t < self.tracks.len() && self.has_chords(t))
+            .or_else(|| (0..self.tracks.len()).find(|&t| self.has_chords(t)));
+        let track = chord_track
+            .or(self.lyrics_track)
+            .filter(|&t| t < self.tracks.len())
+            .or((!self.tracks.is_empty()).then_some(0));
+
+        let song_time = self.headers.first().map(MeasureHeader::time_signature);
+        let has_markers = self.headers.iter().any(|h| h.marker.is_some());
+        let wrap_lyrics = !lyrics.iter().flatten().any(|(_, s)| s.new_line);
+
+        let mut builder = SheetBuilder {
+            measure_starts: &measure_starts,
+            wrap_lyrics,
+            sections: Vec::new(),
+            section: FetchedSection::new(if has_markers { "Intro" } else { "Main" }),
+            section_start: 0,
+            lines: Vec::new(),
+            line: Line::default(),
+            singing: false,
+        };
+
+        let mut tempo = self.tempo;
+        for (index, header) in self.headers.iter().enumerate() {
+            let start = measure_starts[index];
+            let beats = &self.beats[index];
+            if let Some(change) = beats.iter().flatten().find_map(|beat| beat.tempo) {
+                tempo = change;
+            }
+
+            if index == 0 || header.marker.is_some() {
+                let name = header.marker.as_deref().unwrap_or(&builder.section.name);
+                let mut section = FetchedSection::new(name);
+                let time = header.time_signature();
+                section.time_signature = (Some(&time) != song_time.as_ref()).then_some(time);
+                section.bpm_override = (tempo != self.tempo).then_some(tempo);
+                builder.start_section(section, start);
+            }
+
+            let mut events: Vec<(u32, Option<&Syllable>, Option<&str>)> = lyrics[index]
+                .iter()
+                .map(|(tick, syllable)| (*tick, Some(syllable), None))
+                .collect();
+            if let Some(track) = track {
+                let mut previous: Option<&str> = None;
+                for beat in &beats[track] {
+                    if let Some(name) = beat.chord.as_deref() {
+                        // The same diagram repeated within a bar is one chord
+                        if previous != Some(name) {
+                            events.push((start.saturating_add(beat.start), None, Some(name)));
+                        }
+                        previous = Some(name);
+                    }
+                }
+            }
+            // Syllables before chords on the same tick, so the chord takes that syllable
+            events.sort_by_key(|(tick, syllable, _)| (*tick, syllable.is_none()));
+
+            builder.start_measure(start, !lyrics[index].is_empty());
+            for (tick, syllable, chord) in events {
+                if let Some(syllable) = syllable {
+                    builder.syllable(tick, syllable);
+                }
+                if let Some(name) = chord {
+                    builder.chord(tick, name);
+                }
+            }
+        }
+        builder.end_section(end);
+
+        let mut sheet = FetchedChordSheet::new(String::new());
+        sheet.title = Some(self.title.trim().to_string()).filter(|t| !t.is_empty());
+        sheet.artist = Some(self.artist.trim().to_string()).filter(|a| !a.is_empty());
+        // Files default to no sharps or flats, so C major says nothing
+        let key = checked(self.key.checked_add(7), "Key signature")?;
+        sheet.key = (self.key != 0)
+            .then(|| usize::try_from(key).ok())
+            .flatten()
+            .and_then(|index| KEYS.get(index))
+            .map(|key| key.to_string());
+        if let Some(track) = track.map(|t| &self.tracks[t]) {
+            sheet.capo = (track.capo > 0).then_some(track.capo);
+            sheet.tuning = Instrument::presets()
+                .into_iter()
+                .find(|preset| preset.strings == track.strings)
+                .map(|preset| preset.id);
+        }
+        sheet.sections = builder.sections;
+
+        Ok(ImportedSong {
+            sheet,
+            bpm: Some(self.tempo),
+            time_signature: song_time,
+            difficulty: None,
+        })
+    }
+}
+
+/// Read a Guitar Pro 3-5 file into a chord sheet
+pub fn read(data: &[u8]) -> Result<ImportedSong, ImportError> {
+    let mut reader = Reader {
+        data,
+        pos: 0,
+        version: 0,
+    };
+    reader.document()?.to_song()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes just enough of the format to exercise the reader
+    #[derive(Default)]
+    struct Writer {
+        data: Vec<u8>,
+        version: u32,
+    }
+
+    struct TestBeat {
+        chord: Option<&'static str>,
+        rest: bool,
+        duration: i8,
+        tempo: Option<i32>,
+    }
+
+    fn note(duration: i8, chord: Option<&'static str>) -> TestBeat {
+        TestBeat {
+            chord,
+            rest: false,
+            duration,
+            tempo: None,
+        }
+    }
+
+    fn rest(chord: Option<&'static str>) -> TestBeat {
+        TestBeat {
+            rest: true,
+            ..note(0, chord)
+        }
+    }
+
+    struct TestMeasure {
+        time: Option<(u8, u8)>,
+        marker: Option<&'static str>,
+        beats: Vec<TestBeat>,
+    }
+
+    impl Writer {
+        fn u8(&mut self, value: u8) {
+            self.data.push(value);
+        }
+
+        fn i32(&mut self, value: i32) {
+            self.data.extend(value.to_le_bytes());
+        }
+
+        fn zeros(&mut self, count: usize) {
+            self.data.extend(std::iter::repeat_n(0, count));
+        }
+
+        fn byte_string(&mut self, text: &str, size: usize) {
+            self.u8(text.len() as u8);
+            self.data.extend(text.as_bytes());
+            self.zeros(size - text.len());
+        }
+
+        fn int_byte_string(&mut self, text: &str) {
+            self.i32(text.len() as i32 + 1);
+            self.u8(text.len() as u8);
+            self.data.extend(text.as_bytes());
+        }
+
+        fn chord(&mut self, name: &str) {
+            if self.version >= 500 {
+                self.zeros(17);
+                self.byte_string(name, 21);
+                self.zeros(4 + 4 + 28 + 6 + 26);
+            } else {
+                // Old format without a diagram
+                self.u8(0);
+                self.int_byte_string(name);
+                self.i32(0);
+            }
+        }
+
+        fn mix_table(&mut self, tempo: i32) {
+            self.u8(0xFF);
+            if self.version >= 500 {
+                self.zeros(16);
+            }
+            self.data.extend([0xFF; 6]);
+            if self.version >= 500 {
+                self.int_byte_string("");
+            }
+            self.i32(tempo);
+            self.u8(0);
+            if self.version > 500 {
+                self.u8(0);
+            }
+            if self.version >= 400 {
+                self.u8(0);
+            }
+            if self.version >= 500 {
+                self.u8(0xFF);
+                if self.version > 500 {
+                    self.int_byte_string("");
+                    self.int_byte_string("");
+                }
+            }
+        }
+
+        fn beat(&mut self, beat: &TestBeat) {
+            let mut flags = 0;
+            if beat.chord.is_some() {
+                flags |= 0x02;
+            }
+            if beat.tempo.is_some() {
+                flags |= 0x10;
+            }
+            if beat.rest {
+                flags |= 0x40;
+            }
+            self.u8(flags);
+            if beat.rest {
+                self.u8(2);
+            }
+            self.u8(beat.duration as u8);
+            if let Some(name) = beat.chord {
+                self.chord(name);
+            }
+            if let Some(tempo) = beat.tempo {
+                self.mix_table(tempo);
+            }
+            if beat.rest {
+                self.u8(0);
+            } else {
+                // Fret 3 on the 1st string, with a slide
+                self.u8(0x40);
+                self.u8(0x28);
+                self.u8(1);
+                self.u8(3);
+                if self.version >= 500 {
+                    self.u8(0);
+                }
+                if self.version >= 400 {
+                    self.u8(0);
+                    self.u8(0x08);
+                    self.u8(1);
+                } else {
+                    self.u8(0x04);
+                }
+            }
+            if self.version >= 500 {
+                self.zeros(2);
+            }
+        }
+
+        fn song(version: u32, lyrics: &str, capo: i32, measures: &[TestMeasure]) -> Vec<u8> {
+            let mut w = Writer {
+                data: Vec::new(),
+                version,
+            };
+            let name = format!("FICHIER GUITAR PRO v{}.{:02}", version / 100, version % 100);
+            w.byte_string(&name, 30);
+            w.int_byte_string("River Road");
+            w.int_byte_string("");
+            w.int_byte_string("The Example Band");
+            for _ in 0..if version >= 500 { 6 } else { 5 } {
+                w.int_byte_string("");
+            }
+            w.i32(0);
+            if version < 500 {
+                w.u8(0);
+            }
+            if version >= 400 {
+                w.i32(1);
+                for line in 0..5 {
+                    let text = if line == 0 { lyrics } else { "" };
+                    w.i32(1);
+                    w.i32(text.len() as i32);
+                    w.data.extend(text.as_bytes());
+                }
+            }
+            if version >= 510 {
+                w.zeros(19);
+            }
+            if version >= 500 {
+                w.zeros(30);
+                for _ in 0..11 {
+                    w.int_byte_string("");
+                }
+            }
+            w.i32(96);
+            if version >= 510 {
+                w.u8(0);
+            }
+            w.i32(1);
+            if version >= 400 {
+                w.u8(0);
+            }
+            w.zeros(64 * 12);
+            if version >= 500 {
+                w.zeros(42);
+            }
+            w.i32(measures.len() as i32);
+            w.i32(1);
+
+            for (index, measure) in measures.iter().enumerate() {
+                if version >= 500 && index > 0 {
+                    w.u8(0);
+                }
+                let mut flags = 0;
+                if measure.time.is_some() {
+                    flags |= 0x03;
+                }
+                if measure.marker.is_some() {
+                    flags |= 0x20;
+                }
+                w.u8(flags);
+                if let Some((numerator, denominator)) = measure.time {
+                    w.u8(numerator);
+                    w.u8(denominator);
+                }
+                if let Some(marker) = measure.marker {
+                    w.int_byte_string(marker);
+                    w.zeros(4);
+                }
+                if version >= 500 {
+                    if measure.time.is_some() {
+                        w.zeros(4);
+                    }
+                    w.zeros(2);
+                }
+            }
+
+            if version >= 500 {
+                w.u8(0);
+            }
+            w.u8(0);
+            w.byte_string("Guitar", 40);
+            w.i32(6);
+            for pitch in [64, 59, 55, 50, 45, 40, 0] {
+                w.i32(pitch);
+            }
+            w.zeros(16);
+            w.i32(capo);
+            w.zeros(4);
+            if version >= 500 {
+                w.zeros(if version > 500 { 45 } else { 44 });
+                if version > 500 {
+                    w.zeros(4);
+                    w.int_byte_string("");
+                    w.int_byte_string("");
+                }
+                w.zeros(if version == 500 { 2 } else { 1 });
+            }
+
+            for measure in measures {
+                w.i32(measure.beats.len() as i32);
+                for beat in &measure.beats {
+                    w.beat(beat);
+                }
+                if version >= 500 {
+                    w.i32(0);
+                    w.u8(0);
+                }
+            }
+            w.data
+        }
+    }
+
+    /// Lyrics, chords with positions, and bars of each line
+    type LineSummary<'a> = (&'a str, Vec<(&'a str, i32)>, Option<u32>);
+
+    fn lines(section: &FetchedSection) -> Vec<LineSummary<'_>> {
+        section
+            .lines
+            .iter()
+            .map(|line| {
+                let chords = line
+                    .chords
+                    .iter()
+                    .map(|c| (c.chord.as_str(), c.position))
+                    .collect();
+                (line.lyrics.as_str(), chords, line.measures)
+            })
+            .collect()
+    }
+
+    fn verse_and_chorus() -> Vec<TestMeasure> {
+        let mut chorus_start = rest(Some("C"));
+        chorus_start.tempo = Some(120);
+        vec![
+            TestMeasure {
+                time: Some((4, 4)),
+                marker: Some("Verse"),
+                beats: vec![
+                    note(0, Some("C")),
+                    note(0, None),
+                    rest(None),
+                    note(0, Some("G")),
+                ],
+            },
+            TestMeasure {
+                time: None,
+                marker: None,
+                beats: vec![
+                    note(0, Some("Am")),
+                    note(0, Some("Am")),
+                    note(-1, Some("F")),
+                ],
+            },
+            TestMeasure {
+                time: Some((3, 4)),
+                marker: Some("Chorus"),
+                beats: vec![chorus_start, rest(None), rest(Some("G"))],
+            },
+            TestMeasure {
+                time: None,
+                marker: None,
+                beats: vec![rest(Some("Am")), rest(None), rest(None)],
+            },
+        ]
+    }
+
+    #[test]
+    fn test_read_gp5() {
+        for version in [500, 510] {
+            let data = Writer::song(
+                version,
+                "Hel-lo world\r\nsec-ond line",
+                0,
+                &verse_and_chorus(),
+            );
+            let song = read(&data).unwrap();
+            assert_eq!(song.bpm, Some(96));
+            assert_eq!(song.time_signature.as_deref(), Some("4/4"));
+
+            let sheet = &song.sheet;
+            assert_eq!(sheet.title.as_deref(), Some("River Road"));
+            assert_eq!(sheet.artist.as_deref(), Some("The Example Band"));
+            assert_eq!(sheet.key.as_deref(), Some("G"));
+            assert_eq!(sheet.tuning.as_deref(), Some("standard"));
+            assert_eq!(sheet.capo, None);
+
+            let names: Vec<_> = sheet.sections.iter().map(|s| s.name.as_str()).collect();
+            assert_eq!(names, vec!["Verse", "Chorus"]);
+            assert_eq!(
+                lines(&sheet.sections[0]),
+                vec![
+                    ("Hello world", vec![("C", 0), ("G", 6)], Some(1)),
+                    ("second line", vec![("Am", 0), ("F", 7)], Some(1)),
+                ]
+            );
+
+            let chorus = &sheet.sections[1];
+            assert_eq!(chorus.bpm_override, Some(120));
+            assert_eq!(chorus.time_signature.as_deref(), Some("3/4"));
+            assert_eq!(
+                lines(chorus),
+                vec![("", vec![("C", 0), ("G", 4), ("Am", 6)], Some(2))]
+            );
+        }
+    }
+
+    #[test]
+    fn test_read_gp3_without_lyrics() {
+        let measures: Vec<TestMeasure> = ["D", "A", "Bm", "G", "D"]
+            .into_iter()
+            .enumerate()
+            .map(|(index, chord)| TestMeasure {
+                time: (index == 0).then_some((4, 4)),
+                marker: None,
+                beats: vec![note(-2, Some(chord))],
+            })
+            .collect();
+        let song = read(&Writer::song(300, "", 2, &measures)).unwrap();
+        let sheet = &song.sheet;
+        assert_eq!(sheet.capo, Some(2));
+        assert_eq!(sheet.sections.len(), 1);
+        assert_eq!(sheet.sections[0].name, "Main");
+        assert_eq!(
+            lines(&sheet.sections[0]),
+            vec![
+                ("", vec![("D", 0), ("A", 8), ("Bm", 16), ("G", 24)], Some(4)),
+                ("", vec![("D", 0)], Some(1)),
+            ]
+        );
+    }
+
+    /// Files with two tracks, repeats, tuplets, beat text and note effects;
+    /// GP3 has no lyrics and mixes old and new chord diagrams
+    #[test]
+    fn test_fixture_files() {
+        let files: [(&[u8], &str, Option<i32>); 3] = [
+            (include_bytes!("fixtures/amazing_grace.gp3"), "G", None),
+            (include_bytes!("fixtures/amazing_grace.gp4"), "F", None),
+            (include_bytes!("fixtures/amazing_grace.gp5"), "Bb", Some(2)),
+        ];
+        for (data, key, capo) in files {
+            let song = read(data).unwrap();
+            assert_eq!(song.bpm, Some(72));
+            assert_eq!(song.time_signature.as_deref(), Some("3/4"));
+
+            let sheet = &song.sheet;
+            assert_eq!(sheet.title.as_deref(), Some("Amazing Grace"));
+            assert_eq!(sheet.artist.as_deref(), Some("John Newton"));
+            assert_eq!(sheet.key.as_deref(), Some(key));
+            assert_eq!(sheet.capo, capo);
+            assert_eq!(sheet.tuning.as_deref(), Some("standard"));
+
+            let names: Vec<_> = sheet.sections.iter().map(|s| s.name.as_str()).collect();
+            assert_eq!(names, vec!["Verse", "Outro"]);
+            let outro = &sheet.sections[1];
+            assert_eq!(outro.bpm_override, Some(80));
+            assert_eq!(lines(outro), vec![("", vec![("G", 0)], Some(1))]);
+
+            let verse = lines(&sheet.sections[0]);
+            if key == "G" {
+                assert_eq!(
+                    verse,
+                    vec![
+                        ("", vec![("G", 0), ("C", 12), ("G", 18)], Some(4)),
+                        ("", vec![("D", 0), ("Em", 6), ("D7/F#", 9)], Some(2)),
+                    ]
+                );
+            } else {
+                assert_eq!(
+                    verse,
+                    vec![
+                        (
+                            "Amazing grace how sweet the sound",
+                            vec![("G", 0), ("C", 18)],
+                            Some(3)
+                        ),
+                        (
+                            "That saved a wretch like me",
+                            vec![("G", 0), ("D", 5), ("Em", 13), ("D7/F#", 25)],
+                            Some(3)
+                        ),
+                    ]
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_syllables() {
+        let parsed = syllables("[Verse 1] a+b rain-bow\n君 の");
+        let texts: Vec<_> = parsed
+            .iter()
+            .map(|s| (s.text.as_str(), s.joined, s.new_line))
+            .collect();
+        assert_eq!(
+            texts,
+            vec![
+                ("a b", false, false),
+                ("rain", false, false),
+                ("bow", true, false),
+                ("君", false, true),
+                ("の", false, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_invalid_files() {
+        assert!(matches!(
+            read(b"not a guitar pro file"),
+            Err(ImportError::Invalid(_))
+        ));
+        let mut data = vec![24];
+        data.extend(b"FICHIER GUITAR PRO v6.00");
+        data.resize(31, 0);
+        assert!(matches!(
+            read(&data),
+            Err(ImportError::UnsupportedFormat(_))
+        ));
+
+        let full = Writer::song(510, "", 0, &verse_and_chorus());
+        assert!(matches!(
+            read(&full[..full.len() - 3]),
+            Err(ImportError::Invalid(_))
+        ));
+
+        // A lyrics track of i32::MIN cannot be made 0-based
+        let mut data = Writer::song(400, "", 0, &verse_and_chorus());
+        let lyrics_track = 31 + 15 + 5 + 21 + 5 * 5 + 4 + 1;
+        assert_eq!(data[lyrics_track..lyrics_track + 4], 1i32.to_le_bytes());
+        data[lyrics_track..lyrics_track + 4].copy_from_slice(&i32::MIN.to_le_bytes());
+        assert!(matches!(read(&data), Err(ImportError::Invalid(_))));
+    }
+}
@@ -0,0 +1,327 @@
+//! Song imports from files (Guitar Pro 3-5, OpenLyrics, OpenSong, SongbookPro)
+//!
+//! Readers turn a file into `ImportedSong`s: the sheet plus the tempo and
+//! meter the file carries, which have no place in `FetchedChordSheet`.
+//! SongbookPro archives hold a whole library, so a file may give several.
+
+pub mod guitar_pro;
+pub mod openlyrics;
+pub mod opensong;
+pub mod songbookpro;
+
+use crate::difficulty::DifficultyRating;
+use crate::error::ImportError;
+use crate::parsers::{FetchedChordSheet, FetchedSection};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportedSong {
+    pub sheet: FetchedChordSheet,
+    #[serde(default)]
+    pub bpm: Option<u32>,
+    /// "4/4", "3/4", "6/8"
+    #[serde(default)]
+    pub time_signature: Option<String>,
+    /// Filled in by the import commands
+    #[serde(default)]
+    pub difficulty: Option<DifficultyRating>,
+}
+
+/// Songs read from one file
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SongFile {
+    pub songs: Vec<ImportedSong>,
+    /// Songs in an archive that could not be read, each with why
+    #[serde(default)]
+    pub skipped: Vec<String>,
+}
+
+/// Outcome of one file in a batch import
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileImportReport {
+    pub path: String,
+    pub songs: Vec<ImportedSong>,
+    #[serde(default)]
+    pub skipped: Vec<String>,
+    /// Why the file could not be read; `songs` is empty when set
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    GuitarPro,
+    OpenLyrics,
+    OpenSong,
+    SongbookPro,
+}
+
+/// Choose a reader by extension, or by content for XML (OpenSong files
+/// usually have no extension at all)
+fn detect_format(path: &Path, data: &[u8]) -> Option<Format> {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(str::to_ascii_lowercase);
+    match extension.as_deref() {
+        Some("gp3" | "gp4" | "gp5") => return Some(Format::GuitarPro),
+        Some("sbp" | "sbpbackup") => return Some(Format::SongbookPro),
+        _ => {}
+    }
+
+    let head = String::from_utf8_lossy(&data[..data.len().min(1024)]);
+    if head.contains("openlyrics.info") || head.contains("<properties") {
+        Some(Format::OpenLyrics)
+    } else if head
+        .trim_start_matches('\u{feff}')
+        .trim_start()
+        .starts_with('<')
+        && head.contains("<song")
+    {
+        Some(Format::OpenSong)
+    } else {
+        None
+    }
+}
+
+/// XML text, without a byte order mark
+fn xml_text(data: &[u8]) -> Result<&str, ImportError> {
+    std::str::from_utf8(data)
+        .map(|text| text.trim_start_matches('\u{feff}'))
+        .map_err(|e| ImportError::Invalid(format!("Not UTF-8 text: {}", e)))
+}
+
+/// Read every song in a file
+fn read_songs(path: &Path) -> Result<SongFile, ImportError> {
+    let data = std::fs::read(path)?;
+    let format = detect_format(path, &data)
+        .ok_or_else(|| ImportError::UnsupportedFormat(path.display().to_string()))?;
+
+    let one = |song| SongFile {
+        songs: vec![song],
+        skipped: Vec::new(),
+    };
+    let mut file = match format {
+        Format::GuitarPro => one(guitar_pro::read(&data)?),
+        Format::OpenLyrics => one(openlyrics::read(xml_text(&data)?)?),
+        Format::OpenSong => one(opensong::read(xml_text(&data)?)?),
+        Format::SongbookPro => songbookpro::read(&data)?,
+    };
+    let several = file.songs.len() > 1;
+    for (index, song) in file.songs.iter_mut().enumerate() {
+        song.sheet.source_url = if several {
+            format!("{}#{}", path.display(), index + 1)
+        } else {
+            path.display().to_string()
+        };
+    }
+    Ok(file)
+}
+
+/// Read a song file; archives give every song in them
+pub fn import_file(path: &Path) -> Result<SongFile, ImportError> {
+    let file = read_songs(path)?;
+    if file.songs.is_empty() {
+        let mut message = format!("No songs in {}", path.display());
+        if !file.skipped.is_empty() {
+            message = format!("{} ({})", message, file.skipped.join("; "));
+        }
+        return Err(ImportError::Invalid(message));
+    }
+    Ok(file)
+}
+
+/// Read many files, reporting each one's songs or error
+pub fn import_files<P: AsRef<Path>>(paths: &[P]) -> Vec<FileImportReport> {
+    paths
+        .iter()
+        .map(|path| {
+            let path = path.as_ref();
+            let (file, error) = match read_songs(path) {
+                Ok(file) if file.songs.is_empty() => (file, Some("No songs found".to_string())),
+                Ok(file) => (file, None),
+                Err(e) => (SongFile::default(), Some(e.to_string())),
+            };
+            FileImportReport {
+                path: path.display().to_string(),
+                songs: file.songs,
+                skipped: file.skipped,
+                error,
+            }
+        })
+        .collect()
+}
+
+/// Section name for a worship-software verse code: "v1" -> "Verse 1", "C" -> "Chorus"
+fn verse_name(code: &str) -> String {
+    let code = code.trim();
+    let mut chars = code.chars();
+    let Some(first) = chars.next() else {
+        return "Main".to_string();
+    };
+    let rest = chars.as_str();
+    let name = match first.to_ascii_lowercase() {
+        'v' => "Verse",
+        'c' => "Chorus",
+        'p' => "Pre-Chorus",
+        'b' => "Bridge",
+        't' => "Tag",
+        'i' => "Intro",
+        'e' => "Ending",
+        'o' => "Other",
+        _ => return code.to_string(),
+    };
+    // Only a number (or number and letter, "v1a") may follow the letter
+    if !rest.is_empty() && !rest.starts_with(|c: char| c.is_ascii_digit()) {
+        return code.to_string();
+    }
+    if rest.is_empty() {
+        name.to_string()
+    } else {
+        format!("{} {}", name, rest)
+    }
+}
+
+/// Lay sections out in a verse order ("v1 c v2 c"), back-to-back repeats
+/// becoming `repeat_count`
+///
+/// Without an order, or when none of its codes match, the sections stay as
+/// written.
+fn arrange(sections: Vec<(String, FetchedSection)>, order: Option<&str>) -> Vec<FetchedSection> {
+    let find = |code: &str| sections.iter().find(|(c, _)| c.eq_ignore_ascii_case(code));
+    let ordered: Vec<&FetchedSection> = order
+        .unwrap_or("")
+        .split_whitespace()
+        .filter_map(|code| find(code).map(|(_, section)| section))
+        .collect();
+    if ordered.is_empty() {
+        return sections.into_iter().map(|(_, section)| section).collect();
+    }
+
+    let mut out: Vec<FetchedSection> = Vec::new();
+    let mut previous: Option<&FetchedSection> = None;
+    for section in ordered {
+        match out.last_mut() {
+            Some(last) if previous.is_some_and(|p| std::ptr::eq(p, section)) => {
+                last.repeat_count = Some(last.repeat_count.unwrap_or(1) + 1);
+            }
+            _ => out.push(section.clone()),
+        }
+        previous = Some(section);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verse_name() {
+        assert_eq!(verse_name("v1"), "Verse 1");
+        assert_eq!(verse_name("C"), "Chorus");
+        assert_eq!(verse_name("p2"), "Pre-Chorus 2");
+        assert_eq!(verse_name("v1a"), "Verse 1a");
+        assert_eq!(verse_name("Coda"), "Coda");
+    }
+
+    #[test]
+    fn test_arrange() {
+        let sections = vec![
+            ("v1".to_string(), FetchedSection::new("Verse 1")),
+            ("c".to_string(), FetchedSection::new("Chorus")),
+            ("v2".to_string(), FetchedSection::new("Verse 2")),
+        ];
+        let arranged = arrange(sections.clone(), Some("v1 C c v2 c x9"));
+        let names: Vec<_> = arranged
+            .iter()
+            .map(|s| (s.name.as_str(), s.repeat_count))
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                ("Verse 1", None),
+                ("Chorus", Some(2)),
+                ("Verse 2", None),
+                ("Chorus", None)
+            ]
+        );
+        assert_eq!(arrange(sections, Some("x1")).len(), 3);
+    }
+
+    #[test]
+    fn test_import_file_reads_every_song() {
+        use std::io::Write;
+
+        let library = serde_json::json!([
+            { "name": "First", "content": "[G]One" },
+            { "name": "Second", "content": "[C]Two" },
+            { "name": "Third" }
+        ]);
+        let mut writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        writer
+            .start_file("dataFile.txt", zip::write::SimpleFileOptions::default())
+            .unwrap();
+        writer
+            .write_all(format!("1.0\n{}", library).as_bytes())
+            .unwrap();
+        let data = writer.finish().unwrap().into_inner();
+
+        let path = std::env::temp_dir().join(format!("cat4g-import-{}.sbp", std::process::id()));
+        std::fs::write(&path, data).unwrap();
+        let file = import_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let titles: Vec<_> = file
+            .songs
+            .iter()
+            .map(|song| song.sheet.title.as_deref().unwrap())
+            .collect();
+        assert_eq!(titles, vec!["First", "Second"]);
+        assert_eq!(
+            file.songs[1].sheet.source_url,
+            format!("{}#2", path.display())
+        );
+        assert_eq!(file.skipped, vec!["Third: no content"]);
+    }
+
+    #[test]
+    fn test_import_files_report() {
+        let dir = std::env::temp_dir().join(format!("cat4g-import-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let song = dir.join("Amazing Grace");
+        std::fs::write(
+            &song,
+            "<?xml version=\"1.0\"?><song><title>Amazing Grace</title><lyrics>[V1]\n.G\n Amazing grace</lyrics></song>",
+        )
+        .unwrap();
+        let notes = dir.join("notes.txt");
+        std::fs::write(&notes, "just text").unwrap();
+
+        let report = import_files(&[song.clone(), notes, dir.join("missing.gp5")]);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(report.len(), 3);
+        assert_eq!(report[0].error, None);
+        assert_eq!(
+            report[0].songs[0].sheet.title.as_deref(),
+            Some("Amazing Grace")
+        );
+        assert_eq!(
+            report[0].songs[0].sheet.source_url,
+            song.display().to_string()
+        );
+        assert!(report[1]
+            .error
+            .as_deref()
+            .unwrap()
+            .starts_with("Unsupported file format"));
+        assert!(report[2].songs.is_empty());
+        assert!(report[2]
+            .error
+            .as_deref()
+            .unwrap()
+            .starts_with("Failed to read file"));
+    }
+}
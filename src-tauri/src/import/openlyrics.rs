@@ -0,0 +1,266 @@
+//! OpenLyrics XML (OpenLP, Worship Extreme and others)
+//!
+//! Verses hold `<lines>` of text with `<br/>` breaks and `<chord>` marks
+//! placed inline before the syllable they fall on. Chords name themselves
+//! (`name="Am7"`) or, in 0.9, give root, structure and bass separately and may
+//! wrap the text they cover. Translations repeat a verse under another
+//! `lang`; only the first is kept.
+
+use super::{arrange, verse_name, ImportedSong};
+use crate::error::ImportError;
+use crate::parsers::{FetchedChord, FetchedChordSheet, FetchedLine, FetchedSection};
+use roxmltree::{Document, Node};
+
+/// OpenLyrics 0.9 chord structures, as our suffixes
+const STRUCTURES: [(&str, &str); 12] = [
+    ("maj", ""),
+    ("min", "m"),
+    ("dom7", "7"),
+    ("maj7", "maj7"),
+    ("min7", "m7"),
+    ("dim", "dim"),
+    ("dim7", "dim7"),
+    ("aug", "aug"),
+    ("sus2", "sus2"),
+    ("sus4", "sus4"),
+    ("dom9", "9"),
+    ("min7b5", "m7b5"),
+];
+
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|n| n.has_tag_name(name))
+}
+
+fn child_text(node: Node, name: &str) -> Option<String> {
+    child(node, name)
+        .and_then(|n| n.text())
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+        .map(str::to_string)
+}
+
+fn chord_name(node: Node) -> Option<String> {
+    if let Some(name) = node
+        .attribute("name")
+        .map(str::trim)
+        .filter(|n| !n.is_empty())
+    {
+        return Some(name.to_string());
+    }
+    let root = node.attribute("root")?.trim();
+    let structure = node.attribute("structure").unwrap_or("");
+    let suffix = STRUCTURES
+        .iter()
+        .find(|(theirs, _)| *theirs == structure)
+        .map_or(structure, |(_, ours)| ours);
+    let bass = node
+        .attribute("bass")
+        .map_or(String::new(), |b| format!("/{}", b.trim()));
+    Some(format!("{}{}{}", root, suffix, bass))
+}
+
+/// A line being built from text and chord marks
+#[derive(Default)]
+struct LineBuilder {
+    text: String,
+    chords: Vec<FetchedChord>,
+    lines: Vec<FetchedLine>,
+}
+
+impl LineBuilder {
+    /// Append text, collapsing the XML's whitespace
+    fn push_text(&mut self, text: &str) {
+        for c in text.chars() {
+            if c.is_whitespace() {
+                if !self.text.is_empty() && !self.text.ends_with(' ') {
+                    self.text.push(' ');
+                }
+            } else {
+                self.text.push(c);
+            }
+        }
+    }
+
+    fn push_chord(&mut self, name: String) {
+        let position = self.text.chars().count() as i32;
+        self.chords.push(FetchedChord::new(&name, position));
+    }
+
+    fn end_line(&mut self) {
+        let text = self.text.trim_end().to_string();
+        if !text.is_empty() || !self.chords.is_empty() {
+            self.lines.push(FetchedLine::with_chords(
+                &text,
+                std::mem::take(&mut self.chords),
+            ));
+        }
+        self.text.clear();
+    }
+
+    fn walk(&mut self, node: Node) {
+        for n in node.children() {
+            if n.is_text() {
+                self.push_text(n.text().unwrap_or(""));
+                continue;
+            }
+            match n.tag_name().name() {
+                "br" => self.end_line(),
+                "comment" => {}
+                "chord" => {
+                    if let Some(name) = chord_name(n) {
+                        self.push_chord(name);
+                    }
+                    self.walk(n);
+                }
+                "line" => {
+                    self.walk(n);
+                    self.end_line();
+                }
+                _ => self.walk(n),
+            }
+        }
+    }
+}
+
+fn read_verse(verse: Node) -> FetchedSection {
+    let mut builder = LineBuilder::default();
+    for lines in verse.children().filter(|n| n.has_tag_name("lines")) {
+        builder.walk(lines);
+        builder.end_line();
+    }
+    let mut section = FetchedSection::new(&verse_name(verse.attribute("name").unwrap_or("")));
+    section.lines = builder.lines;
+    section
+}
+
+pub fn read(text: &str) -> Result<ImportedSong, ImportError> {
+    let document = Document::parse(text)
+        .map_err(|e| ImportError::Invalid(format!("Bad OpenLyrics XML: {}", e)))?;
+    let song = document.root_element();
+    if !song.has_tag_name("song") {
+        return Err(ImportError::Invalid("Not an OpenLyrics song".to_string()));
+    }
+    let properties = child(song, "properties")
+        .ok_or_else(|| ImportError::Invalid("OpenLyrics song without properties".to_string()))?;
+
+    let mut sheet = FetchedChordSheet::new(String::new());
+    sheet.title = child(properties, "titles").and_then(|titles| child_text(titles, "title"));
+    sheet.artist = child(properties, "authors").and_then(|authors| {
+        let names: Vec<&str> = authors
+            .children()
+            .filter(|n| n.has_tag_name("author") && n.attribute("type") != Some("translation"))
+            .filter_map(|n| n.text().map(str::trim))
+            .filter(|name| !name.is_empty())
+            .collect();
+        (!names.is_empty()).then(|| names.join(", "))
+    });
+    sheet.key = child_text(properties, "key");
+    sheet.capo = child_text(properties, "transposition")
+        .and_then(|t| t.parse::<i32>().ok())
+        .filter(|&capo| capo > 0);
+
+    let bpm = child(properties, "tempo")
+        .filter(|n| n.attribute("type").is_none_or(|t| t == "bpm"))
+        .and_then(|n| n.text())
+        .and_then(|t| t.trim().parse().ok());
+    let time_signature = child_text(properties, "timeSignature");
+
+    let mut verses: Vec<(String, FetchedSection)> = Vec::new();
+    if let Some(lyrics) = child(song, "lyrics") {
+        for verse in lyrics.children().filter(|n| n.has_tag_name("verse")) {
+            let code = verse.attribute("name").unwrap_or("").to_string();
+            if verses.iter().any(|(c, _)| *c == code) {
+                // Same verse in another language
+                continue;
+            }
+            verses.push((code, read_verse(verse)));
+        }
+    }
+    let order = child_text(properties, "verseOrder");
+    sheet.sections = arrange(verses, order.as_deref());
+    if sheet.sections.is_empty() {
+        return Err(ImportError::Invalid(
+            "OpenLyrics song has no verses".to_string(),
+        ));
+    }
+
+    Ok(ImportedSong {
+        sheet,
+        bpm,
+        time_signature,
+        difficulty: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SONG: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<song xmlns="http://openlyrics.info/namespace/2009/song" version="0.9">
+  <properties>
+    <titles><title>Amazing Grace</title></titles>
+    <authors>
+      <author>John Newton</author>
+      <author type="translation" lang="de">Someone Else</author>
+    </authors>
+    <key>G</key>
+    <tempo type="bpm">80</tempo>
+    <timeSignature>3/4</timeSignature>
+    <verseOrder>v1 c c</verseOrder>
+  </properties>
+  <lyrics>
+    <verse name="v1">
+      <lines>
+        A<chord name="G"/>mazing <chord name="G7"/>grace, how
+        <chord root="C" structure="maj"/>sweet<br/><chord root="D" structure="dom7" bass="F#"/>the sound
+      </lines>
+    </verse>
+    <verse name="v1" lang="de">
+      <lines>Erstaunliche Gnade</lines>
+    </verse>
+    <verse name="c">
+      <lines><comment>softly</comment><chord name="Em">I once</chord> was lost</lines>
+    </verse>
+  </lyrics>
+</song>"#;
+
+    fn chords(line: &FetchedLine) -> Vec<(&str, i32)> {
+        line.chords
+            .iter()
+            .map(|c| (c.chord.as_str(), c.position))
+            .collect()
+    }
+
+    #[test]
+    fn test_read_openlyrics() {
+        let song = read(SONG).unwrap();
+        assert_eq!(song.sheet.title.as_deref(), Some("Amazing Grace"));
+        assert_eq!(song.sheet.artist.as_deref(), Some("John Newton"));
+        assert_eq!(song.sheet.key.as_deref(), Some("G"));
+        assert_eq!(song.bpm, Some(80));
+        assert_eq!(song.time_signature.as_deref(), Some("3/4"));
+
+        let sections = &song.sheet.sections;
+        assert_eq!(sections.len(), 2);
+        assert_eq!(sections[0].name, "Verse 1");
+        assert_eq!(sections[0].lines[0].lyrics, "Amazing grace, how sweet");
+        assert_eq!(
+            chords(&sections[0].lines[0]),
+            vec![("G", 1), ("G7", 8), ("C", 19)]
+        );
+        assert_eq!(sections[0].lines[1].lyrics, "the sound");
+        assert_eq!(chords(&sections[0].lines[1]), vec![("D7/F#", 0)]);
+
+        assert_eq!(sections[1].name, "Chorus");
+        assert_eq!(sections[1].repeat_count, Some(2));
+        assert_eq!(sections[1].lines[0].lyrics, "I once was lost");
+        assert_eq!(chords(&sections[1].lines[0]), vec![("Em", 0)]);
+    }
+
+    #[test]
+    fn test_rejects_other_xml() {
+        assert!(read("<html><body/></html>").is_err());
+        assert!(read("<song").is_err());
+    }
+}
@@ -0,0 +1,239 @@
+//! OpenSong song files
+//!
+//! The song is XML, but its `<lyrics>` are plain text where the first
+//! character of each line says what it is: `[V1]` starts a section, `.` a
+//! chord row, ` ` a lyric row and `;` a comment. A lyric row may start with a
+//! digit instead, giving that verse's words under a shared chord row, so
+//! `[V]` with rows "1..." and "2..." holds verses 1 and 2.
+
+use super::{arrange, verse_name, ImportedSong};
+use crate::error::ImportError;
+use crate::parsers::{FetchedChord, FetchedChordSheet, FetchedLine, FetchedSection};
+use roxmltree::{Document, Node};
+
+fn child_text(node: Node, name: &str) -> Option<String> {
+    node.children()
+        .find(|n| n.has_tag_name(name))
+        .and_then(|n| n.text())
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+        .map(str::to_string)
+}
+
+/// Chords in a chord row (leading '.' removed) with their columns
+fn chord_row(row: &str) -> Vec<FetchedChord> {
+    let mut chords = Vec::new();
+    let mut start: Option<usize> = None;
+    let chars: Vec<char> = row.chars().collect();
+    for (column, c) in chars.iter().chain([' '].iter()).enumerate() {
+        match (c.is_whitespace(), start) {
+            (false, None) => start = Some(column),
+            (true, Some(first)) => {
+                let name: String = chars[first..column].iter().collect();
+                chords.push(FetchedChord::new(&name, first as i32));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    chords
+}
+
+/// Coded sections, in the order they first appear
+#[derive(Default)]
+struct Sections {
+    sections: Vec<(String, FetchedSection)>,
+    /// Code of the last [X] header
+    code: String,
+    /// Chord row waiting for its lyric rows
+    chords: Option<Vec<FetchedChord>>,
+    /// The chord row was used by a numbered verse row
+    chords_used: bool,
+}
+
+impl Sections {
+    fn section(&mut self, code: &str) -> &mut FetchedSection {
+        let index = match self.sections.iter().position(|(c, _)| c == code) {
+            Some(index) => index,
+            None => {
+                self.sections
+                    .push((code.to_string(), FetchedSection::new(&verse_name(code))));
+                self.sections.len() - 1
+            }
+        };
+        &mut self.sections[index].1
+    }
+
+    /// Leave a chord row with no lyrics under it as a line of its own
+    fn flush_chords(&mut self) {
+        if let Some(chords) = self.chords.take() {
+            if !self.chords_used {
+                let code = self.code.clone();
+                self.section(&code)
+                    .lines
+                    .push(FetchedLine::with_chords("", chords));
+            }
+        }
+        self.chords_used = false;
+    }
+
+    fn push_lyrics(&mut self, code: &str, lyrics: &str, numbered: bool) {
+        // Bar and page-break marks and underscores keep their columns as spaces
+        let lyrics: String = lyrics
+            .chars()
+            .map(|c| if c == '|' || c == '_' { ' ' } else { c })
+            .collect();
+        let chords = if numbered {
+            self.chords_used = true;
+            self.chords.clone().unwrap_or_default()
+        } else {
+            self.chords_used = false;
+            self.chords.take().unwrap_or_default()
+        };
+        let line = FetchedLine::with_chords(lyrics.trim_end(), chords);
+        if !line.lyrics.is_empty() || !line.chords.is_empty() {
+            self.section(code).lines.push(line);
+        }
+    }
+}
+
+fn parse_lyrics(lyrics: &str) -> Vec<(String, FetchedSection)> {
+    let mut state = Sections::default();
+    for line in lyrics.lines() {
+        let line = line.trim_end_matches('\r');
+        let mut chars = line.chars();
+        match chars.next() {
+            Some('[') => {
+                state.flush_chords();
+                let code = line[1..].split(']').next().unwrap_or("").trim();
+                state.code = code.to_string();
+            }
+            Some('.') => {
+                state.flush_chords();
+                state.chords = Some(chord_row(chars.as_str()));
+            }
+            Some(';') => {}
+            Some(digit) if digit.is_ascii_digit() => {
+                let code = format!("{}{}", state.code, digit);
+                state.push_lyrics(&code, chars.as_str(), true);
+            }
+            Some(' ') => {
+                if state.chords_used {
+                    state.flush_chords();
+                }
+                let code = state.code.clone();
+                state.push_lyrics(&code, chars.as_str(), false);
+            }
+            Some('-') if line.trim_start_matches('-').trim().is_empty() => {}
+            Some(_) => {
+                if state.chords_used {
+                    state.flush_chords();
+                }
+                let code = state.code.clone();
+                state.push_lyrics(&code, line, false);
+            }
+            None => state.flush_chords(),
+        }
+    }
+    state.flush_chords();
+    state
+        .sections
+        .into_iter()
+        .filter(|(_, section)| !section.lines.is_empty())
+        .collect()
+}
+
+pub fn read(text: &str) -> Result<ImportedSong, ImportError> {
+    let document = Document::parse(text)
+        .map_err(|e| ImportError::Invalid(format!("Bad OpenSong XML: {}", e)))?;
+    let song = document.root_element();
+    if !song.has_tag_name("song") {
+        return Err(ImportError::Invalid("Not an OpenSong song".to_string()));
+    }
+
+    let mut sheet = FetchedChordSheet::new(String::new());
+    sheet.title = child_text(song, "title");
+    sheet.artist = child_text(song, "author");
+    sheet.key = child_text(song, "key");
+    sheet.capo = child_text(song, "capo")
+        .and_then(|capo| capo.parse::<i32>().ok())
+        .filter(|&capo| capo > 0);
+    // OpenSong's tempo may also be a word like "Fast"
+    let bpm = child_text(song, "tempo").and_then(|tempo| tempo.parse().ok());
+    let time_signature = child_text(song, "time_sig");
+
+    let lyrics = child_text(song, "lyrics").unwrap_or_default();
+    let order = child_text(song, "presentation");
+    sheet.sections = arrange(parse_lyrics(&lyrics), order.as_deref());
+    if sheet.sections.is_empty() {
+        return Err(ImportError::Invalid(
+            "OpenSong song has no lyrics".to_string(),
+        ));
+    }
+
+    Ok(ImportedSong {
+        sheet,
+        bpm,
+        time_signature,
+        difficulty: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SONG: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+<song>
+  <title>How Great Thou Art</title>
+  <author>Stuart K. Hine</author>
+  <key>Bb</key>
+  <capo print=\"false\">1</capo>
+  <tempo>Slow</tempo>
+  <time_sig>4/4</time_sig>
+  <presentation>V1 V2 C</presentation>
+  <lyrics>[V]
+.Bb            Eb
+1O Lord my God, when I in awesome wonder
+2When through the woods and forest glades I wander
+;both verses share the chords
+[C]
+.Bb         F
+ Then sings my_soul
+.C7  F
+</lyrics>
+</song>";
+
+    fn chords(line: &FetchedLine) -> Vec<(&str, i32)> {
+        line.chords
+            .iter()
+            .map(|c| (c.chord.as_str(), c.position))
+            .collect()
+    }
+
+    #[test]
+    fn test_read_opensong() {
+        let song = read(SONG).unwrap();
+        assert_eq!(song.sheet.title.as_deref(), Some("How Great Thou Art"));
+        assert_eq!(song.sheet.artist.as_deref(), Some("Stuart K. Hine"));
+        assert_eq!(song.sheet.key.as_deref(), Some("Bb"));
+        assert_eq!(song.sheet.capo, Some(1));
+        assert_eq!(song.bpm, None);
+        assert_eq!(song.time_signature.as_deref(), Some("4/4"));
+
+        let sections = &song.sheet.sections;
+        let names: Vec<_> = sections.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["Verse 1", "Verse 2", "Chorus"]);
+        assert_eq!(
+            sections[0].lines[0].lyrics,
+            "O Lord my God, when I in awesome wonder"
+        );
+        assert_eq!(chords(&sections[0].lines[0]), vec![("Bb", 0), ("Eb", 14)]);
+        assert_eq!(chords(&sections[1].lines[0]), vec![("Bb", 0), ("Eb", 14)]);
+
+        assert_eq!(sections[2].lines[0].lyrics, "Then sings my soul");
+        assert_eq!(chords(&sections[2].lines[0]), vec![("Bb", 0), ("F", 11)]);
+        assert_eq!(sections[2].lines[1].lyrics, "");
+        assert_eq!(chords(&sections[2].lines[1]), vec![("C7", 0), ("F", 4)]);
+    }
+}
@@ -0,0 +1,283 @@
+//! SongbookPro library archives (.sbp)
+//!
+//! An archive is a zip holding `dataFile.txt`: a format version line, then
+//! JSON with the library's songs. Each song's `content` is ChordPro-style
+//! text, with chords inline in brackets (`[G]Amazing`) and sections opened
+//! by directives (`{soc}`, `{c: Verse 2}`). Keys are stored as names or as
+//! semitones above C.
+
+use super::{ImportedSong, SongFile};
+use crate::error::ImportError;
+use crate::parsers::{FetchedChord, FetchedChordSheet, FetchedLine, FetchedSection};
+use crate::theory::{note_name, pitch_class};
+use regex::Regex;
+use serde_json::Value;
+use std::io::{Cursor, Read};
+use std::sync::LazyLock;
+
+/// Inline chord: [Am7]
+static CHORD_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\[([^\[\]]*)\]").unwrap());
+
+/// Directive line: {start_of_chorus: Chorus 2}
+static DIRECTIVE_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^\{\s*([A-Za-z_]+)\s*(?::\s*(.*?))?\s*\}$").unwrap());
+
+/// Comments that name a section rather than give an instruction
+static SECTION_COMMENT_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?i)^(intro|verse|pre-?chorus|chorus|bridge|tag|interlude|instrumental|ending|outro)\b",
+    )
+    .unwrap()
+});
+
+/// Name of the archive entry holding the library
+const DATA_FILE: &str = "dataFile.txt";
+
+/// First non-empty value among `keys` (SongbookPro's casing varies by version)
+fn field<'a>(song: &'a Value, keys: &[&str]) -> Option<&'a Value> {
+    keys.iter()
+        .filter_map(|key| song.get(*key))
+        .find(|value| !value.is_null() && value.as_str() != Some(""))
+}
+
+fn text_field(song: &Value, keys: &[&str]) -> Option<String> {
+    field(song, keys)
+        .and_then(Value::as_str)
+        .map(str::trim)
+        .filter(|text| !text.is_empty())
+        .map(str::to_string)
+}
+
+fn number_field(song: &Value, keys: &[&str]) -> Option<i64> {
+    field(song, keys).and_then(|value| {
+        value
+            .as_i64()
+            .or_else(|| value.as_str().and_then(|text| text.trim().parse().ok()))
+    })
+}
+
+fn key_field(song: &Value) -> Option<String> {
+    match field(song, &["key", "Key"])? {
+        Value::Number(number) => {
+            let semitones = number.as_i64()?;
+            Some(note_name(pitch_class(semitones as i32), false).to_string())
+        }
+        Value::String(text) => Some(text.trim().to_string()).filter(|key| !key.is_empty()),
+        _ => None,
+    }
+}
+
+/// Strip inline chords, returning the lyrics and each chord's character offset
+fn strip_chords(line: &str) -> (String, Vec<FetchedChord>) {
+    let mut lyrics = String::new();
+    let mut chords = Vec::new();
+    let mut last = 0;
+    for caps in CHORD_RE.captures_iter(line) {
+        let mark = caps.get(0).unwrap();
+        lyrics.push_str(&line[last..mark.start()]);
+        let name = caps[1].trim();
+        if !name.is_empty() {
+            chords.push(FetchedChord::new(name, lyrics.chars().count() as i32));
+        }
+        last = mark.end();
+    }
+    lyrics.push_str(&line[last..]);
+    (lyrics.trim_end().to_string(), chords)
+}
+
+/// Section name a directive opens, if it opens one
+fn directive_section(name: &str, argument: Option<&str>) -> Option<String> {
+    let default = match name.to_lowercase().as_str() {
+        "soc" | "start_of_chorus" => "Chorus",
+        "sov" | "start_of_verse" => "Verse",
+        "sob" | "start_of_bridge" => "Bridge",
+        "sot" | "start_of_tab" => "Tab",
+        "c" | "comment" | "ci" | "comment_italic" => {
+            return argument
+                .filter(|text| SECTION_COMMENT_RE.is_match(text))
+                .map(str::to_string);
+        }
+        _ => return None,
+    };
+    Some(
+        argument
+            .filter(|text| !text.is_empty())
+            .unwrap_or(default)
+            .to_string(),
+    )
+}
+
+fn parse_content(content: &str) -> Vec<FetchedSection> {
+    let mut sections = Vec::new();
+    let mut current = FetchedSection::new("Main");
+    let mut in_tab = false;
+
+    for line in content.lines() {
+        let trimmed = line.trim();
+        if let Some(caps) = DIRECTIVE_RE.captures(trimmed) {
+            let name = caps[1].to_lowercase();
+            in_tab = matches!(name.as_str(), "sot" | "start_of_tab");
+            if let Some(section_name) = directive_section(&name, caps.get(2).map(|m| m.as_str())) {
+                if !current.is_empty() {
+                    sections.push(current);
+                }
+                current = FetchedSection::new(&section_name);
+            }
+            continue;
+        }
+        if trimmed.starts_with('#') || (trimmed.is_empty() && !in_tab) {
+            continue;
+        }
+        if in_tab {
+            current.push_tab_line(line);
+            continue;
+        }
+        let (lyrics, chords) = strip_chords(trimmed);
+        current
+            .lines
+            .push(FetchedLine::with_chords(&lyrics, chords));
+    }
+    if !current.is_empty() {
+        sections.push(current);
+    }
+    sections
+}
+
+/// A song, or why it was left out
+fn read_song(song: &Value) -> Result<ImportedSong, String> {
+    let title = text_field(song, &["name", "Name", "title", "Title"]);
+    let name = title.as_deref().unwrap_or("Untitled song");
+    let content =
+        text_field(song, &["content", "Content"]).ok_or_else(|| format!("{}: no content", name))?;
+    let mut sheet = FetchedChordSheet::new(String::new());
+    sheet.title = title.clone();
+    sheet.artist = text_field(song, &["author", "Author", "artist", "Artist"]);
+    sheet.key = key_field(song);
+    sheet.capo = number_field(song, &["Capo", "capo"])
+        .map(|capo| capo as i32)
+        .filter(|&capo| capo > 0);
+    sheet.sections = parse_content(&content);
+    if sheet.sections.is_empty() {
+        return Err(format!("{}: no chords or lyrics", name));
+    }
+
+    Ok(ImportedSong {
+        sheet,
+        bpm: number_field(song, &["tempo", "Tempo"])
+            .filter(|&bpm| bpm > 0)
+            .map(|bpm| bpm as u32),
+        time_signature: text_field(song, &["timeSig", "TimeSig", "time_signature"]),
+        difficulty: None,
+    })
+}
+
+/// The library JSON, after the version line
+fn parse_library(text: &str) -> Result<Value, ImportError> {
+    let text = text.trim_start_matches('\u{feff}');
+    let json = match text.find(['{', '[']) {
+        Some(start) => &text[start..],
+        None => text,
+    };
+    serde_json::from_str(json)
+        .map_err(|e| ImportError::Invalid(format!("Bad SongbookPro data: {}", e)))
+}
+
+pub fn read(data: &[u8]) -> Result<SongFile, ImportError> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(data))
+        .map_err(|e| ImportError::Invalid(format!("Bad SongbookPro archive: {}", e)))?;
+    let mut entry = archive
+        .by_name(DATA_FILE)
+        .map_err(|_| ImportError::Invalid(format!("{} not found in archive", DATA_FILE)))?;
+    let mut text = String::new();
+    entry.read_to_string(&mut text)?;
+
+    let library = parse_library(&text)?;
+    let songs = match &library {
+        Value::Array(songs) => songs,
+        _ => library["songs"]
+            .as_array()
+            .or_else(|| library["Songs"].as_array())
+            .ok_or_else(|| ImportError::Invalid("No song list in SongbookPro data".to_string()))?,
+    };
+    let mut file = SongFile::default();
+    for song in songs {
+        match read_song(song) {
+            Ok(song) => file.songs.push(song),
+            Err(reason) => file.skipped.push(reason),
+        }
+    }
+    Ok(file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn archive(data_file: &str) -> Vec<u8> {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        writer
+            .start_file(DATA_FILE, zip::write::SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(data_file.as_bytes()).unwrap();
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn test_read_library() {
+        let library = serde_json::json!({
+            "songs": [
+                {
+                    "name": "Amazing Grace",
+                    "author": "John Newton",
+                    "key": 7,
+                    "Capo": 2,
+                    "tempo": "80",
+                    "timeSig": "3/4",
+                    "content": "{title: Amazing Grace}\n{sov: Verse 1}\nA[G]mazing [G7]grace\n[C]how sweet\n{eov}\n\n{c: Chorus}\n[D]  [G]\n{c: Play softly}\nI once was lost"
+                },
+                { "name": "Empty", "content": "" },
+                { "content": "{c: Play softly}" }
+            ]
+        });
+        let data = archive(&format!("1.0\n{}", library));
+        let SongFile { songs, skipped } = read(&data).unwrap();
+        assert_eq!(songs.len(), 1);
+        assert_eq!(
+            skipped,
+            vec!["Empty: no content", "Untitled song: no chords or lyrics"]
+        );
+
+        let song = &songs[0];
+        assert_eq!(song.sheet.title.as_deref(), Some("Amazing Grace"));
+        assert_eq!(song.sheet.artist.as_deref(), Some("John Newton"));
+        assert_eq!(song.sheet.key.as_deref(), Some("G"));
+        assert_eq!(song.sheet.capo, Some(2));
+        assert_eq!(song.bpm, Some(80));
+        assert_eq!(song.time_signature.as_deref(), Some("3/4"));
+
+        let sections = &song.sheet.sections;
+        assert_eq!(sections.len(), 2);
+        assert_eq!(sections[0].name, "Verse 1");
+        let first = &sections[0].lines[0];
+        assert_eq!(first.lyrics, "Amazing grace");
+        let chords: Vec<_> = first
+            .chords
+            .iter()
+            .map(|c| (c.chord.as_str(), c.position))
+            .collect();
+        assert_eq!(chords, vec![("G", 1), ("G7", 8)]);
+
+        assert_eq!(sections[1].name, "Chorus");
+        assert_eq!(sections[1].lines.len(), 2);
+        assert_eq!(sections[1].lines[0].lyrics, "");
+        assert_eq!(sections[1].lines[0].chords.len(), 2);
+        assert_eq!(sections[1].lines[1].lyrics, "I once was lost");
+    }
+
+    #[test]
+    fn test_invalid_archives() {
+        assert!(read(b"not a zip").is_err());
+        assert!(read(&archive("1.0\nnot json")).is_err());
+    }
+}
@@ -0,0 +1,1015 @@
+//! iReal Pro links (irealb:// and irealbook://), both ways
+//!
+//! A link holds one song, or several separated by "===" with the playlist
+//! name last. A song is "="-separated fields: title, composer ("Last
+//! First"), style, key and the chord progression. `irealbook://` carries the
+//! progression as written; `irealb://` prefixes it with `MUSIC_PREFIX` and
+//! obfuscates it (`scramble`), and adds a field after the composer and one
+//! after the key, both usually empty. An empty composer also makes "===", so
+//! `irealb://` songs are found by their progression rather than split.
+//!
+//! The progression is a string of bar lines (`| [ ] { } Z`), rehearsal marks
+//! (`*A`), time signatures (`T44`), endings (`N1`), repeat signs (`x` one
+//! bar, `r` two bars) and chords in iReal's shorthand (`C^7`, `D-7`, `Bh7`).
+//! Each chord or space fills one of the bar's cells. A section played twice
+//! as a whole becomes `repeat_count`; other repeats and endings are written
+//! out bar by bar. Codas and D.S./D.C. directions are not followed.
+
+use crate::error::ImportError;
+use crate::export::timing::{played_lines, time_line, TimeSignature};
+use crate::export::ExportSong;
+use crate::import::ImportedSong;
+use crate::parsers::{FetchedChord, FetchedChordSheet, FetchedLine, FetchedSection};
+use percent_encoding::{percent_decode_str, utf8_percent_encode, NON_ALPHANUMERIC};
+use regex::Regex;
+use std::sync::LazyLock;
+
+/// Marks an obfuscated progression in irealb:// links
+const MUSIC_PREFIX: &str = "1r34LbKcu7";
+
+/// Field holding the progression: title=composer=(unused)=style=key=
+/// (transposition)=progression in irealb:// links
+const IREALB_MUSIC_FIELD: usize = 6;
+
+/// Field holding the progression: title=composer=style=key=n=progression in
+/// irealbook:// links
+const IREALBOOK_MUSIC_FIELD: usize = 5;
+
+/// Bars per line of the imported sheet, as iReal lays them out
+const BARS_PER_LINE: usize = 4;
+
+/// Cells per bar in iReal's grid
+const CELLS_PER_BAR: usize = 4;
+
+/// Style written when the caller gives none
+pub const DEFAULT_STYLE: &str = "Medium Swing";
+
+/// Chord at the start of the input: root (W repeats the last one), quality, bass
+static CHORD_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(W|[A-G][b#]?)((?:sus|alt|add|[\^\-+ohb#\d])*)(/[A-G][b#]?)?").unwrap()
+});
+
+/// Repeat count in a comment: <3x>
+static TIMES_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(\d+)x").unwrap());
+
+/// Substitutions applied to the progression before scrambling, in decoding order
+const SUBSTITUTIONS: [(&str, &str); 3] = [("Kcl", "| x"), ("LZ", " |"), ("XyQ", "   ")];
+
+/// Swap the first five characters with the last five, and 10..24 with 26..40
+fn scramble_block(block: &[char]) -> Vec<char> {
+    let mut out = block.to_vec();
+    for i in (0..5).chain(10..24) {
+        out[i] = block[49 - i];
+        out[49 - i] = block[i];
+    }
+    out
+}
+
+/// iReal's obfuscation; it is its own inverse
+///
+/// Text is scrambled in 50-character blocks while more than 51 remain.
+fn scramble(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut out = Vec::with_capacity(chars.len());
+    let mut rest = &chars[..];
+    while rest.len() > 51 {
+        out.extend(scramble_block(&rest[..50]));
+        rest = &rest[50..];
+    }
+    out.extend(rest);
+    out.into_iter().collect()
+}
+
+fn unscramble(music: &str) -> String {
+    SUBSTITUTIONS
+        .iter()
+        .fold(scramble(music), |text, (from, to)| text.replace(from, to))
+}
+
+fn obfuscate(music: &str) -> String {
+    let text = SUBSTITUTIONS
+        .iter()
+        .rev()
+        .fold(music.to_string(), |text, (to, from)| text.replace(from, to));
+    scramble(&text)
+}
+
+/// iReal quality shorthand to ours: "^7" -> "maj7", "-7" -> "m7", "h7" -> "m7b5"
+fn quality_from_ireal(quality: &str) -> String {
+    let chars: Vec<char> = quality.chars().collect();
+    let mut out = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let next_digit = chars.get(i + 1).is_some_and(|c| c.is_ascii_digit());
+        match chars[i] {
+            '^' if next_digit => out.push_str("maj"),
+            '^' => out.push_str("maj7"),
+            '-' => out.push('m'),
+            '+' => out.push_str("aug"),
+            'o' if chars.get(i + 1) == Some(&'7') => {
+                out.push_str("dim7");
+                i += 1;
+            }
+            'o' => out.push_str("dim"),
+            'h' => {
+                let digits: String = chars[i + 1..]
+                    .iter()
+                    .take_while(|c| c.is_ascii_digit())
+                    .collect();
+                i += digits.len();
+                let extension = if digits.is_empty() { "7" } else { &digits };
+                out.push_str(&format!("m{}b5", extension));
+            }
+            c => out.push(c),
+        }
+        i += 1;
+    }
+    out
+}
+
+/// Our chord suffixes to iReal's, longest first
+const QUALITIES_TO_IREAL: [(&str, &str); 17] = [
+    ("m(maj7)", "-^7"),
+    ("mmaj", "-^"),
+    ("mM7", "-^7"),
+    ("m7b5", "h7"),
+    ("m9b5", "h9"),
+    ("maj", "^"),
+    ("M7", "^7"),
+    ("M9", "^9"),
+    ("dim7", "o7"),
+    ("dim", "o"),
+    ("aug", "+"),
+    ("sus4", "sus"),
+    ("min", "-"),
+    ("m", "-"),
+    ("♭", "b"),
+    ("♯", "#"),
+    ("(", ""),
+];
+
+fn quality_to_ireal(quality: &str) -> String {
+    let mut out = String::new();
+    let mut rest = quality;
+    while let Some(c) = rest.chars().next() {
+        match QUALITIES_TO_IREAL
+            .iter()
+            .find(|(ours, _)| rest.starts_with(ours))
+        {
+            Some((ours, theirs)) => {
+                out.push_str(theirs);
+                rest = &rest[ours.len()..];
+            }
+            None => {
+                if c != ')' {
+                    out.push(c);
+                }
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+    out
+}
+
+/// "Ebm7/Bb" -> "Eb-7/Bb"; None for anything without a note name
+fn chord_to_ireal(name: &str) -> Option<String> {
+    let name = name.replace('♭', "b").replace('♯', "#");
+    let (chord, bass) = match name.split_once('/') {
+        Some((chord, bass)) => (chord, Some(bass)),
+        None => (name.as_str(), None),
+    };
+    let root_len = match chord.as_bytes() {
+        [b'A'..=b'G', b'b' | b'#', ..] => 2,
+        [b'A'..=b'G', ..] => 1,
+        _ => return None,
+    };
+    let mut out = format!(
+        "{}{}",
+        &chord[..root_len],
+        quality_to_ireal(&chord[root_len..])
+    );
+    if let Some(bass) = bass {
+        out.push('/');
+        out.push_str(bass);
+    }
+    Some(out)
+}
+
+/// "A-" (A minor) -> "Am"
+fn key_from_ireal(key: &str) -> Option<String> {
+    let key = key.trim();
+    let (root, minor) = match key.strip_suffix('-') {
+        Some(root) => (root, true),
+        None => (key, false),
+    };
+    let valid = CHORD_RE
+        .find(root)
+        .is_some_and(|m| m.len() == root.len() && !root.starts_with('W'));
+    valid.then(|| format!("{}{}", root, if minor { "m" } else { "" }))
+}
+
+fn key_to_ireal(key: Option<&str>) -> String {
+    match key.map(str::trim) {
+        Some(key) if key.ends_with('m') => format!("{}-", &key[..key.len() - 1]),
+        Some(key) if !key.is_empty() => key.to_string(),
+        _ => "C".to_string(),
+    }
+}
+
+/// iReal stores composers as "Last First"
+fn swap_name(name: &str) -> String {
+    let words: Vec<&str> = name.split_whitespace().collect();
+    match words.as_slice() {
+        [first, second] => format!("{} {}", second, first),
+        _ => words.join(" "),
+    }
+}
+
+fn section_name(mark: char) -> String {
+    match mark {
+        'i' => "Intro".to_string(),
+        'V' => "Verse".to_string(),
+        c => c.to_ascii_uppercase().to_string(),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BarRepeat {
+    /// x: the previous bar again
+    One,
+    /// r: the previous two bars again
+    Two,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Bar {
+    /// Chords with the cell each starts in
+    chords: Vec<(String, usize)>,
+    cells: usize,
+    mark: Option<char>,
+    time: Option<TimeSignature>,
+    ending: Option<u32>,
+    repeat_start: bool,
+    repeat_end: bool,
+    repeat_times: Option<u32>,
+    repeat: Option<BarRepeat>,
+    /// The whole section is played this many times
+    section_repeat: Option<u32>,
+}
+
+/// Split a progression into bars
+fn parse_bars(music: &str) -> Vec<Bar> {
+    let mut bars: Vec<Bar> = Vec::new();
+    let mut bar = Bar::default();
+    let mut last_chord: Option<String> = None;
+    let mut times: Option<u32> = None;
+    let mut rest = music;
+
+    while let Some(c) = rest.chars().next() {
+        if let Some(caps) = CHORD_RE.captures(rest) {
+            let whole = caps.get(0).unwrap().as_str();
+            let bass = caps.get(3).map_or("", |m| m.as_str());
+            let name = if &caps[1] == "W" {
+                // Invisible root: the last chord over a new bass
+                last_chord
+                    .as_deref()
+                    .map(|last| format!("{}{}", last.split('/').next().unwrap_or(last), bass))
+            } else {
+                Some(format!(
+                    "{}{}{}",
+                    &caps[1],
+                    quality_from_ireal(&caps[2]),
+                    bass
+                ))
+            };
+            if let Some(name) = name {
+                bar.chords.push((name.clone(), bar.cells));
+                last_chord = Some(name);
+            }
+            bar.cells += 1;
+            rest = &rest[whole.len()..];
+            continue;
+        }
+
+        let mut advance = c.len_utf8();
+        match c {
+            '|' | '[' | ']' | '{' | '}' | 'Z' => {
+                if bar.cells > 0 {
+                    bars.push(std::mem::take(&mut bar));
+                }
+                match c {
+                    '{' => bar.repeat_start = true,
+                    '}' => {
+                        let target = bars.last_mut().filter(|_| !bar.repeat_start);
+                        if let Some(target) = target {
+                            target.repeat_end = true;
+                            target.repeat_times = times.take();
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            '*' => {
+                bar.mark = rest[1..].chars().next();
+                advance += bar.mark.map_or(0, char::len_utf8);
+            }
+            'T' if rest.len() >= 3 && rest.as_bytes()[1..3].iter().all(u8::is_ascii_digit) => {
+                let digits = &rest[1..3];
+                bar.time = match digits {
+                    "12" => Some(TimeSignature {
+                        beats: 12,
+                        beat_type: 8,
+                    }),
+                    _ => Some(TimeSignature::parse(Some(&format!(
+                        "{}/{}",
+                        &digits[..1],
+                        &digits[1..]
+                    )))),
+                };
+                advance = 3;
+            }
+            'N' => {
+                // Only an ending when a digit follows
+                if let Some(digit) = rest[1..].chars().next().filter(char::is_ascii_digit) {
+                    bar.ending = digit.to_digit(10);
+                    advance += 1;
+                }
+            }
+            '<' => {
+                let end = rest.find('>').map_or(rest.len(), |i| i + 1);
+                if let Some(caps) = TIMES_RE.captures(&rest[..end]) {
+                    times = caps[1].parse().ok();
+                }
+                advance = end;
+            }
+            '(' => {
+                // Alternate chord shown small above the bar
+                advance = rest.find(')').map_or(rest.len(), |i| i + 1);
+            }
+            'x' => {
+                bar.repeat = Some(BarRepeat::One);
+                bar.cells += 1;
+            }
+            'r' => {
+                bar.repeat = Some(BarRepeat::Two);
+                bar.cells += 1;
+            }
+            ' ' | 'n' | 'p' => bar.cells += 1,
+            _ => {}
+        }
+        rest = &rest[advance.min(rest.len())..];
+    }
+    if bar.cells > 0 {
+        bars.push(bar);
+    }
+
+    // The bar a closing brace lands on after its own bar line
+    if let (Some(times), Some(last)) = (times, bars.last_mut()) {
+        if last.repeat_end && last.repeat_times.is_none() {
+            last.repeat_times = Some(times);
+        }
+    }
+    resolve_bar_repeats(&mut bars);
+    bars
+}
+
+/// Fill in x and r bars; a repeated chord that is already sounding is left out
+fn resolve_bar_repeats(bars: &mut [Bar]) {
+    let mut sounding: Option<String> = None;
+    let mut second_of_two: Option<Vec<(String, usize)>> = None;
+    for index in 0..bars.len() {
+        let source = match bars[index].repeat {
+            Some(BarRepeat::One) if index >= 1 => Some(bars[index - 1].chords.clone()),
+            Some(BarRepeat::Two) if index >= 2 => {
+                second_of_two = Some(bars[index - 1].chords.clone());
+                Some(bars[index - 2].chords.clone())
+            }
+            _ if bars[index].chords.is_empty() => second_of_two.take(),
+            _ => None,
+        };
+        if bars[index].repeat != Some(BarRepeat::Two) && !bars[index].chords.is_empty() {
+            second_of_two = None;
+        }
+        if let Some(mut chords) = source {
+            if chords.len() == 1 && Some(&chords[0].0) == sounding.as_ref() {
+                chords.clear();
+            }
+            bars[index].chords = chords;
+        }
+        if let Some((name, _)) = bars[index].chords.last() {
+            sounding = Some(name.clone());
+        }
+    }
+}
+
+/// Turn sections that are one plain repeat into `section_repeat`
+fn mark_section_repeats(bars: &mut [Bar]) {
+    let mut starts: Vec<usize> = (0..bars.len())
+        .filter(|&i| i == 0 || bars[i].mark.is_some())
+        .collect();
+    starts.push(bars.len());
+    for range in starts.windows(2) {
+        let (first, last) = (range[0], range[1] - 1);
+        if range[1] <= range[0] {
+            continue;
+        }
+        let section = &bars[first..=last];
+        let simple = section[0].repeat_start
+            && section[section.len() - 1].repeat_end
+            && section.iter().all(|bar| bar.ending.is_none())
+            && section[1..].iter().all(|bar| !bar.repeat_start)
+            && section[..section.len() - 1]
+                .iter()
+                .all(|bar| !bar.repeat_end);
+        if simple {
+            let times = bars[last].repeat_times.unwrap_or(2);
+            bars[first].repeat_start = false;
+            bars[last].repeat_end = false;
+            bars[first].section_repeat = Some(times);
+        }
+    }
+}
+
+/// Write out repeats and endings in playing order
+fn expand_repeats(bars: &[Bar]) -> Vec<Bar> {
+    let mut out = Vec::with_capacity(bars.len());
+    let mut start = 0;
+    for (index, bar) in bars.iter().enumerate() {
+        if bar.repeat_start {
+            start = index;
+        }
+        out.push(bar.clone());
+        if bar.repeat_end {
+            // Later passes skip the first ending and stay in the same section
+            let first_ending = (start..=index).find(|&i| bars[i].ending.is_some());
+            for _ in 1..bar.repeat_times.unwrap_or(2) {
+                out.extend(
+                    bars[start..first_ending.unwrap_or(index + 1)]
+                        .iter()
+                        .map(|bar| Bar {
+                            mark: None,
+                            ..bar.clone()
+                        }),
+                );
+            }
+            start = index + 1;
+        }
+    }
+    out
+}
+
+fn bars_to_sections(bars: &[Bar], song_time: TimeSignature) -> Vec<FetchedSection> {
+    let mut sections: Vec<FetchedSection> = Vec::new();
+    let mut time = song_time;
+    let mut line: Vec<&Bar> = Vec::new();
+
+    let flush = |line: &mut Vec<&Bar>, sections: &mut Vec<FetchedSection>| {
+        if line.is_empty() {
+            return;
+        }
+        let chords = line
+            .iter()
+            .enumerate()
+            .flat_map(|(index, bar)| {
+                bar.chords.iter().map(move |(name, cell)| {
+                    let cell = (*cell).min(CELLS_PER_BAR - 1);
+                    FetchedChord::new(name, (index * CELLS_PER_BAR * 2 + cell * 2) as i32)
+                })
+            })
+            .collect();
+        let mut fetched = FetchedLine::with_chords("", chords);
+        fetched.measures = Some(line.len() as u32);
+        if let Some(section) = sections.last_mut() {
+            section.lines.push(fetched);
+        }
+        line.clear();
+    };
+
+    for (index, bar) in bars.iter().enumerate() {
+        if let Some(bar_time) = bar.time {
+            time = bar_time;
+        }
+        if index == 0 || bar.mark.is_some() || line.len() == BARS_PER_LINE {
+            flush(&mut line, &mut sections);
+        }
+        if index == 0 || bar.mark.is_some() {
+            let default_name = if bars.iter().any(|b| b.mark.is_some()) {
+                "Intro"
+            } else {
+                "Main"
+            };
+            let mut section =
+                FetchedSection::new(&bar.mark.map_or(default_name.to_string(), section_name));
+            section.repeat_count = bar.section_repeat;
+            section.time_signature =
+                (time != song_time).then(|| format!("{}/{}", time.beats, time.beat_type));
+            sections.push(section);
+        }
+        line.push(bar);
+    }
+    flush(&mut line, &mut sections);
+    sections
+}
+
+/// One song's fields, from the title on, with the progression at
+/// `music_index`; fields after the progression hold the tempo
+fn decode_song(fields: &[&str], music_index: usize) -> Result<ImportedSong, ImportError> {
+    if fields.len() <= music_index {
+        return Err(ImportError::Invalid(format!(
+            "Incomplete iReal song: {}",
+            fields.join("=")
+        )));
+    }
+    let music = fields[music_index];
+    let music = match music.strip_prefix(MUSIC_PREFIX) {
+        Some(scrambled) => unscramble(scrambled),
+        None => music.to_string(),
+    };
+
+    let mut bars = parse_bars(&music);
+    if bars.is_empty() {
+        return Err(ImportError::Invalid(format!(
+            "No bars in iReal song '{}'",
+            fields[0]
+        )));
+    }
+    let song_time = bars[0].time.unwrap_or_default();
+    mark_section_repeats(&mut bars);
+    let bars = expand_repeats(&bars);
+
+    let mut sheet = FetchedChordSheet::new(String::new());
+    sheet.title = Some(fields[0].trim().to_string());
+    sheet.artist = Some(swap_name(fields[1])).filter(|name| !name.is_empty());
+    // Two fields before the progression in both forms
+    sheet.key = key_from_ireal(fields[music_index - 2]);
+    sheet.sections = bars_to_sections(&bars, song_time);
+
+    let bpm = fields[music_index + 1..]
+        .iter()
+        .find_map(|f| f.parse::<u32>().ok().filter(|&bpm| bpm > 0));
+    Ok(ImportedSong {
+        sheet,
+        bpm,
+        time_signature: Some(format!("{}/{}", song_time.beats, song_time.beat_type)),
+        difficulty: None,
+    })
+}
+
+/// Decode an irealb:// or irealbook:// link into its songs
+pub fn decode_url(url: &str) -> Result<Vec<ImportedSong>, ImportError> {
+    let url = url.trim();
+    let body = url
+        .strip_prefix("irealb://")
+        .or_else(|| url.strip_prefix("irealbook://"))
+        .ok_or_else(|| ImportError::UnsupportedFormat(format!("Not an iReal Pro link: {}", url)))?;
+    let body = percent_decode_str(body)
+        .decode_utf8()
+        .map_err(|e| ImportError::Invalid(format!("Bad link encoding: {}", e)))?;
+
+    if !url.starts_with("irealb://") {
+        let mut songs: Vec<&str> = body.split("===").collect();
+        if songs.len() > 1 {
+            // Playlist name
+            songs.pop();
+        }
+        return songs
+            .into_iter()
+            .filter(|song| !song.trim().is_empty())
+            .map(|song| decode_song(&song.split('=').collect::<Vec<_>>(), IREALBOOK_MUSIC_FIELD))
+            .collect();
+    }
+
+    let fields: Vec<&str> = body.split('=').collect();
+    let progressions: Vec<usize> = fields
+        .iter()
+        .enumerate()
+        .filter(|(_, field)| field.starts_with(MUSIC_PREFIX))
+        .map(|(index, _)| index)
+        .collect();
+    if progressions.is_empty() {
+        return Err(ImportError::Invalid(format!(
+            "Incomplete iReal song: {}",
+            body
+        )));
+    }
+    progressions
+        .iter()
+        .map(|&music| {
+            let start = music.checked_sub(IREALB_MUSIC_FIELD).ok_or_else(|| {
+                ImportError::Invalid(format!(
+                    "Incomplete iReal song: {}",
+                    fields[..=music].join("=")
+                ))
+            })?;
+            // Up to the "===" before the next song or the playlist name
+            let end = fields[music..]
+                .windows(2)
+                .position(|pair| pair[0].is_empty() && pair[1].is_empty())
+                .map_or(fields.len(), |offset| music + offset);
+            decode_song(&fields[start..end], IREALB_MUSIC_FIELD)
+        })
+        .collect()
+}
+
+/// Section mark for a section name: A-D by order of first appearance
+fn mark_for(name: &str, names: &mut Vec<String>) -> Option<char> {
+    let name = name.trim();
+    let lower = name.to_lowercase();
+    if lower.starts_with("intro") || name == "イントロ" {
+        return Some('i');
+    }
+    if lower.starts_with("verse") {
+        return Some('V');
+    }
+    if let [c @ b'A'..=b'D'] = name.as_bytes() {
+        return Some(*c as char);
+    }
+    let index = names.iter().position(|n| n == name).unwrap_or_else(|| {
+        names.push(name.to_string());
+        names.len() - 1
+    });
+    (index < 4).then(|| (b'A' + index as u8) as char)
+}
+
+/// Chords of a section, bar by bar
+fn section_bars(section: &FetchedSection, time: &TimeSignature) -> Vec<Vec<String>> {
+    let mut bars = Vec::new();
+    for line in played_lines(section) {
+        let timed = time_line(line, time);
+        let mut line_bars = vec![Vec::new(); timed.bars];
+        for chord in &timed.chords {
+            let index = (chord.start / time.beats as usize).min(timed.bars - 1);
+            if let Some(name) = chord_to_ireal(chord.name) {
+                line_bars[index].push(name);
+            }
+        }
+        bars.extend(line_bars);
+    }
+    bars
+}
+
+/// One bar's cells: each chord fills a cell, spaces fill the rest
+fn bar_cells(chords: &[String]) -> String {
+    let cells = chords.len().max(CELLS_PER_BAR);
+    let mut slots = vec![None; cells];
+    for (index, chord) in chords.iter().enumerate() {
+        slots[index * cells / chords.len()] = Some(chord);
+    }
+    let mut out = String::new();
+    for (index, slot) in slots.iter().enumerate() {
+        match slot {
+            Some(chord) => {
+                out.push_str(chord);
+                if slots.get(index + 1).is_some_and(Option::is_some) {
+                    out.push(',');
+                }
+            }
+            None => out.push(' '),
+        }
+    }
+    out
+}
+
+fn time_token(time: &TimeSignature) -> String {
+    match (time.beats, time.beat_type) {
+        (12, 8) => "T12".to_string(),
+        (beats, beat_type) => format!("T{}{}", beats, beat_type),
+    }
+}
+
+fn encode_music(song: &ExportSong) -> String {
+    let song_time = TimeSignature::parse(song.time_signature.as_deref());
+    let mut names = Vec::new();
+    let mut current: Option<TimeSignature> = None;
+    let mut music = String::new();
+
+    for section in &song.sheet.sections {
+        let time = match section.time_signature.as_deref() {
+            Some(text) => TimeSignature::parse(Some(text)),
+            None => song_time,
+        };
+        let bars = section_bars(section, &time);
+        if bars.is_empty() {
+            continue;
+        }
+        let repeat = section.repeat_count.filter(|&count| count > 1);
+        music.push(if repeat.is_some() { '{' } else { '[' });
+        if let Some(mark) = mark_for(&section.name, &mut names) {
+            music.push('*');
+            music.push(mark);
+        }
+        if current != Some(time) {
+            music.push_str(&time_token(&time));
+            current = Some(time);
+        }
+        let cells: Vec<String> = bars.iter().map(|bar| bar_cells(bar)).collect();
+        music.push_str(&cells.join("|"));
+        match repeat {
+            Some(count) if count > 2 => music.push_str(&format!("<{}x>}}", count)),
+            Some(_) => music.push('}'),
+            None => music.push(']'),
+        }
+    }
+    if music.ends_with(']') {
+        music.pop();
+    }
+    music.push('Z');
+    music
+}
+
+fn encode_song(song: &ExportSong, style: &str) -> String {
+    let sheet = &song.sheet;
+    // Field separators can't appear inside a field
+    let clean = |text: &str| text.replace('=', "-");
+    format!(
+        "{}={}=={}={}=={}{}=={}=0",
+        clean(song.title()),
+        clean(&swap_name(sheet.artist.as_deref().unwrap_or("Unknown"))),
+        clean(style),
+        key_to_ireal(sheet.key.as_deref()),
+        MUSIC_PREFIX,
+        obfuscate(&encode_music(song)),
+        song.bpm.unwrap_or(0),
+    )
+}
+
+/// Encode songs as an irealb:// link; more than one makes a playlist
+pub fn encode_url(songs: &[ExportSong], style: &str, playlist: Option<&str>) -> String {
+    let mut body = songs
+        .iter()
+        .map(|song| encode_song(song, style))
+        .collect::<Vec<_>>()
+        .join("===");
+    if songs.len() > 1 || playlist.is_some() {
+        body.push_str("===");
+        body.push_str(playlist.unwrap_or("Playlist"));
+    }
+    format!("irealb://{}", utf8_percent_encode(&body, NON_ALPHANUMERIC))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chords(line: &FetchedLine) -> Vec<(&str, i32)> {
+        line.chords
+            .iter()
+            .map(|c| (c.chord.as_str(), c.position))
+            .collect()
+    }
+
+    #[test]
+    fn test_scramble() {
+        let text = "0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnop";
+        let scrambled = scramble(text);
+        assert_eq!(
+            scrambled,
+            "nmlkj56789dcbaZYXWVUTSRQOPNMLKJIHGFEDCBAefghi43210op"
+        );
+        assert_eq!(scramble(&scrambled), text);
+        // 51 characters or fewer are left alone
+        assert_eq!(scramble(&text[..51]), &text[..51]);
+    }
+
+    #[test]
+    fn test_quality_names() {
+        let cases = [
+            ("C^7", "Cmaj7"),
+            ("A^", "Amaj7"),
+            ("D-7", "Dm7"),
+            ("Bh7", "Bm7b5"),
+            ("Bh", "Bm7b5"),
+            ("Eo7", "Edim7"),
+            ("F-^7", "Fmmaj7"),
+            ("C+", "Caug"),
+            ("G7b9/B", "G7b9/B"),
+        ];
+        for (ireal, ours) in cases {
+            let caps = CHORD_RE.captures(ireal).unwrap();
+            let bass = caps.get(3).map_or("", |m| m.as_str());
+            assert_eq!(
+                format!("{}{}{}", &caps[1], quality_from_ireal(&caps[2]), bass),
+                ours
+            );
+        }
+        assert_eq!(chord_to_ireal("Cmaj7").as_deref(), Some("C^7"));
+        assert_eq!(chord_to_ireal("Bm7b5").as_deref(), Some("Bh7"));
+        assert_eq!(chord_to_ireal("E♭m7/B♭").as_deref(), Some("Eb-7/Bb"));
+        assert_eq!(chord_to_ireal("Fmmaj7").as_deref(), Some("F-^7"));
+        assert_eq!(chord_to_ireal("N.C."), None);
+    }
+
+    #[test]
+    fn test_decode_irealbook() {
+        let songs = decode_url(
+            "irealbook://Song Title=LastName FirstName=Style=Ab=n=T44*A{C^7 |A-7 |D-9 |G7#5 }",
+        )
+        .unwrap();
+        assert_eq!(songs.len(), 1);
+        let song = &songs[0];
+        assert_eq!(song.sheet.title.as_deref(), Some("Song Title"));
+        assert_eq!(song.sheet.artist.as_deref(), Some("FirstName LastName"));
+        assert_eq!(song.sheet.key.as_deref(), Some("Ab"));
+        assert_eq!(song.time_signature.as_deref(), Some("4/4"));
+        assert_eq!(song.bpm, None);
+
+        let section = &song.sheet.sections[0];
+        assert_eq!(section.name, "A");
+        assert_eq!(section.repeat_count, Some(2));
+        assert_eq!(section.lines.len(), 1);
+        assert_eq!(section.lines[0].measures, Some(4));
+        assert_eq!(
+            chords(&section.lines[0]),
+            vec![("Cmaj7", 0), ("Am7", 8), ("Dm9", 16), ("G7#5", 24)]
+        );
+    }
+
+    #[test]
+    fn test_endings_and_bar_repeats() {
+        let songs =
+            decode_url("irealbook://Blues=Doe Jane=Swing=C-=n=T34{*AC- |x |N1D7 }|N2G7 Z").unwrap();
+        let song = &songs[0];
+        assert_eq!(song.sheet.key.as_deref(), Some("Cm"));
+        assert_eq!(song.time_signature.as_deref(), Some("3/4"));
+        let section = &song.sheet.sections[0];
+        assert_eq!(section.repeat_count, None);
+        let lines: Vec<_> = section
+            .lines
+            .iter()
+            .map(|l| (chords(l), l.measures))
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                (vec![("Cm", 0), ("D7", 16), ("Cm", 24)], Some(4)),
+                (vec![("G7", 8)], Some(2)),
+            ]
+        );
+    }
+
+    /// A blues as iReal Pro itself writes it: XyQ/LZ/Kcl shorthand, Y line
+    /// spacers, small chords and an alternate chord in parentheses
+    #[test]
+    fn test_decode_ireal_export() {
+        let songs = decode_url(concat!(
+            "irealb://Blues%20for%20Rain%3DDoe%20Jane%3D%3DMedium%20Swing%3DF%3D%3D1r34Lb",
+            "Kcu7%7CQyX74F7Xy7bB%7CQyX7bBZL%20lKcQyX7F%7CQyX7bB%7CQXyQ%7CF4TA%2A%7B%2C7Fs",
+            "27%29XyQ7C%207-GZL7D%207F1%7CNQyX7C%7CQyX7-G%7CY%20%7DY%7CNbA%287DD7%2CG-7%2",
+            "CC7%2CZ%3DJazz-Medium%20Swing%3D132%3D0",
+        ))
+        .unwrap();
+        let song = &songs[0];
+        assert_eq!(song.sheet.title.as_deref(), Some("Blues for Rain"));
+        assert_eq!(song.sheet.artist.as_deref(), Some("Jane Doe"));
+        assert_eq!(song.sheet.key.as_deref(), Some("F"));
+        assert_eq!(song.bpm, Some(132));
+
+        let lines: Vec<_> = song.sheet.sections[0]
+            .lines
+            .iter()
+            .map(|l| (chords(l), l.measures))
+            .collect();
+        let first_eight = [
+            (vec![("F7", 0), ("Bb7", 8), ("F7", 16)], Some(4)),
+            (
+                vec![("Bb7", 0), ("Bb7", 8), ("F7", 16), ("D7", 24)],
+                Some(4),
+            ),
+        ];
+        assert_eq!(lines[..2], first_eight);
+        assert_eq!(lines[3..5], first_eight);
+        assert_eq!(
+            lines[2],
+            (
+                vec![
+                    ("Gm7", 0),
+                    ("C7", 8),
+                    ("F7", 16),
+                    ("D7", 20),
+                    ("Gm7", 24),
+                    ("C7", 28)
+                ],
+                Some(4)
+            )
+        );
+        assert_eq!(
+            lines[5],
+            (
+                vec![
+                    ("Gm7", 0),
+                    ("C7", 8),
+                    ("F7", 16),
+                    ("D7", 18),
+                    ("Gm7", 20),
+                    ("C7", 22)
+                ],
+                Some(3)
+            )
+        );
+    }
+
+    #[test]
+    fn test_n_before_other_text() {
+        let songs = decode_url("irealbook://T=D J=Swing=C=n=[C   |N%E6%97%A5G |").unwrap();
+        let section = &songs[0].sheet.sections[0];
+        assert_eq!(chords(&section.lines[0]), vec![("C", 0), ("G", 8)]);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut sheet = FetchedChordSheet::new(String::new());
+        sheet.title = Some("Blue Room".to_string());
+        sheet.artist = Some("Richard Rodgers".to_string());
+        sheet.key = Some("Bb".to_string());
+        let mut intro = FetchedSection::new("Intro");
+        intro.lines.push(FetchedLine::with_chords(
+            "",
+            vec![FetchedChord::new("Bbmaj7", 0), FetchedChord::new("G7", 4)],
+        ));
+        let mut chorus = FetchedSection::new("Chorus");
+        chorus.repeat_count = Some(3);
+        chorus.time_signature = Some("3/4".to_string());
+        let mut line = FetchedLine::with_chords(
+            "words are dropped",
+            vec![
+                FetchedChord::new("Cm7", 0),
+                FetchedChord::new("F7", 6),
+                FetchedChord::new("Dm7b5", 12),
+            ],
+        );
+        line.measures = Some(4);
+        chorus.lines.push(line);
+        sheet.sections = vec![intro, chorus];
+        let song = ExportSong {
+            sheet,
+            bpm: Some(140),
+            time_signature: None,
+            notes: None,
+        };
+
+        let url = encode_url(&[song.clone(), song], DEFAULT_STYLE, Some("Standards"));
+        assert!(url.starts_with(
+            "irealb://Blue%20Room%3DRodgers%20Richard%3D%3DMedium%20Swing%3DBb%3D%3D1r34LbKcu7"
+        ));
+
+        let songs = decode_url(&url).unwrap();
+        assert_eq!(songs.len(), 2);
+        let decoded = &songs[1];
+        assert_eq!(decoded.bpm, Some(140));
+        assert_eq!(decoded.sheet.artist.as_deref(), Some("Richard Rodgers"));
+        assert_eq!(decoded.sheet.key.as_deref(), Some("Bb"));
+
+        let sections = &decoded.sheet.sections;
+        assert_eq!(sections[0].name, "Intro");
+        assert_eq!(
+            chords(&sections[0].lines[0]),
+            vec![("Bbmaj7", 0), ("G7", 4)]
+        );
+        assert_eq!(sections[1].name, "A");
+        assert_eq!(sections[1].repeat_count, Some(3));
+        assert_eq!(sections[1].time_signature.as_deref(), Some("3/4"));
+        assert_eq!(sections[1].lines[0].measures, Some(4));
+        assert_eq!(
+            chords(&sections[1].lines[0]),
+            vec![("Cm7", 0), ("F7", 8), ("Dm7b5", 16)]
+        );
+    }
+
+    #[test]
+    fn test_decode_blank_composer() {
+        // An unknown composer is left empty, which puts "===" inside the song
+        let music = format!("{}{}", MUSIC_PREFIX, obfuscate("*A[C^7 |A-7 |D-7 |G7 Z"));
+        let body = format!(
+            "Untitled===Medium Swing=Eb=={}=Jazz-Medium Swing=120=0===\
+             Blues=Doe Jane==Swing=F=={}=Jazz-Swing=96=0===Set",
+            music, music
+        );
+        let url = format!("irealb://{}", utf8_percent_encode(&body, NON_ALPHANUMERIC));
+
+        let songs = decode_url(&url).unwrap();
+        assert_eq!(songs.len(), 2);
+        assert_eq!(songs[0].sheet.title.as_deref(), Some("Untitled"));
+        assert_eq!(songs[0].sheet.artist, None);
+        assert_eq!(songs[0].sheet.key.as_deref(), Some("Eb"));
+        assert_eq!(songs[0].bpm, Some(120));
+        assert_eq!(
+            chords(&songs[0].sheet.sections[0].lines[0]),
+            vec![("Cmaj7", 0), ("Am7", 8), ("Dm7", 16), ("G7", 24)]
+        );
+        assert_eq!(songs[1].sheet.artist.as_deref(), Some("Jane Doe"));
+        assert_eq!(songs[1].sheet.key.as_deref(), Some("F"));
+        assert_eq!(songs[1].bpm, Some(96));
+    }
+
+    #[test]
+    fn test_rejects_other_links() {
+        assert!(matches!(
+            decode_url("https://example.com"),
+            Err(ImportError::UnsupportedFormat(_))
+        ));
+        assert!(matches!(
+            decode_url("irealbook://Title"),
+            Err(ImportError::Invalid(_))
+        ));
+    }
+}
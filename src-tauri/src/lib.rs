@@ -1,15 +1,72 @@
-use tauri::Manager;
+use std::sync::Arc;
+use tauri::{Emitter, Manager};
 use url::Url;
 
+mod arrange;
+mod capo;
+mod difficulty;
+mod duplicates;
 mod error;
+mod export;
+#[cfg(test)]
+mod fixtures;
 mod http;
+mod import;
+mod ireal;
+mod library;
+mod merge;
+mod normalize;
 mod parsers;
+mod practice;
+mod queue;
+mod revisions;
+mod setlist;
+mod simplify;
+mod sync;
+mod theory;
 
+use arrange::ArrangedSheet;
+use capo::CapoSuggestion;
+use difficulty::{DifficultyRating, RatedSheet};
+use duplicates::{DuplicateCluster, MergedSong};
 use error::FetchError;
-use parsers::{chordwiki, gakkime, jtotal, ufret, FetchedChordSheet};
+use export::click::ClickOptions;
+use export::html::HtmlOptions;
+use export::midi::MidiStyle;
+use export::pdf::PdfOptions;
+use export::view::SheetView;
+use export::ExportSong;
+use import::{FileImportReport, ImportedSong, SongFile};
+use library::{
+    ConflictStrategy, ExistingLibrary, Library, LibraryImport, LibraryManifest, LibrarySong,
+};
+use merge::MergeResult;
+use parsers::{
+    chordwiki, gakkime, jtotal, ufret, ultimate_guitar, FetchedChordSheet, FetchedSection,
+};
+use practice::{
+    PracticeSession, PracticeSong, PracticeSuggestion, TempoProgress, UpcomingSetlist,
+    WeeklyPractice,
+};
+use queue::{
+    now_ms, Operation, OperationError, OperationQueue, QueueEntry, QueueEvent, QueueStatus,
+    SongInputs,
+};
+use revisions::{Revision, RevisionSource, RevisionStore, RevisionSummary, SheetDiff};
+use setlist::{ResolvedSetlist, Setlist};
+use simplify::{SimplifiedSheet, SimplifyLevel};
+use sync::postgrest::{PostgrestClient, PostgrestConfig};
+use sync::{RowChange, Side, SyncCursor, SyncInput, SyncInputs, SyncResult};
+use theory::chord::Chord;
+use theory::instrument::Instrument;
+use theory::key::Key;
+use theory::nashville::{NumberStyle, NumberedSheet};
+use theory::voicing::Voicing;
+
+type ParserFn = fn(&str) -> Result<FetchedChordSheet, FetchError>;
 
 /// Determine which parser to use based on URL domain
-fn get_parser(url: &str) -> Result<fn(&str) -> Result<FetchedChordSheet, FetchError>, FetchError> {
+fn get_parser(url: &str) -> Result<ParserFn, FetchError> {
     let parsed = Url::parse(url).map_err(|_| FetchError::UnsupportedSite(url.to_string()))?;
     let host = parsed.host_str().unwrap_or("");
 
@@ -21,38 +78,60 @@ fn get_parser(url: &str) -> Result<fn(&str) -> Result<FetchedChordSheet, FetchEr
         Ok(gakkime::parse)
     } else if host == "chordwiki.org" || host.ends_with(".chordwiki.org") {
         Ok(chordwiki::parse)
+    } else if host == "ultimate-guitar.com" || host.ends_with(".ultimate-guitar.com") {
+        Ok(ultimate_guitar::parse)
     } else {
         Err(FetchError::UnsupportedSite(url.to_string()))
     }
 }
 
-/// Fetch chord sheet from URL (backend HTTP request)
+/// Fetch chord sheet from URL (backend HTTP request); artists are spelled as
+/// in `known_artists` when they only differ in width, kana or spacing
 #[tauri::command]
-async fn fetch_chord_sheet(url: String) -> Result<FetchedChordSheet, String> {
+async fn fetch_chord_sheet(
+    url: String,
+    known_artists: Option<Vec<String>>,
+) -> Result<RatedSheet, String> {
+    let sheet = fetch_sheet(url, &known_artists.unwrap_or_default()).await?;
+    Ok(RatedSheet::new(sheet))
+}
+
+/// Fetch and parse a sheet, keeping the error type so the operation queue can
+/// tell a lost connection from a page it cannot parse
+async fn fetch_sheet(
+    url: String,
+    known_artists: &[String],
+) -> Result<FetchedChordSheet, FetchError> {
     // Get appropriate parser
-    let parser = get_parser(&url).map_err(|e| e.to_string())?;
+    let parser = get_parser(&url)?;
 
     // Fetch HTML
-    let html = http::fetch_page(&url).await.map_err(|e| e.to_string())?;
+    let html = http::fetch_page(&url).await?;
 
     // Parse content
-    let mut result = parser(&html).map_err(|e| e.to_string())?;
+    let mut result = parser(&html)?;
     result.source_url = url;
+    normalize::normalize_sheet(&mut result, known_artists);
 
     Ok(result)
 }
 
 /// Parse HTML content into chord sheet (for frontend-fetched HTML)
 #[tauri::command]
-fn parse_chord_sheet(url: String, html: String) -> Result<FetchedChordSheet, String> {
+fn parse_chord_sheet(
+    url: String,
+    html: String,
+    known_artists: Option<Vec<String>>,
+) -> Result<RatedSheet, String> {
     // Get appropriate parser
     let parser = get_parser(&url).map_err(|e| e.to_string())?;
 
     // Parse content
     let mut result = parser(&html).map_err(|e| e.to_string())?;
     result.source_url = url;
+    normalize::normalize_sheet(&mut result, &known_artists.unwrap_or_default());
 
-    Ok(result)
+    Ok(RatedSheet::new(result))
 }
 
 /// Get list of supported sites
@@ -74,6 +153,12 @@ fn get_supported_sites() -> Vec<SupportedSite> {
             domain: "gakufu.gakki.me".to_string(),
             example_url: "https://gakufu.gakki.me/m/data/M00211.html".to_string(),
         },
+        SupportedSite {
+            name: "Ultimate Guitar".to_string(),
+            domain: "tabs.ultimate-guitar.com".to_string(),
+            example_url: "https://tabs.ultimate-guitar.com/tab/artist/song-chords-123456"
+                .to_string(),
+        },
         // ChordWiki excluded from auto-fetch due to Cloudflare - use manual HTML input
     ]
 }
@@ -85,6 +170,623 @@ struct SupportedSite {
     example_url: String,
 }
 
+/// Get built-in instrument and tuning presets
+#[tauri::command]
+fn get_instruments() -> Vec<Instrument> {
+    Instrument::presets()
+}
+
+/// Transpose every chord in a sheet by semitones
+#[tauri::command]
+fn transpose_chord_sheet(sheet: FetchedChordSheet, semitones: i32) -> FetchedChordSheet {
+    theory::transpose_sheet(&sheet, semitones)
+}
+
+/// Look up an instrument preset by tuning id (songs.tuning)
+fn get_instrument(tuning: &str) -> Result<Instrument, String> {
+    Instrument::by_id(tuning).ok_or_else(|| format!("Unknown tuning: {}", tuning))
+}
+
+/// Render a sheet for an instrument/tuning (chord names + voicings)
+#[tauri::command]
+fn arrange_chord_sheet(sheet: FetchedChordSheet, tuning: String) -> Result<ArrangedSheet, String> {
+    let instrument = get_instrument(&tuning)?;
+    Ok(arrange::arrange_sheet(&sheet, &instrument))
+}
+
+/// Find voicings for a single chord on an instrument/tuning
+#[tauri::command]
+fn get_chord_voicings(
+    chord: String,
+    tuning: String,
+    limit: Option<usize>,
+) -> Result<Vec<Voicing>, String> {
+    let instrument = get_instrument(&tuning)?;
+    let parsed = Chord::parse(&chord).ok_or_else(|| format!("Invalid chord: {}", chord))?;
+    Ok(theory::voicing::find_voicings(
+        &parsed,
+        &instrument,
+        limit.unwrap_or(5),
+    ))
+}
+
+/// Rank capo positions by how easy the resulting chord shapes are
+#[tauri::command]
+fn suggest_capo(
+    sheet: FetchedChordSheet,
+    tuning: Option<String>,
+) -> Result<Vec<CapoSuggestion>, String> {
+    let instrument = get_instrument(tuning.as_deref().unwrap_or("standard"))?;
+    if !instrument.uses_capo {
+        return Err(format!("{} is not played with a capo", instrument.name));
+    }
+    Ok(capo::suggest_capo(&sheet, &instrument))
+}
+
+/// Guess a sheet's key from its chords
+#[tauri::command]
+fn detect_sheet_key(sheet: FetchedChordSheet) -> Option<String> {
+    theory::key::detect_key(&sheet).map(|key| key.to_string())
+}
+
+/// Resolve the key to number against: explicit, then the sheet's, then detected
+fn resolve_key(sheet: &FetchedChordSheet, key: Option<&str>) -> Result<Key, String> {
+    match key {
+        Some(text) => Key::parse(text).ok_or_else(|| format!("Invalid key: {}", text)),
+        None => theory::key::sheet_key(sheet).ok_or_else(|| "Could not detect key".to_string()),
+    }
+}
+
+/// Convert chord names to Nashville numbers or Roman numerals
+#[tauri::command]
+fn convert_to_numbers(
+    sheet: FetchedChordSheet,
+    key: Option<String>,
+    style: NumberStyle,
+) -> Result<NumberedSheet, String> {
+    let key = resolve_key(&sheet, key.as_deref())?;
+    Ok(theory::nashville::sheet_to_numbers(&sheet, &key, style))
+}
+
+/// Convert a numbered sheet back to chord names in any key
+#[tauri::command]
+fn convert_from_numbers(
+    sheet: FetchedChordSheet,
+    key: String,
+    style: NumberStyle,
+) -> Result<FetchedChordSheet, String> {
+    let key = Key::parse(&key).ok_or_else(|| format!("Invalid key: {}", key))?;
+    Ok(theory::nashville::sheet_from_numbers(&sheet, &key, style))
+}
+
+/// Reduce chords to sevenths, triads or power chords for beginners
+#[tauri::command]
+fn simplify_sheet(
+    sheet: FetchedChordSheet,
+    level: SimplifyLevel,
+    collapse_slash: Option<bool>,
+) -> SimplifiedSheet {
+    simplify::simplify_sheet(&sheet, level, collapse_slash.unwrap_or(false))
+}
+
+/// Rate how hard a sheet is to play (1-5, with reasons)
+#[tauri::command]
+fn rate_difficulty(sheet: FetchedChordSheet) -> DifficultyRating {
+    difficulty::rate_difficulty(&sheet)
+}
+
+/// Export one song or a whole playlist as a PDF songbook
+#[tauri::command]
+async fn export_pdf(
+    songs: Vec<ExportSong>,
+    options: PdfOptions,
+    path: String,
+) -> Result<(), String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        export::pdf::export_pdf(&songs, &options, std::path::Path::new(&path))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(String::from)
+}
+
+/// Export a sheet as a single-file HTML document, or as SVG pages
+#[tauri::command]
+fn export_html(sheet: FetchedChordSheet, options: HtmlOptions) -> Result<Vec<String>, String> {
+    let instrument = get_instrument(options.tuning.as_deref().unwrap_or("standard"))?;
+    Ok(export::html::export_html(&sheet, &options, &instrument))
+}
+
+/// Export a sheet as plain text, optionally as lyrics only or a chord chart
+#[tauri::command]
+fn export_text(
+    sheet: FetchedChordSheet,
+    view: Option<SheetView>,
+    time_signature: Option<String>,
+) -> String {
+    let song = ExportSong {
+        sheet: view
+            .unwrap_or_default()
+            .apply(&sheet, time_signature.as_deref()),
+        bpm: None,
+        time_signature,
+        notes: None,
+    };
+    export::text::export_text(&song)
+}
+
+/// Export a song as a MusicXML lead sheet
+#[tauri::command]
+fn export_musicxml(song: ExportSong) -> String {
+    export::musicxml::export_musicxml(&song)
+}
+
+/// Render a MIDI backing track (guitar, bass, drums) and write it to `path`
+#[tauri::command]
+async fn render_midi(
+    sheet: FetchedChordSheet,
+    style: Option<MidiStyle>,
+    bpm: Option<u32>,
+    time_signature: Option<String>,
+    path: String,
+) -> Result<(), String> {
+    let song = ExportSong {
+        sheet,
+        bpm,
+        time_signature,
+        notes: None,
+    };
+    tauri::async_runtime::spawn_blocking(move || {
+        export::midi::export_midi(
+            &song,
+            style.unwrap_or_default(),
+            std::path::Path::new(&path),
+        )
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(String::from)
+}
+
+/// Render a click track following the song's sections and write it to `path` as WAV
+#[tauri::command]
+async fn render_click_track(
+    sheet: FetchedChordSheet,
+    bpm: Option<u32>,
+    time_signature: Option<String>,
+    options: Option<ClickOptions>,
+    path: String,
+) -> Result<(), String> {
+    let song = ExportSong {
+        sheet,
+        bpm,
+        time_signature,
+        notes: None,
+    };
+    tauri::async_runtime::spawn_blocking(move || {
+        let options = options.unwrap_or_default();
+        export::click::export_click_track(&song, &options, std::path::Path::new(&path))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(String::from)
+}
+
+/// Apply a setlist's per-entry overrides and estimate its durations
+#[tauri::command]
+fn resolve_setlist(setlist: Setlist) -> Result<ResolvedSetlist, String> {
+    setlist::resolve_setlist(&setlist).map_err(String::from)
+}
+
+/// Export a resolved setlist as one PDF songbook, songs in playing order
+#[tauri::command]
+async fn export_setlist_pdf(
+    setlist: Setlist,
+    options: PdfOptions,
+    path: String,
+) -> Result<ResolvedSetlist, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let resolved = setlist::resolve_setlist(&setlist)?;
+        let options = PdfOptions {
+            title: options.title.clone().or_else(|| resolved.name.clone()),
+            ..options
+        };
+        export::pdf::export_pdf(&resolved.songs(), &options, std::path::Path::new(&path))?;
+        Ok::<_, error::ExportError>(resolved)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(String::from)
+}
+
+/// Tidy titles and artists of imported songs and rate them, as after a site fetch
+fn normalize_imported<'a>(
+    songs: impl IntoIterator<Item = &'a mut ImportedSong>,
+    known_artists: &[String],
+) {
+    for song in songs {
+        normalize::normalize_sheet(&mut song.sheet, known_artists);
+        song.difficulty = Some(difficulty::rate_difficulty(&song.sheet));
+    }
+}
+
+/// Import the songs in a Guitar Pro, OpenLyrics, OpenSong or SongbookPro file
+#[tauri::command]
+async fn import_file(path: String, known_artists: Option<Vec<String>>) -> Result<SongFile, String> {
+    let mut file = tauri::async_runtime::spawn_blocking(move || {
+        import::import_file(std::path::Path::new(&path))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(String::from)?;
+    normalize_imported(&mut file.songs, &known_artists.unwrap_or_default());
+    Ok(file)
+}
+
+/// Import many song files at once, reporting each file's songs or error
+#[tauri::command]
+async fn import_files(
+    paths: Vec<String>,
+    known_artists: Option<Vec<String>>,
+) -> Result<Vec<FileImportReport>, String> {
+    let mut reports = tauri::async_runtime::spawn_blocking(move || import::import_files(&paths))
+        .await
+        .map_err(|e| e.to_string())?;
+    let known_artists = known_artists.unwrap_or_default();
+    for report in &mut reports {
+        normalize_imported(&mut report.songs, &known_artists);
+    }
+    Ok(reports)
+}
+
+/// Decode the songs in an irealb:// or irealbook:// link
+#[tauri::command]
+fn import_ireal_url(
+    url: String,
+    known_artists: Option<Vec<String>>,
+) -> Result<Vec<ImportedSong>, String> {
+    let mut songs = ireal::decode_url(&url).map_err(String::from)?;
+    normalize_imported(&mut songs, &known_artists.unwrap_or_default());
+    Ok(songs)
+}
+
+/// Encode songs as an irealb:// link, a playlist when there are several
+#[tauri::command]
+fn export_ireal_url(
+    songs: Vec<ExportSong>,
+    style: Option<String>,
+    playlist: Option<String>,
+) -> String {
+    let style = style.as_deref().unwrap_or(ireal::DEFAULT_STYLE);
+    ireal::encode_url(&songs, style, playlist.as_deref())
+}
+
+/// Back up the whole library to a zip archive
+#[tauri::command]
+async fn export_library(library: Library, path: String) -> Result<LibraryManifest, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        library::export_library(&library, std::path::Path::new(&path))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(String::from)
+}
+
+/// Read a library archive and settle it against the current library
+#[tauri::command]
+async fn import_library(
+    path: String,
+    existing: ExistingLibrary,
+    strategy: Option<ConflictStrategy>,
+) -> Result<LibraryImport, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let strategy = strategy.unwrap_or_default();
+        library::import_library(std::path::Path::new(&path), &existing, strategy)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(String::from)
+}
+
+/// The app's one revision store, so saves from commands and the queue worker take turns
+fn revision_store(app: &tauri::AppHandle) -> Result<Arc<RevisionStore>, String> {
+    app.try_state::<Arc<RevisionStore>>()
+        .map(|store| store.inner().clone())
+        .ok_or_else(|| "Revision store is not ready".to_string())
+}
+
+/// Record a song's sections as a new revision
+#[tauri::command]
+fn save_revision(
+    app: tauri::AppHandle,
+    song_id: String,
+    sections: Vec<FetchedSection>,
+    source: RevisionSource,
+    note: Option<String>,
+) -> Result<RevisionSummary, String> {
+    let revision = revision_store(&app)?.save(&song_id, sections, source, note)?;
+    Ok(RevisionSummary::from(&revision))
+}
+
+/// List a song's revisions, newest first
+#[tauri::command]
+fn list_revisions(app: tauri::AppHandle, song_id: String) -> Result<Vec<RevisionSummary>, String> {
+    revision_store(&app)?.list(&song_id).map_err(String::from)
+}
+
+/// Get one revision with its sections
+#[tauri::command]
+fn get_revision(
+    app: tauri::AppHandle,
+    song_id: String,
+    revision_id: u32,
+) -> Result<Revision, String> {
+    revision_store(&app)?
+        .get(&song_id, revision_id)
+        .map_err(String::from)
+}
+
+/// Structural diff between two revisions of a song
+#[tauri::command]
+fn diff_revisions(
+    app: tauri::AppHandle,
+    song_id: String,
+    from: u32,
+    to: u32,
+) -> Result<SheetDiff, String> {
+    revision_store(&app)?
+        .diff(&song_id, from, to)
+        .map_err(String::from)
+}
+
+/// Structural diff between two versions of a sheet's sections
+#[tauri::command]
+fn diff_sections(old: Vec<FetchedSection>, new: Vec<FetchedSection>) -> SheetDiff {
+    revisions::diff(&old, &new)
+}
+
+/// Restore a revision; its sections come back as the newest revision
+#[tauri::command]
+fn restore_revision(
+    app: tauri::AppHandle,
+    song_id: String,
+    revision_id: u32,
+) -> Result<Revision, String> {
+    revision_store(&app)?
+        .restore(&song_id, revision_id)
+        .map_err(String::from)
+}
+
+/// Re-fetch a song from its source site and merge the site's changes into
+/// the edited copy. The fetch is stored as a Refetch revision to merge against
+/// next time; the frontend saves the merged sheet once conflicts are settled.
+#[tauri::command]
+async fn refresh_song(
+    app: tauri::AppHandle,
+    song_id: String,
+    current: FetchedChordSheet,
+) -> Result<MergeResult, String> {
+    refresh(&app, song_id, current).await.map_err(String::from)
+}
+
+async fn refresh(
+    app: &tauri::AppHandle,
+    song_id: String,
+    current: FetchedChordSheet,
+) -> Result<MergeResult, OperationError> {
+    if current.source_url.is_empty() {
+        return Err(OperationError::permanent(format!(
+            "Song {} has no source URL",
+            song_id
+        )));
+    }
+    let fetched = fetch_sheet(current.source_url.clone(), &[]).await?;
+    let store = revision_store(app)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        merge::merge_refetch(&store, &song_id, &current, fetched)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(OperationError::from)
+}
+
+/// Find songs that are likely the same, grouped into clusters
+#[tauri::command]
+async fn find_duplicates(
+    songs: Vec<LibrarySong>,
+    threshold: Option<f64>,
+) -> Result<Vec<DuplicateCluster>, String> {
+    let threshold = threshold.unwrap_or(duplicates::DEFAULT_THRESHOLD);
+    tauri::async_runtime::spawn_blocking(move || duplicates::find_duplicates(&songs, threshold))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Merge duplicate songs into the one kept; the frontend repoints playlist
+/// entries to it and deletes `removed_ids`
+#[tauri::command]
+fn merge_duplicates(keep: LibrarySong, others: Vec<LibrarySong>) -> MergedSong {
+    duplicates::merge_songs(keep, others)
+}
+
+/// Sync the local library with Supabase: `changes` are the rows in the
+/// sync_log, `overrides` pick a side for conflicted lines by line id
+#[tauri::command]
+async fn sync_library(
+    config: PostgrestConfig,
+    cursor: Option<SyncCursor>,
+    changes: Vec<RowChange>,
+    overrides: Option<std::collections::HashMap<String, Side>>,
+) -> Result<SyncResult, String> {
+    let client = PostgrestClient::new(config)?;
+    sync::sync(
+        &client,
+        &cursor.unwrap_or_default(),
+        changes,
+        &overrides.unwrap_or_default(),
+    )
+    .await
+    .map_err(String::from)
+}
+
+/// Run a queued operation; its result is what the matching command returns
+async fn run_operation(
+    app: &tauri::AppHandle,
+    operation: Operation,
+) -> Result<serde_json::Value, OperationError> {
+    let result = match operation {
+        Operation::FetchUrl { url, known_artists } => {
+            serde_json::to_value(RatedSheet::new(fetch_sheet(url, &known_artists).await?))
+        }
+        Operation::RefreshSong { song_id } => {
+            let inputs = app
+                .try_state::<Arc<SongInputs>>()
+                .ok_or_else(|| OperationError::permanent("Refresh is not ready"))?;
+            let current = inputs
+                .request(&song_id, || {
+                    let _ = app.emit("queue:song-input", &song_id);
+                })
+                .await?;
+            serde_json::to_value(refresh(app, song_id, current).await?)
+        }
+        Operation::SyncPush {} => {
+            let inputs = app
+                .try_state::<Arc<SyncInputs>>()
+                .ok_or_else(|| OperationError::permanent("Sync is not ready"))?;
+            let input = inputs
+                .request(|| {
+                    let _ = app.emit("queue:sync-input", ());
+                })
+                .await?;
+            let client = PostgrestClient::new(input.config)?;
+            serde_json::to_value(
+                sync::sync(&client, &input.cursor, input.changes, &input.overrides).await?,
+            )
+        }
+    };
+    result.map_err(|e| OperationError::permanent(e.to_string()))
+}
+
+/// Hand a queued sync the session and the change log as they are now, in
+/// answer to `queue:sync-input`; `None` when nobody is signed in. False when
+/// no sync is waiting
+#[tauri::command]
+fn provide_sync_input(inputs: tauri::State<'_, Arc<SyncInputs>>, input: Option<SyncInput>) -> bool {
+    inputs.provide(input)
+}
+
+/// Hand a queued re-fetch the song as it is now, in answer to
+/// `queue:song-input`; `None` when the song was deleted. False when no
+/// re-fetch of that song is waiting
+#[tauri::command]
+fn provide_song_input(
+    inputs: tauri::State<'_, Arc<SongInputs>>,
+    song_id: String,
+    current: Option<FetchedChordSheet>,
+) -> bool {
+    inputs.provide(&song_id, current)
+}
+
+/// Queue a network operation; it runs as soon as the network allows and its
+/// outcome arrives as a `queue:completed` or `queue:failed` event
+#[tauri::command]
+fn enqueue_operation(
+    queue: tauri::State<'_, Arc<OperationQueue>>,
+    operation: Operation,
+) -> Result<QueueEntry, String> {
+    queue.enqueue(operation, now_ms()).map_err(String::from)
+}
+
+/// Queued operations and whether the last attempt reached the network
+#[tauri::command]
+fn get_queue_status(queue: tauri::State<'_, Arc<OperationQueue>>) -> QueueStatus {
+    queue.status()
+}
+
+/// Retry one operation, or all waiting and failed ones, without waiting for
+/// the backoff
+#[tauri::command]
+fn retry_queue(
+    queue: tauri::State<'_, Arc<OperationQueue>>,
+    id: Option<String>,
+) -> Result<(), String> {
+    queue.retry(id.as_deref(), now_ms()).map_err(String::from)
+}
+
+/// Remove an operation from the queue: acknowledge a result or cancel
+#[tauri::command]
+fn remove_queued_operation(
+    queue: tauri::State<'_, Arc<OperationQueue>>,
+    id: String,
+) -> Result<QueueEntry, String> {
+    queue.remove(&id).map_err(String::from)
+}
+
+/// Check a finished practice session and give it an id; the frontend stores
+/// it in practice_sessions
+#[tauri::command]
+fn log_practice_session(
+    song_id: String,
+    started_at: String,
+    ended_at: String,
+    tempo: Option<u32>,
+    rating: Option<u8>,
+    notes: Option<String>,
+) -> Result<PracticeSession, String> {
+    PracticeSession::new(song_id, started_at, ended_at, tempo, rating, notes).map_err(String::from)
+}
+
+/// Songs to practise today, most urgent first. `today` is the local date
+/// ("YYYY-MM-DD") and `utc_offset` the local offset in minutes east of UTC,
+/// which dates the sessions; setlists are playlists with a performance date.
+#[tauri::command]
+fn suggest_practice(
+    songs: Vec<PracticeSong>,
+    sessions: Vec<PracticeSession>,
+    setlists: Option<Vec<UpcomingSetlist>>,
+    today: Option<String>,
+    utc_offset: Option<i32>,
+    limit: Option<usize>,
+) -> Result<Vec<PracticeSuggestion>, String> {
+    let offset = practice::utc_offset(utc_offset.unwrap_or_default())?;
+    let today = match today {
+        Some(today) => practice::parse_date(&today)?,
+        None => practice::today(offset),
+    };
+    Ok(practice::suggest(
+        &songs,
+        &sessions,
+        &setlists.unwrap_or_default(),
+        today,
+        offset,
+        limit.unwrap_or(10),
+    ))
+}
+
+/// Practice time per song per local week, from `since` ("YYYY-MM-DD") on;
+/// `utc_offset` is in minutes east of UTC
+#[tauri::command]
+fn practice_time_by_week(
+    sessions: Vec<PracticeSession>,
+    since: Option<String>,
+    utc_offset: Option<i32>,
+) -> Result<Vec<WeeklyPractice>, String> {
+    let offset = practice::utc_offset(utc_offset.unwrap_or_default())?;
+    let since = since.as_deref().map(practice::parse_date).transpose()?;
+    Ok(practice::weekly_time(&sessions, since, offset))
+}
+
+/// Tempo reached per local practice day, per song; `utc_offset` is in
+/// minutes east of UTC
+#[tauri::command]
+fn practice_tempo_progress(
+    sessions: Vec<PracticeSession>,
+    utc_offset: Option<i32>,
+) -> Result<Vec<TempoProgress>, String> {
+    let offset = practice::utc_offset(utc_offset.unwrap_or_default())?;
+    Ok(practice::tempo_progress(&sessions, offset))
+}
+
 /// Get application version
 #[tauri::command]
 fn get_version() -> String {
@@ -100,9 +802,81 @@ pub fn run() {
             fetch_chord_sheet,
             parse_chord_sheet,
             get_supported_sites,
+            get_instruments,
+            transpose_chord_sheet,
+            arrange_chord_sheet,
+            get_chord_voicings,
+            suggest_capo,
+            detect_sheet_key,
+            convert_to_numbers,
+            convert_from_numbers,
+            simplify_sheet,
+            rate_difficulty,
+            export_pdf,
+            export_html,
+            export_text,
+            export_musicxml,
+            render_midi,
+            render_click_track,
+            import_file,
+            import_files,
+            import_ireal_url,
+            export_ireal_url,
+            export_library,
+            import_library,
+            save_revision,
+            list_revisions,
+            get_revision,
+            diff_revisions,
+            diff_sections,
+            restore_revision,
+            refresh_song,
+            find_duplicates,
+            merge_duplicates,
+            sync_library,
+            enqueue_operation,
+            get_queue_status,
+            retry_queue,
+            remove_queued_operation,
+            provide_sync_input,
+            provide_song_input,
+            resolve_setlist,
+            export_setlist_pdf,
+            log_practice_session,
+            suggest_practice,
+            practice_time_by_week,
+            practice_tempo_progress,
             get_version
         ])
         .setup(|app| {
+            let data_dir = app.path().app_data_dir()?;
+            let queue = Arc::new(OperationQueue::open(queue::queue_path(&data_dir))?);
+            app.manage(queue.clone());
+            app.manage(Arc::new(SyncInputs::default()));
+            app.manage(Arc::new(SongInputs::default()));
+            app.manage(Arc::new(RevisionStore::new(revisions::revisions_dir(
+                &data_dir,
+            ))));
+            let handle = app.handle().clone();
+            tauri::async_runtime::spawn(async move {
+                let events = handle.clone();
+                queue::run_worker(
+                    queue,
+                    |operation| {
+                        let handle = handle.clone();
+                        async move { run_operation(&handle, operation).await }
+                    },
+                    move |event| {
+                        let _ = match event {
+                            QueueEvent::Changed(status) => events.emit("queue:changed", status),
+                            QueueEvent::Completed(entry) => events.emit("queue:completed", entry),
+                            QueueEvent::Failed(entry) => events.emit("queue:failed", entry),
+                        };
+                    },
+                )
+                .await
+            });
+
             #[cfg(debug_assertions)]
             {
                 let window = app.get_webview_window("main").unwrap();
@@ -0,0 +1,660 @@
+//! Whole-library backup archives
+//!
+//! The frontend owns the SQLite library, so it hands the records over as a
+//! `Library` and applies what comes back. The archive is a zip with one JSON
+//! file per song plus playlists, tags, chord preferences and settings, and a
+//! manifest recording the schema version and each file's SHA-256.
+//!
+//! Importing checks the version and hashes, then settles clashes with the
+//! library already on this machine (`ExistingLibrary`) by the chosen
+//! `ConflictStrategy`, remapping ids so playlists and tags still point at the
+//! right songs.
+
+use crate::error::ArchiveError;
+use crate::parsers::FetchedChordSheet;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{Read, Write};
+use std::path::Path;
+use zip::write::SimpleFileOptions;
+use zip::{ZipArchive, ZipWriter};
+
+/// Archive layout this build writes; older ones are read as long as the
+/// layout can still be understood
+pub const LIBRARY_SCHEMA_VERSION: u32 = 1;
+
+/// Marks our archives apart from any other zip
+const FORMAT: &str = "cat4g-library";
+
+const MANIFEST_FILE: &str = "manifest.json";
+const PLAYLISTS_FILE: &str = "playlists.json";
+const TAGS_FILE: &str = "tags.json";
+const CHORD_PREFERENCES_FILE: &str = "chord_preferences.json";
+const SETTINGS_FILE: &str = "settings.json";
+const SONGS_DIR: &str = "songs/";
+
+/// A song row with its sections and lines, as the sheet model
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LibrarySong {
+    pub id: String,
+    pub sheet: FetchedChordSheet,
+    #[serde(default)]
+    pub bpm: Option<u32>,
+    #[serde(default)]
+    pub time_signature: Option<String>,
+    #[serde(default)]
+    pub transpose: i32,
+    #[serde(default)]
+    pub playback_speed: Option<f64>,
+    #[serde(default)]
+    pub notes: Option<String>,
+    #[serde(default)]
+    pub is_favorite: bool,
+    #[serde(default)]
+    pub play_count: u32,
+    /// Tag ids (song_tags)
+    #[serde(default)]
+    pub tag_ids: Vec<String>,
+    #[serde(default)]
+    pub created_at: Option<String>,
+    #[serde(default)]
+    pub updated_at: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LibraryPlaylist {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Songs in playing order (playlist_songs.order_index)
+    #[serde(default)]
+    pub song_ids: Vec<String>,
+    #[serde(default)]
+    pub created_at: Option<String>,
+    #[serde(default)]
+    pub updated_at: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LibraryTag {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub color: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LibraryChordPreference {
+    pub id: String,
+    pub chord_name: String,
+    /// chord_preferences.fingering_json, kept as the frontend wrote it
+    pub fingering: serde_json::Value,
+    #[serde(default)]
+    pub is_default: bool,
+    #[serde(default)]
+    pub created_at: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Library {
+    #[serde(default)]
+    pub songs: Vec<LibrarySong>,
+    #[serde(default)]
+    pub playlists: Vec<LibraryPlaylist>,
+    #[serde(default)]
+    pub tags: Vec<LibraryTag>,
+    #[serde(default)]
+    pub chord_preferences: Vec<LibraryChordPreference>,
+    #[serde(default)]
+    pub settings: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LibraryManifest {
+    pub format: String,
+    pub schema_version: u32,
+    /// Version of the app that wrote the archive
+    pub app_version: String,
+    /// Seconds since the Unix epoch
+    pub exported_at: u64,
+    pub song_count: usize,
+    pub playlist_count: usize,
+    /// SHA-256 (hex) of every other file in the archive
+    pub files: BTreeMap<String, String>,
+}
+
+/// What is already in this machine's library, for spotting clashes
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ExistingLibrary {
+    #[serde(default)]
+    pub song_ids: Vec<String>,
+    #[serde(default)]
+    pub playlist_ids: Vec<String>,
+    #[serde(default)]
+    pub tags: Vec<LibraryTag>,
+    #[serde(default)]
+    pub chord_preference_ids: Vec<String>,
+    #[serde(default)]
+    pub setting_keys: Vec<String>,
+}
+
+/// What to do with an archived record that is already in the library
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictStrategy {
+    /// Keep the library's record
+    #[default]
+    Skip,
+    /// Replace it with the archived one
+    Overwrite,
+    /// Keep both, giving the archived one a new id. Tags and settings are
+    /// unique by name, so for them this behaves like `Skip`.
+    Duplicate,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImportCounts {
+    pub added: usize,
+    pub overwritten: usize,
+    pub duplicated: usize,
+    pub skipped: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LibraryImportSummary {
+    pub songs: ImportCounts,
+    pub playlists: ImportCounts,
+    pub tags: ImportCounts,
+    pub chord_preferences: ImportCounts,
+    pub settings: ImportCounts,
+}
+
+/// Records to write into the library, and what happened to each kind
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LibraryImport {
+    pub manifest: LibraryManifest,
+    /// Only the records to insert or replace, with ids already remapped
+    pub library: Library,
+    pub summary: LibraryImportSummary,
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+fn json_error(e: serde_json::Error) -> ArchiveError {
+    ArchiveError::Invalid(format!("Bad JSON: {}", e))
+}
+
+fn to_json<T: Serialize>(value: &T) -> Result<Vec<u8>, ArchiveError> {
+    serde_json::to_vec_pretty(value).map_err(json_error)
+}
+
+fn new_id() -> String {
+    uuid::Uuid::new_v4().to_string()
+}
+
+/// Archive path for a song; ids are UUIDs but are cleaned in case they are not
+fn song_file(id: &str) -> String {
+    let safe: String = id
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("{}{}.json", SONGS_DIR, safe)
+}
+
+/// Write the library to a zip archive at `path`
+pub fn export_library(library: &Library, path: &Path) -> Result<LibraryManifest, ArchiveError> {
+    let mut files: Vec<(String, Vec<u8>)> = Vec::new();
+    for song in &library.songs {
+        files.push((song_file(&song.id), to_json(song)?));
+    }
+    files.push((PLAYLISTS_FILE.to_string(), to_json(&library.playlists)?));
+    files.push((TAGS_FILE.to_string(), to_json(&library.tags)?));
+    files.push((
+        CHORD_PREFERENCES_FILE.to_string(),
+        to_json(&library.chord_preferences)?,
+    ));
+    files.push((SETTINGS_FILE.to_string(), to_json(&library.settings)?));
+
+    let mut seen = HashSet::new();
+    if let Some((name, _)) = files.iter().find(|(name, _)| !seen.insert(name.as_str())) {
+        return Err(ArchiveError::Invalid(format!(
+            "Two records share the file {}",
+            name
+        )));
+    }
+
+    let manifest = LibraryManifest {
+        format: FORMAT.to_string(),
+        schema_version: LIBRARY_SCHEMA_VERSION,
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        exported_at: std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs()),
+        song_count: library.songs.len(),
+        playlist_count: library.playlists.len(),
+        files: files
+            .iter()
+            .map(|(name, data)| (name.clone(), sha256_hex(data)))
+            .collect(),
+    };
+
+    let mut writer = ZipWriter::new(std::fs::File::create(path)?);
+    let options = SimpleFileOptions::default();
+    let zip_error = |e: zip::result::ZipError| ArchiveError::Invalid(e.to_string());
+    writer
+        .start_file(MANIFEST_FILE, options)
+        .map_err(zip_error)?;
+    writer.write_all(&to_json(&manifest)?)?;
+    for (name, data) in &files {
+        writer
+            .start_file(name.as_str(), options)
+            .map_err(zip_error)?;
+        writer.write_all(data)?;
+    }
+    writer.finish().map_err(zip_error)?;
+    Ok(manifest)
+}
+
+fn read_entry<R: Read + std::io::Seek>(
+    archive: &mut ZipArchive<R>,
+    name: &str,
+) -> Result<Vec<u8>, ArchiveError> {
+    let mut entry = archive
+        .by_name(name)
+        .map_err(|_| ArchiveError::Invalid(format!("{} is missing", name)))?;
+    let mut data = Vec::new();
+    entry.read_to_end(&mut data)?;
+    Ok(data)
+}
+
+/// Read and verify an archive: format, schema version and every file's hash
+pub fn read_library(path: &Path) -> Result<(LibraryManifest, Library), ArchiveError> {
+    let mut archive = ZipArchive::new(std::fs::File::open(path)?)
+        .map_err(|e| ArchiveError::Invalid(e.to_string()))?;
+    let manifest: LibraryManifest =
+        serde_json::from_slice(&read_entry(&mut archive, MANIFEST_FILE)?).map_err(json_error)?;
+    if manifest.format != FORMAT {
+        return Err(ArchiveError::Invalid(format!(
+            "Not a library archive ({})",
+            manifest.format
+        )));
+    }
+    if manifest.schema_version == 0 || manifest.schema_version > LIBRARY_SCHEMA_VERSION {
+        return Err(ArchiveError::UnsupportedVersion(
+            manifest.schema_version,
+            LIBRARY_SCHEMA_VERSION,
+        ));
+    }
+
+    let mut contents: HashMap<&str, Vec<u8>> = HashMap::new();
+    for (name, hash) in &manifest.files {
+        let data = read_entry(&mut archive, name)?;
+        if sha256_hex(&data) != *hash {
+            return Err(ArchiveError::Corrupt(name.clone()));
+        }
+        contents.insert(name, data);
+    }
+
+    fn parse<T: DeserializeOwned + Default>(
+        contents: &HashMap<&str, Vec<u8>>,
+        name: &str,
+    ) -> Result<T, ArchiveError> {
+        contents.get(name).map_or(Ok(T::default()), |data| {
+            serde_json::from_slice(data).map_err(json_error)
+        })
+    }
+
+    let mut songs = Vec::new();
+    for (name, data) in &contents {
+        if name.starts_with(SONGS_DIR) {
+            songs.push(serde_json::from_slice::<LibrarySong>(data).map_err(json_error)?);
+        }
+    }
+    // Keep a stable order whatever order the manifest lists the files in
+    songs.sort_by(|a, b| a.id.cmp(&b.id));
+
+    let library = Library {
+        songs,
+        playlists: parse(&contents, PLAYLISTS_FILE)?,
+        tags: parse(&contents, TAGS_FILE)?,
+        chord_preferences: parse(&contents, CHORD_PREFERENCES_FILE)?,
+        settings: parse(&contents, SETTINGS_FILE)?,
+    };
+    Ok((manifest, library))
+}
+
+/// Decide one record's fate by id: Some(new id) to write it, None to skip it
+fn resolve_id(
+    id: &str,
+    existing: &HashSet<&str>,
+    strategy: ConflictStrategy,
+    counts: &mut ImportCounts,
+) -> Option<String> {
+    if !existing.contains(id) {
+        counts.added += 1;
+        return Some(id.to_string());
+    }
+    match strategy {
+        ConflictStrategy::Skip => {
+            counts.skipped += 1;
+            None
+        }
+        ConflictStrategy::Overwrite => {
+            counts.overwritten += 1;
+            Some(id.to_string())
+        }
+        ConflictStrategy::Duplicate => {
+            counts.duplicated += 1;
+            Some(new_id())
+        }
+    }
+}
+
+/// Settle an archived library against the one already on this machine
+pub fn merge_library(
+    archived: Library,
+    existing: &ExistingLibrary,
+    strategy: ConflictStrategy,
+) -> (Library, LibraryImportSummary) {
+    let mut summary = LibraryImportSummary::default();
+    let mut out = Library::default();
+
+    // Tags are unique by name: a tag the library already has keeps its id
+    let mut tag_ids: HashMap<String, String> = HashMap::new();
+    let existing_tag_ids: HashSet<&str> = existing.tags.iter().map(|t| t.id.as_str()).collect();
+    for tag in archived.tags {
+        match existing.tags.iter().find(|t| t.name == tag.name) {
+            Some(current) => {
+                tag_ids.insert(tag.id.clone(), current.id.clone());
+                if strategy == ConflictStrategy::Overwrite {
+                    summary.tags.overwritten += 1;
+                    out.tags.push(LibraryTag {
+                        id: current.id.clone(),
+                        ..tag
+                    });
+                } else {
+                    summary.tags.skipped += 1;
+                }
+            }
+            None => {
+                let id = if existing_tag_ids.contains(tag.id.as_str()) {
+                    new_id()
+                } else {
+                    tag.id.clone()
+                };
+                summary.tags.added += 1;
+                tag_ids.insert(tag.id.clone(), id.clone());
+                out.tags.push(LibraryTag { id, ..tag });
+            }
+        }
+    }
+
+    let existing_songs: HashSet<&str> = existing.song_ids.iter().map(String::as_str).collect();
+    let mut song_ids: HashMap<String, String> = HashMap::new();
+    for mut song in archived.songs {
+        if let Some(id) = resolve_id(&song.id, &existing_songs, strategy, &mut summary.songs) {
+            song_ids.insert(song.id.clone(), id.clone());
+            song.id = id;
+            for tag_id in &mut song.tag_ids {
+                if let Some(mapped) = tag_ids.get(tag_id.as_str()) {
+                    *tag_id = mapped.clone();
+                }
+            }
+            out.songs.push(song);
+        }
+    }
+
+    let existing_playlists: HashSet<&str> =
+        existing.playlist_ids.iter().map(String::as_str).collect();
+    for mut playlist in archived.playlists {
+        if let Some(id) = resolve_id(
+            &playlist.id,
+            &existing_playlists,
+            strategy,
+            &mut summary.playlists,
+        ) {
+            playlist.id = id;
+            for song_id in &mut playlist.song_ids {
+                if let Some(mapped) = song_ids.get(song_id.as_str()) {
+                    *song_id = mapped.clone();
+                }
+            }
+            out.playlists.push(playlist);
+        }
+    }
+
+    let existing_preferences: HashSet<&str> = existing
+        .chord_preference_ids
+        .iter()
+        .map(String::as_str)
+        .collect();
+    for mut preference in archived.chord_preferences {
+        if let Some(id) = resolve_id(
+            &preference.id,
+            &existing_preferences,
+            strategy,
+            &mut summary.chord_preferences,
+        ) {
+            preference.id = id;
+            out.chord_preferences.push(preference);
+        }
+    }
+
+    let existing_settings: HashSet<&str> =
+        existing.setting_keys.iter().map(String::as_str).collect();
+    for (key, value) in archived.settings {
+        let strategy = match strategy {
+            ConflictStrategy::Duplicate => ConflictStrategy::Skip,
+            strategy => strategy,
+        };
+        if resolve_id(&key, &existing_settings, strategy, &mut summary.settings).is_some() {
+            out.settings.insert(key, value);
+        }
+    }
+
+    (out, summary)
+}
+
+/// Read an archive and settle it against the current library
+pub fn import_library(
+    path: &Path,
+    existing: &ExistingLibrary,
+    strategy: ConflictStrategy,
+) -> Result<LibraryImport, ArchiveError> {
+    let (manifest, archived) = read_library(path)?;
+    let (library, summary) = merge_library(archived, existing, strategy);
+    Ok(LibraryImport {
+        manifest,
+        library,
+        summary,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::{FetchedChord, FetchedLine, FetchedSection};
+    use std::path::PathBuf;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("cat4g-{}-{}.zip", name, std::process::id()))
+    }
+
+    fn song(id: &str, title: &str, tag_ids: &[&str]) -> LibrarySong {
+        let mut sheet = FetchedChordSheet::new(String::new());
+        sheet.title = Some(title.to_string());
+        let mut section = FetchedSection::new("Verse");
+        section.lines.push(FetchedLine::with_chords(
+            "Hello",
+            vec![FetchedChord::new("G", 0)],
+        ));
+        sheet.sections.push(section);
+        LibrarySong {
+            id: id.to_string(),
+            sheet,
+            bpm: Some(96),
+            time_signature: Some("3/4".to_string()),
+            transpose: 2,
+            playback_speed: None,
+            notes: Some("Capo on 2 live".to_string()),
+            is_favorite: true,
+            play_count: 4,
+            tag_ids: tag_ids.iter().map(|t| t.to_string()).collect(),
+            created_at: None,
+            updated_at: None,
+        }
+    }
+
+    fn library() -> Library {
+        Library {
+            songs: vec![song("s1", "First", &["t1"]), song("s2", "Second", &[])],
+            playlists: vec![LibraryPlaylist {
+                id: "p1".to_string(),
+                name: "Sunday".to_string(),
+                description: None,
+                song_ids: vec!["s2".to_string(), "s1".to_string()],
+                created_at: None,
+                updated_at: None,
+            }],
+            tags: vec![LibraryTag {
+                id: "t1".to_string(),
+                name: "Worship".to_string(),
+                color: Some("#ff0000".to_string()),
+            }],
+            chord_preferences: vec![LibraryChordPreference {
+                id: "c1".to_string(),
+                chord_name: "F".to_string(),
+                fingering: serde_json::json!({"frets": [1, 3, 3, 2, 1, 1]}),
+                is_default: true,
+                created_at: None,
+            }],
+            settings: BTreeMap::from([("theme".to_string(), "dark".to_string())]),
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let path = temp_path("library-round-trip");
+        let manifest = export_library(&library(), &path).unwrap();
+        assert_eq!(manifest.schema_version, LIBRARY_SCHEMA_VERSION);
+        assert_eq!(manifest.song_count, 2);
+        assert!(manifest.files.contains_key("songs/s1.json"));
+
+        let import = import_library(&path, &ExistingLibrary::default(), ConflictStrategy::Skip);
+        std::fs::remove_file(&path).unwrap();
+        let import = import.unwrap();
+
+        let restored = &import.library;
+        assert_eq!(restored.songs.len(), 2);
+        assert_eq!(restored.songs[0].sheet.title.as_deref(), Some("First"));
+        assert_eq!(
+            restored.songs[0].sheet.sections[0].lines[0].chords[0].chord,
+            "G"
+        );
+        assert_eq!(restored.songs[0].transpose, 2);
+        assert_eq!(restored.playlists[0].song_ids, vec!["s2", "s1"]);
+        assert_eq!(restored.chord_preferences[0].fingering["frets"][1], 3);
+        assert_eq!(restored.settings["theme"], "dark");
+        assert_eq!(import.summary.songs.added, 2);
+    }
+
+    #[test]
+    fn test_conflict_strategies() {
+        let existing = ExistingLibrary {
+            song_ids: vec!["s1".to_string()],
+            playlist_ids: vec!["p1".to_string()],
+            tags: vec![LibraryTag {
+                id: "local".to_string(),
+                name: "Worship".to_string(),
+                color: None,
+            }],
+            chord_preference_ids: Vec::new(),
+            setting_keys: vec!["theme".to_string()],
+        };
+
+        let (skipped, summary) = merge_library(library(), &existing, ConflictStrategy::Skip);
+        assert_eq!(
+            summary.songs,
+            ImportCounts {
+                added: 1,
+                skipped: 1,
+                ..Default::default()
+            }
+        );
+        assert_eq!(skipped.songs.len(), 1);
+        assert!(skipped.playlists.is_empty());
+        assert!(skipped.tags.is_empty());
+        assert!(skipped.settings.is_empty());
+
+        let (overwritten, summary) =
+            merge_library(library(), &existing, ConflictStrategy::Overwrite);
+        assert_eq!(summary.songs.overwritten, 1);
+        assert_eq!(overwritten.songs[0].id, "s1");
+        // The archived tag lands on the library's tag of the same name
+        assert_eq!(overwritten.songs[0].tag_ids, vec!["local"]);
+        assert_eq!(overwritten.tags[0].id, "local");
+        assert_eq!(overwritten.tags[0].color.as_deref(), Some("#ff0000"));
+        assert_eq!(overwritten.settings["theme"], "dark");
+
+        let (duplicated, summary) =
+            merge_library(library(), &existing, ConflictStrategy::Duplicate);
+        assert_eq!(summary.songs.duplicated, 1);
+        assert_eq!(summary.settings.skipped, 1);
+        let copy = &duplicated.songs[0];
+        assert_ne!(copy.id, "s1");
+        assert_ne!(duplicated.playlists[0].id, "p1");
+        // The copied playlist points at the copied song
+        assert_eq!(
+            duplicated.playlists[0].song_ids,
+            vec!["s2".to_string(), copy.id.clone()]
+        );
+    }
+
+    #[test]
+    fn test_rejects_tampered_and_newer_archives() {
+        let path = temp_path("library-tampered");
+        export_library(&library(), &path).unwrap();
+        let (mut manifest, _) = read_library(&path).unwrap();
+
+        let rewrite = |manifest: &LibraryManifest, settings: &str| {
+            let mut writer = ZipWriter::new(std::fs::File::create(&path).unwrap());
+            let options = SimpleFileOptions::default();
+            writer.start_file(MANIFEST_FILE, options).unwrap();
+            writer
+                .write_all(&serde_json::to_vec(manifest).unwrap())
+                .unwrap();
+            writer.start_file(SETTINGS_FILE, options).unwrap();
+            writer.write_all(settings.as_bytes()).unwrap();
+            writer.finish().unwrap();
+        };
+
+        manifest.files.retain(|name, _| name == SETTINGS_FILE);
+        rewrite(&manifest, r#"{"theme": "light"}"#);
+        let tampered = read_library(&path);
+
+        manifest
+            .files
+            .insert(SETTINGS_FILE.to_string(), sha256_hex(b"{}"));
+        manifest.schema_version = LIBRARY_SCHEMA_VERSION + 1;
+        rewrite(&manifest, "{}");
+        let newer = read_library(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(matches!(tampered, Err(ArchiveError::Corrupt(name)) if name == SETTINGS_FILE));
+        assert!(matches!(newer, Err(ArchiveError::UnsupportedVersion(2, 1))));
+    }
+}
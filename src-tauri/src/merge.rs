@@ -0,0 +1,531 @@
+//! Three-way merge of a re-fetched sheet into the user's edited copy
+//!
+//! The base is the sheet as last fetched, "ours" the edited copy in the
+//! library and "theirs" the new fetch. Sections are aligned by name and lines
+//! by lyrics (diff3 over both); within a line present on all three sides each
+//! chord position is merged on its own. A change made on one side only is
+//! applied; the same change on both sides is taken once; different changes
+//! to the same place are conflicts, where the edited copy is kept and all
+//! three versions are returned for review.
+//!
+//! The base is the Import revision stored when a fetched song is first saved,
+//! then each re-fetch's Refetch revision. Without one (a song saved before
+//! revisions were kept) nothing can be told apart, so every difference from
+//! the site is a conflict.
+
+use crate::error::RevisionError;
+use crate::parsers::{FetchedChord, FetchedChordSheet, FetchedLine, FetchedSection};
+use crate::revisions::{lcs, line_key, RevisionSource, RevisionStore};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::ops::Range;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum MergeConflict {
+    /// Both sides changed the chord at one position of a line
+    Chord {
+        section: usize,
+        line: usize,
+        position: i32,
+        base: Option<String>,
+        ours: Option<String>,
+        theirs: Option<String>,
+    },
+    /// Both sides rewrote the same run of lines; `line` is where ours starts
+    Lines {
+        section: usize,
+        line: usize,
+        base: Vec<FetchedLine>,
+        ours: Vec<FetchedLine>,
+        theirs: Vec<FetchedLine>,
+    },
+    /// Both sides added, removed or renamed sections at the same place
+    Sections {
+        section: usize,
+        base: Vec<FetchedSection>,
+        ours: Vec<FetchedSection>,
+        theirs: Vec<FetchedSection>,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MergeResult {
+    /// The edited copy with the site's changes applied; indices in conflicts
+    /// refer to this sheet
+    pub sheet: FetchedChordSheet,
+    /// Changes taken from the site
+    pub applied: usize,
+    pub conflicts: Vec<MergeConflict>,
+    /// False when there was no earlier fetch to compare against
+    pub has_base: bool,
+}
+
+/// A run of items the three sides agree on, or one where they differ
+#[derive(Debug, PartialEq, Eq)]
+enum Chunk {
+    Stable(usize, usize, usize),
+    Changed {
+        base: Range<usize>,
+        ours: Range<usize>,
+        theirs: Range<usize>,
+    },
+}
+
+/// diff3: split three key lists into agreed items and changed runs
+fn diff3(base: &[String], ours: &[String], theirs: &[String]) -> Vec<Chunk> {
+    let to_ours: HashMap<usize, usize> = lcs(base, ours).into_iter().collect();
+    let to_theirs: HashMap<usize, usize> = lcs(base, theirs).into_iter().collect();
+    let (mut b, mut o, mut t) = (0, 0, 0);
+    let mut chunks = Vec::new();
+    loop {
+        let next = (b..base.len()).find(|j| to_ours.contains_key(j) && to_theirs.contains_key(j));
+        let (nb, no, nt) = match next {
+            Some(j) => (j, to_ours[&j], to_theirs[&j]),
+            None => (base.len(), ours.len(), theirs.len()),
+        };
+        if nb > b || no > o || nt > t {
+            chunks.push(Chunk::Changed {
+                base: b..nb,
+                ours: o..no,
+                theirs: t..nt,
+            });
+        }
+        if next.is_none() {
+            break;
+        }
+        chunks.push(Chunk::Stable(nb, no, nt));
+        (b, o, t) = (nb + 1, no + 1, nt + 1);
+    }
+    chunks
+}
+
+/// Section keys: name plus occurrence, so two "Chorus" sections stay apart
+fn section_keys(sections: &[FetchedSection]) -> Vec<String> {
+    let mut seen: HashMap<&str, usize> = HashMap::new();
+    sections
+        .iter()
+        .map(|section| {
+            let count = seen.entry(section.name.as_str()).or_default();
+            *count += 1;
+            format!("{}#{}", section.name, count)
+        })
+        .collect()
+}
+
+enum Choice {
+    Ours,
+    Theirs,
+    Conflict,
+}
+
+struct Merger {
+    has_base: bool,
+    applied: usize,
+    conflicts: Vec<MergeConflict>,
+}
+
+impl Merger {
+    /// Which side wins for one value, counting taken site changes
+    fn choose(&mut self, base_is_ours: bool, base_is_theirs: bool, ours_is_theirs: bool) -> Choice {
+        if ours_is_theirs || base_is_theirs {
+            Choice::Ours
+        } else if base_is_ours && self.has_base {
+            self.applied += 1;
+            Choice::Theirs
+        } else {
+            Choice::Conflict
+        }
+    }
+
+    /// Merge a value where a conflict quietly keeps ours (section settings)
+    fn pick<T: PartialEq + Clone>(&mut self, base: &T, ours: &T, theirs: &T) -> T {
+        match self.choose(base == ours, base == theirs, ours == theirs) {
+            Choice::Theirs => theirs.clone(),
+            Choice::Ours | Choice::Conflict => ours.clone(),
+        }
+    }
+
+    fn merge_chords(
+        &mut self,
+        base: &FetchedLine,
+        ours: &FetchedLine,
+        theirs: &FetchedLine,
+        section: usize,
+        line: usize,
+    ) -> Vec<FetchedChord> {
+        let at = |l: &FetchedLine, position: i32| {
+            l.chords
+                .iter()
+                .find(|c| c.position == position)
+                .map(|c| c.chord.clone())
+        };
+        let positions: BTreeSet<i32> = [base, ours, theirs]
+            .iter()
+            .flat_map(|l| l.chords.iter().map(|c| c.position))
+            .collect();
+
+        let mut chords = Vec::new();
+        for position in positions {
+            let (b, o, t) = (at(base, position), at(ours, position), at(theirs, position));
+            let chord = match self.choose(b == o, b == t, o == t) {
+                Choice::Ours => o,
+                Choice::Theirs => t,
+                Choice::Conflict => {
+                    self.conflicts.push(MergeConflict::Chord {
+                        section,
+                        line,
+                        position,
+                        base: b,
+                        ours: o.clone(),
+                        theirs: t,
+                    });
+                    o
+                }
+            };
+            if let Some(chord) = chord {
+                chords.push(FetchedChord::new(&chord, position));
+            }
+        }
+        chords
+    }
+
+    fn merge_lines(
+        &mut self,
+        base: &FetchedSection,
+        ours: &FetchedSection,
+        theirs: &FetchedSection,
+        section: usize,
+    ) -> Vec<FetchedLine> {
+        let keys = |s: &FetchedSection| s.lines.iter().map(line_key).collect::<Vec<_>>();
+        let mut lines = Vec::new();
+        for chunk in diff3(&keys(base), &keys(ours), &keys(theirs)) {
+            match chunk {
+                Chunk::Stable(b, o, t) => {
+                    let (b, o, t) = (&base.lines[b], &ours.lines[o], &theirs.lines[t]);
+                    let chords = self.merge_chords(b, o, t, section, lines.len());
+                    let mut line = FetchedLine::with_chords(&o.lyrics, chords);
+                    line.measures = self.pick(&b.measures, &o.measures, &t.measures);
+                    line.tab = o.tab.clone();
+                    lines.push(line);
+                }
+                Chunk::Changed {
+                    base: b,
+                    ours: o,
+                    theirs: t,
+                } => {
+                    let (b, o, t) = (&base.lines[b], &ours.lines[o], &theirs.lines[t]);
+                    match self.choose(b == o, b == t, o == t) {
+                        Choice::Ours => lines.extend_from_slice(o),
+                        Choice::Theirs => lines.extend_from_slice(t),
+                        Choice::Conflict => {
+                            self.conflicts.push(MergeConflict::Lines {
+                                section,
+                                line: lines.len(),
+                                base: b.to_vec(),
+                                ours: o.to_vec(),
+                                theirs: t.to_vec(),
+                            });
+                            lines.extend_from_slice(o);
+                        }
+                    }
+                }
+            }
+        }
+        lines
+    }
+
+    fn merge_sections(
+        &mut self,
+        base: &[FetchedSection],
+        ours: &[FetchedSection],
+        theirs: &[FetchedSection],
+    ) -> Vec<FetchedSection> {
+        let mut sections = Vec::new();
+        let chunks = diff3(
+            &section_keys(base),
+            &section_keys(ours),
+            &section_keys(theirs),
+        );
+        for chunk in chunks {
+            match chunk {
+                Chunk::Stable(b, o, t) => {
+                    let (b, o, t) = (&base[b], &ours[o], &theirs[t]);
+                    let mut section = FetchedSection::new(&o.name);
+                    section.lines = self.merge_lines(b, o, t, sections.len());
+                    section.repeat_count =
+                        self.pick(&b.repeat_count, &o.repeat_count, &t.repeat_count);
+                    section.bpm_override =
+                        self.pick(&b.bpm_override, &o.bpm_override, &t.bpm_override);
+                    section.time_signature =
+                        self.pick(&b.time_signature, &o.time_signature, &t.time_signature);
+                    sections.push(section);
+                }
+                Chunk::Changed {
+                    base: b,
+                    ours: o,
+                    theirs: t,
+                } => {
+                    let (b, o, t) = (&base[b], &ours[o], &theirs[t]);
+                    match self.choose(b == o, b == t, o == t) {
+                        Choice::Ours => sections.extend_from_slice(o),
+                        Choice::Theirs => sections.extend_from_slice(t),
+                        Choice::Conflict => {
+                            self.conflicts.push(MergeConflict::Sections {
+                                section: sections.len(),
+                                base: b.to_vec(),
+                                ours: o.to_vec(),
+                                theirs: t.to_vec(),
+                            });
+                            sections.extend_from_slice(o);
+                        }
+                    }
+                }
+            }
+        }
+        sections
+    }
+}
+
+/// Merge the site's new version into the edited copy
+///
+/// `base` is the sheet as last fetched, or None when there is none.
+pub fn merge_sheets(
+    base: Option<&[FetchedSection]>,
+    ours: &FetchedChordSheet,
+    theirs: &FetchedChordSheet,
+) -> MergeResult {
+    let mut merger = Merger {
+        has_base: base.is_some(),
+        applied: 0,
+        conflicts: Vec::new(),
+    };
+    let base = base.unwrap_or(&ours.sections);
+
+    let mut sheet = ours.clone();
+    sheet.sections = merger.merge_sections(base, &ours.sections, &theirs.sections);
+    // The site's own metadata has no base copy; fill in only what is missing
+    sheet.key = ours.key.clone().or_else(|| theirs.key.clone());
+    sheet.source_difficulty = ours.source_difficulty.or(theirs.source_difficulty);
+
+    MergeResult {
+        sheet,
+        applied: merger.applied,
+        conflicts: merger.conflicts,
+        has_base: merger.has_base,
+    }
+}
+
+/// Merge a re-fetch of a song into its edited copy against the sheet as last
+/// fetched, and store the fetch as the Refetch revision to merge against next
+/// time
+pub fn merge_refetch(
+    store: &RevisionStore,
+    song_id: &str,
+    current: &FetchedChordSheet,
+    fetched: FetchedChordSheet,
+) -> Result<MergeResult, RevisionError> {
+    let base = store.latest_fetched(song_id)?;
+    let result = merge_sheets(
+        base.as_ref().map(|r| r.sections.as_slice()),
+        current,
+        &fetched,
+    );
+    store.save(
+        song_id,
+        fetched.sections,
+        RevisionSource::Refetch,
+        Some("Fetched from site".to_string()),
+    )?;
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(lyrics: &str, chords: &[(&str, i32)]) -> FetchedLine {
+        FetchedLine::with_chords(
+            lyrics,
+            chords
+                .iter()
+                .map(|&(c, p)| FetchedChord::new(c, p))
+                .collect(),
+        )
+    }
+
+    fn sheet(sections: Vec<(&str, Vec<FetchedLine>)>) -> FetchedChordSheet {
+        let mut sheet = FetchedChordSheet::new("https://example.com/song".to_string());
+        sheet.sections = sections
+            .into_iter()
+            .map(|(name, lines)| {
+                let mut section = FetchedSection::new(name);
+                section.lines = lines;
+                section
+            })
+            .collect();
+        sheet
+    }
+
+    fn chords(line: &FetchedLine) -> Vec<(&str, i32)> {
+        line.chords
+            .iter()
+            .map(|c| (c.chord.as_str(), c.position))
+            .collect()
+    }
+
+    fn base() -> FetchedChordSheet {
+        sheet(vec![
+            (
+                "Verse",
+                vec![
+                    line("First line", &[("C", 0), ("G", 6)]),
+                    line("Second line", &[("Am", 0)]),
+                    line("Third line", &[("F", 0)]),
+                ],
+            ),
+            ("Chorus", vec![line("Sing it", &[("C", 0)])]),
+        ])
+    }
+
+    #[test]
+    fn test_diff3_chunks() {
+        let keys = |s: &str| s.chars().map(String::from).collect::<Vec<_>>();
+        let chunks = diff3(&keys("abc"), &keys("abxc"), &keys("bc"));
+        assert_eq!(
+            chunks,
+            vec![
+                Chunk::Changed {
+                    base: 0..1,
+                    ours: 0..1,
+                    theirs: 0..0
+                },
+                Chunk::Stable(1, 1, 0),
+                Chunk::Changed {
+                    base: 2..2,
+                    ours: 2..3,
+                    theirs: 1..1
+                },
+                Chunk::Stable(2, 3, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_merge() {
+        // Local fixes: G -> G7 on the first line, F -> Fmaj7 on the third
+        let ours = sheet(vec![
+            (
+                "Verse",
+                vec![
+                    line("First line", &[("C", 0), ("G7", 6)]),
+                    line("Second line", &[("Am", 0)]),
+                    line("Third line", &[("Fmaj7", 0)]),
+                ],
+            ),
+            ("Chorus", vec![line("Sing it", &[("C", 0)])]),
+        ]);
+        // Site: C -> Cadd9, Am -> Am7, F -> F6, lyric fix in the chorus, new bridge
+        let theirs = sheet(vec![
+            (
+                "Verse",
+                vec![
+                    line("First line", &[("Cadd9", 0), ("G", 6)]),
+                    line("Second line", &[("Am7", 0)]),
+                    line("Third line", &[("F6", 0)]),
+                ],
+            ),
+            ("Bridge", vec![line("New part", &[("Dm", 0)])]),
+            ("Chorus", vec![line("Sing it loud", &[("C", 0)])]),
+        ]);
+
+        let result = merge_sheets(Some(&base().sections), &ours, &theirs);
+        assert!(result.has_base);
+        let sections = &result.sheet.sections;
+        let names: Vec<_> = sections.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["Verse", "Bridge", "Chorus"]);
+        assert_eq!(chords(&sections[0].lines[0]), vec![("Cadd9", 0), ("G7", 6)]);
+        assert_eq!(chords(&sections[0].lines[1]), vec![("Am7", 0)]);
+        // Both changed the same chord: ours stays, conflict reported
+        assert_eq!(chords(&sections[0].lines[2]), vec![("Fmaj7", 0)]);
+        assert_eq!(sections[2].lines[0].lyrics, "Sing it loud");
+        assert_eq!(result.applied, 4);
+
+        assert_eq!(result.conflicts.len(), 1);
+        match &result.conflicts[0] {
+            MergeConflict::Chord {
+                section,
+                line,
+                position,
+                base,
+                ours,
+                theirs,
+            } => {
+                assert_eq!((*section, *line, *position), (0, 2, 0));
+                assert_eq!(base.as_deref(), Some("F"));
+                assert_eq!(ours.as_deref(), Some("Fmaj7"));
+                assert_eq!(theirs.as_deref(), Some("F6"));
+            }
+            other => panic!("unexpected conflict {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_conflicting_lines_and_no_base() {
+        let ours = sheet(vec![(
+            "Verse",
+            vec![line("First line", &[("C", 0)]), line("My words", &[])],
+        )]);
+        let theirs = sheet(vec![(
+            "Verse",
+            vec![line("First line", &[("C", 0)]), line("Their words", &[])],
+        )]);
+        let base = sheet(vec![(
+            "Verse",
+            vec![line("First line", &[("C", 0)]), line("Old words", &[])],
+        )]);
+        let result = merge_sheets(Some(&base.sections), &ours, &theirs);
+        assert_eq!(result.sheet.sections[0].lines[1].lyrics, "My words");
+        assert!(matches!(
+            &result.conflicts[..],
+            [MergeConflict::Lines { section: 0, line: 1, theirs, .. }] if theirs[0].lyrics == "Their words"
+        ));
+
+        // Without a base even a one-sided change needs review
+        let result = merge_sheets(None, &base, &theirs);
+        assert!(!result.has_base);
+        assert_eq!(result.applied, 0);
+        assert_eq!(result.conflicts.len(), 1);
+        assert_eq!(result.sheet.sections[0].lines[1].lyrics, "Old words");
+        assert!(merge_sheets(None, &theirs, &theirs).conflicts.is_empty());
+    }
+
+    #[test]
+    fn test_import_edit_refetch() {
+        let dir = std::env::temp_dir().join(format!("cat4g-refetch-{}", std::process::id()));
+        let store = RevisionStore::new(&dir);
+        // Saving a fetched song keeps the sheet as fetched
+        store
+            .save("song", base().sections, RevisionSource::Import, None)
+            .unwrap();
+
+        let mut edited = base();
+        edited.sections[0].lines[0].chords[1].chord = "G7".to_string();
+        let mut fetched = base();
+        fetched.sections[0].lines[2].chords[0].chord = "Fmaj7".to_string();
+
+        let result = merge_refetch(&store, "song", &edited, fetched.clone()).unwrap();
+        assert!(result.has_base);
+        assert!(result.conflicts.is_empty());
+        assert_eq!(result.applied, 1);
+        let verse = &result.sheet.sections[0];
+        assert_eq!(chords(&verse.lines[0]), vec![("C", 0), ("G7", 6)]);
+        assert_eq!(chords(&verse.lines[2]), vec![("Fmaj7", 0)]);
+
+        // The next re-fetch merges against this one
+        let latest = store.latest_fetched("song").unwrap().unwrap();
+        assert_eq!(latest.source, RevisionSource::Refetch);
+        assert_eq!(latest.sections, fetched.sections);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,332 @@
+//! Title and artist clean-up, run on every parsed or imported sheet
+//!
+//! Parsers copy whatever the page holds: full-width spaces, 「」 around the
+//! title, a drama tie-in in parentheses, a " - U-FRET" suffix or, when a
+//! selector misses, the site's own heading. The normaliser tidies the
+//! strings, moves trailing subtitles and tie-ins into their own fields, and
+//! spells an artist the way the library already does when the two differ
+//! only in width, kana or spacing.
+
+use crate::parsers::FetchedChordSheet;
+use regex::Regex;
+use std::sync::LazyLock;
+
+/// Site names left at the end of a title or artist: "Lemon - U-FRET"
+static SITE_SUFFIX_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?i)(?:^|\s*[-|｜–—:：]\s*|\s+)(?:u-?fret|u-フレット|chordwiki|j-?total(?:\s*music!?)?|楽器\.me|gakki\.me|ultimate[- ]guitar(?:\.com)?)\s*$",
+    )
+    .unwrap()
+});
+
+/// "ギターコード/ウクレレコード" or "Chords by ..." after the title
+static CHORD_WORDS_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?i)(?:^|\s+|の)(?:(?:ギター|ウクレレ|ピアノ)?コード譜?\s*[/／・]?\s*)+$|\s+(?:chords|tabs?)(?:\s+by\s+.+)?$|^(?:chords|tabs?)$",
+    )
+    .unwrap()
+});
+
+/// Label some sites put before the artist
+static ARTIST_LABEL_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)^(?:歌|歌手|アーティスト|artist)\s*[:：]\s*").unwrap());
+
+/// Words marking a parenthetical as a tie-in rather than a subtitle
+static TIE_IN_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?i)主題歌|挿入歌|テーマ|イメージソング|応援ソング|タイアップ|ソング$|ドラマ|映画|アニメ|cm|ＣＭ|オープニング|エンディング|\b(?:op|ed|theme|opening|ending|soundtrack)\b",
+    )
+    .unwrap()
+});
+
+/// Half-width katakana from U+FF66 (ｦ) to U+FF9D (ﾝ)
+const HALFWIDTH_KANA: &str =
+    "ヲァィゥェォャュョッーアイウエオカキクケコサシスセソタチツテトナニヌネノハヒフヘホマミムメモヤユヨラリルレロワン";
+
+/// Fold width and kana so that "ｶﾞｰﾙ", "ガール" and "がーる" compare equal,
+/// and ASCII letters to lowercase
+pub fn fold(text: &str) -> String {
+    let halfwidth: Vec<char> = HALFWIDTH_KANA.chars().collect();
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        let code = c as u32;
+        let c = match code {
+            // Full-width ASCII and the ideographic space
+            0xFF01..=0xFF5E => char::from_u32(code - 0xFEE0).unwrap_or(c),
+            0x3000 => ' ',
+            0xFF61 => '。',
+            0xFF62 => '「',
+            0xFF63 => '」',
+            0xFF64 => '、',
+            0xFF65 => '・',
+            0xFF66..=0xFF9D => halfwidth[(code - 0xFF66) as usize],
+            // Voiced marks join the kana before them
+            0xFF9E | 0xFF9F => {
+                let mark = if code == 0xFF9E { 1 } else { 2 };
+                if let Some(last) = out.pop() {
+                    out.push(voice(last, mark));
+                }
+                continue;
+            }
+            _ => c,
+        };
+        out.extend(c.to_lowercase());
+    }
+    // Katakana to hiragana last, so converted half-width kana are folded too
+    out.chars()
+        .map(|c| match c as u32 {
+            code @ 0x30A1..=0x30F6 => char::from_u32(code - 0x60).unwrap_or(c),
+            _ => c,
+        })
+        .collect()
+}
+
+/// Katakana with a dakuten (1) or handakuten (2) added, where it takes one
+fn voice(kana: char, mark: u32) -> char {
+    let code = kana as u32;
+    let voiced = match code {
+        0x30A6 if mark == 1 => 0x30F4,
+        // カ..チ and ツ..ト alternate plain and voiced; ッ breaks the run
+        0x30AB..=0x30C2 if mark == 1 && (code - 0x30AB).is_multiple_of(2) => code + 1,
+        0x30C4..=0x30C9 if mark == 1 && (code - 0x30C4).is_multiple_of(2) => code + 1,
+        0x30CF..=0x30DD if (code - 0x30CF).is_multiple_of(3) => code + mark,
+        _ => code,
+    };
+    char::from_u32(voiced).unwrap_or(kana)
+}
+
+/// Folded letters and digits only, dropping spaces and punctuation
+pub fn letters(text: &str) -> String {
+    fold(text).chars().filter(|c| c.is_alphanumeric()).collect()
+}
+
+/// Collapse runs of any whitespace, full-width spaces included, to one space
+pub fn clean_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn strip_site_suffix(text: &str) -> String {
+    let mut text = text.to_string();
+    loop {
+        let stripped = CHORD_WORDS_RE.replace(&text, "");
+        let stripped = SITE_SUFFIX_RE.replace(&stripped, "").trim().to_string();
+        if stripped == text {
+            return text;
+        }
+        text = stripped;
+    }
+}
+
+fn closing_bracket(open: char) -> Option<char> {
+    match open {
+        '「' => Some('」'),
+        '『' => Some('』'),
+        '(' => Some(')'),
+        '（' => Some('）'),
+        '[' => Some(']'),
+        '【' => Some('】'),
+        '〔' => Some('〕'),
+        '〜' | '～' | '~' => Some(open),
+        _ => None,
+    }
+}
+
+/// A trailing bracketed group: the text before it and the group's content
+fn split_trailing_group(title: &str) -> Option<(&str, &str)> {
+    // 「」 stay: 君の名は「希望」 is the whole title
+    let close = title
+        .chars()
+        .next_back()
+        .filter(|c| matches!(c, ')' | '）' | ']' | '】' | '〕' | '〜' | '～' | '~'))?;
+    let mut depth = 0;
+    for (index, c) in title.char_indices().rev() {
+        if index + c.len_utf8() == title.len() {
+            continue;
+        }
+        if closing_bracket(c) == Some(close) {
+            if depth == 0 {
+                let head = title[..index].trim_end();
+                let content = title[index + c.len_utf8()..title.len() - close.len_utf8()].trim();
+                return (!head.is_empty() && !content.is_empty()).then_some((head, content));
+            }
+            depth -= 1;
+        } else if c == close {
+            depth += 1;
+        }
+    }
+    None
+}
+
+/// A title taken apart: the title itself, a subtitle and a tie-in
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct TitleParts {
+    pub title: Option<String>,
+    pub subtitle: Option<String>,
+    pub tie_in: Option<String>,
+}
+
+pub fn split_title(raw: &str) -> TitleParts {
+    let mut title = strip_site_suffix(&clean_whitespace(raw));
+
+    // 「栄光の架橋」(…) keeps the words inside the brackets
+    if let Some(open) = title.chars().next() {
+        if let Some(close) = closing_bracket(open).filter(|_| matches!(open, '「' | '『')) {
+            if let Some(end) = title.find(close) {
+                let inner = &title[open.len_utf8()..end];
+                let rest = &title[end + close.len_utf8()..];
+                title = format!("{} {}", inner.trim(), rest.trim())
+                    .trim()
+                    .to_string();
+            }
+        }
+    }
+
+    let mut subtitles = Vec::new();
+    let mut tie_ins = Vec::new();
+    while let Some((head, content)) = split_trailing_group(&title) {
+        if TIE_IN_RE.is_match(content) {
+            tie_ins.insert(0, content.to_string());
+        } else {
+            subtitles.insert(0, content.to_string());
+        }
+        title = head.to_string();
+    }
+
+    let join = |parts: Vec<String>| (!parts.is_empty()).then(|| parts.join(" / "));
+    TitleParts {
+        title: (!title.is_empty()).then_some(title),
+        subtitle: join(subtitles),
+        tie_in: join(tie_ins),
+    }
+}
+
+/// Clean an artist name and, when the library knows the same artist under
+/// another spelling, use that spelling
+pub fn normalize_artist(raw: &str, known_artists: &[String]) -> Option<String> {
+    let artist = clean_whitespace(raw);
+    let artist = strip_site_suffix(&ARTIST_LABEL_RE.replace(&artist, ""));
+    if artist.is_empty() {
+        return None;
+    }
+    let key = letters(&artist);
+    let known = known_artists
+        .iter()
+        .find(|name| !key.is_empty() && letters(name) == key);
+    Some(known.cloned().unwrap_or(artist))
+}
+
+/// Run the clean-up on a sheet straight from a parser or importer
+pub fn normalize_sheet(sheet: &mut FetchedChordSheet, known_artists: &[String]) {
+    if let Some(raw) = sheet.title.take() {
+        let parts = split_title(&raw);
+        sheet.title = parts.title;
+        sheet.subtitle = parts.subtitle.or(sheet.subtitle.take());
+        sheet.tie_in = parts.tie_in.or(sheet.tie_in.take());
+    }
+    sheet.artist = sheet
+        .artist
+        .take()
+        .and_then(|raw| normalize_artist(&raw, known_artists));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parts(title: Option<&str>, subtitle: Option<&str>, tie_in: Option<&str>) -> TitleParts {
+        TitleParts {
+            title: title.map(str::to_string),
+            subtitle: subtitle.map(str::to_string),
+            tie_in: tie_in.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_fold() {
+        assert_eq!(fold("ｶﾞｰﾙ　ＡＢＣ"), "がーる abc");
+        assert_eq!(fold("ﾊﾟﾋﾞﾌﾟｳﾞ"), "ぱびぷゔ");
+        assert_eq!(fold("ﾂﾞﾄﾞ"), "づど");
+        assert_eq!(letters("Mr.Children　"), "mrchildren");
+    }
+
+    #[test]
+    fn test_split_title() {
+        assert_eq!(
+            split_title("粉雪　(ドラマ「1リットルの涙」挿入歌)"),
+            parts(Some("粉雪"), None, Some("ドラマ「1リットルの涙」挿入歌"))
+        );
+        assert_eq!(
+            split_title("「栄光の架橋」（NHKアテネ五輪テーマソング）"),
+            parts(Some("栄光の架橋"), None, Some("NHKアテネ五輪テーマソング"))
+        );
+        assert_eq!(
+            split_title("Lemon - U-FRET"),
+            parts(Some("Lemon"), None, None)
+        );
+        assert_eq!(
+            split_title("夜に駆ける ギターコード/ウクレレコード - U-フレット"),
+            parts(Some("夜に駆ける"), None, None)
+        );
+        assert_eq!(
+            split_title("Wonderwall Chords by Oasis"),
+            parts(Some("Wonderwall"), None, None)
+        );
+        assert_eq!(
+            split_title("ハナミズキ 〜Live ver.〜 (映画「ハナミズキ」主題歌)"),
+            parts(
+                Some("ハナミズキ"),
+                Some("Live ver."),
+                Some("映画「ハナミズキ」主題歌")
+            )
+        );
+        assert_eq!(
+            split_title("Don't Stop Me Now (Remastered 2011)"),
+            parts(Some("Don't Stop Me Now"), Some("Remastered 2011"), None)
+        );
+        // A site heading grabbed instead of the title
+        assert_eq!(split_title("J-Total Music!"), parts(None, None, None));
+        // Brackets that are the whole title stay
+        assert_eq!(split_title("(未定)"), parts(Some("(未定)"), None, None));
+        assert_eq!(
+            split_title("君の名は「希望」"),
+            parts(Some("君の名は「希望」"), None, None)
+        );
+    }
+
+    #[test]
+    fn test_normalize_sheet() {
+        let known = vec!["米津玄師".to_string(), "Mr.Children".to_string()];
+        assert_eq!(
+            normalize_artist("米津 玄師", &known).as_deref(),
+            Some("米津玄師")
+        );
+        assert_eq!(
+            normalize_artist("歌：ＭＲ．ＣＨＩＬＤＲＥＮ", &known).as_deref(),
+            Some("Mr.Children")
+        );
+        assert_eq!(
+            normalize_artist("　あいみょん ", &known).as_deref(),
+            Some("あいみょん")
+        );
+        assert_eq!(normalize_artist(" - U-FRET", &known), None);
+
+        let mut sheet = FetchedChordSheet::new(String::new());
+        sheet.title = Some("Lemon（ドラマ「アンナチュラル」主題歌） - U-FRET".to_string());
+        sheet.artist = Some("米津　玄師".to_string());
+        normalize_sheet(&mut sheet, &known);
+        assert_eq!(sheet.title.as_deref(), Some("Lemon"));
+        assert_eq!(
+            sheet.tie_in.as_deref(),
+            Some("ドラマ「アンナチュラル」主題歌")
+        );
+        assert_eq!(sheet.artist.as_deref(), Some("米津玄師"));
+
+        // Running it again changes nothing
+        normalize_sheet(&mut sheet, &known);
+        assert_eq!(sheet.title.as_deref(), Some("Lemon"));
+        assert_eq!(
+            sheet.tie_in.as_deref(),
+            Some("ドラマ「アンナチュラル」主題歌")
+        );
+    }
+}
@@ -95,7 +95,7 @@ fn extract_artist(subtitle: &str) -> String {
         let after_prefix = &trimmed[pos + "歌：".len()..];
         // Take until the next delimiter (space, tab, 　, 作詞, 作曲)
         let end_pos = after_prefix
-            .find(|c| c == '　' || c == '\t' || c == ' ')
+            .find(['　', '\t', ' '])
             .or_else(|| after_prefix.find("作詞"))
             .or_else(|| after_prefix.find("作曲"))
             .unwrap_or(after_prefix.len());
@@ -419,7 +419,7 @@ mod tests {
         assert_eq!(result.artist, Some("レミオロメン".to_string()));
 
         // First section should be BPM comment, second should be instrument comment
-        assert!(result.sections.len() >= 1);
+        assert!(!result.sections.is_empty());
 
         // Check that we have chord data
         let mut found_chords = false;
@@ -1,5 +1,5 @@
 use crate::error::FetchError;
-use crate::parsers::{FetchedChord, FetchedChordSheet, FetchedLine, FetchedSection};
+use crate::parsers::{tab, FetchedChord, FetchedChordSheet, FetchedLine, FetchedSection};
 use regex::Regex;
 use scraper::{Html, Selector};
 use std::sync::LazyLock;
@@ -7,7 +7,7 @@ use std::sync::LazyLock;
 /// Validates chord names like C, Am, G7, F#m, Bb, Dm/F, Cmaj7
 static CHORD_PATTERN_RE: LazyLock<Regex> = LazyLock::new(|| {
     Regex::new(
-        r"^[A-G][#♯b♭]?(m|M|maj|min|dim|aug|sus[24]?|add\d+|\d+|7|9|11|13)?(/[A-G][#♯b♭]?)?$"
+        r"^[A-G][#♯b♭]?(m|M|maj|min|dim|aug|sus[24]?|add\d+)?\d*(/[A-G][#♯b♭]?)?$"
     ).unwrap()
 });
 
@@ -51,15 +51,21 @@ fn parse_jtotal_text(text: &str) -> Result<Vec<FetchedSection>, FetchError> {
     let mut sections = Vec::new();
     let mut current_section = FetchedSection::new("Intro");
 
-    for line in lines {
-        let line = line.trim();
+    for raw in lines {
+        let line = raw.trim();
         if line.is_empty() {
             continue;
         }
 
+        // Tab rows are kept as written, indentation included
+        if tab::is_tab_line(line) {
+            current_section.push_tab_line(raw);
+            continue;
+        }
+
         // Section detection
         if is_section_header(line) {
-            if !current_section.lines.is_empty() {
+            if !current_section.is_empty() {
                 sections.push(current_section);
             }
             current_section = FetchedSection::new(normalize_section_name(line));
@@ -84,7 +90,7 @@ fn parse_jtotal_text(text: &str) -> Result<Vec<FetchedSection>, FetchError> {
         }
     }
 
-    if !current_section.lines.is_empty() {
+    if !current_section.is_empty() {
         sections.push(current_section);
     }
 
@@ -129,7 +135,7 @@ fn is_chord_line(line: &str) -> bool {
     }
 
     let chord_count = tokens.iter().filter(|t| is_valid_chord(t)).count();
-    tokens.len() > 0 && (chord_count as f32 / tokens.len() as f32) > 0.5
+    (chord_count as f32 / tokens.len() as f32) > 0.5
 }
 
 fn is_valid_chord(token: &str) -> bool {
@@ -171,10 +177,22 @@ mod tests {
         assert!(!is_section_header("C  G  Am  F"));
     }
 
+    #[test]
+    fn test_is_valid_chord() {
+        assert!(is_valid_chord("G7"));
+        assert!(is_valid_chord("Cmaj7"));
+        assert!(is_valid_chord("Am7"));
+        assert!(is_valid_chord("Dsus4"));
+        assert!(is_valid_chord("Bbm7/F"));
+        assert!(!is_valid_chord("Cmaj7maj"));
+        assert!(!is_valid_chord("Hello"));
+    }
+
     #[test]
     fn test_is_chord_line() {
         assert!(is_chord_line("C  G  Am  F"));
         assert!(is_chord_line("D  A  E"));
+        assert!(is_chord_line("Cmaj7  Am7  Dm7  G7"));
         assert!(!is_chord_line("今日も空は青い"));
     }
 
@@ -225,4 +243,15 @@ mod tests {
         assert_eq!(chords[2].chord, "C");
         assert_eq!(chords[2].position, 20);
     }
+
+    #[test]
+    fn test_tab_stays_between_lyric_lines() {
+        let text = "[Aメロ]\nC    G\n歌詞一\ne|--0--3--|\nB|--1--0--|\nAm   F\n歌詞二";
+        let sections = parse_jtotal_text(text).unwrap();
+        let lines = &sections[0].lines;
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0].lyrics, "歌詞一");
+        assert_eq!(lines[1].tab.as_ref().unwrap().lines, ["e|--0--3--|", "B|--1--0--|"]);
+        assert_eq!(lines[2].lyrics, "歌詞二");
+    }
 }
@@ -2,32 +2,72 @@ pub mod ufret;
 pub mod chordwiki;
 pub mod jtotal;
 pub mod gakkime;
+pub mod tab;
+pub mod ultimate_guitar;
 
+use tab::TabBlock;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// `songs.difficulty` levels
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DifficultyLevel {
+    Beginner,
+    Intermediate,
+    Advanced,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct FetchedChordSheet {
     pub title: Option<String>,
+    /// "Live ver." and the like, split off the title by `normalize`
+    #[serde(default)]
+    pub subtitle: Option<String>,
+    /// Drama, film or CM the song was written for, split off the title
+    #[serde(default)]
+    pub tie_in: Option<String>,
     pub artist: Option<String>,
     pub key: Option<String>,
     pub capo: Option<i32>,
     pub sections: Vec<FetchedSection>,
     pub source_url: String,
+    /// Difficulty stated by the source site, when it gives one
+    #[serde(default)]
+    pub source_difficulty: Option<DifficultyLevel>,
+    /// Instrument tuning id (songs.tuning), when the source names one
+    #[serde(default)]
+    pub tuning: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct FetchedSection {
     pub name: String,
     pub lines: Vec<FetchedLine>,
+    /// Times the section is played (sections.repeat_count); None means once
+    #[serde(default)]
+    pub repeat_count: Option<u32>,
+    /// Tempo for this section only (sections.bpm_override)
+    #[serde(default)]
+    pub bpm_override: Option<u32>,
+    /// Meter change for this section, e.g. "2/4"
+    #[serde(default)]
+    pub time_signature: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct FetchedLine {
     pub lyrics: String,
     pub chords: Vec<FetchedChord>,
+    /// Bars the line spans (lines.measures); None means derive from the chords
+    #[serde(default)]
+    pub measures: Option<u32>,
+    /// Tablature (a solo or riff) standing in this line's place; such a line
+    /// has no lyrics or chords (lines.tab)
+    #[serde(default)]
+    pub tab: Option<TabBlock>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct FetchedChord {
     pub chord: String,
     pub position: i32,
@@ -37,11 +77,15 @@ impl FetchedChordSheet {
     pub fn new(source_url: String) -> Self {
         Self {
             title: None,
+            subtitle: None,
+            tie_in: None,
             artist: None,
             key: None,
             capo: None,
             sections: Vec::new(),
             source_url,
+            source_difficulty: None,
+            tuning: None,
         }
     }
 }
@@ -51,6 +95,26 @@ impl FetchedSection {
         Self {
             name: name.to_string(),
             lines: Vec::new(),
+            repeat_count: None,
+            bpm_override: None,
+            time_signature: None,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    /// Add a tab row where the section has got to: onto the tab block just
+    /// above, or as a new tab line
+    pub fn push_tab_line(&mut self, line: &str) {
+        match self.lines.last_mut().and_then(|last| last.tab.as_mut()) {
+            Some(tab) => tab.push_line(line),
+            None => {
+                let mut tab_line = FetchedLine::new("");
+                tab_line.tab = Some(TabBlock::from_lines(&[line]));
+                self.lines.push(tab_line);
+            }
         }
     }
 }
@@ -60,6 +124,8 @@ impl FetchedLine {
         Self {
             lyrics: lyrics.to_string(),
             chords: Vec::new(),
+            measures: None,
+            tab: None,
         }
     }
 
@@ -67,6 +133,8 @@ impl FetchedLine {
         Self {
             lyrics: lyrics.to_string(),
             chords,
+            measures: None,
+            tab: None,
         }
     }
 }
@@ -0,0 +1,310 @@
+//! ASCII tablature blocks
+//!
+//! Tab rows are kept exactly as written so they still line up in a monospace
+//! font, and are also read into a note list. Rows are listed as written, which
+//! for guitar tab is the 1st (highest) string first; a block may hold several
+//! systems, each starting again from the top string.
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::sync::LazyLock;
+
+/// Rows like "e|--3--5h7--|" or "|-0-2-|"
+static TAB_LINE_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^([A-Ga-g][#b]?)?\s*\|[-0-9hpbrx/\\~|() ]{4,}$").unwrap());
+
+/// Highest fret a shifted tab may use
+const MAX_FRET: i32 = 24;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TabTechnique {
+    HammerOn,
+    PullOff,
+    Bend,
+    Release,
+    SlideUp,
+    SlideDown,
+}
+
+impl TabTechnique {
+    fn from_symbol(c: char) -> Option<Self> {
+        match c {
+            'h' => Some(Self::HammerOn),
+            'p' => Some(Self::PullOff),
+            'b' => Some(Self::Bend),
+            'r' => Some(Self::Release),
+            '/' => Some(Self::SlideUp),
+            '\\' => Some(Self::SlideDown),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TabNote {
+    /// System within the block, from 0
+    pub system: usize,
+    /// String as written, 1 = top row
+    pub string: u8,
+    /// Character offset after the row's first bar line
+    pub column: usize,
+    pub fret: u8,
+    /// How this note is reached from the one before it ("5h7" marks the 7)
+    pub technique: Option<TabTechnique>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TabBlock {
+    /// Rows exactly as written
+    pub lines: Vec<String>,
+    /// Notes in playing order
+    pub notes: Vec<TabNote>,
+}
+
+pub fn is_tab_line(line: &str) -> bool {
+    let line = line.trim();
+    TAB_LINE_RE.is_match(line) && line.contains("--")
+}
+
+/// String label before the first bar line ("e", "D#"), if any
+fn label(line: &str) -> &str {
+    line.split('|').next().unwrap_or("").trim()
+}
+
+/// Byte offset where the row's content starts (after the first bar line)
+fn content_start(line: &str) -> usize {
+    line.find('|').map_or(0, |i| i + 1)
+}
+
+/// Split rows into systems: a repeated top label, or six unlabelled rows, starts a new one
+fn systems(lines: &[String]) -> Vec<&[String]> {
+    let mut systems = Vec::new();
+    let mut start = 0;
+    for index in 1..lines.len() {
+        let rows = index - start;
+        let first = label(&lines[start]);
+        let new_system = if first.is_empty() {
+            rows == 6
+        } else {
+            rows >= 4 && label(&lines[index]) == first
+        };
+        if new_system {
+            systems.push(&lines[start..index]);
+            start = index;
+        }
+    }
+    if start < lines.len() {
+        systems.push(&lines[start..]);
+    }
+    systems
+}
+
+/// Fret numbers in a row: (char column after the bar line, char length, fret)
+fn frets(line: &str) -> Vec<(usize, usize, u8)> {
+    let chars: Vec<char> = line[content_start(line)..].chars().collect();
+    let mut frets = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        if let Some(digit) = chars[i].to_digit(10) {
+            // Two digits make one fret when it is a playable one
+            let two = chars
+                .get(i + 1)
+                .and_then(|c| c.to_digit(10))
+                .map(|d| digit * 10 + d);
+            match two.filter(|&fret| fret <= MAX_FRET as u32) {
+                Some(fret) => {
+                    frets.push((i, 2, fret as u8));
+                    i += 2;
+                }
+                None => {
+                    frets.push((i, 1, digit as u8));
+                    i += 1;
+                }
+            }
+        } else {
+            i += 1;
+        }
+    }
+    frets
+}
+
+fn parse_notes(lines: &[String]) -> Vec<TabNote> {
+    let mut notes = Vec::new();
+    for (system, rows) in systems(lines).into_iter().enumerate() {
+        for (row, line) in rows.iter().enumerate() {
+            let chars: Vec<char> = line[content_start(line)..].chars().collect();
+            for (column, _, fret) in frets(line) {
+                let technique = column
+                    .checked_sub(1)
+                    .and_then(|before| TabTechnique::from_symbol(chars[before]))
+                    .filter(|_| column >= 2 && chars[column - 2].is_ascii_digit());
+                notes.push(TabNote {
+                    system,
+                    string: row as u8 + 1,
+                    column,
+                    fret,
+                    technique,
+                });
+            }
+        }
+    }
+    notes.sort_by_key(|n| (n.system, n.column, n.string));
+    notes
+}
+
+impl TabBlock {
+    pub fn from_lines<S: AsRef<str>>(lines: &[S]) -> Self {
+        let lines: Vec<String> = lines
+            .iter()
+            .map(|l| l.as_ref().trim_end().to_string())
+            .collect();
+        let notes = parse_notes(&lines);
+        Self { lines, notes }
+    }
+
+    /// Append a row as written and re-read the notes
+    pub fn push_line(&mut self, line: &str) {
+        self.lines.push(line.trim_end().to_string());
+        self.notes = parse_notes(&self.lines);
+    }
+
+    /// Move every note by `frets`, widening columns where a fret gains a digit
+    ///
+    /// None when a note would leave the fretboard.
+    pub fn shift(&self, frets_by: i32) -> Option<Self> {
+        if self
+            .notes
+            .iter()
+            .any(|n| !(0..=MAX_FRET).contains(&(n.fret as i32 + frets_by)))
+        {
+            return None;
+        }
+
+        let mut lines = Vec::with_capacity(self.lines.len());
+        for rows in systems(&self.lines) {
+            let tokens: Vec<Vec<(usize, usize, String)>> = rows
+                .iter()
+                .map(|line| {
+                    frets(line)
+                        .into_iter()
+                        .map(|(column, len, fret)| {
+                            (column, len, (fret as i32 + frets_by).to_string())
+                        })
+                        .collect()
+                })
+                .collect();
+
+            // Extra width needed at each column so every row stays aligned
+            let width = rows.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+            let mut extra = vec![0; width + 1];
+            for (column, len, text) in tokens.iter().flatten() {
+                extra[*column] = extra[*column].max(text.len().saturating_sub(*len));
+            }
+
+            for (line, tokens) in rows.iter().zip(&tokens) {
+                let start = content_start(line);
+                let mut out = line[..start].to_string();
+                let chars: Vec<char> = line[start..].chars().collect();
+                let mut column = 0;
+                while column < chars.len() {
+                    if let Some((_, len, text)) = tokens.iter().find(|t| t.0 == column) {
+                        let span: usize = len + extra[column..column + len].iter().sum::<usize>();
+                        out.push_str(text);
+                        out.push_str(&"-".repeat(span.saturating_sub(text.len())));
+                        column += len;
+                    } else {
+                        let c = chars[column];
+                        out.push(c);
+                        let fill = if c == ' ' { ' ' } else { '-' };
+                        out.extend(std::iter::repeat_n(fill, extra[column]));
+                        column += 1;
+                    }
+                }
+                lines.push(out);
+            }
+        }
+
+        Some(Self::from_lines(&lines))
+    }
+
+    /// Shift by `semitones`, an octave either way if that keeps it on the fretboard
+    pub fn transpose(&self, semitones: i32) -> Option<Self> {
+        let semitones = semitones.rem_euclid(12);
+        self.shift(semitones).or_else(|| self.shift(semitones - 12))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOLO: [&str; 6] = [
+        "e|-----------------|",
+        "B|-----------------|",
+        "G|-----------------|",
+        "D|-----2h4p2-------|",
+        "A|-2/5-------5b7---|",
+        "E|-----------------|",
+    ];
+
+    #[test]
+    fn test_is_tab_line() {
+        assert!(is_tab_line("e|-----------------|"));
+        assert!(is_tab_line("D#|--2--|"));
+        assert!(is_tab_line("|-0--2-|"));
+        assert!(!is_tab_line("| C G | Am F |"));
+        assert!(!is_tab_line("君の名前を"));
+    }
+
+    #[test]
+    fn test_notes_and_techniques() {
+        let block = TabBlock::from_lines(&SOLO);
+        assert_eq!(block.lines, SOLO);
+        let notes: Vec<_> = block
+            .notes
+            .iter()
+            .map(|n| (n.string, n.column, n.fret, n.technique))
+            .collect();
+        assert_eq!(
+            notes,
+            vec![
+                (5, 1, 2, None),
+                (5, 3, 5, Some(TabTechnique::SlideUp)),
+                (4, 5, 2, None),
+                (4, 7, 4, Some(TabTechnique::HammerOn)),
+                (4, 9, 2, Some(TabTechnique::PullOff)),
+                (5, 11, 5, None),
+                (5, 13, 7, Some(TabTechnique::Bend)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_systems() {
+        let mut lines = SOLO.to_vec();
+        lines.extend(SOLO);
+        let block = TabBlock::from_lines(&lines);
+        assert_eq!(block.notes.len(), 14);
+        assert_eq!(block.notes[7].system, 1);
+        assert_eq!(block.notes[7].string, 5);
+    }
+
+    #[test]
+    fn test_shift_keeps_alignment() {
+        let block = TabBlock::from_lines(&SOLO);
+        let shifted = block.shift(5).unwrap();
+        assert_eq!(shifted.lines[3], "D|------7h9p7---------|");
+        assert_eq!(shifted.lines[4], "A|-7/10-------10b12---|");
+        assert_eq!(shifted.lines[0], "e|--------------------|");
+        assert!(shifted
+            .lines
+            .iter()
+            .all(|l| l.len() == shifted.lines[0].len()));
+        assert_eq!(shifted.notes[1].fret, 10);
+        assert_eq!(shifted.notes[6].technique, Some(TabTechnique::Bend));
+
+        assert!(block.shift(-3).is_none());
+        assert_eq!(block.transpose(-1).unwrap().notes[0].fret, 13);
+    }
+}
@@ -1,5 +1,5 @@
 use crate::error::FetchError;
-use crate::parsers::{FetchedChord, FetchedChordSheet, FetchedLine, FetchedSection};
+use crate::parsers::{tab, FetchedChord, FetchedChordSheet, FetchedLine, FetchedSection};
 use regex::Regex;
 use scraper::{Html, Selector, ElementRef};
 use std::sync::LazyLock;
@@ -32,7 +32,7 @@ static DIGITS_RE: LazyLock<Regex> = LazyLock::new(|| {
 /// Validates chord names like C, Am, G7, F#m, Bb, Dm/F, Cmaj7
 static CHORD_PATTERN_RE: LazyLock<Regex> = LazyLock::new(|| {
     Regex::new(
-        r"^[A-G][#♯b♭]?(m|M|maj|min|dim|aug|sus[24]?|add\d+|\d+|7|9|11|13)?(/[A-G][#♯b♭]?)?$"
+        r"^[A-G][#♯b♭]?(m|M|maj|min|dim|aug|sus[24]?|add\d+)?\d*(/[A-G][#♯b♭]?)?$"
     ).unwrap()
 });
 
@@ -208,15 +208,21 @@ fn parse_ufret_lines(lines: &[String]) -> Result<Vec<FetchedSection>, FetchError
     let mut current_section = FetchedSection::new("Main");
     let chord_re = &*CHORD_MARKER_RE;
 
-    for line in lines {
-        let line = line.trim();
+    for raw in lines {
+        let line = raw.trim();
         if line.is_empty() {
             continue;
         }
 
+        // Tab rows are kept as written, indentation included
+        if tab::is_tab_line(line) {
+            current_section.push_tab_line(raw);
+            continue;
+        }
+
         // Check if this is a section marker
         if is_section_marker(line) {
-            if !current_section.lines.is_empty() {
+            if !current_section.is_empty() {
                 sections.push(current_section);
             }
             current_section = FetchedSection::new(line);
@@ -232,11 +238,7 @@ fn parse_ufret_lines(lines: &[String]) -> Result<Vec<FetchedSection>, FetchError
         // Check if this is a chord-only line (no lyrics between chords)
         let is_chord_only = {
             let without_chords = chord_re.replace_all(line, "");
-            let cleaned = without_chords
-                .replace('\u{3000}', "")
-                .replace(' ', "")
-                .replace('\r', "")
-                .replace('\n', "");
+            let cleaned = without_chords.replace(['\u{3000}', ' ', '\r', '\n'], "");
             cleaned.is_empty()
         };
 
@@ -302,7 +304,7 @@ fn parse_ufret_lines(lines: &[String]) -> Result<Vec<FetchedSection>, FetchError
         }
     }
 
-    if !current_section.lines.is_empty() {
+    if !current_section.is_empty() {
         sections.push(current_section);
     }
 
@@ -381,7 +383,7 @@ fn parse_ufret_chord_content(element: ElementRef) -> Result<Vec<FetchedSection>,
                 }
             }
 
-            if !current_section.lines.is_empty() {
+            if !current_section.is_empty() {
                 sections.push(current_section);
             }
 
@@ -458,15 +460,21 @@ fn parse_chord_text(text: &str) -> Result<Vec<FetchedSection>, FetchError> {
     let mut sections = Vec::new();
     let mut current_section = FetchedSection::new("Intro");
 
-    for line in lines {
-        let line = line.trim();
+    for raw in lines {
+        let line = raw.trim();
         if line.is_empty() {
             continue;
         }
 
+        // Tab rows are kept as written, indentation included
+        if tab::is_tab_line(line) {
+            current_section.push_tab_line(raw);
+            continue;
+        }
+
         // Section marker detection [Intro], [Verse], etc.
         if line.starts_with('[') && line.ends_with(']') {
-            if !current_section.lines.is_empty() {
+            if !current_section.is_empty() {
                 sections.push(current_section);
             }
             let section_name = &line[1..line.len() - 1];
@@ -476,7 +484,7 @@ fn parse_chord_text(text: &str) -> Result<Vec<FetchedSection>, FetchError> {
 
         // Section markers without brackets
         if is_section_marker(line) {
-            if !current_section.lines.is_empty() {
+            if !current_section.is_empty() {
                 sections.push(current_section);
             }
             current_section = FetchedSection::new(line);
@@ -501,7 +509,7 @@ fn parse_chord_text(text: &str) -> Result<Vec<FetchedSection>, FetchError> {
         }
     }
 
-    if !current_section.lines.is_empty() {
+    if !current_section.is_empty() {
         sections.push(current_section);
     }
 
@@ -531,7 +539,7 @@ fn is_chord_line(line: &str) -> bool {
         return false;
     }
     let chord_count = tokens.iter().filter(|t| is_valid_chord(t)).count();
-    tokens.len() > 0 && (chord_count as f32 / tokens.len() as f32) > 0.5
+    (chord_count as f32 / tokens.len() as f32) > 0.5
 }
 
 fn is_valid_chord(token: &str) -> bool {
@@ -565,6 +573,9 @@ mod tests {
         assert!(is_valid_chord("Bb"));
         assert!(is_valid_chord("Dm/F"));
         assert!(is_valid_chord("Cmaj7"));
+        assert!(is_valid_chord("Am7"));
+        assert!(is_valid_chord("Gsus4"));
+        assert!(is_valid_chord("Dm7/C"));
         assert!(!is_valid_chord("Hello"));
         assert!(!is_valid_chord("123"));
     }
@@ -573,6 +584,7 @@ mod tests {
     fn test_is_chord_line() {
         assert!(is_chord_line("C  G  Am  F"));
         assert!(is_chord_line("D   A   Bm   G"));
+        assert!(is_chord_line("Cmaj7  Am7  Dm7  G7"));
         assert!(!is_chord_line("この街で生きている"));
         assert!(!is_chord_line(""));
     }
@@ -0,0 +1,304 @@
+use crate::error::FetchError;
+use crate::parsers::{
+    tab, DifficultyLevel, FetchedChord, FetchedChordSheet, FetchedLine, FetchedSection,
+};
+use crate::theory::instrument::{Instrument, InstrumentFamily};
+use crate::theory::parse_note;
+use regex::Regex;
+use scraper::{Html, Selector};
+use serde_json::Value;
+use std::sync::LazyLock;
+
+/// Matches inline chord markup: [ch]Am7[/ch]
+static CHORD_TAG_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\[ch\](.*?)\[/ch\]").unwrap());
+
+/// Matches section headers on their own line: [Verse 1], [Chorus]
+static SECTION_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^\[([^\[\]]+)\]$").unwrap());
+
+pub fn parse(html: &str) -> Result<FetchedChordSheet, FetchError> {
+    let document = Html::parse_document(html);
+    let mut sheet = FetchedChordSheet::new(String::new());
+
+    // Everything lives in the JSON store the page hydrates from
+    let store_selector = Selector::parse(".js-store")
+        .map_err(|_| FetchError::ParseError("Invalid store selector".to_string()))?;
+    let content = document
+        .select(&store_selector)
+        .next()
+        .and_then(|el| el.value().attr("data-content"))
+        .ok_or_else(|| FetchError::ElementNotFound("js-store data not found".to_string()))?;
+    let store: Value = serde_json::from_str(content)
+        .map_err(|e| FetchError::ParseError(format!("Invalid js-store JSON: {}", e)))?;
+
+    let data = &store["store"]["page"]["data"];
+    let tab = &data["tab"];
+    let meta = &data["tab_view"]["meta"];
+
+    sheet.title = non_empty(&tab["song_name"]);
+    sheet.artist = non_empty(&tab["artist_name"]);
+    sheet.key = non_empty(&meta["tonality"]).or_else(|| non_empty(&tab["tonality_name"]));
+    sheet.capo = meta["capo"].as_i64().map(|capo| capo as i32);
+    sheet.tuning = meta["tuning"]["value"].as_str().and_then(tuning_id);
+    sheet.source_difficulty = non_empty(&meta["difficulty"])
+        .or_else(|| non_empty(&tab["difficulty"]))
+        .and_then(|text| difficulty_level(&text));
+
+    let text = data["tab_view"]["wiki_tab"]["content"]
+        .as_str()
+        .ok_or_else(|| FetchError::ElementNotFound("Tab content not found".to_string()))?;
+    sheet.sections = parse_content(text);
+
+    Ok(sheet)
+}
+
+fn non_empty(value: &Value) -> Option<String> {
+    value
+        .as_str()
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+}
+
+/// Map Ultimate Guitar's difficulty labels onto ours
+fn difficulty_level(text: &str) -> Option<DifficultyLevel> {
+    match text.to_lowercase().as_str() {
+        "absolute beginner" | "beginner" | "novice" => Some(DifficultyLevel::Beginner),
+        "intermediate" => Some(DifficultyLevel::Intermediate),
+        "upper intermediate" | "advanced" | "expert" => Some(DifficultyLevel::Advanced),
+        _ => None,
+    }
+}
+
+/// Match a tuning like "Eb Ab Db Gb Bb Eb" (lowest string first) to a guitar preset
+fn tuning_id(value: &str) -> Option<String> {
+    let notes: Vec<u8> = value
+        .split_whitespace()
+        .map(|name| {
+            parse_note(name)
+                .filter(|&(_, len)| len == name.len())
+                .map(|(pc, _)| pc)
+        })
+        .collect::<Option<_>>()?;
+
+    Instrument::presets()
+        .into_iter()
+        .filter(|preset| preset.family == InstrumentFamily::Guitar)
+        .find(|preset| {
+            preset.strings.len() == notes.len()
+                && preset
+                    .strings
+                    .iter()
+                    .rev()
+                    .zip(&notes)
+                    .all(|(&open, &pc)| open % 12 == pc)
+        })
+        .map(|preset| preset.id)
+}
+
+/// Strip [ch] markup, returning the plain text and each chord's character offset in it
+fn strip_chords(line: &str) -> (String, Vec<FetchedChord>) {
+    let mut text = String::new();
+    let mut chords = Vec::new();
+    let mut last = 0;
+
+    for caps in CHORD_TAG_RE.captures_iter(line) {
+        let tag = caps.get(0).unwrap();
+        text.push_str(&line[last..tag.start()]);
+        let name = caps[1].trim();
+        if !name.is_empty() {
+            chords.push(FetchedChord::new(name, text.chars().count() as i32));
+        }
+        // Keep the chord's width so later offsets match the monospace layout
+        text.push_str(&" ".repeat(caps[1].chars().count()));
+        last = tag.end();
+    }
+    text.push_str(&line[last..]);
+    (text, chords)
+}
+
+/// Pair a chord row with the lyric row under it; the lyric indent is dropped
+/// and the chords move left with it
+fn pair(chords: Vec<FetchedChord>, lyrics: &str) -> FetchedLine {
+    let lyrics = lyrics.trim_end();
+    let indent = lyrics.chars().take_while(|c| c.is_whitespace()).count() as i32;
+    let chords = chords
+        .into_iter()
+        .map(|c| FetchedChord::new(&c.chord, (c.position - indent).max(0)))
+        .collect();
+    FetchedLine::with_chords(lyrics.trim_start(), chords)
+}
+
+fn parse_content(content: &str) -> Vec<FetchedSection> {
+    let content = content
+        .replace("\r\n", "\n")
+        .replace("[tab]", "")
+        .replace("[/tab]", "");
+    let mut sections = Vec::new();
+    let mut current_section = FetchedSection::new("Intro");
+    // A chord row waiting for the lyric row below it
+    let mut pending: Option<Vec<FetchedChord>> = None;
+
+    for line in content.lines() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            if let Some(chords) = pending.take() {
+                current_section
+                    .lines
+                    .push(FetchedLine::with_chords("", chords));
+            }
+            continue;
+        }
+
+        if let Some(caps) = SECTION_RE
+            .captures(trimmed)
+            .filter(|_| !trimmed.starts_with("[ch]"))
+        {
+            if let Some(chords) = pending.take() {
+                current_section
+                    .lines
+                    .push(FetchedLine::with_chords("", chords));
+            }
+            if !current_section.is_empty() {
+                sections.push(current_section);
+            }
+            current_section = FetchedSection::new(caps[1].trim());
+            continue;
+        }
+
+        if tab::is_tab_line(trimmed) {
+            if let Some(chords) = pending.take() {
+                current_section
+                    .lines
+                    .push(FetchedLine::with_chords("", chords));
+            }
+            current_section.push_tab_line(line);
+            continue;
+        }
+
+        let (text, chords) = strip_chords(line);
+        if let Some(previous) = pending.take() {
+            if chords.is_empty() {
+                current_section.lines.push(pair(previous, &text));
+                continue;
+            }
+            current_section
+                .lines
+                .push(FetchedLine::with_chords("", previous));
+        }
+
+        if chords.is_empty() {
+            current_section.lines.push(FetchedLine::new(text.trim()));
+        } else if text.trim().is_empty() {
+            pending = Some(chords);
+        } else {
+            // Chords inline with text, e.g. "[ch]D[/ch] (x2)"
+            current_section
+                .lines
+                .push(FetchedLine::with_chords(text.trim_end(), chords));
+        }
+    }
+
+    if let Some(chords) = pending.take() {
+        current_section
+            .lines
+            .push(FetchedLine::with_chords("", chords));
+    }
+    if !current_section.is_empty() {
+        sections.push(current_section);
+    }
+
+    if sections.is_empty() {
+        sections.push(FetchedSection::new("Main"));
+    }
+
+    sections
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHORDS_PAGE: &str = include_str!("fixtures/ultimate_guitar_chords.html");
+    const TAB_PAGE: &str = include_str!("fixtures/ultimate_guitar_tab.html");
+
+    fn chords(line: &FetchedLine) -> Vec<(&str, i32)> {
+        line.chords
+            .iter()
+            .map(|c| (c.chord.as_str(), c.position))
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_metadata() {
+        let sheet = parse(CHORDS_PAGE).unwrap();
+        assert_eq!(sheet.title.as_deref(), Some("River Road"));
+        assert_eq!(sheet.artist.as_deref(), Some("The Example Band"));
+        assert_eq!(sheet.key.as_deref(), Some("G"));
+        assert_eq!(sheet.capo, Some(2));
+        assert_eq!(sheet.tuning.as_deref(), Some("standard"));
+        assert_eq!(sheet.source_difficulty, Some(DifficultyLevel::Beginner));
+
+        let sheet = parse(TAB_PAGE).unwrap();
+        assert_eq!(sheet.key, None);
+        assert_eq!(sheet.capo, None);
+        assert_eq!(sheet.tuning.as_deref(), Some("half-step-down"));
+        assert_eq!(sheet.source_difficulty, Some(DifficultyLevel::Intermediate));
+    }
+
+    #[test]
+    fn test_parse_sections_and_offsets() {
+        let sheet = parse(CHORDS_PAGE).unwrap();
+        let names: Vec<_> = sheet.sections.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["Intro", "Verse 1", "Chorus"]);
+
+        let intro = &sheet.sections[0].lines;
+        assert_eq!(intro.len(), 1);
+        assert_eq!(intro[0].lyrics, "");
+        assert_eq!(
+            chords(&intro[0]),
+            vec![("G", 0), ("D", 4), ("Em", 8), ("C", 13)]
+        );
+
+        // The lyric's two-space indent is dropped and the chords follow it
+        let verse = &sheet.sections[1].lines;
+        assert_eq!(verse[0].lyrics, "Walking down the river road");
+        assert_eq!(chords(&verse[0]), vec![("G", 0), ("D", 11)]);
+        assert_eq!(verse[1].lyrics, "Counting every stone I know");
+        assert_eq!(chords(&verse[1]), vec![("Em", 5), ("C", 18)]);
+
+        let chorus = &sheet.sections[2].lines;
+        assert_eq!(chorus[0].lyrics, "Oh, the morning light");
+        assert_eq!(chords(&chorus[0]), vec![("C", 0), ("G/B", 8), ("Am7", 15)]);
+        assert_eq!(chorus[1].lyrics, "        (x2)");
+        assert_eq!(chords(&chorus[1]), vec![("Dsus4", 0), ("D", 6)]);
+    }
+
+    #[test]
+    fn test_tab_section() {
+        let sheet = parse(TAB_PAGE).unwrap();
+        let solo = &sheet.sections[1];
+        assert_eq!(solo.name, "Solo");
+        assert_eq!(solo.lines.len(), 1);
+        let tab = solo.lines[0].tab.as_ref().unwrap();
+        assert_eq!(tab.lines.len(), 6);
+        assert_eq!(tab.lines[3], "D|-----2h4p2-------|-----------------|");
+        assert_eq!(tab.notes.len(), 8);
+        assert_eq!(tab.notes.last().map(|n| (n.string, n.fret)), Some((6, 0)));
+    }
+
+    #[test]
+    fn test_missing_store() {
+        assert!(matches!(
+            parse("<html><body><pre>C G</pre></body></html>"),
+            Err(FetchError::ElementNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_tuning_id() {
+        assert_eq!(tuning_id("D A D G B E").as_deref(), Some("drop-d"));
+        assert_eq!(tuning_id("D A D G A D").as_deref(), Some("dadgad"));
+        assert_eq!(tuning_id("C G C F A D"), None);
+        assert_eq!(tuning_id("E A D G B E X"), None);
+    }
+}
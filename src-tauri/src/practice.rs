@@ -0,0 +1,650 @@
+//! Practice log and scheduling
+//!
+//! Each practice session records the song, when it started and ended, the
+//! tempo reached and a 1-5 self-rating. The scheduler replays a song's
+//! sessions through an SM-2 style spaced-repetition model: a good rating
+//! stretches the gap before the next practice, a poor one brings the song back
+//! the next day. Songs in a setlist performed soon are pulled forward so every
+//! one of them has been played a few times before the gig.
+//!
+//! Sessions are stored in UTC; days and weeks are the player's, so callers
+//! pass their UTC offset and every session is dated in it.
+
+use crate::error::PracticeError;
+use crate::sync::parse_time;
+use chrono::{DateTime, Datelike, Days, FixedOffset, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+pub const MIN_RATING: u8 = 1;
+pub const MAX_RATING: u8 = 5;
+
+/// Rating assumed for a session the player did not rate
+const DEFAULT_RATING: u8 = 3;
+
+/// Ratings below this count as a lapse and restart the intervals
+const PASSING_RATING: u8 = 3;
+
+const START_EASE: f64 = 2.5;
+const MIN_EASE: f64 = 1.3;
+
+/// Setlists further away than this do not affect the schedule
+const UPCOMING_DAYS: i64 = 21;
+
+/// Songs never practised that are suggested per day, outside setlists
+const NEW_SONGS_PER_DAY: usize = 2;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PracticeSession {
+    pub id: String,
+    pub song_id: String,
+    /// RFC 3339 or SQLite datetime text
+    pub started_at: String,
+    pub ended_at: String,
+    /// Highest tempo played cleanly, in BPM
+    #[serde(default)]
+    pub tempo: Option<u32>,
+    /// Self-rating from 1 (struggled) to 5 (performance ready)
+    #[serde(default)]
+    pub rating: Option<u8>,
+    #[serde(default)]
+    pub notes: Option<String>,
+}
+
+impl PracticeSession {
+    /// Check a session and give it an id; the frontend stores the row in
+    /// practice_sessions and bumps songs.play_count
+    pub fn new(
+        song_id: String,
+        started_at: String,
+        ended_at: String,
+        tempo: Option<u32>,
+        rating: Option<u8>,
+        notes: Option<String>,
+    ) -> Result<Self, PracticeError> {
+        let start = parse_time(&started_at).ok_or_else(|| {
+            PracticeError::InvalidSession(format!("bad start time {}", started_at))
+        })?;
+        let end = parse_time(&ended_at)
+            .ok_or_else(|| PracticeError::InvalidSession(format!("bad end time {}", ended_at)))?;
+        if end < start {
+            return Err(PracticeError::InvalidSession(
+                "session ends before it starts".to_string(),
+            ));
+        }
+        if let Some(rating) = rating.filter(|r| !(MIN_RATING..=MAX_RATING).contains(r)) {
+            return Err(PracticeError::InvalidSession(format!(
+                "rating {} is not between {} and {}",
+                rating, MIN_RATING, MAX_RATING
+            )));
+        }
+        Ok(Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            song_id,
+            started_at,
+            ended_at,
+            tempo: tempo.filter(|&t| t > 0),
+            rating,
+            notes: notes.filter(|n| !n.trim().is_empty()),
+        })
+    }
+
+    fn start(&self) -> Option<DateTime<Utc>> {
+        parse_time(&self.started_at)
+    }
+
+    /// The day the session started, where the player is
+    fn date(&self, offset: FixedOffset) -> Option<NaiveDate> {
+        self.start()
+            .map(|start| start.with_timezone(&offset).date_naive())
+    }
+
+    /// Practice time; 0 when the times cannot be read
+    pub fn seconds(&self) -> i64 {
+        match (self.start(), parse_time(&self.ended_at)) {
+            (Some(start), Some(end)) => (end - start).num_seconds().max(0),
+            _ => 0,
+        }
+    }
+}
+
+/// A song as the scheduler needs it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PracticeSong {
+    pub id: String,
+    #[serde(default)]
+    pub title: Option<String>,
+    /// Performance tempo (songs.bpm), to compare practice tempos against
+    #[serde(default)]
+    pub bpm: Option<u32>,
+}
+
+/// A playlist with a performance date
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpcomingSetlist {
+    pub playlist_id: String,
+    #[serde(default)]
+    pub name: Option<String>,
+    /// playlists.performance_date, "YYYY-MM-DD"
+    pub date: String,
+    pub song_ids: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SuggestionReason {
+    /// In a setlist performed soon
+    Performance,
+    /// Past the day the schedule set for it
+    Overdue,
+    Due,
+    /// Never practised
+    New,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PracticeSuggestion {
+    pub song_id: String,
+    pub title: Option<String>,
+    pub reason: SuggestionReason,
+    /// Higher is more urgent
+    pub score: f64,
+    pub last_practiced: Option<String>,
+    pub due: Option<String>,
+    pub last_rating: Option<u8>,
+    pub best_tempo: Option<u32>,
+    pub target_tempo: Option<u32>,
+    /// Date and name of the next setlist the song is in
+    pub performance_date: Option<String>,
+    pub performance: Option<String>,
+}
+
+/// A song's spaced-repetition state after replaying its sessions
+#[derive(Debug, Clone)]
+struct Review {
+    last: NaiveDate,
+    /// Days until the next practice
+    interval: f64,
+    ease: f64,
+    /// Passing days in a row
+    streak: u32,
+    last_rating: Option<u8>,
+    best_tempo: Option<u32>,
+}
+
+impl Review {
+    fn new(last: NaiveDate) -> Self {
+        Self {
+            last,
+            interval: 1.0,
+            ease: START_EASE,
+            streak: 0,
+            last_rating: None,
+            best_tempo: None,
+        }
+    }
+
+    /// One day of practice, rated by its last rated session
+    fn practise(&mut self, date: NaiveDate, rating: u8) {
+        self.last = date;
+        if rating < PASSING_RATING {
+            self.streak = 0;
+            self.interval = 1.0;
+        } else {
+            self.streak += 1;
+            self.interval = match self.streak {
+                1 => 1.0,
+                2 => 3.0,
+                _ => self.interval * self.ease,
+            };
+        }
+        let miss = (MAX_RATING - rating) as f64;
+        self.ease = (self.ease + 0.1 - miss * (0.08 + miss * 0.02)).max(MIN_EASE);
+    }
+
+    fn due(&self) -> NaiveDate {
+        self.last + Days::new(self.interval.round().max(1.0) as u64)
+    }
+}
+
+/// Replay each song's sessions, a day at a time
+fn reviews(sessions: &[PracticeSession], offset: FixedOffset) -> HashMap<&str, Review> {
+    let mut days: BTreeMap<(&str, NaiveDate), Vec<&PracticeSession>> = BTreeMap::new();
+    for session in sessions {
+        if let Some(date) = session.date(offset) {
+            days.entry((session.song_id.as_str(), date))
+                .or_default()
+                .push(session);
+        }
+    }
+
+    let mut reviews: HashMap<&str, Review> = HashMap::new();
+    for ((song_id, date), mut day) in days {
+        day.sort_by_key(|s| s.start());
+        let rating = day.iter().rev().find_map(|s| s.rating);
+        let review = reviews.entry(song_id).or_insert_with(|| Review::new(date));
+        review.practise(date, rating.unwrap_or(DEFAULT_RATING));
+        review.last_rating = rating.or(review.last_rating);
+        review.best_tempo = review
+            .best_tempo
+            .max(day.iter().filter_map(|s| s.tempo).max());
+    }
+    reviews
+}
+
+pub fn parse_date(text: &str) -> Result<NaiveDate, PracticeError> {
+    NaiveDate::parse_from_str(text.trim(), "%Y-%m-%d")
+        .map_err(|_| PracticeError::InvalidDate(text.to_string()))
+}
+
+/// A UTC offset in minutes east of UTC (540 for Japan); JavaScript's
+/// `getTimezoneOffset()` is the same negated
+pub fn utc_offset(minutes: i32) -> Result<FixedOffset, PracticeError> {
+    minutes
+        .checked_mul(60)
+        .and_then(FixedOffset::east_opt)
+        .ok_or(PracticeError::UtcOffset(minutes))
+}
+
+/// Today at `offset`, for callers that do not pass the local date
+pub fn today(offset: FixedOffset) -> NaiveDate {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default();
+    DateTime::from_timestamp(now, 0)
+        .unwrap_or_default()
+        .with_timezone(&offset)
+        .date_naive()
+}
+
+/// Songs to practise on `today`, most urgent first, with sessions dated at
+/// `offset`. Songs already practised today are left out.
+pub fn suggest(
+    songs: &[PracticeSong],
+    sessions: &[PracticeSession],
+    setlists: &[UpcomingSetlist],
+    today: NaiveDate,
+    offset: FixedOffset,
+    limit: usize,
+) -> Vec<PracticeSuggestion> {
+    let reviews = reviews(sessions, offset);
+
+    // The nearest performance of each song within the horizon
+    let mut performances: HashMap<&str, (i64, &UpcomingSetlist)> = HashMap::new();
+    for setlist in setlists {
+        let Ok(date) = parse_date(&setlist.date) else {
+            continue;
+        };
+        let days = (date - today).num_days();
+        if !(0..=UPCOMING_DAYS).contains(&days) {
+            continue;
+        }
+        for song_id in &setlist.song_ids {
+            let nearest = performances.entry(song_id).or_insert((days, setlist));
+            if days < nearest.0 {
+                *nearest = (days, setlist);
+            }
+        }
+    }
+
+    let mut suggestions = Vec::new();
+    let mut new_songs = Vec::new();
+    for song in songs {
+        let review = reviews.get(song.id.as_str());
+        let performance = performances.get(song.id.as_str());
+        // Urgency of a coming gig: 1 on the day, near 0 at the horizon
+        let urgency = performance.map(|(days, _)| 1.0 - *days as f64 / (UPCOMING_DAYS + 1) as f64);
+
+        let (reason, score) = match review {
+            Some(review) if review.last >= today => continue,
+            Some(review) => {
+                let elapsed = (today - review.last).num_days() as f64;
+                let mut interval = review.interval.round().max(1.0);
+                if let Some((days, _)) = performance {
+                    // Practise at least every third of the time left
+                    interval = interval.min((*days as f64 / 3.0).floor().max(1.0));
+                }
+                let ratio = elapsed / interval;
+                if ratio < 1.0 {
+                    continue;
+                }
+                let reason = if performance.is_some() && review.due() > today {
+                    SuggestionReason::Performance
+                } else if review.due() < today {
+                    SuggestionReason::Overdue
+                } else {
+                    SuggestionReason::Due
+                };
+                (reason, ratio + urgency.unwrap_or(0.0) * 2.0)
+            }
+            None => match urgency {
+                Some(urgency) => (SuggestionReason::Performance, 2.0 + urgency * 2.0),
+                None => {
+                    new_songs.push(song);
+                    continue;
+                }
+            },
+        };
+        suggestions.push(suggestion(song, review, performance, reason, score));
+    }
+
+    // A couple of new songs a day, after everything due
+    suggestions.extend(
+        new_songs
+            .into_iter()
+            .take(NEW_SONGS_PER_DAY)
+            .map(|song| suggestion(song, None, None, SuggestionReason::New, 0.5)),
+    );
+    suggestions.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then_with(|| a.title.cmp(&b.title))
+    });
+    suggestions.truncate(limit);
+    suggestions
+}
+
+fn suggestion(
+    song: &PracticeSong,
+    review: Option<&Review>,
+    performance: Option<&(i64, &UpcomingSetlist)>,
+    reason: SuggestionReason,
+    score: f64,
+) -> PracticeSuggestion {
+    PracticeSuggestion {
+        song_id: song.id.clone(),
+        title: song.title.clone(),
+        reason,
+        score,
+        last_practiced: review.map(|r| r.last.to_string()),
+        due: review.map(|r| r.due().to_string()),
+        last_rating: review.and_then(|r| r.last_rating),
+        best_tempo: review.and_then(|r| r.best_tempo),
+        target_tempo: song.bpm,
+        performance_date: performance.map(|(_, setlist)| setlist.date.clone()),
+        performance: performance.and_then(|(_, setlist)| setlist.name.clone()),
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WeeklyPractice {
+    pub song_id: String,
+    /// Monday of the week, "YYYY-MM-DD"
+    pub week: String,
+    pub seconds: i64,
+    pub sessions: usize,
+}
+
+/// Practice time per song per week at `offset`, oldest week first
+pub fn weekly_time(
+    sessions: &[PracticeSession],
+    since: Option<NaiveDate>,
+    offset: FixedOffset,
+) -> Vec<WeeklyPractice> {
+    let mut weeks: BTreeMap<(NaiveDate, &str), (i64, usize)> = BTreeMap::new();
+    for session in sessions {
+        let Some(date) = session
+            .date(offset)
+            .filter(|d| since.is_none_or(|since| *d >= since))
+        else {
+            continue;
+        };
+        let monday = date - Days::new(date.weekday().num_days_from_monday() as u64);
+        let week = weeks.entry((monday, &session.song_id)).or_default();
+        week.0 += session.seconds();
+        week.1 += 1;
+    }
+    weeks
+        .into_iter()
+        .map(|((week, song_id), (seconds, sessions))| WeeklyPractice {
+            song_id: song_id.to_string(),
+            week: week.to_string(),
+            seconds,
+            sessions,
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TempoPoint {
+    pub date: String,
+    /// Best tempo that day
+    pub tempo: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TempoProgress {
+    pub song_id: String,
+    pub points: Vec<TempoPoint>,
+    pub first: u32,
+    pub best: u32,
+    pub latest: u32,
+}
+
+/// Tempo reached per practice day at `offset` for every song with recorded
+/// tempos
+pub fn tempo_progress(sessions: &[PracticeSession], offset: FixedOffset) -> Vec<TempoProgress> {
+    let mut days: BTreeMap<&str, BTreeMap<NaiveDate, u32>> = BTreeMap::new();
+    for session in sessions {
+        if let (Some(date), Some(tempo)) = (session.date(offset), session.tempo) {
+            let best = days
+                .entry(&session.song_id)
+                .or_default()
+                .entry(date)
+                .or_default();
+            *best = (*best).max(tempo);
+        }
+    }
+    days.into_iter()
+        .map(|(song_id, days)| {
+            let tempos: Vec<u32> = days.values().copied().collect();
+            TempoProgress {
+                song_id: song_id.to_string(),
+                first: tempos[0],
+                best: tempos.iter().copied().max().unwrap_or_default(),
+                latest: tempos[tempos.len() - 1],
+                points: days
+                    .into_iter()
+                    .map(|(date, tempo)| TempoPoint {
+                        date: date.to_string(),
+                        tempo,
+                    })
+                    .collect(),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(
+        song_id: &str,
+        day: &str,
+        minutes: u32,
+        tempo: Option<u32>,
+        rating: Option<u8>,
+    ) -> PracticeSession {
+        PracticeSession::new(
+            song_id.to_string(),
+            format!("{}T19:00:00Z", day),
+            format!("{}T19:{:02}:00Z", day, minutes),
+            tempo,
+            rating,
+            None,
+        )
+        .unwrap()
+    }
+
+    fn song(id: &str) -> PracticeSong {
+        PracticeSong {
+            id: id.to_string(),
+            title: Some(id.to_string()),
+            bpm: Some(120),
+        }
+    }
+
+    fn date(text: &str) -> NaiveDate {
+        parse_date(text).unwrap()
+    }
+
+    fn utc() -> FixedOffset {
+        utc_offset(0).unwrap()
+    }
+
+    #[test]
+    fn test_session_validation() {
+        let ok = session("s1", "2026-03-02", 30, Some(100), Some(4));
+        assert_eq!(ok.seconds(), 1800);
+        let backwards = PracticeSession::new(
+            "s1".to_string(),
+            "2026-03-02 20:00:00".to_string(),
+            "2026-03-02 19:00:00".to_string(),
+            None,
+            None,
+            None,
+        );
+        assert!(backwards.is_err());
+        let rating = PracticeSession::new(
+            "s1".to_string(),
+            "2026-03-02 19:00:00".to_string(),
+            "2026-03-02 20:00:00".to_string(),
+            None,
+            Some(6),
+            None,
+        );
+        assert!(rating.is_err());
+    }
+
+    #[test]
+    fn test_intervals_grow_and_reset() {
+        let good = [
+            session("s1", "2026-03-01", 20, None, Some(5)),
+            session("s1", "2026-03-02", 20, None, Some(5)),
+            session("s1", "2026-03-05", 20, None, Some(5)),
+        ];
+        let review = &reviews(&good, utc())["s1"];
+        assert_eq!(review.streak, 3);
+        assert!(review.interval > 7.0);
+        assert!(review.due() > date("2026-03-12"));
+
+        let mut lapse = good.to_vec();
+        lapse.push(session("s1", "2026-03-13", 20, None, Some(1)));
+        let review = &reviews(&lapse, utc())["s1"];
+        assert_eq!(review.interval, 1.0);
+        assert_eq!(review.due(), date("2026-03-14"));
+        assert!(review.ease < START_EASE);
+    }
+
+    #[test]
+    fn test_suggestions() {
+        let songs: Vec<_> = ["due", "rested", "gig", "today", "new1", "new2", "new3"]
+            .into_iter()
+            .map(song)
+            .collect();
+        let sessions = [
+            session("due", "2026-03-01", 20, None, Some(2)),
+            // Just practised well: not due for days
+            session("rested", "2026-03-08", 20, None, Some(5)),
+            session("rested", "2026-03-09", 20, None, Some(5)),
+            session("gig", "2026-03-08", 20, None, Some(5)),
+            session("gig", "2026-03-09", 20, None, Some(5)),
+            session("today", "2026-03-10", 20, None, Some(1)),
+        ];
+        let setlists = [UpcomingSetlist {
+            playlist_id: "p1".to_string(),
+            name: Some("Live".to_string()),
+            date: "2026-03-13".to_string(),
+            song_ids: vec!["gig".to_string()],
+        }];
+        let suggestions = suggest(&songs, &sessions, &setlists, date("2026-03-10"), utc(), 10);
+        let picked: Vec<_> = suggestions
+            .iter()
+            .map(|s| (s.song_id.as_str(), s.reason))
+            .collect();
+        assert_eq!(
+            picked,
+            vec![
+                ("due", SuggestionReason::Overdue),
+                ("gig", SuggestionReason::Performance),
+                ("new1", SuggestionReason::New),
+                ("new2", SuggestionReason::New),
+            ]
+        );
+        assert_eq!(
+            suggestions[1].performance_date.as_deref(),
+            Some("2026-03-13")
+        );
+        assert_eq!(suggestions[0].last_rating, Some(2));
+    }
+
+    #[test]
+    fn test_stats() {
+        let sessions = [
+            session("s1", "2026-03-02", 30, Some(90), None),
+            session("s1", "2026-03-02", 10, Some(96), None),
+            session("s1", "2026-03-08", 20, Some(100), None),
+            session("s1", "2026-03-09", 20, Some(98), None),
+            session("s2", "2026-03-09", 15, None, None),
+        ];
+        let weeks = weekly_time(&sessions, None, utc());
+        let rows: Vec<_> = weeks
+            .iter()
+            .map(|w| (w.week.as_str(), w.song_id.as_str(), w.seconds, w.sessions))
+            .collect();
+        assert_eq!(
+            rows,
+            vec![
+                ("2026-03-02", "s1", 3600, 3),
+                ("2026-03-09", "s1", 1200, 1),
+                ("2026-03-09", "s2", 900, 1),
+            ]
+        );
+        assert_eq!(
+            weekly_time(&sessions, Some(date("2026-03-09")), utc()).len(),
+            2
+        );
+
+        let progress = tempo_progress(&sessions, utc());
+        assert_eq!(progress.len(), 1);
+        assert_eq!(progress[0].points.len(), 3);
+        assert_eq!(
+            (progress[0].first, progress[0].best, progress[0].latest),
+            (96, 100, 98)
+        );
+    }
+
+    #[test]
+    fn test_sessions_dated_at_local_offset() {
+        let jst = utc_offset(9 * 60).unwrap();
+        let at = |start: &str, end: &str| {
+            PracticeSession::new(
+                "s1".to_string(),
+                start.to_string(),
+                end.to_string(),
+                Some(100),
+                Some(4),
+                None,
+            )
+            .unwrap()
+        };
+        // Sunday morning in Tokyo is still Saturday in UTC
+        let sunday = [at("2026-10-18T08:00:00+09:00", "2026-10-18T08:30:00+09:00")];
+        let today = date("2026-10-18");
+        assert!(suggest(&[song("s1")], &sunday, &[], today, jst, 10).is_empty());
+        let utc_suggestions = suggest(&[song("s1")], &sunday, &[], today, utc(), 10);
+        assert_eq!(
+            utc_suggestions[0].last_practiced.as_deref(),
+            Some("2026-10-17")
+        );
+        assert_eq!(tempo_progress(&sunday, jst)[0].points[0].date, "2026-10-18");
+
+        // Monday morning in Tokyo starts a new week
+        let monday = [at("2026-10-19T08:00:00+09:00", "2026-10-19T08:20:00+09:00")];
+        assert_eq!(weekly_time(&monday, None, jst)[0].week, "2026-10-19");
+        assert_eq!(weekly_time(&monday, None, utc())[0].week, "2026-10-12");
+        assert!(utc_offset(24 * 60).is_err());
+    }
+}
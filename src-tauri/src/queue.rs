@@ -0,0 +1,690 @@
+//! Persistent queue for operations that need the network
+//!
+//! Fetching a sheet, re-fetching a song and pushing a sync are queued rather
+//! than run from the UI, so they survive a venue without Wi-Fi and app
+//! restarts. A sync is queued as an intent only, and a re-fetch as the song's
+//! id; the session, the changes and the edited song are read from the app
+//! when the operation runs. A worker runs due operations oldest first. A
+//! failure that looks like lost connectivity (connection refused, timeout, 5xx, 429) is retried
+//! with exponential backoff and marks the queue offline; any other failure
+//! parks the operation as failed until the user retries or drops it.
+//! Finished operations keep their result until the UI acknowledges them.
+
+use crate::error::{FetchError, QueueError, RevisionError, SyncError};
+use crate::parsers::FetchedChordSheet;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tauri_plugin_http::reqwest;
+use tokio::sync::{oneshot, Notify};
+
+/// First retry delay; doubled on every further failure
+const BACKOFF_BASE_MS: u64 = 5_000;
+
+/// Longest wait between retries
+const BACKOFF_MAX_MS: u64 = 10 * 60 * 1000;
+
+/// How long the worker sleeps when nothing is scheduled
+const IDLE_MS: u64 = 60 * 60 * 1000;
+
+/// How long a re-fetch waits for the app to hand over the edited song
+const INPUT_TIMEOUT_SECS: u64 = 30;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Operation {
+    FetchUrl {
+        url: String,
+        #[serde(default)]
+        known_artists: Vec<String>,
+    },
+    /// The edited song is read when it runs, so edits made while it waits
+    /// are merged against rather than overwritten
+    RefreshSong { song_id: String },
+    /// Only the intent: the session and the changes are read when it runs
+    SyncPush {},
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EntryStatus {
+    Pending,
+    Completed,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueueEntry {
+    pub id: String,
+    pub operation: Operation,
+    pub status: EntryStatus,
+    /// Failed attempts so far
+    pub attempts: u32,
+    /// Unix time in milliseconds
+    pub created_at: u64,
+    pub next_attempt_at: u64,
+    #[serde(default)]
+    pub last_error: Option<String>,
+    /// The operation's output once completed (sheet, merge or sync result)
+    #[serde(default)]
+    pub result: Option<Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueueStatus {
+    /// False from a failure that looked like lost connectivity until the
+    /// next success
+    pub online: bool,
+    pub pending: usize,
+    pub failed: usize,
+    pub entries: Vec<QueueEntry>,
+    /// Where a queue file that could not be read at startup was moved; its
+    /// operations are not in `entries`
+    pub set_aside: Option<String>,
+}
+
+/// Why an operation failed, and whether trying again later may help
+#[derive(Debug, Clone)]
+pub struct OperationError {
+    pub message: String,
+    pub transient: bool,
+}
+
+impl OperationError {
+    pub fn permanent(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            transient: false,
+        }
+    }
+}
+
+fn transient_status(status: u16) -> bool {
+    status == 408 || status == 429 || status >= 500
+}
+
+fn transient_http(error: &reqwest::Error) -> bool {
+    error.is_connect()
+        || error.is_timeout()
+        || error.is_request()
+        || error.status().is_some_and(|s| transient_status(s.as_u16()))
+}
+
+impl From<FetchError> for OperationError {
+    fn from(error: FetchError) -> Self {
+        let transient = match &error {
+            FetchError::HttpError(e) => transient_http(e),
+            FetchError::Timeout(_) => true,
+            _ => false,
+        };
+        Self {
+            message: error.to_string(),
+            transient,
+        }
+    }
+}
+
+impl From<SyncError> for OperationError {
+    fn from(error: SyncError) -> Self {
+        let transient = match &error {
+            SyncError::Http(e) => transient_http(e),
+            SyncError::Server { status, .. } => transient_status(*status),
+            SyncError::NoInput => true,
+            SyncError::Invalid(_) | SyncError::SignedOut => false,
+        };
+        Self {
+            message: error.to_string(),
+            transient,
+        }
+    }
+}
+
+impl From<RevisionError> for OperationError {
+    fn from(error: RevisionError) -> Self {
+        Self::permanent(error.to_string())
+    }
+}
+
+impl From<String> for OperationError {
+    fn from(message: String) -> Self {
+        Self::permanent(message)
+    }
+}
+
+impl From<OperationError> for String {
+    fn from(error: OperationError) -> Self {
+        error.message
+    }
+}
+
+/// Hands a queued re-fetch the edited song when it runs
+#[derive(Default)]
+pub struct SongInputs {
+    waiting: Mutex<Option<(String, oneshot::Sender<Option<FetchedChordSheet>>)>>,
+}
+
+impl SongInputs {
+    /// Ask the app for the song with `ask` and wait for its answer, which is
+    /// empty when the song has been deleted
+    pub async fn request(
+        &self,
+        song_id: &str,
+        ask: impl FnOnce(),
+    ) -> Result<FetchedChordSheet, OperationError> {
+        let (sender, receiver) = oneshot::channel();
+        *self.waiting.lock().unwrap_or_else(|e| e.into_inner()) =
+            Some((song_id.to_string(), sender));
+        ask();
+        let answer = tokio::time::timeout(Duration::from_secs(INPUT_TIMEOUT_SECS), receiver).await;
+        self.waiting
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take();
+        match answer {
+            Ok(Ok(Some(current))) => Ok(current),
+            Ok(Ok(None)) => Err(OperationError::permanent(format!(
+                "Song {} no longer exists",
+                song_id
+            ))),
+            Ok(Err(_)) | Err(_) => Err(OperationError {
+                message: "The app did not hand over the song to refresh in time".to_string(),
+                transient: true,
+            }),
+        }
+    }
+
+    /// Answer the waiting request for `song_id`; false when none is waiting
+    pub fn provide(&self, song_id: &str, current: Option<FetchedChordSheet>) -> bool {
+        let mut waiting = self.waiting.lock().unwrap_or_else(|e| e.into_inner());
+        if waiting.as_ref().is_none_or(|(id, _)| id != song_id) {
+            return false;
+        }
+        waiting
+            .take()
+            .is_some_and(|(_, sender)| sender.send(current).is_ok())
+    }
+}
+
+/// Delay before the attempt after `attempts` failures
+pub fn backoff(attempts: u32) -> Duration {
+    let factor = 1u64 << attempts.saturating_sub(1).min(20);
+    Duration::from_millis((BACKOFF_BASE_MS * factor).min(BACKOFF_MAX_MS))
+}
+
+pub fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct QueueState {
+    entries: Vec<QueueEntry>,
+    #[serde(skip)]
+    offline: bool,
+    #[serde(skip)]
+    set_aside: Option<String>,
+}
+
+/// The queue and its file; shared between commands and the worker
+pub struct OperationQueue {
+    path: PathBuf,
+    state: Mutex<QueueState>,
+    wake: Notify,
+}
+
+impl OperationQueue {
+    /// Load the queue kept at `path`, or start an empty one; a file that is
+    /// not a queue is set aside, and reported in the status, rather than
+    /// keeping the app from starting
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self, QueueError> {
+        let path = path.into();
+        let state = match std::fs::read(&path) {
+            Ok(data) => match serde_json::from_slice(&data) {
+                Ok(state) => state,
+                Err(_) => {
+                    let aside = path.with_extension(format!("json.corrupt-{}", now_ms()));
+                    std::fs::rename(&path, &aside)?;
+                    QueueState {
+                        set_aside: Some(aside.to_string_lossy().into_owned()),
+                        ..QueueState::default()
+                    }
+                }
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => QueueState::default(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Self {
+            path,
+            state: Mutex::new(state),
+            wake: Notify::new(),
+        })
+    }
+
+    /// Write through a temporary file so a crash never loses the queue
+    fn store(&self, state: &QueueState) -> Result<(), QueueError> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let temp = self.path.with_extension("json.tmp");
+        let data = serde_json::to_vec(state)
+            .map_err(|e| QueueError::Invalid(format!("{}: {}", self.path.display(), e)))?;
+        std::fs::write(&temp, data)?;
+        std::fs::rename(&temp, &self.path)?;
+        Ok(())
+    }
+
+    fn update<T>(
+        &self,
+        change: impl FnOnce(&mut QueueState) -> Result<T, QueueError>,
+    ) -> Result<T, QueueError> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let value = change(&mut state)?;
+        self.store(&state)?;
+        Ok(value)
+    }
+
+    pub fn enqueue(&self, operation: Operation, now: u64) -> Result<QueueEntry, QueueError> {
+        let entry = QueueEntry {
+            id: uuid::Uuid::new_v4().to_string(),
+            operation,
+            status: EntryStatus::Pending,
+            attempts: 0,
+            created_at: now,
+            next_attempt_at: now,
+            last_error: None,
+            result: None,
+        };
+        self.update(|state| {
+            state.entries.push(entry.clone());
+            Ok(())
+        })?;
+        self.wake.notify_one();
+        Ok(entry)
+    }
+
+    pub fn status(&self) -> QueueStatus {
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let count = |status| state.entries.iter().filter(|e| e.status == status).count();
+        QueueStatus {
+            online: !state.offline,
+            pending: count(EntryStatus::Pending),
+            failed: count(EntryStatus::Failed),
+            entries: state.entries.clone(),
+            set_aside: state.set_aside.clone(),
+        }
+    }
+
+    /// The oldest pending operation whose time has come
+    pub fn next_due(&self, now: u64) -> Option<QueueEntry> {
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state
+            .entries
+            .iter()
+            .filter(|e| e.status == EntryStatus::Pending && e.next_attempt_at <= now)
+            .min_by_key(|e| e.created_at)
+            .cloned()
+    }
+
+    /// When the next pending operation is due
+    fn next_attempt_at(&self) -> Option<u64> {
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state
+            .entries
+            .iter()
+            .filter(|e| e.status == EntryStatus::Pending)
+            .map(|e| e.next_attempt_at)
+            .min()
+    }
+
+    pub fn complete(&self, id: &str, result: Value) -> Result<(), QueueError> {
+        self.update(|state| {
+            state.offline = false;
+            let entry = find(&mut state.entries, id)?;
+            entry.status = EntryStatus::Completed;
+            entry.last_error = None;
+            entry.result = Some(result);
+            Ok(())
+        })
+    }
+
+    /// Schedule a retry for a transient failure, park the operation otherwise
+    pub fn fail(
+        &self,
+        id: &str,
+        error: &OperationError,
+        now: u64,
+    ) -> Result<QueueEntry, QueueError> {
+        self.update(|state| {
+            if error.transient {
+                state.offline = true;
+            }
+            let entry = find(&mut state.entries, id)?;
+            entry.attempts += 1;
+            entry.last_error = Some(error.message.clone());
+            if error.transient {
+                entry.next_attempt_at = now + backoff(entry.attempts).as_millis() as u64;
+            } else {
+                entry.status = EntryStatus::Failed;
+            }
+            Ok(entry.clone())
+        })
+    }
+
+    /// Run one operation, or every waiting one, now: for when the UI sees
+    /// the network come back or the user asks to retry
+    pub fn retry(&self, id: Option<&str>, now: u64) -> Result<(), QueueError> {
+        self.update(|state| {
+            if let Some(id) = id {
+                find(&mut state.entries, id)?;
+            }
+            let waiting = state
+                .entries
+                .iter_mut()
+                .filter(|e| e.status != EntryStatus::Completed && id.is_none_or(|id| e.id == id));
+            for entry in waiting {
+                entry.status = EntryStatus::Pending;
+                entry.next_attempt_at = now;
+            }
+            Ok(())
+        })?;
+        self.wake.notify_one();
+        Ok(())
+    }
+
+    /// Drop an operation: a completed one once its result is used, or one
+    /// the user no longer wants
+    pub fn remove(&self, id: &str) -> Result<QueueEntry, QueueError> {
+        self.update(|state| {
+            let index = state
+                .entries
+                .iter()
+                .position(|e| e.id == id)
+                .ok_or_else(|| QueueError::NotFound(id.to_string()))?;
+            Ok(state.entries.remove(index))
+        })
+    }
+}
+
+fn find<'a>(entries: &'a mut [QueueEntry], id: &str) -> Result<&'a mut QueueEntry, QueueError> {
+    entries
+        .iter_mut()
+        .find(|e| e.id == id)
+        .ok_or_else(|| QueueError::NotFound(id.to_string()))
+}
+
+pub fn queue_path(app_data: &Path) -> PathBuf {
+    app_data.join("operation_queue.json")
+}
+
+/// What the worker reports as it goes
+pub enum QueueEvent<'a> {
+    Changed(QueueStatus),
+    Completed(&'a QueueEntry),
+    Failed(&'a QueueEntry),
+}
+
+/// Run queued operations forever, oldest first, sleeping until the next one
+/// is due or the queue is woken
+pub async fn run_worker<F, Fut>(queue: Arc<OperationQueue>, execute: F, notify: impl Fn(QueueEvent))
+where
+    F: Fn(Operation) -> Fut,
+    Fut: Future<Output = Result<Value, OperationError>>,
+{
+    loop {
+        let now = now_ms();
+        let Some(entry) = queue.next_due(now) else {
+            let wait = queue
+                .next_attempt_at()
+                .map_or(IDLE_MS, |at| at.saturating_sub(now).max(1));
+            tokio::select! {
+                _ = queue.wake.notified() => {}
+                _ = tokio::time::sleep(Duration::from_millis(wait)) => {}
+            }
+            continue;
+        };
+
+        // An entry removed while it ran is simply not found; the status
+        // event still shows what is left
+        match execute(entry.operation.clone()).await {
+            Ok(result) => {
+                if queue.complete(&entry.id, result).is_ok() {
+                    let status = queue.status();
+                    if let Some(done) = status.entries.iter().find(|e| e.id == entry.id) {
+                        notify(QueueEvent::Completed(done));
+                    }
+                }
+            }
+            Err(error) => {
+                if let Ok(failed) = queue.fail(&entry.id, &error, now_ms()) {
+                    if failed.status == EntryStatus::Failed {
+                        notify(QueueEvent::Failed(&failed));
+                    }
+                }
+            }
+        }
+        notify(QueueEvent::Changed(queue.status()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fetch(url: &str) -> Operation {
+        Operation::FetchUrl {
+            url: url.to_string(),
+            known_artists: Vec::new(),
+        }
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("cat4g-queue-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        queue_path(&dir)
+    }
+
+    #[test]
+    fn test_backoff() {
+        assert_eq!(backoff(1), Duration::from_secs(5));
+        assert_eq!(backoff(2), Duration::from_secs(10));
+        assert_eq!(backoff(4), Duration::from_secs(40));
+        assert_eq!(backoff(30), Duration::from_secs(600));
+    }
+
+    #[test]
+    fn test_corrupt_file_set_aside() {
+        let path = temp_path("corrupt");
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, b"{\"entries\": [").unwrap();
+
+        let queue = OperationQueue::open(&path).unwrap();
+        let status = queue.status();
+        assert!(status.entries.is_empty());
+        queue.enqueue(fetch("https://ufret.jp/a"), 1_000).unwrap();
+
+        let aside: Vec<_> = std::fs::read_dir(path.parent().unwrap())
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|p| p.to_string_lossy().contains(".corrupt-"))
+            .collect();
+        assert_eq!(aside.len(), 1);
+        assert_eq!(
+            status.set_aside,
+            Some(aside[0].to_string_lossy().into_owned())
+        );
+        assert_eq!(std::fs::read(&aside[0]).unwrap(), b"{\"entries\": [");
+        let reopened = OperationQueue::open(&path).unwrap().status();
+        assert_eq!(reopened.entries.len(), 1);
+        assert_eq!(reopened.set_aside, None);
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn test_sync_push_keeps_no_credentials() {
+        // Entries queued before the intent-only form still load, without them
+        let queued = r#"{"kind": "sync_push", "config": {"url": "https://x.supabase.co", "api_key": "anon", "access_token": "jwt", "user_id": "u"}, "changes": []}"#;
+        let operation: Operation = serde_json::from_str(queued).unwrap();
+        assert!(matches!(operation, Operation::SyncPush {}));
+        assert_eq!(
+            serde_json::to_value(&operation).unwrap(),
+            serde_json::json!({"kind": "sync_push"})
+        );
+    }
+
+    #[test]
+    fn test_retry_and_persistence() {
+        let path = temp_path("retry");
+        let queue = OperationQueue::open(&path).unwrap();
+        let first = queue.enqueue(fetch("https://ufret.jp/a"), 1_000).unwrap();
+        let second = queue.enqueue(fetch("https://ufret.jp/b"), 2_000).unwrap();
+        assert_eq!(queue.next_due(2_000).unwrap().id, first.id);
+
+        // Offline: retried later, after the other operation
+        let offline = OperationError {
+            message: "connection refused".to_string(),
+            transient: true,
+        };
+        let failed = queue.fail(&first.id, &offline, 2_000).unwrap();
+        assert_eq!(failed.status, EntryStatus::Pending);
+        assert_eq!(failed.next_attempt_at, 7_000);
+        assert!(!queue.status().online);
+        assert_eq!(queue.next_due(2_000).unwrap().id, second.id);
+
+        // A parse error will not fix itself
+        queue
+            .fail(&second.id, &OperationError::permanent("Parse error"), 2_000)
+            .unwrap();
+        assert!(queue.next_due(6_999).is_none());
+        assert_eq!(queue.next_due(7_000).unwrap().id, first.id);
+
+        queue
+            .complete(&first.id, serde_json::json!({"title": "A"}))
+            .unwrap();
+        let status = queue.status();
+        assert!(status.online);
+        assert_eq!((status.pending, status.failed), (0, 1));
+
+        // Survives a restart
+        let reopened = OperationQueue::open(&path).unwrap();
+        let status = reopened.status();
+        assert_eq!(status.entries.len(), 2);
+        assert_eq!(status.entries[0].result.as_ref().unwrap()["title"], "A");
+        assert_eq!(status.entries[1].last_error.as_deref(), Some("Parse error"));
+
+        reopened.retry(Some(&second.id), 8_000).unwrap();
+        assert_eq!(reopened.next_due(8_000).unwrap().id, second.id);
+        reopened.remove(&first.id).unwrap();
+        assert!(reopened.remove(&first.id).is_err());
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn test_refresh_keeps_no_sheet() {
+        // Entries queued with the sheet as it was still load, without it
+        let queued = r#"{"kind": "refresh_song", "song_id": "s1", "current": {"title": "Old", "artist": null, "key": null, "capo": null, "sections": [], "source_url": "https://ufret.jp/a"}}"#;
+        let operation: Operation = serde_json::from_str(queued).unwrap();
+        assert!(matches!(&operation, Operation::RefreshSong { song_id } if song_id == "s1"));
+        assert_eq!(
+            serde_json::to_value(&operation).unwrap(),
+            serde_json::json!({"kind": "refresh_song", "song_id": "s1"})
+        );
+    }
+
+    #[tokio::test]
+    async fn test_song_inputs_handed_over_on_request() {
+        let inputs = Arc::new(SongInputs::default());
+        assert!(!inputs.provide("s1", None));
+
+        let answer = |song_id: &'static str, current: Option<FetchedChordSheet>| {
+            let inputs = inputs.clone();
+            move || {
+                tokio::spawn(async move {
+                    assert!(!inputs.provide("other", None));
+                    assert!(inputs.provide(song_id, current));
+                });
+            }
+        };
+        let mut edited = FetchedChordSheet::new("https://ufret.jp/a".to_string());
+        edited.title = Some("Edited".to_string());
+        let received = inputs
+            .request("s1", answer("s1", Some(edited)))
+            .await
+            .unwrap();
+        assert_eq!(received.title.as_deref(), Some("Edited"));
+        let deleted = inputs.request("s1", answer("s1", None)).await;
+        assert!(deleted.is_err_and(|e| !e.transient));
+    }
+
+    #[tokio::test]
+    async fn test_worker() {
+        let path = temp_path("worker");
+        let queue = Arc::new(OperationQueue::open(&path).unwrap());
+        let entry = queue
+            .enqueue(fetch("https://ufret.jp/a"), now_ms())
+            .unwrap();
+        let attempts = Arc::new(Mutex::new(0));
+        let events = Arc::new(Mutex::new(Vec::new()));
+
+        let worker = {
+            let (queue, attempts, events) = (queue.clone(), attempts.clone(), events.clone());
+            tokio::spawn(run_worker(
+                queue,
+                move |_| {
+                    let attempts = attempts.clone();
+                    async move {
+                        let mut attempts = attempts.lock().unwrap();
+                        *attempts += 1;
+                        if *attempts == 1 {
+                            Err(OperationError {
+                                message: "offline".to_string(),
+                                transient: true,
+                            })
+                        } else {
+                            Ok(serde_json::json!("done"))
+                        }
+                    }
+                },
+                move |event| {
+                    let name = match event {
+                        QueueEvent::Changed(_) => "changed",
+                        QueueEvent::Completed(_) => "completed",
+                        QueueEvent::Failed(_) => "failed",
+                    };
+                    events.lock().unwrap().push(name);
+                },
+            ))
+        };
+
+        // The first attempt fails offline; the UI reports the network is back
+        for _ in 0..100 {
+            if *attempts.lock().unwrap() == 1 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        queue.retry(None, now_ms()).unwrap();
+        for _ in 0..100 {
+            if queue.status().entries[0].status == EntryStatus::Completed {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        worker.abort();
+
+        let status = queue.status();
+        assert_eq!(status.entries[0].id, entry.id);
+        assert_eq!(status.entries[0].status, EntryStatus::Completed);
+        assert_eq!(status.entries[0].attempts, 1);
+        assert!(status.online);
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec!["changed", "completed", "changed"]
+        );
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+}
@@ -0,0 +1,778 @@
+//! Song revision history
+//!
+//! Each song's past sections and lines are kept as numbered revisions in one
+//! JSON file per song under the app data directory. Saving a revision that
+//! matches the latest one is a no-op, and restoring records the restored
+//! content as a new revision so the restore can itself be undone.
+//!
+//! `diff` compares two revisions structurally: sections are paired by name,
+//! or by content when renamed; lines are paired by lyrics within a section
+//! and then across sections (a move); what is left pairs up in place as an
+//! edit, or counts as added or removed.
+
+use crate::error::RevisionError;
+use crate::parsers::{FetchedLine, FetchedSection};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Oldest revisions are dropped past this many per song, except the newest
+/// fetched one
+const MAX_REVISIONS: usize = 100;
+
+/// Share of lines two sections must have in common to count as a rename
+const RENAME_SIMILARITY: f64 = 0.5;
+
+/// What produced a revision
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RevisionSource {
+    ManualEdit,
+    Refetch,
+    /// Transpose, simplify and other whole-sheet transforms
+    Transform,
+    Import,
+    Restore,
+}
+
+impl RevisionSource {
+    /// Content as the site had it, which a re-fetch merges against
+    fn is_fetched(self) -> bool {
+        matches!(self, Self::Import | Self::Refetch)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Revision {
+    /// Counts up from 1 per song
+    pub id: u32,
+    /// Seconds since the Unix epoch
+    pub created_at: u64,
+    pub source: RevisionSource,
+    #[serde(default)]
+    pub note: Option<String>,
+    pub sections: Vec<FetchedSection>,
+}
+
+/// A revision without its content, for listing
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RevisionSummary {
+    pub id: u32,
+    pub created_at: u64,
+    pub source: RevisionSource,
+    pub note: Option<String>,
+    pub section_count: usize,
+    pub line_count: usize,
+}
+
+impl From<&Revision> for RevisionSummary {
+    fn from(revision: &Revision) -> Self {
+        Self {
+            id: revision.id,
+            created_at: revision.created_at,
+            source: revision.source,
+            note: revision.note.clone(),
+            section_count: revision.sections.len(),
+            line_count: revision.sections.iter().map(|s| s.lines.len()).sum(),
+        }
+    }
+}
+
+/// Revision files for every song, in one directory
+pub struct RevisionStore {
+    dir: PathBuf,
+    /// Held from load to store so two saves cannot drop each other's revision
+    lock: Mutex<()>,
+}
+
+impl RevisionStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            lock: Mutex::new(()),
+        }
+    }
+
+    fn path(&self, song_id: &str) -> PathBuf {
+        let safe: String = song_id
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '-' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        self.dir.join(format!("{}.json", safe))
+    }
+
+    fn load(&self, song_id: &str) -> Result<Vec<Revision>, RevisionError> {
+        match std::fs::read(self.path(song_id)) {
+            Ok(data) => serde_json::from_slice(&data)
+                .map_err(|e| RevisionError::Invalid(format!("{}: {}", song_id, e))),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Write through a temporary file so a crash never leaves half a history
+    fn store(&self, song_id: &str, revisions: &[Revision]) -> Result<(), RevisionError> {
+        std::fs::create_dir_all(&self.dir)?;
+        let path = self.path(song_id);
+        let temp = path.with_extension("json.tmp");
+        let data = serde_json::to_vec(revisions)
+            .map_err(|e| RevisionError::Invalid(format!("{}: {}", song_id, e)))?;
+        std::fs::write(&temp, data)?;
+        std::fs::rename(&temp, &path)?;
+        Ok(())
+    }
+
+    /// Record the song's current sections; returns the latest revision
+    /// unchanged when the content is the same
+    pub fn save(
+        &self,
+        song_id: &str,
+        sections: Vec<FetchedSection>,
+        source: RevisionSource,
+        note: Option<String>,
+    ) -> Result<Revision, RevisionError> {
+        let _guard = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+        let mut revisions = self.load(song_id)?;
+        if let Some(latest) = revisions.last() {
+            if latest.sections == sections {
+                return Ok(latest.clone());
+            }
+        }
+
+        let revision = Revision {
+            id: revisions.last().map_or(1, |r| r.id + 1),
+            created_at: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs()),
+            source,
+            note,
+            sections,
+        };
+        revisions.push(revision.clone());
+        trim(&mut revisions);
+        self.store(song_id, &revisions)?;
+        Ok(revision)
+    }
+
+    /// Revisions of a song, newest first
+    pub fn list(&self, song_id: &str) -> Result<Vec<RevisionSummary>, RevisionError> {
+        Ok(self
+            .load(song_id)?
+            .iter()
+            .rev()
+            .map(RevisionSummary::from)
+            .collect())
+    }
+
+    pub fn get(&self, song_id: &str, id: u32) -> Result<Revision, RevisionError> {
+        self.load(song_id)?
+            .into_iter()
+            .find(|r| r.id == id)
+            .ok_or_else(|| RevisionError::NotFound(format!("{} revision {}", song_id, id)))
+    }
+
+    /// The newest revision that came from the site: what a re-fetch merges against
+    pub fn latest_fetched(&self, song_id: &str) -> Result<Option<Revision>, RevisionError> {
+        Ok(self
+            .load(song_id)?
+            .into_iter()
+            .rev()
+            .find(|r| r.source.is_fetched()))
+    }
+
+    /// Bring back a revision's content as the newest revision
+    pub fn restore(&self, song_id: &str, id: u32) -> Result<Revision, RevisionError> {
+        let old = self.get(song_id, id)?;
+        self.save(
+            song_id,
+            old.sections,
+            RevisionSource::Restore,
+            Some(format!("Restored revision {}", id)),
+        )
+    }
+
+    pub fn diff(&self, song_id: &str, from: u32, to: u32) -> Result<SheetDiff, RevisionError> {
+        let old = self.get(song_id, from)?;
+        let new = self.get(song_id, to)?;
+        Ok(diff(&old.sections, &new.sections))
+    }
+}
+
+/// Drop the oldest revisions past `MAX_REVISIONS`, keeping the newest fetched one
+fn trim(revisions: &mut Vec<Revision>) {
+    let Some(excess) = revisions.len().checked_sub(MAX_REVISIONS) else {
+        return;
+    };
+    let keep = revisions
+        .iter()
+        .rposition(|r| r.source.is_fetched())
+        .filter(|&index| index < excess);
+    let cut = excess + usize::from(keep.is_some());
+    let mut index = 0;
+    revisions.retain(|_| {
+        let kept = index >= cut || Some(index) == keep;
+        index += 1;
+        kept
+    });
+}
+
+/// Section-level change; indices are in the old sheet for removals and the
+/// new one otherwise
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SectionChange {
+    Added {
+        index: usize,
+        name: String,
+    },
+    Removed {
+        index: usize,
+        name: String,
+    },
+    Renamed {
+        old_index: usize,
+        index: usize,
+        from: String,
+        to: String,
+    },
+}
+
+/// A chord that appeared, went away or changed at a lyric offset
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChordChange {
+    pub position: i32,
+    pub from: Option<String>,
+    pub to: Option<String>,
+}
+
+/// Line-level change; `section`/`line` index the new sheet, `old_*` the old
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum LineChange {
+    Added {
+        section: usize,
+        line: usize,
+        lyrics: String,
+    },
+    Removed {
+        old_section: usize,
+        old_line: usize,
+        lyrics: String,
+    },
+    /// Same lyrics in another place; `chords` lists any chord changes too
+    Moved {
+        old_section: usize,
+        old_line: usize,
+        section: usize,
+        line: usize,
+        lyrics: String,
+        chords: Vec<ChordChange>,
+    },
+    /// Lyrics kept, chords changed
+    ChordsChanged {
+        section: usize,
+        line: usize,
+        lyrics: String,
+        chords: Vec<ChordChange>,
+    },
+    /// Lyrics rewritten in place
+    Edited {
+        section: usize,
+        line: usize,
+        from: String,
+        to: String,
+        chords: Vec<ChordChange>,
+    },
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SheetDiff {
+    pub sections: Vec<SectionChange>,
+    pub lines: Vec<LineChange>,
+}
+
+/// What identifies a line across revisions: its lyrics, its chords when it has
+/// none, or its rows for a tab line
+pub(crate) fn line_key(line: &FetchedLine) -> String {
+    let lyrics = line.lyrics.trim();
+    if let Some(tab) = &line.tab {
+        format!("\u{1}{}", tab.lines.join("\n"))
+    } else if lyrics.is_empty() {
+        let chords: Vec<&str> = line.chords.iter().map(|c| c.chord.as_str()).collect();
+        format!("\u{0}{}", chords.join(" "))
+    } else {
+        lyrics.to_string()
+    }
+}
+
+fn chord_changes(old: &FetchedLine, new: &FetchedLine) -> Vec<ChordChange> {
+    let positions: BTreeSet<i32> = old
+        .chords
+        .iter()
+        .chain(&new.chords)
+        .map(|c| c.position)
+        .collect();
+    let at = |line: &FetchedLine, position: i32| {
+        line.chords
+            .iter()
+            .find(|c| c.position == position)
+            .map(|c| c.chord.clone())
+    };
+    positions
+        .into_iter()
+        .filter_map(|position| {
+            let (from, to) = (at(old, position), at(new, position));
+            (from != to).then_some(ChordChange { position, from, to })
+        })
+        .collect()
+}
+
+/// Longest common subsequence of two key lists, as index pairs
+pub(crate) fn lcs(a: &[String], b: &[String]) -> Vec<(usize, usize)> {
+    let mut table = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            table[i][j] = if a[i] == b[j] {
+                table[i + 1][j + 1] + 1
+            } else {
+                table[i + 1][j].max(table[i][j + 1])
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    let mut pairs = Vec::new();
+    while i < a.len() && j < b.len() {
+        if a[i] == b[j] {
+            pairs.push((i, j));
+            i += 1;
+            j += 1;
+        } else if table[i + 1][j] >= table[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    pairs
+}
+
+fn keys(section: &FetchedSection) -> Vec<String> {
+    section.lines.iter().map(line_key).collect()
+}
+
+/// Share of lines two sections have in common
+fn similarity(a: &FetchedSection, b: &FetchedSection) -> f64 {
+    let longest = a.lines.len().max(b.lines.len());
+    if longest == 0 {
+        return 0.0;
+    }
+    lcs(&keys(a), &keys(b)).len() as f64 / longest as f64
+}
+
+/// Pair old and new sections: by name first, then by content
+fn pair_sections(old: &[FetchedSection], new: &[FetchedSection]) -> Vec<(usize, usize)> {
+    let mut pairs = Vec::new();
+    let mut old_used = vec![false; old.len()];
+    let mut new_used = vec![false; new.len()];
+    for (j, section) in new.iter().enumerate() {
+        if let Some(i) = (0..old.len()).find(|&i| !old_used[i] && old[i].name == section.name) {
+            old_used[i] = true;
+            new_used[j] = true;
+            pairs.push((i, j));
+        }
+    }
+
+    loop {
+        let best = (0..old.len())
+            .filter(|&i| !old_used[i])
+            .flat_map(|i| {
+                (0..new.len())
+                    .filter(|&j| !new_used[j])
+                    .map(move |j| (i, j))
+            })
+            .map(|(i, j)| (similarity(&old[i], &new[j]), i, j))
+            .filter(|(score, _, _)| *score >= RENAME_SIMILARITY)
+            .max_by(|a, b| a.0.total_cmp(&b.0));
+        let Some((_, i, j)) = best else {
+            break;
+        };
+        old_used[i] = true;
+        new_used[j] = true;
+        pairs.push((i, j));
+    }
+    pairs.sort_by_key(|&(_, j)| j);
+    pairs
+}
+
+/// Lines left over in one section pair, between two matched lines
+struct Gap {
+    section: usize,
+    old_section: usize,
+    old: Vec<usize>,
+    new: Vec<usize>,
+}
+
+/// Structural differences from `old` to `new`
+pub fn diff(old: &[FetchedSection], new: &[FetchedSection]) -> SheetDiff {
+    let mut result = SheetDiff::default();
+    let pairs = pair_sections(old, new);
+
+    for (index, section) in new.iter().enumerate() {
+        match pairs.iter().find(|&&(_, j)| j == index) {
+            Some(&(i, _)) if old[i].name != section.name => {
+                result.sections.push(SectionChange::Renamed {
+                    old_index: i,
+                    index,
+                    from: old[i].name.clone(),
+                    to: section.name.clone(),
+                })
+            }
+            Some(_) => {}
+            None => result.sections.push(SectionChange::Added {
+                index,
+                name: section.name.clone(),
+            }),
+        }
+    }
+    for (index, section) in old.iter().enumerate() {
+        if !pairs.iter().any(|&(i, _)| i == index) {
+            result.sections.push(SectionChange::Removed {
+                index,
+                name: section.name.clone(),
+            });
+        }
+    }
+
+    // Lines matched in place, and gaps of unmatched lines between them
+    let mut gaps: Vec<Gap> = Vec::new();
+    for &(i, j) in &pairs {
+        let (old_keys, new_keys) = (keys(&old[i]), keys(&new[j]));
+        let matched = lcs(&old_keys, &new_keys);
+        let (mut last_old, mut last_new) = (0, 0);
+        for &(a, b) in matched
+            .iter()
+            .chain([(old_keys.len(), new_keys.len())].iter())
+        {
+            gaps.push(Gap {
+                section: j,
+                old_section: i,
+                old: (last_old..a).collect(),
+                new: (last_new..b).collect(),
+            });
+            if a < old_keys.len() {
+                let chords = chord_changes(&old[i].lines[a], &new[j].lines[b]);
+                if !chords.is_empty() {
+                    result.lines.push(LineChange::ChordsChanged {
+                        section: j,
+                        line: b,
+                        lyrics: new[j].lines[b].lyrics.clone(),
+                        chords,
+                    });
+                }
+            }
+            (last_old, last_new) = (a + 1, b + 1);
+        }
+    }
+    // Sections only on one side are gaps of their own
+    for (i, section) in old.iter().enumerate() {
+        if !pairs.iter().any(|&(a, _)| a == i) {
+            gaps.push(Gap {
+                section: usize::MAX,
+                old_section: i,
+                old: (0..section.lines.len()).collect(),
+                new: Vec::new(),
+            });
+        }
+    }
+    for (j, section) in new.iter().enumerate() {
+        if !pairs.iter().any(|&(_, b)| b == j) {
+            gaps.push(Gap {
+                section: j,
+                old_section: usize::MAX,
+                old: Vec::new(),
+                new: (0..section.lines.len()).collect(),
+            });
+        }
+    }
+
+    // Lines that left one gap and turned up in another moved
+    let mut old_left: Vec<(usize, usize, usize)> = Vec::new();
+    for (g, gap) in gaps.iter().enumerate() {
+        old_left.extend(gap.old.iter().map(|&line| (g, gap.old_section, line)));
+    }
+    for gap_index in 0..gaps.len() {
+        let (section, lines) = (gaps[gap_index].section, gaps[gap_index].new.clone());
+        for line in lines {
+            let key = line_key(&new[section].lines[line]);
+            let found = old_left
+                .iter()
+                .position(|&(g, s, l)| g != gap_index && line_key(&old[s].lines[l]) == key);
+            let Some(found) = found else {
+                continue;
+            };
+            let (g, s, l) = old_left.remove(found);
+            let (old_line, new_line) = (&old[s].lines[l], &new[section].lines[line]);
+            result.lines.push(LineChange::Moved {
+                old_section: s,
+                old_line: l,
+                section,
+                line,
+                lyrics: new_line.lyrics.clone(),
+                chords: chord_changes(old_line, new_line),
+            });
+            gaps[g].old.retain(|&x| x != l);
+            gaps[gap_index].new.retain(|&x| x != line);
+        }
+    }
+
+    // What remains pairs up in place as edits; the rest was added or removed
+    for gap in &gaps {
+        for k in 0..gap.old.len().max(gap.new.len()) {
+            match (gap.old.get(k), gap.new.get(k)) {
+                (Some(&a), Some(&b)) => {
+                    let (old_line, new_line) =
+                        (&old[gap.old_section].lines[a], &new[gap.section].lines[b]);
+                    result.lines.push(LineChange::Edited {
+                        section: gap.section,
+                        line: b,
+                        from: old_line.lyrics.clone(),
+                        to: new_line.lyrics.clone(),
+                        chords: chord_changes(old_line, new_line),
+                    });
+                }
+                (Some(&a), None) => result.lines.push(LineChange::Removed {
+                    old_section: gap.old_section,
+                    old_line: a,
+                    lyrics: old[gap.old_section].lines[a].lyrics.clone(),
+                }),
+                (None, Some(&b)) => result.lines.push(LineChange::Added {
+                    section: gap.section,
+                    line: b,
+                    lyrics: new[gap.section].lines[b].lyrics.clone(),
+                }),
+                (None, None) => {}
+            }
+        }
+    }
+    result
+}
+
+/// Directory holding revision files, under the app's data directory
+pub fn revisions_dir(app_data: &Path) -> PathBuf {
+    app_data.join("revisions")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::FetchedChord;
+
+    fn line(lyrics: &str, chords: &[(&str, i32)]) -> FetchedLine {
+        FetchedLine::with_chords(
+            lyrics,
+            chords
+                .iter()
+                .map(|&(c, p)| FetchedChord::new(c, p))
+                .collect(),
+        )
+    }
+
+    fn section(name: &str, lines: Vec<FetchedLine>) -> FetchedSection {
+        let mut section = FetchedSection::new(name);
+        section.lines = lines;
+        section
+    }
+
+    fn original() -> Vec<FetchedSection> {
+        vec![
+            section(
+                "Verse",
+                vec![
+                    line("Hello darkness", &[("Am", 0)]),
+                    line("my old friend", &[("G", 0)]),
+                    line("I've come to talk", &[("C", 0)]),
+                ],
+            ),
+            section("Chorus", vec![line("And the vision", &[("F", 4)])]),
+        ]
+    }
+
+    #[test]
+    fn test_diff() {
+        let edited = vec![
+            section(
+                "Verse 1",
+                vec![
+                    line("Hello darkness", &[("Am7", 0)]),
+                    line("I've come to talk", &[("C", 0)]),
+                ],
+            ),
+            section(
+                "Chorus",
+                vec![
+                    line("And the visions", &[("F", 4)]),
+                    line("my old friend", &[("G", 0)]),
+                ],
+            ),
+            section("Outro", vec![line("", &[("Am", 0)])]),
+        ];
+        let result = diff(&original(), &edited);
+
+        assert_eq!(
+            result.sections,
+            vec![
+                SectionChange::Renamed {
+                    old_index: 0,
+                    index: 0,
+                    from: "Verse".to_string(),
+                    to: "Verse 1".to_string(),
+                },
+                SectionChange::Added {
+                    index: 2,
+                    name: "Outro".to_string(),
+                },
+            ]
+        );
+        assert!(result.lines.contains(&LineChange::ChordsChanged {
+            section: 0,
+            line: 0,
+            lyrics: "Hello darkness".to_string(),
+            chords: vec![ChordChange {
+                position: 0,
+                from: Some("Am".to_string()),
+                to: Some("Am7".to_string()),
+            }],
+        }));
+        assert!(result.lines.contains(&LineChange::Moved {
+            old_section: 0,
+            old_line: 1,
+            section: 1,
+            line: 1,
+            lyrics: "my old friend".to_string(),
+            chords: Vec::new(),
+        }));
+        assert!(result.lines.contains(&LineChange::Edited {
+            section: 1,
+            line: 0,
+            from: "And the vision".to_string(),
+            to: "And the visions".to_string(),
+            chords: Vec::new(),
+        }));
+        assert!(result.lines.contains(&LineChange::Added {
+            section: 2,
+            line: 0,
+            lyrics: String::new(),
+        }));
+        assert_eq!(result.lines.len(), 4);
+
+        assert_eq!(diff(&original(), &original()), SheetDiff::default());
+    }
+
+    #[test]
+    fn test_store_and_restore() {
+        let dir = std::env::temp_dir().join(format!("cat4g-revisions-{}", std::process::id()));
+        let store = RevisionStore::new(&dir);
+
+        let first = store
+            .save("song-1", original(), RevisionSource::Import, None)
+            .unwrap();
+        assert_eq!(first.id, 1);
+        // Saving the same content again adds nothing
+        let again = store
+            .save("song-1", original(), RevisionSource::ManualEdit, None)
+            .unwrap();
+        assert_eq!(again.id, 1);
+
+        let mut edited = original();
+        edited[1].name = "Refrain".to_string();
+        let second = store
+            .save(
+                "song-1",
+                edited,
+                RevisionSource::Refetch,
+                Some("Site fix".to_string()),
+            )
+            .unwrap();
+        assert_eq!(second.id, 2);
+
+        let diff = store.diff("song-1", 1, 2).unwrap();
+        assert_eq!(diff.sections.len(), 1);
+        assert!(diff.lines.is_empty());
+
+        let restored = store.restore("song-1", 1).unwrap();
+        assert_eq!(restored.id, 3);
+        assert_eq!(restored.source, RevisionSource::Restore);
+        assert_eq!(restored.sections[1].name, "Chorus");
+
+        let list = store.list("song-1").unwrap();
+        let ids: Vec<u32> = list.iter().map(|r| r.id).collect();
+        assert_eq!(ids, vec![3, 2, 1]);
+        assert_eq!(list[1].note.as_deref(), Some("Site fix"));
+        assert_eq!(list[0].line_count, 4);
+
+        assert!(matches!(
+            store.get("song-1", 9),
+            Err(RevisionError::NotFound(_))
+        ));
+        assert!(store.list("other").unwrap().is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    fn numbered(n: usize) -> Vec<FetchedSection> {
+        vec![section("Verse", vec![line(&format!("take {}", n), &[])])]
+    }
+
+    #[test]
+    fn test_trim_keeps_latest_fetched() {
+        let dir = std::env::temp_dir().join(format!("cat4g-trim-{}", std::process::id()));
+        let store = RevisionStore::new(&dir);
+        store
+            .save("song", numbered(0), RevisionSource::Import, None)
+            .unwrap();
+        for n in 1..=MAX_REVISIONS {
+            store
+                .save("song", numbered(n), RevisionSource::ManualEdit, None)
+                .unwrap();
+        }
+
+        let list = store.list("song").unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(list.len(), MAX_REVISIONS);
+        assert_eq!(list[0].id as usize, MAX_REVISIONS + 1);
+        // Revision 2 went instead of the import
+        let oldest: Vec<_> = list.iter().rev().take(2).map(|r| r.id).collect();
+        assert_eq!(oldest, vec![1, 3]);
+    }
+
+    #[test]
+    fn test_concurrent_saves_keep_every_revision() {
+        let dir = std::env::temp_dir().join(format!("cat4g-concurrent-{}", std::process::id()));
+        let store = std::sync::Arc::new(RevisionStore::new(&dir));
+        let threads: Vec<_> = (0..4)
+            .map(|thread| {
+                let store = store.clone();
+                std::thread::spawn(move || {
+                    for n in 0..10 {
+                        let sections = numbered(thread * 100 + n);
+                        store
+                            .save("song", sections, RevisionSource::ManualEdit, None)
+                            .unwrap();
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        let ids: Vec<u32> = store.list("song").unwrap().iter().map(|r| r.id).collect();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(ids, (1..=40).rev().collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,344 @@
+//! Setlists: playlists played live
+//!
+//! Each playlist entry can override the song's key, capo, tempo and notes for
+//! the gig, and can start a new set after a break. Resolving a setlist applies
+//! the overrides to every sheet and estimates how long each song, set and the
+//! whole evening runs from the tempo and the bars in each section.
+
+use crate::error::ExportError;
+use crate::export::timing::{section_beats, TimeSignature};
+use crate::export::ExportSong;
+use crate::parsers::FetchedChordSheet;
+use crate::theory::key::detect_key;
+use crate::theory::{parse_note, pitch_class, transpose_sheet};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Setlist {
+    /// The playlist's name, used as the songbook title
+    #[serde(default)]
+    pub name: Option<String>,
+    /// playlist_songs rows with their songs, in order_index order
+    pub entries: Vec<SetlistEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetlistEntry {
+    pub song_id: String,
+    /// The song with its own defaults
+    pub song: ExportSong,
+    /// Key to sound in (playlist_songs.key_override)
+    #[serde(default)]
+    pub key_override: Option<String>,
+    #[serde(default)]
+    pub capo_override: Option<i32>,
+    #[serde(default)]
+    pub bpm_override: Option<u32>,
+    /// Replaces the song's notes for this performance
+    #[serde(default)]
+    pub notes: Option<String>,
+    /// Name of the set this entry opens ("Set 2", "Encore"); an empty name
+    /// still starts a new set
+    #[serde(default)]
+    pub set_break: Option<String>,
+    /// Interval before the set this entry opens
+    #[serde(default)]
+    pub break_minutes: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResolvedEntry {
+    pub song_id: String,
+    /// Transposed sheet with the capo, tempo and notes played
+    pub song: ExportSong,
+    /// Semitones applied to the song's chords
+    pub transpose: i32,
+    /// None when the song has no tempo
+    pub duration_seconds: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResolvedSet {
+    pub name: Option<String>,
+    /// Interval before this set
+    pub break_minutes: u32,
+    pub entries: Vec<ResolvedEntry>,
+    /// Songs with a known length only
+    pub duration_seconds: u32,
+    /// Songs left out of the duration for lack of a tempo
+    pub untimed: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResolvedSetlist {
+    pub name: Option<String>,
+    pub sets: Vec<ResolvedSet>,
+    pub playing_seconds: u32,
+    /// Playing time plus the breaks between sets
+    pub total_seconds: u32,
+    pub untimed: usize,
+}
+
+impl ResolvedSetlist {
+    /// The resolved songs in playing order, for the songbook export
+    pub fn songs(&self) -> Vec<ExportSong> {
+        self.sets
+            .iter()
+            .flat_map(|set| &set.entries)
+            .map(|entry| entry.song.clone())
+            .collect()
+    }
+}
+
+/// Estimated playing time: each section's bars at its tempo and meter,
+/// repeated `repeat_count` times. None when a section has no tempo.
+pub fn estimate_seconds(song: &ExportSong) -> Option<f64> {
+    let song_time = TimeSignature::parse(song.time_signature.as_deref());
+    let mut seconds = 0.0;
+    for section in &song.sheet.sections {
+        let time = match section.time_signature.as_deref() {
+            Some(text) => TimeSignature::parse(Some(text)),
+            None => song_time,
+        };
+        let beats = section_beats(section, &time);
+        if beats == 0 {
+            continue;
+        }
+        let bpm = section.bpm_override.or(song.bpm).filter(|&bpm| bpm > 0)?;
+        let repeats = section.repeat_count.unwrap_or(1).max(1);
+        seconds += (beats as u32 * repeats) as f64 * 60.0 / bpm as f64 * time.beat_in_quarters();
+    }
+    Some(seconds)
+}
+
+/// Pitch class of the key a sheet is written in, from its key or its chords
+fn written_key(sheet: &FetchedChordSheet) -> Option<u8> {
+    sheet
+        .key
+        .as_deref()
+        .and_then(parse_note)
+        .map(|(pc, _)| pc)
+        .or_else(|| detect_key(sheet).map(|key| key.tonic))
+}
+
+/// Semitones that move the shapes from the song's capo and key to the
+/// entry's. The sounding key is the written key plus the capo, so a new capo
+/// alone changes the shapes but not what the audience hears.
+fn shape_transpose(entry: &SetlistEntry) -> Result<i32, ExportError> {
+    let sheet = &entry.song.sheet;
+    let capo = sheet.capo.unwrap_or(0);
+    let new_capo = entry.capo_override.unwrap_or(capo);
+    let key_shift = match entry
+        .key_override
+        .as_deref()
+        .filter(|k| !k.trim().is_empty())
+    {
+        Some(target) => {
+            let target = parse_note(target.trim())
+                .map(|(pc, _)| pc)
+                .ok_or_else(|| ExportError::Setlist(format!("Invalid key: {}", target)))?;
+            let written = written_key(sheet).ok_or_else(|| {
+                ExportError::Setlist(format!("Cannot tell the key of {}", entry.song.title()))
+            })?;
+            // The nearer way round: up to a tritone up, otherwise down
+            let shift = pitch_class(target as i32 - (written as i32 + capo)) as i32;
+            if shift > 6 {
+                shift - 12
+            } else {
+                shift
+            }
+        }
+        None => 0,
+    };
+    Ok(key_shift + capo - new_capo)
+}
+
+fn resolve_entry(entry: &SetlistEntry) -> Result<ResolvedEntry, ExportError> {
+    let transpose = shape_transpose(entry)?;
+    let mut sheet = transpose_sheet(&entry.song.sheet, transpose);
+    if entry.capo_override.is_some() {
+        sheet.capo = entry.capo_override;
+    }
+
+    // Section tempos keep their ratio to the song's when the song is played
+    // faster or slower
+    let bpm = entry.bpm_override.or(entry.song.bpm);
+    if let (Some(new), Some(old)) = (entry.bpm_override, entry.song.bpm.filter(|&b| b > 0)) {
+        for section in &mut sheet.sections {
+            if let Some(section_bpm) = section.bpm_override.as_mut() {
+                *section_bpm = (*section_bpm as f64 * new as f64 / old as f64).round() as u32;
+            }
+        }
+    }
+
+    let song = ExportSong {
+        sheet,
+        bpm,
+        time_signature: entry.song.time_signature.clone(),
+        notes: entry.notes.clone().or_else(|| entry.song.notes.clone()),
+    };
+    let duration_seconds = estimate_seconds(&song).map(|s| s.round() as u32);
+    Ok(ResolvedEntry {
+        song_id: entry.song_id.clone(),
+        song,
+        transpose,
+        duration_seconds,
+    })
+}
+
+/// Apply every entry's overrides and split the entries into sets
+pub fn resolve_setlist(setlist: &Setlist) -> Result<ResolvedSetlist, ExportError> {
+    let mut sets: Vec<ResolvedSet> = Vec::new();
+    for (index, entry) in setlist.entries.iter().enumerate() {
+        if index == 0 || entry.set_break.is_some() {
+            sets.push(ResolvedSet {
+                name: entry
+                    .set_break
+                    .as_deref()
+                    .map(str::trim)
+                    .filter(|name| !name.is_empty())
+                    .map(str::to_string),
+                break_minutes: if index == 0 {
+                    0
+                } else {
+                    entry.break_minutes.unwrap_or(0)
+                },
+                entries: Vec::new(),
+                duration_seconds: 0,
+                untimed: 0,
+            });
+        }
+        let resolved = resolve_entry(entry)?;
+        let set = sets.last_mut().expect("a set is opened on the first entry");
+        match resolved.duration_seconds {
+            Some(seconds) => set.duration_seconds += seconds,
+            None => set.untimed += 1,
+        }
+        set.entries.push(resolved);
+    }
+
+    let playing_seconds = sets.iter().map(|set| set.duration_seconds).sum();
+    let breaks: u32 = sets.iter().map(|set| set.break_minutes * 60).sum();
+    Ok(ResolvedSetlist {
+        name: setlist.name.clone(),
+        untimed: sets.iter().map(|set| set.untimed).sum(),
+        sets,
+        playing_seconds,
+        total_seconds: playing_seconds + breaks,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::{FetchedChord, FetchedLine, FetchedSection};
+
+    /// Four one-bar lines of G C D G, written with the given capo
+    fn song(key: &str, capo: i32, bpm: Option<u32>) -> ExportSong {
+        let mut sheet = FetchedChordSheet::new(String::new());
+        sheet.title = Some("Song".to_string());
+        sheet.key = Some(key.to_string());
+        sheet.capo = Some(capo);
+        let mut section = FetchedSection::new("Verse");
+        for chord in ["G", "C", "D", "G"] {
+            let mut line = FetchedLine::with_chords("歌詞", vec![FetchedChord::new(chord, 0)]);
+            line.measures = Some(1);
+            section.lines.push(line);
+        }
+        sheet.sections.push(section);
+        ExportSong {
+            sheet,
+            bpm,
+            time_signature: None,
+            notes: Some("Song notes".to_string()),
+        }
+    }
+
+    fn entry(song: ExportSong) -> SetlistEntry {
+        SetlistEntry {
+            song_id: "s1".to_string(),
+            song,
+            key_override: None,
+            capo_override: None,
+            bpm_override: None,
+            notes: None,
+            set_break: None,
+            break_minutes: None,
+        }
+    }
+
+    fn chords(entry: &ResolvedEntry) -> Vec<&str> {
+        entry.song.sheet.sections[0]
+            .lines
+            .iter()
+            .map(|line| line.chords[0].chord.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_key_and_capo_overrides() {
+        // Written in G with capo 2, sounding A
+        let mut capo_only = entry(song("G", 2, Some(120)));
+        capo_only.capo_override = Some(0);
+        let resolved = resolve_entry(&capo_only).unwrap();
+        assert_eq!(resolved.transpose, 2);
+        assert_eq!(chords(&resolved), vec!["A", "D", "E", "A"]);
+        assert_eq!(resolved.song.sheet.capo, Some(0));
+
+        // Down a tone to sound G, keeping capo 2: shapes in F
+        let mut key_only = entry(song("G", 2, Some(120)));
+        key_only.key_override = Some("G".to_string());
+        let resolved = resolve_entry(&key_only).unwrap();
+        assert_eq!(resolved.transpose, -2);
+        assert_eq!(resolved.song.sheet.key.as_deref(), Some("F"));
+        assert_eq!(resolved.song.sheet.capo, Some(2));
+
+        let mut bad = entry(song("G", 0, None));
+        bad.key_override = Some("H".to_string());
+        assert!(resolve_entry(&bad).is_err());
+    }
+
+    #[test]
+    fn test_duration_follows_tempo() {
+        let mut slow = song("G", 0, Some(120));
+        slow.sheet.sections[0].repeat_count = Some(2);
+        slow.sheet.sections[0].bpm_override = Some(60);
+        // Eight bars of 4/4 at 60 bpm
+        assert_eq!(estimate_seconds(&slow), Some(32.0));
+
+        let mut faster = entry(slow);
+        faster.bpm_override = Some(180);
+        faster.notes = Some("Segue into next".to_string());
+        let resolved = resolve_entry(&faster).unwrap();
+        assert_eq!(resolved.song.sheet.sections[0].bpm_override, Some(90));
+        assert_eq!(resolved.duration_seconds, Some(21));
+        assert_eq!(resolved.song.notes.as_deref(), Some("Segue into next"));
+
+        assert_eq!(estimate_seconds(&song("G", 0, None)), None);
+    }
+
+    #[test]
+    fn test_sets_and_totals() {
+        let mut encore = entry(song("G", 0, Some(60)));
+        encore.set_break = Some(" Encore ".to_string());
+        encore.break_minutes = Some(5);
+        let setlist = Setlist {
+            name: Some("Live".to_string()),
+            entries: vec![
+                entry(song("G", 0, Some(120))),
+                entry(song("C", 0, None)),
+                encore,
+            ],
+        };
+        let resolved = resolve_setlist(&setlist).unwrap();
+        assert_eq!(resolved.sets.len(), 2);
+        assert_eq!(resolved.sets[0].name, None);
+        assert_eq!(resolved.sets[0].duration_seconds, 8);
+        assert_eq!(resolved.sets[0].untimed, 1);
+        assert_eq!(resolved.sets[1].name.as_deref(), Some("Encore"));
+        assert_eq!(resolved.playing_seconds, 24);
+        assert_eq!(resolved.total_seconds, 324);
+        assert_eq!(resolved.untimed, 1);
+        assert_eq!(resolved.songs().len(), 3);
+    }
+}
@@ -0,0 +1,236 @@
+//! Chord simplification for beginners
+//!
+//! Chords are rebuilt from their parsed structure at the requested level, so
+//! `Gadd9` becomes `G` and `Em7(9)` becomes `Em7` or `Em`. Slash chords can be
+//! collapsed to their upper chord, and a chord repeated back to back on the
+//! same line is merged into the first one.
+
+use crate::parsers::FetchedChordSheet;
+use crate::theory::chord::{Chord, Quality, Seventh};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SimplifyLevel {
+    /// Drop tensions, keep sevenths (`Em7(9)` -> `Em7`)
+    Sevenths,
+    /// Plain triads (`Em7(9)` -> `Em`, `Bm7-5` -> `Bdim`)
+    Triads,
+    /// Root and fifth only (`Em7(9)` -> `E5`)
+    Power,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SimplifiedSheet {
+    pub sheet: FetchedChordSheet,
+    /// Every chord name that was rewritten, in order of first appearance
+    pub changes: Vec<ChordChange>,
+    /// Chords dropped because they repeated the previous chord on the line
+    pub merged: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChordChange {
+    pub original: String,
+    pub simplified: String,
+    pub occurrences: usize,
+}
+
+pub fn simplify_sheet(
+    sheet: &FetchedChordSheet,
+    level: SimplifyLevel,
+    collapse_slash: bool,
+) -> SimplifiedSheet {
+    let mut result = sheet.clone();
+    let mut changes: Vec<ChordChange> = Vec::new();
+    let mut merged = 0;
+
+    for section in &mut result.sections {
+        for line in &mut section.lines {
+            let mut previous: Option<String> = None;
+            let chords = std::mem::take(&mut line.chords);
+            for mut chord in chords {
+                let simplified = simplify_chord_name(&chord.chord, level, collapse_slash);
+                if simplified != chord.chord {
+                    match changes.iter_mut().find(|c| c.original == chord.chord) {
+                        Some(change) => change.occurrences += 1,
+                        None => changes.push(ChordChange {
+                            original: chord.chord.clone(),
+                            simplified: simplified.clone(),
+                            occurrences: 1,
+                        }),
+                    }
+                    chord.chord = simplified;
+                }
+
+                if previous.as_deref() == Some(chord.chord.as_str()) {
+                    merged += 1;
+                    continue;
+                }
+                previous = Some(chord.chord.clone());
+                line.chords.push(chord);
+            }
+        }
+    }
+
+    SimplifiedSheet {
+        sheet: result,
+        changes,
+        merged,
+    }
+}
+
+/// Simplify a single chord name; unparseable names are returned unchanged
+pub fn simplify_chord_name(name: &str, level: SimplifyLevel, collapse_slash: bool) -> String {
+    let Some(mut chord) = Chord::parse(name) else {
+        return name.to_string();
+    };
+    let suffix = simple_suffix(&chord, level);
+    // `Cmaj7` -> `CM7` is only a respelling: keep the name as written
+    let same_quality = suffix == chord.suffix
+        || Chord::parse(&format!("{}{}", chord.root_name(), suffix))
+            .is_some_and(|simple| intervals(&simple) == intervals(&chord));
+    if same_quality && !(collapse_slash && chord.bass.is_some()) {
+        return name.to_string();
+    }
+
+    if !same_quality {
+        chord.suffix = suffix;
+    }
+    if collapse_slash {
+        chord.bass = None;
+    }
+    chord.to_string()
+}
+
+fn intervals(chord: &Chord) -> Vec<u8> {
+    let mut intervals: Vec<u8> = chord.tones().iter().map(|t| t.interval).collect();
+    intervals.sort_unstable();
+    intervals
+}
+
+fn simple_suffix(chord: &Chord, level: SimplifyLevel) -> String {
+    if level == SimplifyLevel::Power {
+        return "5".to_string();
+    }
+
+    let triad = match chord.quality {
+        Quality::Major => "",
+        Quality::Minor if chord.is_half_diminished() && level == SimplifyLevel::Triads => "dim",
+        Quality::Minor => "m",
+        Quality::Diminished => "dim",
+        Quality::Augmented => "aug",
+        Quality::Sus2 => "sus2",
+        Quality::Sus4 => "sus4",
+        Quality::Power => "5",
+    };
+    if level == SimplifyLevel::Triads || chord.quality == Quality::Power {
+        return triad.to_string();
+    }
+
+    if chord.is_half_diminished() {
+        return "m7-5".to_string();
+    }
+    match (chord.quality, chord.seventh) {
+        (_, None) => triad.to_string(),
+        (Quality::Diminished, Some(Seventh::Diminished)) => "dim7".to_string(),
+        (Quality::Major, Some(Seventh::Major)) => "M7".to_string(),
+        (Quality::Minor, Some(Seventh::Major)) => "mM7".to_string(),
+        (Quality::Sus2 | Quality::Sus4, Some(_)) => format!("7{}", triad),
+        (_, Some(_)) => format!("{}7", triad),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::{FetchedChord, FetchedLine, FetchedSection};
+
+    fn names(sheet: &FetchedChordSheet) -> Vec<&str> {
+        sheet.sections[0].lines[0]
+            .chords
+            .iter()
+            .map(|c| c.chord.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_simplify_levels() {
+        use SimplifyLevel::*;
+        let cases = [
+            ("Gadd9", Sevenths, "G"),
+            ("Em7(9)", Sevenths, "Em7"),
+            ("Em7(9)", Triads, "Em"),
+            ("Em7(9)", Power, "E5"),
+            ("Bm7-5", Sevenths, "Bm7-5"),
+            ("Bm7-5", Triads, "Bdim"),
+            ("CM9", Sevenths, "CM7"),
+            ("G7sus4", Triads, "Gsus4"),
+            ("Ab13", Sevenths, "Ab7"),
+            ("Cdim7", Triads, "Cdim"),
+            ("Am", Triads, "Am"),
+            ("N.C.", Power, "N.C."),
+            // Respellings are not simplifications
+            ("Cmaj7", Sevenths, "Cmaj7"),
+            ("Bm7(b5)", Sevenths, "Bm7(b5)"),
+            ("Cmin", Triads, "Cmin"),
+        ];
+        for (input, level, expected) in cases {
+            assert_eq!(
+                simplify_chord_name(input, level, false),
+                expected,
+                "{} at {:?}",
+                input,
+                level
+            );
+        }
+    }
+
+    #[test]
+    fn test_slash_chords() {
+        assert_eq!(
+            simplify_chord_name("F#m7/E", SimplifyLevel::Triads, false),
+            "F#m/E"
+        );
+        assert_eq!(
+            simplify_chord_name("F#m7/E", SimplifyLevel::Triads, true),
+            "F#m"
+        );
+        assert_eq!(
+            simplify_chord_name("GonB", SimplifyLevel::Triads, true),
+            "G"
+        );
+        assert_eq!(
+            simplify_chord_name("C6/9", SimplifyLevel::Triads, true),
+            "C"
+        );
+        assert_eq!(
+            simplify_chord_name("Cmaj7/E", SimplifyLevel::Sevenths, true),
+            "Cmaj7"
+        );
+    }
+
+    #[test]
+    fn test_merges_and_reports() {
+        let mut sheet = FetchedChordSheet::new(String::new());
+        let mut section = FetchedSection::new("Aメロ");
+        section.lines.push(FetchedLine::with_chords(
+            "歌詞",
+            vec![
+                FetchedChord::new("Gadd9", 0),
+                FetchedChord::new("G", 2),
+                FetchedChord::new("Em7", 4),
+                FetchedChord::new("Gadd9", 6),
+            ],
+        ));
+        sheet.sections.push(section);
+
+        let result = simplify_sheet(&sheet, SimplifyLevel::Triads, false);
+        assert_eq!(names(&result.sheet), vec!["G", "Em", "G"]);
+        assert_eq!(result.merged, 1);
+        assert_eq!(result.changes.len(), 2);
+        assert_eq!(result.changes[0].original, "Gadd9");
+        assert_eq!(result.changes[0].occurrences, 2);
+        assert_eq!(result.changes[1].simplified, "Em");
+    }
+}
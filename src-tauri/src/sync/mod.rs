@@ -0,0 +1,829 @@
+//! Two-way sync between the local SQLite library and Supabase
+//!
+//! Local changes come from the `sync_log` table, which triggers fill on every
+//! insert, update and delete (migrations/004_sync_log.sql); the frontend sends
+//! each logged row with the time it changed, deletions as tombstones holding
+//! just the key. Remote rows carry `updated_at` (when the row was changed, on
+//! whichever device), `deleted_at` (tombstone) and `synced_at` (when the
+//! server received it, the pull cursor), see
+//! supabase/migrations/005_sync_columns.sql.
+//!
+//! A sync pulls every table since its cursor, settles each row changed on
+//! both sides by last-writer-wins, pushes the remaining local changes and
+//! returns the remote changes for the frontend to write with logging paused.
+//! Lines are the exception: two different edits of one line are held back
+//! as conflicts until an override picks a side. Artists, tags and chord
+//! preferences are also unique by name on the server, so a local row the
+//! server already holds under another id takes that id before settling.
+
+pub mod postgrest;
+
+use crate::error::SyncError;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use postgrest::{PostgrestClient, PostgrestConfig};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::oneshot;
+
+/// How long a queued sync waits for the app to hand over its library
+const INPUT_TIMEOUT_SECS: u64 = 30;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncTable {
+    Artists,
+    Songs,
+    Sections,
+    Lines,
+    Tags,
+    SongTags,
+    Playlists,
+    PlaylistSongs,
+    ChordPreferences,
+}
+
+impl SyncTable {
+    /// Parents before children, so foreign keys resolve when pushing
+    pub const ALL: [SyncTable; 9] = [
+        SyncTable::Artists,
+        SyncTable::Songs,
+        SyncTable::Sections,
+        SyncTable::Lines,
+        SyncTable::Tags,
+        SyncTable::SongTags,
+        SyncTable::Playlists,
+        SyncTable::PlaylistSongs,
+        SyncTable::ChordPreferences,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SyncTable::Artists => "artists",
+            SyncTable::Songs => "songs",
+            SyncTable::Sections => "sections",
+            SyncTable::Lines => "lines",
+            SyncTable::Tags => "tags",
+            SyncTable::SongTags => "song_tags",
+            SyncTable::Playlists => "playlists",
+            SyncTable::PlaylistSongs => "playlist_songs",
+            SyncTable::ChordPreferences => "chord_preferences",
+        }
+    }
+
+    pub fn key_columns(self) -> &'static [&'static str] {
+        match self {
+            SyncTable::SongTags => &["song_id", "tag_id"],
+            SyncTable::PlaylistSongs => &["playlist_id", "song_id"],
+            _ => &["id"],
+        }
+    }
+
+    /// Columns besides `user_id` that Supabase keeps unique, so the same row
+    /// made on two devices meets the other's under a different id
+    fn natural_key(self) -> &'static [&'static str] {
+        match self {
+            SyncTable::Artists | SyncTable::Tags => &["name"],
+            SyncTable::ChordPreferences => &["chord_name", "is_default"],
+            _ => &[],
+        }
+    }
+
+    /// Columns holding the id of a row with a natural key
+    fn references(self) -> &'static [(&'static str, SyncTable)] {
+        match self {
+            SyncTable::Songs => &[("artist_id", SyncTable::Artists)],
+            SyncTable::SongTags => &[("tag_id", SyncTable::Tags)],
+            _ => &[],
+        }
+    }
+
+    /// Columns SQLite keeps as JSON text and Postgres as jsonb
+    fn json_columns(self) -> &'static [&'static str] {
+        match self {
+            SyncTable::Lines => &["chords_json"],
+            SyncTable::ChordPreferences => &["fingering_json"],
+            _ => &[],
+        }
+    }
+
+    /// Columns SQLite keeps as 0/1 and Postgres as boolean
+    fn bool_columns(self) -> &'static [&'static str] {
+        match self {
+            SyncTable::Songs => &["is_favorite"],
+            SyncTable::ChordPreferences => &["is_default"],
+            _ => &[],
+        }
+    }
+
+    /// Timestamp columns the local table has
+    fn local_timestamps(self) -> &'static [&'static str] {
+        match self {
+            SyncTable::Songs | SyncTable::Playlists => &["created_at", "updated_at"],
+            SyncTable::Artists | SyncTable::ChordPreferences => &["created_at"],
+            _ => &[],
+        }
+    }
+}
+
+/// One changed row, local or remote
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RowChange {
+    pub table: SyncTable,
+    /// Full row for a change; just the key columns for a deletion
+    pub row: Value,
+    #[serde(default)]
+    pub deleted: bool,
+    /// When the row was changed
+    pub updated_at: String,
+}
+
+impl RowChange {
+    /// Key columns joined, unique within the table
+    pub fn key(&self) -> String {
+        self.table
+            .key_columns()
+            .iter()
+            .map(|column| match &self.row[*column] {
+                Value::String(text) => text.clone(),
+                other => other.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    /// The row as pushed: JSON columns parsed, tombstone cleared, stamped
+    /// with its change time and owner
+    fn to_remote(&self, user_id: &str) -> Result<Value, SyncError> {
+        let mut row = self.row.as_object().cloned().ok_or_else(|| {
+            SyncError::Invalid(format!("{} row is not an object", self.table.name()))
+        })?;
+        for column in self.table.json_columns() {
+            if let Some(Value::String(text)) = row.get(*column) {
+                let parsed = serde_json::from_str(text).map_err(|e| {
+                    SyncError::Invalid(format!("{}.{}: {}", self.table.name(), column, e))
+                })?;
+                row.insert(column.to_string(), parsed);
+            }
+        }
+        for column in self.table.bool_columns() {
+            if let Some(flag) = row.get(*column).and_then(Value::as_i64) {
+                row.insert(column.to_string(), Value::Bool(flag != 0));
+            }
+        }
+        row.insert("user_id".to_string(), Value::from(user_id));
+        row.insert(
+            "updated_at".to_string(),
+            Value::from(self.updated_at.as_str()),
+        );
+        row.insert("deleted_at".to_string(), Value::Null);
+        Ok(Value::Object(row))
+    }
+
+    /// A pulled row, as a change to write locally
+    fn from_remote(table: SyncTable, remote: Map<String, Value>) -> Self {
+        let deleted = remote.get("deleted_at").is_some_and(|v| !v.is_null());
+        let updated_at = ["updated_at", "synced_at"]
+            .iter()
+            .find_map(|column| remote.get(*column).and_then(Value::as_str))
+            .unwrap_or_default()
+            .to_string();
+
+        let mut row = Map::new();
+        for (column, value) in remote {
+            let server_only = matches!(column.as_str(), "user_id" | "deleted_at" | "synced_at");
+            // A tombstone needs only its key
+            let local =
+                if server_only || (deleted && !table.key_columns().contains(&column.as_str())) {
+                    continue;
+                } else if table.json_columns().contains(&column.as_str()) {
+                    Value::from(value.to_string())
+                } else if table.bool_columns().contains(&column.as_str()) {
+                    Value::from(value.as_bool().map(i64::from).unwrap_or_default())
+                } else if column.ends_with("_at") {
+                    if !table.local_timestamps().contains(&column.as_str()) {
+                        continue;
+                    }
+                    value
+                        .as_str()
+                        .map(|text| Value::from(local_timestamp(text)))
+                        .unwrap_or(value)
+                } else {
+                    value
+                };
+            row.insert(column, local);
+        }
+        Self {
+            table,
+            row: Value::Object(row),
+            deleted,
+            updated_at,
+        }
+    }
+
+    /// Same content on both sides, ignoring when it was written
+    fn same_content(&self, other: &RowChange) -> bool {
+        let content = |change: &RowChange| {
+            let mut row = change.row.as_object().cloned().unwrap_or_default();
+            row.retain(|column, _| !column.ends_with("_at"));
+            // JSON text may differ only in spacing
+            for column in change.table.json_columns() {
+                if let Some(Value::String(text)) = row.get(*column) {
+                    let parsed =
+                        serde_json::from_str(text).unwrap_or_else(|_| Value::from(text.as_str()));
+                    row.insert(column.to_string(), parsed);
+                }
+            }
+            (change.deleted, row)
+        };
+        content(self) == content(other)
+    }
+}
+
+/// A row's natural key, booleans as 0/1 whichever side it came from; none
+/// when a column is unset, as Postgres never matches nulls
+fn natural_key(table: SyncTable, row: &Value) -> Option<Vec<String>> {
+    table
+        .natural_key()
+        .iter()
+        .map(|column| match &row[*column] {
+            Value::Null => None,
+            Value::String(text) => Some(text.clone()),
+            Value::Bool(flag) => Some(i64::from(*flag).to_string()),
+            other => Some(other.to_string()),
+        })
+        .collect()
+}
+
+/// A local row the server already holds under another id
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IdRemap {
+    pub table: SyncTable,
+    pub from: String,
+    pub to: String,
+}
+
+/// Give local rows the id the server holds for their natural key, in the
+/// rows themselves and in the rows referring to them
+///
+/// `remote` maps each natural key to its server id, per table.
+fn remap_ids(
+    changes: &mut [RowChange],
+    remote: &HashMap<SyncTable, HashMap<Vec<String>, String>>,
+) -> Vec<IdRemap> {
+    let mut remapped = Vec::new();
+    for change in changes.iter().filter(|change| !change.deleted) {
+        let Some(known) = remote.get(&change.table) else {
+            continue;
+        };
+        let (Some(key), Some(id)) = (
+            natural_key(change.table, &change.row),
+            change.row["id"].as_str(),
+        ) else {
+            continue;
+        };
+        match known.get(&key) {
+            Some(to) if to != id => remapped.push(IdRemap {
+                table: change.table,
+                from: id.to_string(),
+                to: to.clone(),
+            }),
+            _ => {}
+        }
+    }
+
+    let to = |table: SyncTable, value: &Value| {
+        remapped
+            .iter()
+            .find(|remap| remap.table == table && value.as_str() == Some(remap.from.as_str()))
+            .map(|remap| Value::from(remap.to.as_str()))
+    };
+    for change in changes.iter_mut() {
+        let Some(row) = change.row.as_object_mut() else {
+            continue;
+        };
+        let mut columns = change.table.references().to_vec();
+        if !change.table.natural_key().is_empty() {
+            columns.push(("id", change.table));
+        }
+        for (column, table) in columns {
+            if let Some(id) = row.get(column).and_then(|value| to(table, value)) {
+                row.insert(column.to_string(), id);
+            }
+        }
+    }
+    remapped
+}
+
+/// SQLite's datetime('now') text, RFC 3339 from Postgres or the change log
+pub fn parse_time(text: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(text)
+        .map(|time| time.with_timezone(&Utc))
+        .ok()
+        .or_else(|| {
+            NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S%.f")
+                .ok()
+                .map(|time| time.and_utc())
+        })
+}
+
+/// A remote time in the form SQLite's datetime('now') writes
+fn local_timestamp(text: &str) -> String {
+    parse_time(text)
+        .map(|time| time.format("%Y-%m-%d %H:%M:%S").to_string())
+        .unwrap_or_else(|| text.to_string())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Side {
+    Local,
+    Remote,
+}
+
+/// A line edited differently on this device and another one
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LineConflict {
+    pub line_id: String,
+    pub local: RowChange,
+    pub remote: RowChange,
+}
+
+/// What to do with the changes from both sides
+#[derive(Debug, Default)]
+pub struct SyncPlan {
+    /// Local changes to send
+    pub push: Vec<RowChange>,
+    /// Remote changes to write locally
+    pub apply: Vec<RowChange>,
+    /// Local changes that lost to a newer remote change
+    pub overwritten: usize,
+    pub conflicts: Vec<LineConflict>,
+}
+
+/// Settle local changes against the pulled remote ones
+///
+/// A row changed on one side only goes to the other. A row changed on both
+/// goes to the later writer, the server on a tie; for lines, a different
+/// edit on each side is a conflict unless `overrides` (by line id) picks one.
+pub fn plan(
+    local: Vec<RowChange>,
+    remote: Vec<RowChange>,
+    overrides: &HashMap<String, Side>,
+) -> SyncPlan {
+    let mut plan = SyncPlan::default();
+    let mut pending: HashMap<(SyncTable, String), RowChange> = HashMap::new();
+    for change in local {
+        pending.insert((change.table, change.key()), change);
+    }
+
+    for theirs in remote {
+        let Some(ours) = pending.remove(&(theirs.table, theirs.key())) else {
+            plan.apply.push(theirs);
+            continue;
+        };
+        if ours.same_content(&theirs) {
+            continue;
+        }
+        let side = if theirs.table == SyncTable::Lines {
+            match overrides.get(&theirs.key()) {
+                Some(side) => *side,
+                None => {
+                    plan.conflicts.push(LineConflict {
+                        line_id: theirs.key(),
+                        local: ours,
+                        remote: theirs,
+                    });
+                    continue;
+                }
+            }
+        } else if parse_time(&ours.updated_at) > parse_time(&theirs.updated_at) {
+            Side::Local
+        } else {
+            Side::Remote
+        };
+        match side {
+            Side::Local => plan.push.push(ours),
+            Side::Remote => {
+                plan.overwritten += 1;
+                plan.apply.push(theirs);
+            }
+        }
+    }
+
+    let mut push: Vec<RowChange> = pending.into_values().collect();
+    push.append(&mut plan.push);
+    push.sort_by_key(|change| change.table);
+    plan.push = push;
+    plan
+}
+
+/// Where each table's last pull stopped: the server's `synced_at`
+pub type SyncCursor = BTreeMap<SyncTable, String>;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncResult {
+    /// Remote changes to write locally, with the change log paused
+    pub apply: Vec<RowChange>,
+    pub pushed: usize,
+    pub overwritten: usize,
+    /// Lines left as they are on both sides; their local changes stay in the
+    /// log, and the next sync settles them once an override is given
+    pub conflicts: Vec<LineConflict>,
+    /// Local ids to rewrite, with the rows referring to them, before
+    /// writing `apply`: the server already held these rows under its own id
+    pub remapped: Vec<IdRemap>,
+    /// Cursor for the next sync
+    pub cursor: SyncCursor,
+}
+
+/// What a sync reads from the app: the signed-in session and the change log
+/// as they are when it runs
+#[derive(Debug, Clone, Deserialize)]
+pub struct SyncInput {
+    pub config: PostgrestConfig,
+    #[serde(default)]
+    pub cursor: SyncCursor,
+    pub changes: Vec<RowChange>,
+    #[serde(default)]
+    pub overrides: HashMap<String, Side>,
+}
+
+/// Hands a queued sync its input when it runs, so the queue keeps neither
+/// credentials nor a change list that goes stale while it waits
+#[derive(Default)]
+pub struct SyncInputs {
+    waiting: Mutex<Option<oneshot::Sender<Option<SyncInput>>>>,
+}
+
+impl SyncInputs {
+    /// Ask the app for the input with `ask` and wait for its answer, which is
+    /// empty when nobody is signed in
+    pub async fn request(&self, ask: impl FnOnce()) -> Result<SyncInput, SyncError> {
+        let (sender, receiver) = oneshot::channel();
+        *self.waiting.lock().unwrap_or_else(|e| e.into_inner()) = Some(sender);
+        ask();
+        let answer = tokio::time::timeout(Duration::from_secs(INPUT_TIMEOUT_SECS), receiver).await;
+        self.waiting
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take();
+        match answer {
+            Ok(Ok(Some(input))) => Ok(input),
+            Ok(Ok(None)) => Err(SyncError::SignedOut),
+            Ok(Err(_)) | Err(_) => Err(SyncError::NoInput),
+        }
+    }
+
+    /// Answer the waiting request; false when none is waiting
+    pub fn provide(&self, input: Option<SyncInput>) -> bool {
+        let waiting = self
+            .waiting
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take();
+        waiting.is_some_and(|sender| sender.send(input).is_ok())
+    }
+}
+
+/// Pull, settle and push; every change-log entry sent can be cleared
+/// afterwards except the conflicted lines
+pub async fn sync(
+    client: &PostgrestClient,
+    cursor: &SyncCursor,
+    changes: Vec<RowChange>,
+    overrides: &HashMap<String, Side>,
+) -> Result<SyncResult, SyncError> {
+    let mut remote = Vec::new();
+    let mut next = cursor.clone();
+    // When each pulled row was received, to hold the cursor before conflicts
+    let mut received: HashMap<(SyncTable, String), String> = HashMap::new();
+    for table in SyncTable::ALL {
+        for row in client
+            .pull(table, cursor.get(&table).map(String::as_str))
+            .await?
+        {
+            let Value::Object(row) = row else {
+                return Err(SyncError::Invalid(format!(
+                    "{} row is not an object",
+                    table.name()
+                )));
+            };
+            let synced_at = row
+                .get("synced_at")
+                .and_then(Value::as_str)
+                .map(str::to_string);
+            let change = RowChange::from_remote(table, row);
+            if let Some(synced_at) = synced_at {
+                received.insert((table, change.key()), synced_at.clone());
+                let later = next
+                    .get(&table)
+                    .is_none_or(|last| parse_time(&synced_at) > parse_time(last));
+                if later {
+                    next.insert(table, synced_at);
+                }
+            }
+            remote.push(change);
+        }
+    }
+
+    // Rows made on two devices meet on their natural key, not their id
+    let mut changes = changes;
+    let mut known = HashMap::new();
+    for table in SyncTable::ALL {
+        let pushing = changes
+            .iter()
+            .any(|change| change.table == table && !change.deleted);
+        if table.natural_key().is_empty() || !pushing {
+            continue;
+        }
+        let ids: HashMap<Vec<String>, String> = client
+            .natural_keys(table)
+            .await?
+            .iter()
+            .filter_map(|row| Some((natural_key(table, row)?, row["id"].as_str()?.to_string())))
+            .collect();
+        known.insert(table, ids);
+    }
+    let remapped = remap_ids(&mut changes, &known);
+
+    let plan = plan(changes, remote, overrides);
+
+    // Conflicted lines must come back on the next pull
+    let held = plan
+        .conflicts
+        .iter()
+        .filter_map(|c| received.get(&(SyncTable::Lines, c.line_id.clone())))
+        .min_by_key(|synced_at| parse_time(synced_at));
+    if let Some(held) = held {
+        let before = received
+            .iter()
+            .filter(|((table, _), at)| {
+                *table == SyncTable::Lines && parse_time(at) < parse_time(held)
+            })
+            .map(|(_, at)| at)
+            .max_by_key(|at| parse_time(at));
+        match before.or(cursor.get(&SyncTable::Lines)) {
+            Some(at) => next.insert(SyncTable::Lines, at.clone()),
+            None => next.remove(&SyncTable::Lines),
+        };
+    }
+
+    for table in SyncTable::ALL {
+        let rows = plan
+            .push
+            .iter()
+            .filter(|change| change.table == table && !change.deleted)
+            .map(|change| change.to_remote(client.user_id()))
+            .collect::<Result<Vec<_>, _>>()?;
+        client.upsert(table, &rows).await?;
+    }
+    for change in plan.push.iter().filter(|change| change.deleted) {
+        client.tombstone(change, &change.updated_at).await?;
+    }
+
+    // Step the cursor over this sync's own writes so the next pull leaves
+    // them out, stopping at the first row another device wrote meanwhile
+    let pushed: HashMap<(SyncTable, String), Option<DateTime<Utc>>> = plan
+        .push
+        .iter()
+        .map(|change| ((change.table, change.key()), parse_time(&change.updated_at)))
+        .collect();
+    for table in SyncTable::ALL {
+        if !plan.push.iter().any(|change| change.table == table) {
+            continue;
+        }
+        for row in client
+            .pull(table, next.get(&table).map(String::as_str))
+            .await?
+        {
+            let Value::Object(row) = row else {
+                break;
+            };
+            let Some(synced_at) = row
+                .get("synced_at")
+                .and_then(Value::as_str)
+                .map(str::to_string)
+            else {
+                break;
+            };
+            let change = RowChange::from_remote(table, row);
+            if pushed.get(&(table, change.key())) != Some(&parse_time(&change.updated_at)) {
+                break;
+            }
+            next.insert(table, synced_at);
+        }
+    }
+
+    Ok(SyncResult {
+        apply: plan.apply,
+        pushed: plan.push.len(),
+        overwritten: plan.overwritten,
+        conflicts: plan.conflicts,
+        remapped,
+        cursor: next,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn change(table: SyncTable, row: Value, updated_at: &str) -> RowChange {
+        RowChange {
+            table,
+            row,
+            deleted: false,
+            updated_at: updated_at.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_parse_time() {
+        let sqlite = parse_time("2024-05-01 10:00:00").unwrap();
+        let postgres = parse_time("2024-05-01T19:00:00+09:00").unwrap();
+        assert_eq!(sqlite, postgres);
+        assert!(parse_time("2024-05-01T10:00:00.500Z").unwrap() > sqlite);
+        assert_eq!(
+            local_timestamp("2024-05-01T19:00:00.123+09:00"),
+            "2024-05-01 10:00:00"
+        );
+    }
+
+    #[test]
+    fn test_row_conversion() {
+        let line = change(
+            SyncTable::Lines,
+            json!({"id": "l1", "section_id": "s1", "lyrics": "Hi", "chords_json": "[{\"chord\":\"C\",\"position\":0}]"}),
+            "2024-05-01T10:00:00Z",
+        );
+        let remote = line.to_remote("user-1").unwrap();
+        assert_eq!(remote["chords_json"][0]["chord"], "C");
+        assert_eq!(remote["user_id"], "user-1");
+        assert_eq!(remote["updated_at"], "2024-05-01T10:00:00Z");
+        assert!(remote["deleted_at"].is_null());
+
+        let song = json!({
+            "id": "s1", "user_id": "user-1", "title": "Song", "is_favorite": true,
+            "created_at": "2024-05-01T10:00:00+00:00", "updated_at": "2024-05-02T10:00:00+00:00",
+            "synced_at": "2024-05-02T10:00:01+00:00", "deleted_at": null
+        });
+        let Value::Object(song) = song else {
+            unreachable!()
+        };
+        let local = RowChange::from_remote(SyncTable::Songs, song);
+        assert!(!local.deleted);
+        assert_eq!(local.updated_at, "2024-05-02T10:00:00+00:00");
+        assert_eq!(local.row["is_favorite"], 1);
+        assert_eq!(local.row["updated_at"], "2024-05-02 10:00:00");
+        assert!(local.row.get("user_id").is_none());
+        assert!(local.row.get("synced_at").is_none());
+
+        let tag = json!({"song_id": "s1", "tag_id": "t1", "updated_at": "2024-05-02T10:00:00+00:00", "deleted_at": "2024-05-02T10:00:00+00:00"});
+        let Value::Object(tag) = tag else {
+            unreachable!()
+        };
+        let local = RowChange::from_remote(SyncTable::SongTags, tag);
+        assert!(local.deleted);
+        assert_eq!(local.key(), "s1/t1");
+        assert_eq!(local.row, json!({"song_id": "s1", "tag_id": "t1"}));
+    }
+
+    #[test]
+    fn test_plan_last_writer_wins() {
+        let song = |title: &str, at: &str| {
+            change(SyncTable::Songs, json!({"id": "s1", "title": title}), at)
+        };
+        let local = vec![
+            song("Mine", "2024-05-02 10:00:00"),
+            change(
+                SyncTable::Tags,
+                json!({"id": "t1", "name": "Old"}),
+                "2024-05-01 10:00:00",
+            ),
+            change(
+                SyncTable::Artists,
+                json!({"id": "a1", "name": "New"}),
+                "2024-05-01 10:00:00",
+            ),
+        ];
+        let remote = vec![
+            song("Theirs", "2024-05-01T10:00:00+00:00"),
+            change(
+                SyncTable::Tags,
+                json!({"id": "t1", "name": "Newer"}),
+                "2024-05-01T10:00:00+00:00",
+            ),
+            change(
+                SyncTable::Playlists,
+                json!({"id": "p1", "name": "Set"}),
+                "2024-05-01T10:00:00+00:00",
+            ),
+        ];
+        let plan = plan(local, remote, &HashMap::new());
+
+        // Pushed parents first
+        let pushed: Vec<_> = plan.push.iter().map(|c| (c.table, c.key())).collect();
+        assert_eq!(
+            pushed,
+            vec![
+                (SyncTable::Artists, "a1".to_string()),
+                (SyncTable::Songs, "s1".to_string())
+            ]
+        );
+        // A tie goes to the server
+        let applied: Vec<_> = plan.apply.iter().map(|c| c.key()).collect();
+        assert_eq!(applied, vec!["t1", "p1"]);
+        assert_eq!(plan.overwritten, 1);
+        assert!(plan.conflicts.is_empty());
+    }
+
+    #[test]
+    fn test_plan_line_conflicts() {
+        let line = |lyrics: &str, at: &str| {
+            change(SyncTable::Lines, json!({"id": "l1", "lyrics": lyrics}), at)
+        };
+        let local = || vec![line("Mine", "2024-05-02 10:00:00")];
+        let remote = || vec![line("Theirs", "2024-05-01T10:00:00+00:00")];
+
+        let held = plan(local(), remote(), &HashMap::new());
+        assert!(held.push.is_empty() && held.apply.is_empty());
+        assert_eq!(held.conflicts.len(), 1);
+        assert_eq!(held.conflicts[0].line_id, "l1");
+        assert_eq!(held.conflicts[0].remote.row["lyrics"], "Theirs");
+
+        // The older remote edit still wins when picked
+        let overrides = HashMap::from([("l1".to_string(), Side::Remote)]);
+        let settled = plan(local(), remote(), &overrides);
+        assert!(settled.conflicts.is_empty());
+        assert_eq!(settled.apply[0].row["lyrics"], "Theirs");
+
+        // The same edit on both sides is no conflict
+        let same = plan(
+            local(),
+            vec![line("Mine", "2024-05-01T10:00:00+00:00")],
+            &HashMap::new(),
+        );
+        assert!(same.conflicts.is_empty() && same.push.is_empty() && same.apply.is_empty());
+    }
+
+    #[test]
+    fn test_remap_chord_preferences() {
+        let mut changes = vec![
+            change(
+                SyncTable::ChordPreferences,
+                json!({"id": "c2", "chord_name": "F", "is_default": 1}),
+                "2024-05-02 10:00:00",
+            ),
+            change(
+                SyncTable::ChordPreferences,
+                json!({"id": "c3", "chord_name": "F", "is_default": 0}),
+                "2024-05-02 10:00:00",
+            ),
+        ];
+        // The server's booleans match SQLite's 0/1
+        let remote = json!({"id": "c1", "chord_name": "F", "is_default": true});
+        let known = HashMap::from([(
+            SyncTable::ChordPreferences,
+            HashMap::from([(
+                natural_key(SyncTable::ChordPreferences, &remote).unwrap(),
+                "c1".to_string(),
+            )]),
+        )]);
+        let remapped = remap_ids(&mut changes, &known);
+        assert_eq!(remapped.len(), 1);
+        assert_eq!(
+            (remapped[0].from.as_str(), remapped[0].to.as_str()),
+            ("c2", "c1")
+        );
+        assert_eq!(changes[0].row["id"], "c1");
+        assert_eq!(changes[1].row["id"], "c3");
+    }
+
+    #[tokio::test]
+    async fn test_sync_inputs_handed_over_on_request() {
+        let inputs = std::sync::Arc::new(SyncInputs::default());
+        assert!(!inputs.provide(None));
+
+        let input: SyncInput = serde_json::from_value(json!({
+            "config": {"url": "https://x.supabase.co", "api_key": "anon", "access_token": "fresh", "user_id": "u"},
+            "changes": []
+        }))
+        .unwrap();
+        let answer = |input: Option<SyncInput>| {
+            let inputs = inputs.clone();
+            move || {
+                tokio::spawn(async move { assert!(inputs.provide(input)) });
+            }
+        };
+        let received = inputs.request(answer(Some(input))).await.unwrap();
+        assert_eq!(received.config.access_token.as_deref(), Some("fresh"));
+        assert!(matches!(
+            inputs.request(answer(None)).await,
+            Err(SyncError::SignedOut)
+        ));
+    }
+}
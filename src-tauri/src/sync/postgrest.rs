@@ -0,0 +1,463 @@
+//! PostgREST client for the Supabase tables
+//!
+//! Rows are read with `GET /rest/v1/<table>`, written with an upsert
+//! (`POST` with `Prefer: resolution=merge-duplicates`) and deleted by
+//! `PATCH`ing `deleted_at`, so other devices see the tombstone on their next
+//! pull. Requests carry the project's anon key and, once signed in, the
+//! user's access token, which row level security checks against `user_id`.
+
+use super::{RowChange, SyncTable};
+use crate::error::SyncError;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::time::Duration;
+use tauri_plugin_http::reqwest::{Client, Method, RequestBuilder, Response};
+use url::Url;
+
+/// Rows fetched per request
+const PAGE_SIZE: usize = 1000;
+
+const TIMEOUT_SECS: u64 = 30;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PostgrestConfig {
+    /// Project URL, e.g. https://xyz.supabase.co; `/rest/v1` is appended
+    pub url: String,
+    pub api_key: String,
+    /// Signed-in user's JWT; the anon key is sent when there is none
+    #[serde(default)]
+    pub access_token: Option<String>,
+    pub user_id: String,
+}
+
+pub struct PostgrestClient {
+    client: Client,
+    config: PostgrestConfig,
+}
+
+impl PostgrestClient {
+    pub fn new(config: PostgrestConfig) -> Result<Self, SyncError> {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(TIMEOUT_SECS))
+            .build()?;
+        Ok(Self { client, config })
+    }
+
+    pub fn user_id(&self) -> &str {
+        &self.config.user_id
+    }
+
+    fn table_url(&self, table: SyncTable) -> Result<Url, SyncError> {
+        let base = self.config.url.trim_end_matches('/');
+        Url::parse(&format!("{}/rest/v1/{}", base, table.name()))
+            .map_err(|e| SyncError::Invalid(format!("Bad sync URL {}: {}", base, e)))
+    }
+
+    fn request(&self, method: Method, url: Url) -> RequestBuilder {
+        let token = self
+            .config
+            .access_token
+            .as_deref()
+            .unwrap_or(&self.config.api_key);
+        self.client
+            .request(method, url)
+            .header("apikey", &self.config.api_key)
+            .bearer_auth(token)
+    }
+
+    async fn check(response: Response) -> Result<Response, SyncError> {
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+        Err(SyncError::Server {
+            status: status.as_u16(),
+            body: response.text().await.unwrap_or_default(),
+        })
+    }
+
+    /// Rows the server received after `since`, oldest first
+    pub async fn pull(
+        &self,
+        table: SyncTable,
+        since: Option<&str>,
+    ) -> Result<Vec<Value>, SyncError> {
+        self.read(table, "*", "synced_at.asc", since).await
+    }
+
+    /// Every row's id and natural key, tombstones included since they still
+    /// hold the key
+    pub async fn natural_keys(&self, table: SyncTable) -> Result<Vec<Value>, SyncError> {
+        let select = ["id"]
+            .iter()
+            .chain(table.natural_key())
+            .copied()
+            .collect::<Vec<_>>()
+            .join(",");
+        self.read(table, &select, "id.asc", None).await
+    }
+
+    async fn read(
+        &self,
+        table: SyncTable,
+        select: &str,
+        order: &str,
+        since: Option<&str>,
+    ) -> Result<Vec<Value>, SyncError> {
+        let mut rows = Vec::new();
+        loop {
+            let mut url = self.table_url(table)?;
+            {
+                let mut query = url.query_pairs_mut();
+                query
+                    .append_pair("select", select)
+                    .append_pair("order", order)
+                    .append_pair("limit", &PAGE_SIZE.to_string())
+                    .append_pair("offset", &rows.len().to_string());
+                if let Some(since) = since {
+                    query.append_pair("synced_at", &format!("gt.{}", since));
+                }
+            }
+            let response = self.request(Method::GET, url).send().await?;
+            let page: Vec<Value> = Self::check(response).await?.json().await?;
+            let done = page.len() < PAGE_SIZE;
+            rows.extend(page);
+            if done {
+                return Ok(rows);
+            }
+        }
+    }
+
+    /// Insert rows or update the ones whose key already exists
+    pub async fn upsert(&self, table: SyncTable, rows: &[Value]) -> Result<(), SyncError> {
+        if rows.is_empty() {
+            return Ok(());
+        }
+        let mut url = self.table_url(table)?;
+        url.query_pairs_mut()
+            .append_pair("on_conflict", &table.key_columns().join(","));
+        let response = self
+            .request(Method::POST, url)
+            .header("Prefer", "resolution=merge-duplicates,return=minimal")
+            .json(rows)
+            .send()
+            .await?;
+        Self::check(response).await?;
+        Ok(())
+    }
+
+    /// Mark a row deleted; a row the server never had is left alone
+    pub async fn tombstone(&self, change: &RowChange, at: &str) -> Result<(), SyncError> {
+        let mut url = self.table_url(change.table)?;
+        {
+            let mut query = url.query_pairs_mut();
+            for column in change.table.key_columns() {
+                let value = change.row[*column].as_str().ok_or_else(|| {
+                    SyncError::Invalid(format!(
+                        "{} tombstone lacks {}",
+                        change.table.name(),
+                        column
+                    ))
+                })?;
+                query.append_pair(column, &format!("eq.{}", value));
+            }
+        }
+        let response = self
+            .request(Method::PATCH, url)
+            .header("Prefer", "return=minimal")
+            .json(&serde_json::json!({ "deleted_at": at, "updated_at": at }))
+            .send()
+            .await?;
+        Self::check(response).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{sync, IdRemap, Side, SyncCursor};
+    use super::*;
+    use serde_json::json;
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+
+    type Tables = Arc<Mutex<HashMap<String, Vec<Value>>>>;
+
+    const CONFLICT: &str =
+        r#"{"code":"23505","message":"duplicate key value violates unique constraint"}"#;
+
+    /// Just enough of PostgREST for a sync: filtered GETs, upserts and PATCHes.
+    /// `synced_at` is stamped from a counter, as the server's clock would, and
+    /// artist and tag names are unique per user.
+    async fn serve(listener: TcpListener, tables: Tables) {
+        let mut clock = 0u32;
+        loop {
+            let Ok((stream, _)) = listener.accept().await else {
+                return;
+            };
+            let mut stream = BufReader::new(stream);
+            let mut request_line = String::new();
+            stream.read_line(&mut request_line).await.unwrap();
+            let mut length = 0;
+            let mut authorized = false;
+            loop {
+                let mut header = String::new();
+                stream.read_line(&mut header).await.unwrap();
+                let header = header.trim().to_lowercase();
+                if header.is_empty() {
+                    break;
+                }
+                if let Some(value) = header.strip_prefix("content-length:") {
+                    length = value.trim().parse().unwrap();
+                }
+                authorized |= header == "authorization: bearer user-token";
+            }
+            let mut body = vec![0; length];
+            stream.read_exact(&mut body).await.unwrap();
+
+            let mut parts = request_line.split_whitespace();
+            let method = parts.next().unwrap().to_string();
+            let url = Url::parse(&format!("http://mock{}", parts.next().unwrap())).unwrap();
+            let table = url.path().trim_start_matches("/rest/v1/").to_string();
+            let query: HashMap<String, String> = url.query_pairs().into_owned().collect();
+
+            let reply = {
+                let mut tables = tables.lock().unwrap();
+                let rows = tables.entry(table.clone()).or_default();
+                clock += 1;
+                let now = format!("2024-06-01T00:00:{:02}+00:00", clock);
+                match method.as_str() {
+                    _ if !authorized => "[]".to_string(),
+                    "GET" => {
+                        let since = query.get("synced_at").map(|s| s.trim_start_matches("gt."));
+                        let found: Vec<&Value> = rows
+                            .iter()
+                            .filter(|row| {
+                                since.is_none_or(|since| row["synced_at"].as_str().unwrap() > since)
+                            })
+                            .collect();
+                        serde_json::to_string(&found).unwrap()
+                    }
+                    "POST" => {
+                        let keys: Vec<String> = query["on_conflict"]
+                            .split(',')
+                            .map(str::to_string)
+                            .collect();
+                        let unique: &[&str] = match table.as_str() {
+                            "artists" | "tags" => &["user_id", "name"],
+                            _ => &[],
+                        };
+                        let incoming: Vec<Value> = serde_json::from_slice(&body).unwrap();
+                        let taken = incoming.iter().any(|row| {
+                            !unique.is_empty()
+                                && rows.iter().any(|old| {
+                                    keys.iter().any(|k| old[k] != row[k])
+                                        && unique.iter().all(|u| old[u] == row[u])
+                                })
+                        });
+                        if taken {
+                            CONFLICT.to_string()
+                        } else {
+                            for mut row in incoming {
+                                row["synced_at"] = json!(now);
+                                rows.retain(|old| keys.iter().any(|k| old[k] != row[k]));
+                                rows.push(row);
+                            }
+                            String::new()
+                        }
+                    }
+                    "PATCH" => {
+                        let patch: Value = serde_json::from_slice(&body).unwrap();
+                        for row in rows.iter_mut() {
+                            let matches = query.iter().all(|(column, value)| {
+                                row[column].as_str() == value.strip_prefix("eq.")
+                            });
+                            if matches {
+                                for (column, value) in patch.as_object().unwrap() {
+                                    row[column] = value.clone();
+                                }
+                                row["synced_at"] = json!(now);
+                            }
+                        }
+                        String::new()
+                    }
+                    _ => String::new(),
+                }
+            };
+            let status = if reply.is_empty() {
+                "204 No Content"
+            } else if reply == CONFLICT {
+                "409 Conflict"
+            } else {
+                "200 OK"
+            };
+            let response = format!(
+                "HTTP/1.1 {}\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                status,
+                reply.len(),
+                reply
+            );
+            stream
+                .get_mut()
+                .write_all(response.as_bytes())
+                .await
+                .unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sync_against_mock_server() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let tables: Tables = Arc::default();
+        tables.lock().unwrap().insert(
+            "songs".to_string(),
+            vec![json!({
+                "id": "s1", "user_id": "user-1", "title": "Edited elsewhere", "is_favorite": true,
+                "updated_at": "2024-05-03T00:00:00+00:00", "synced_at": "2024-05-03T00:00:01+00:00",
+                "deleted_at": null
+            })],
+        );
+        tokio::spawn(serve(listener, tables.clone()));
+
+        let client = PostgrestClient::new(PostgrestConfig {
+            url: format!("http://{}/", address),
+            api_key: "anon".to_string(),
+            access_token: Some("user-token".to_string()),
+            user_id: "user-1".to_string(),
+        })
+        .unwrap();
+        let changes = vec![
+            // Older than the remote edit: overwritten
+            RowChange {
+                table: SyncTable::Songs,
+                row: json!({"id": "s1", "title": "Edited here", "is_favorite": 0}),
+                deleted: false,
+                updated_at: "2024-05-02 00:00:00".to_string(),
+            },
+            RowChange {
+                table: SyncTable::Lines,
+                row: json!({"id": "l1", "section_id": "x1", "lyrics": "New line", "chords_json": "[]", "order_index": 0}),
+                deleted: false,
+                updated_at: "2024-05-04T00:00:00.000Z".to_string(),
+            },
+            RowChange {
+                table: SyncTable::SongTags,
+                row: json!({"song_id": "s1", "tag_id": "t1"}),
+                deleted: true,
+                updated_at: "2024-05-04T00:00:00.000Z".to_string(),
+            },
+        ];
+        let result = sync(
+            &client,
+            &SyncCursor::new(),
+            changes,
+            &HashMap::<String, Side>::new(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(result.overwritten, 1);
+        assert_eq!(result.pushed, 2);
+        assert_eq!(result.apply.len(), 1);
+        assert_eq!(result.apply[0].row["title"], "Edited elsewhere");
+        assert_eq!(result.apply[0].row["is_favorite"], 1);
+        assert_eq!(
+            result.cursor.get(&SyncTable::Songs).map(String::as_str),
+            Some("2024-05-03T00:00:01+00:00")
+        );
+
+        let line = tables.lock().unwrap()["lines"][0].clone();
+        assert_eq!(line["user_id"], "user-1");
+        assert_eq!(line["chords_json"], json!([]));
+        assert_eq!(line["updated_at"], "2024-05-04T00:00:00.000Z");
+
+        // The next pull leaves out what this sync pushed
+        let again = sync(&client, &result.cursor, Vec::new(), &HashMap::new())
+            .await
+            .unwrap();
+        assert!(again.apply.is_empty());
+        assert_eq!(again.cursor, result.cursor);
+    }
+
+    #[tokio::test]
+    async fn test_sync_matches_rows_on_natural_key() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let tables: Tables = Arc::default();
+        let stamp = json!({
+            "user_id": "user-1", "created_at": "2024-05-01T00:00:00+00:00",
+            "updated_at": "2024-05-01T00:00:00+00:00", "synced_at": "2024-05-01T00:00:01+00:00",
+            "deleted_at": null
+        });
+        let remote = |id: &str, name: &str| {
+            let mut row = stamp.clone();
+            row["id"] = json!(id);
+            row["name"] = json!(name);
+            row
+        };
+        {
+            let mut tables = tables.lock().unwrap();
+            tables.insert("artists".to_string(), vec![remote("a1", "Band")]);
+            tables.insert("tags".to_string(), vec![remote("t1", "Live")]);
+        }
+        tokio::spawn(serve(listener, tables.clone()));
+
+        let client = PostgrestClient::new(PostgrestConfig {
+            url: format!("http://{}", address),
+            api_key: "anon".to_string(),
+            access_token: Some("user-token".to_string()),
+            user_id: "user-1".to_string(),
+        })
+        .unwrap();
+        let local = |table, row| RowChange {
+            table,
+            row,
+            deleted: false,
+            updated_at: "2024-05-05T00:00:00.000Z".to_string(),
+        };
+        // Made on this device before it first synced
+        let changes = vec![
+            local(SyncTable::Artists, json!({"id": "a2", "name": "Band"})),
+            local(
+                SyncTable::Songs,
+                json!({"id": "s2", "title": "Song", "artist_id": "a2", "is_favorite": 0}),
+            ),
+            local(SyncTable::Tags, json!({"id": "t2", "name": "Live"})),
+            local(
+                SyncTable::SongTags,
+                json!({"song_id": "s2", "tag_id": "t2"}),
+            ),
+        ];
+        let result = sync(&client, &SyncCursor::new(), changes, &HashMap::new())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            result.remapped,
+            vec![
+                IdRemap {
+                    table: SyncTable::Artists,
+                    from: "a2".to_string(),
+                    to: "a1".to_string()
+                },
+                IdRemap {
+                    table: SyncTable::Tags,
+                    from: "t2".to_string(),
+                    to: "t1".to_string()
+                },
+            ]
+        );
+        // Once renamed, the artist and tag match the server's and only the
+        // rows referring to them go up
+        assert!(result.apply.is_empty());
+        assert_eq!(result.pushed, 2);
+        let tables = tables.lock().unwrap();
+        assert_eq!(tables["artists"].len(), 1);
+        assert_eq!(tables["artists"][0]["id"], "a1");
+        assert_eq!(tables["songs"][0]["artist_id"], "a1");
+        assert_eq!(tables["song_tags"][0]["tag_id"], "t1");
+    }
+}
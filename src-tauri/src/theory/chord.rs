@@ -0,0 +1,574 @@
+//! Chord symbol parsing
+//!
+//! Handles the notations seen on the supported sites:
+//! - Slash chords: `D/F#`, `Am7/G`
+//! - 楽器.me on-chords: `GonB`
+//! - Japanese-style alterations: `Bm7-5`, `Em7(9)`, `C7(#9)`
+//! - Common aliases: `M7`/`maj7`/`Δ7`, `m`/`min`/`-`, `dim`/`°`, `aug`/`+`, `ø`
+
+use super::{note_name, parse_note, pitch_class, uses_flat};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Triad quality (what replaces or colours the third and fifth)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Quality {
+    Major,
+    Minor,
+    Diminished,
+    Augmented,
+    Sus2,
+    Sus4,
+    /// Root and fifth only (`C5`)
+    Power,
+}
+
+/// Seventh added on top of the triad
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Seventh {
+    /// Flat seventh (`C7`, `Cm7`)
+    Minor,
+    /// Major seventh (`CM7`, `CmM7`)
+    Major,
+    /// Double-flat seventh (`Cdim7`)
+    Diminished,
+}
+
+/// How a bass note was written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BassNotation {
+    Slash,
+    On,
+}
+
+/// A parsed chord symbol
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chord {
+    pub root: u8,
+    /// Suffix exactly as written (kept verbatim on transposition)
+    pub suffix: String,
+    pub bass: Option<u8>,
+    pub bass_notation: BassNotation,
+    pub prefer_flat: bool,
+    pub quality: Quality,
+    pub seventh: Option<Seventh>,
+    pub sixth: bool,
+    /// Highest stacked extension (9, 11 or 13)
+    pub extension: Option<u8>,
+    /// Added tones without the seventh (`add9` = 9, `add4` = 11)
+    pub added: Vec<u8>,
+    /// Altered degrees as (degree, semitone shift), e.g. (5, -1) for `-5`
+    pub alterations: Vec<(u8, i8)>,
+    /// Degrees explicitly removed (`omit3`, `no5`)
+    pub omitted: Vec<u8>,
+}
+
+/// A chord tone relative to the root
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChordTone {
+    /// Semitones above the root, in `0..12`
+    pub interval: u8,
+    /// Voicings may drop tones that are not required
+    pub required: bool,
+}
+
+impl Chord {
+    /// Parse a chord symbol; returns None if it does not start with a note name
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = text.trim();
+        let (root, root_len) = parse_note(text)?;
+        let rest = &text[root_len..];
+
+        let (suffix, bass, bass_notation) = split_bass(rest);
+
+        let mut chord = Chord {
+            root,
+            suffix: suffix.to_string(),
+            bass: bass.map(|(pc, _)| pc),
+            bass_notation,
+            prefer_flat: uses_flat(text) || bass.is_some_and(|(_, flat)| flat),
+            quality: Quality::Major,
+            seventh: None,
+            sixth: false,
+            extension: None,
+            added: Vec::new(),
+            alterations: Vec::new(),
+            omitted: Vec::new(),
+        };
+        chord.parse_suffix(suffix);
+        Some(chord)
+    }
+
+    fn parse_suffix(&mut self, suffix: &str) {
+        let normalized = suffix
+            .replace('♯', "#")
+            .replace('♭', "b")
+            .replace('Δ', "maj")
+            .replace('°', "dim")
+            .replace('ø', "m7b5");
+        let mut s = normalized.as_str();
+        let mut at_start = true;
+
+        while !s.is_empty() {
+            // Separators carry no meaning: "m7(9)" == "m79" with the 9 as an extension
+            if let Some(rest) = s.strip_prefix(['(', ')', ',', ' ', '/']) {
+                s = rest;
+                continue;
+            }
+
+            if at_start {
+                at_start = false;
+                if let Some(rest) = strip_any(s, &["mM", "mmaj", "minmaj", "m(maj", "-maj"]) {
+                    self.quality = Quality::Minor;
+                    self.seventh = Some(Seventh::Major);
+                    s = self.take_number_or(rest, 7);
+                    continue;
+                }
+                if let Some(rest) = strip_any(s, &["maj", "Maj", "M"]) {
+                    self.seventh = Some(Seventh::Major);
+                    s = self.take_number_or(rest, 7);
+                    continue;
+                }
+                if let Some(rest) = strip_any(s, &["min", "mi", "m", "-"]) {
+                    self.quality = Quality::Minor;
+                    s = rest;
+                    continue;
+                }
+                if let Some(rest) = s.strip_prefix("dim") {
+                    self.quality = Quality::Diminished;
+                    s = match rest.strip_prefix('7') {
+                        Some(rest) => {
+                            self.seventh = Some(Seventh::Diminished);
+                            rest
+                        }
+                        None => rest,
+                    };
+                    continue;
+                }
+                if let Some(rest) = strip_any(s, &["aug", "+"]) {
+                    self.quality = Quality::Augmented;
+                    s = rest;
+                    continue;
+                }
+                if let Some(rest) = s.strip_prefix('5') {
+                    if !rest.starts_with(|c: char| c.is_ascii_digit()) {
+                        self.quality = Quality::Power;
+                        s = rest;
+                        continue;
+                    }
+                }
+            }
+
+            if let Some(rest) = strip_any(s, &["maj", "Maj", "M"]) {
+                self.seventh = Some(Seventh::Major);
+                s = self.take_number_or(rest, 7);
+                continue;
+            }
+            if let Some(rest) = s.strip_prefix("sus") {
+                let (degree, rest) = take_number(rest);
+                self.quality = if degree == Some(2) || degree == Some(9) {
+                    Quality::Sus2
+                } else {
+                    Quality::Sus4
+                };
+                s = rest;
+                continue;
+            }
+            if let Some(rest) = s.strip_prefix("add") {
+                let (degree, rest) = take_number(rest);
+                if let Some(degree) = degree {
+                    self.added.push(normalize_degree(degree));
+                }
+                s = rest;
+                continue;
+            }
+            if let Some(rest) = strip_any(s, &["omit", "no"]) {
+                let (degree, rest) = take_number(rest);
+                if let Some(degree) = degree {
+                    self.omitted.push(degree);
+                }
+                s = rest;
+                continue;
+            }
+            if let Some(rest) = s.strip_prefix("dim") {
+                // "7dim5" style flat fifth
+                self.alterations.push((5, -1));
+                let (_, rest) = take_number(rest);
+                s = rest;
+                continue;
+            }
+            if let Some(rest) = strip_any(s, &["aug", "+"]) {
+                let (degree, rest) = take_number(rest);
+                self.alterations.push((degree.unwrap_or(5), 1));
+                s = rest;
+                continue;
+            }
+            if let Some(rest) = strip_any(s, &["b", "-"]) {
+                let (degree, rest) = take_number(rest);
+                if let Some(degree) = degree {
+                    self.alterations.push((degree, -1));
+                }
+                s = rest;
+                continue;
+            }
+            if let Some(rest) = s.strip_prefix('#') {
+                let (degree, rest) = take_number(rest);
+                if let Some(degree) = degree {
+                    self.alterations.push((degree, 1));
+                }
+                s = rest;
+                continue;
+            }
+
+            let (number, rest) = take_number(s);
+            match number {
+                Some(6) => {
+                    self.sixth = true;
+                    // "69" = sixth with added ninth
+                    if let Some(rest) = rest.strip_prefix('9') {
+                        self.added.push(9);
+                        s = rest;
+                        continue;
+                    }
+                }
+                Some(7) => {
+                    self.seventh.get_or_insert(Seventh::Minor);
+                }
+                Some(n @ (9 | 11 | 13)) => {
+                    // After a seventh, a bare number is a tension: "m7(9)", "7(13)"
+                    if self.seventh.is_none() && !self.sixth {
+                        self.seventh = Some(Seventh::Minor);
+                    }
+                    self.extension = Some(self.extension.map_or(n, |e| e.max(n)));
+                }
+                Some(2) => self.added.push(9),
+                Some(4) => self.added.push(11),
+                Some(_) => {}
+                None => {
+                    // Unknown character: skip it and keep going
+                    let skip = s.chars().next().map_or(1, char::len_utf8);
+                    s = &s[skip..];
+                    continue;
+                }
+            }
+            s = rest;
+        }
+    }
+
+    fn take_number_or<'a>(&mut self, rest: &'a str, default: u8) -> &'a str {
+        let (number, remaining) = take_number(rest);
+        let number = number.unwrap_or(default);
+        if matches!(number, 9 | 11 | 13) {
+            self.extension = Some(number);
+        }
+        remaining
+    }
+
+    /// Chord tones as intervals above the root, without duplicates
+    pub fn tones(&self) -> Vec<ChordTone> {
+        let mut tones: Vec<ChordTone> = Vec::new();
+        let mut push = |interval: i32, required: bool| {
+            let interval = pitch_class(interval);
+            if let Some(existing) = tones.iter_mut().find(|t| t.interval == interval) {
+                existing.required |= required;
+            } else {
+                tones.push(ChordTone { interval, required });
+            }
+        };
+
+        push(0, true);
+
+        let third = match self.quality {
+            Quality::Major | Quality::Augmented => Some(4),
+            Quality::Minor | Quality::Diminished => Some(3),
+            Quality::Sus2 => Some(2),
+            Quality::Sus4 => Some(5),
+            Quality::Power => None,
+        };
+        if let Some(third) = third {
+            if !self.omitted.contains(&3) {
+                push(third, true);
+            }
+        }
+
+        let fifth_shift = self.alteration(5);
+        let fifth = match self.quality {
+            Quality::Diminished => 6,
+            Quality::Augmented => 8,
+            _ => 7 + fifth_shift,
+        };
+        if !self.omitted.contains(&5) {
+            // An altered fifth is part of the chord's colour; a perfect fifth can go
+            let characteristic = fifth != 7 || self.quality == Quality::Power;
+            push(fifth, characteristic);
+        }
+
+        if self.sixth {
+            push(9, true);
+        }
+        if let Some(seventh) = self.seventh {
+            push(
+                match seventh {
+                    Seventh::Minor => 10,
+                    Seventh::Major => 11,
+                    Seventh::Diminished => 9,
+                },
+                true,
+            );
+        }
+
+        if let Some(extension) = self.extension {
+            // 9th and 11th under a 13th are conventionally optional
+            for degree in [9, 11, 13] {
+                if degree > extension {
+                    break;
+                }
+                if degree == 11 && extension == 13 {
+                    continue;
+                }
+                let interval = degree_interval(degree) + self.alteration(degree);
+                push(interval, degree == extension);
+            }
+        }
+        for &degree in &self.added {
+            push(degree_interval(degree), true);
+        }
+        for &(degree, shift) in &self.alterations {
+            if degree != 5 {
+                push(degree_interval(degree) + shift as i32, true);
+            }
+        }
+
+        tones
+    }
+
+    /// Lowest note that should sound: the slash bass, or the root
+    pub fn bass_note(&self) -> u8 {
+        self.bass.unwrap_or(self.root)
+    }
+
+    fn alteration(&self, degree: u8) -> i32 {
+        self.alterations
+            .iter()
+            .filter(|(d, _)| *d == degree)
+            .map(|(_, shift)| *shift as i32)
+            .sum()
+    }
+
+    /// Whether this is a half-diminished chord (`m7-5`, `ø`)
+    pub fn is_half_diminished(&self) -> bool {
+        self.quality == Quality::Minor
+            && self.seventh == Some(Seventh::Minor)
+            && self.alteration(5) == -1
+    }
+
+    /// Anything beyond a plain triad, seventh or sixth
+    pub fn has_tensions(&self) -> bool {
+        self.extension.is_some()
+            || !self.added.is_empty()
+            || self.alterations.iter().any(|&(degree, _)| degree != 5)
+    }
+
+    /// Transpose by semitones, keeping the written suffix and bass notation
+    pub fn transpose(&self, semitones: i32) -> Self {
+        let mut chord = self.clone();
+        chord.root = pitch_class(self.root as i32 + semitones);
+        chord.bass = self.bass.map(|b| pitch_class(b as i32 + semitones));
+        chord
+    }
+
+    /// Root spelled with the chord's accidental preference
+    pub fn root_name(&self) -> &'static str {
+        note_name(self.root, self.prefer_flat)
+    }
+
+    /// Bass spelled with the chord's accidental preference
+    pub fn bass_name(&self) -> Option<&'static str> {
+        self.bass.map(|b| note_name(b, self.prefer_flat))
+    }
+}
+
+impl fmt::Display for Chord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.root_name(), self.suffix)?;
+        if let Some(bass) = self.bass_name() {
+            match self.bass_notation {
+                BassNotation::Slash => write!(f, "/{}", bass)?,
+                BassNotation::On => write!(f, "on{}", bass)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Split "m7/G" or "onB" into suffix and bass note (with its flat spelling flag)
+fn split_bass(rest: &str) -> (&str, Option<(u8, bool)>, BassNotation) {
+    // Only a trailing "/X" is a bass; "6/9" keeps its slash
+    if let Some(index) = rest.rfind('/') {
+        let candidate = &rest[index + 1..];
+        if let Some((pc, len)) = parse_note(candidate) {
+            if len == candidate.len() {
+                return (
+                    &rest[..index],
+                    Some((pc, uses_flat(candidate))),
+                    BassNotation::Slash,
+                );
+            }
+        }
+    }
+    if let Some(index) = rest.rfind("on") {
+        let candidate = &rest[index + 2..];
+        if let Some((pc, len)) = parse_note(candidate) {
+            if len == candidate.len() {
+                return (
+                    &rest[..index],
+                    Some((pc, uses_flat(candidate))),
+                    BassNotation::On,
+                );
+            }
+        }
+    }
+    (rest, None, BassNotation::Slash)
+}
+
+fn strip_any<'a>(s: &'a str, prefixes: &[&str]) -> Option<&'a str> {
+    prefixes.iter().find_map(|p| s.strip_prefix(p))
+}
+
+fn take_number(s: &str) -> (Option<u8>, &str) {
+    let end = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    if end == 0 {
+        return (None, s);
+    }
+    // "79" after a 7 is not seventy-nine: read 7, 9, 11 and 13 as separate degrees
+    let digits = &s[..end];
+    let take = if digits.starts_with("11") || digits.starts_with("13") {
+        2
+    } else {
+        1
+    };
+    (digits[..take].parse().ok(), &s[take..])
+}
+
+/// Map compound degrees onto their extension names (2 -> 9, 4 -> 11)
+fn normalize_degree(degree: u8) -> u8 {
+    match degree {
+        2 => 9,
+        4 => 11,
+        6 => 13,
+        d => d,
+    }
+}
+
+fn degree_interval(degree: u8) -> i32 {
+    match degree {
+        2 | 9 => 2,
+        3 => 4,
+        4 | 11 => 5,
+        5 => 7,
+        6 | 13 => 9,
+        7 => 10,
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn intervals(name: &str) -> Vec<u8> {
+        let mut result: Vec<u8> = Chord::parse(name)
+            .unwrap()
+            .tones()
+            .iter()
+            .map(|t| t.interval)
+            .collect();
+        result.sort();
+        result
+    }
+
+    #[test]
+    fn test_parse_basic_qualities() {
+        assert_eq!(intervals("C"), vec![0, 4, 7]);
+        assert_eq!(intervals("Am"), vec![0, 3, 7]);
+        assert_eq!(intervals("G7"), vec![0, 4, 7, 10]);
+        assert_eq!(intervals("CM7"), vec![0, 4, 7, 11]);
+        assert_eq!(intervals("Cmaj7"), vec![0, 4, 7, 11]);
+        assert_eq!(intervals("CmM7"), vec![0, 3, 7, 11]);
+        assert_eq!(intervals("Bdim"), vec![0, 3, 6]);
+        assert_eq!(intervals("Bdim7"), vec![0, 3, 6, 9]);
+        assert_eq!(intervals("Caug"), vec![0, 4, 8]);
+        assert_eq!(intervals("Dsus4"), vec![0, 5, 7]);
+        assert_eq!(intervals("Dsus2"), vec![0, 2, 7]);
+        assert_eq!(intervals("E5"), vec![0, 7]);
+        assert_eq!(intervals("C6"), vec![0, 4, 7, 9]);
+    }
+
+    #[test]
+    fn test_parse_tensions() {
+        assert_eq!(intervals("Gadd9"), vec![0, 2, 4, 7]);
+        assert_eq!(intervals("Em7(9)"), vec![0, 2, 3, 7, 10]);
+        assert_eq!(intervals("C9"), vec![0, 2, 4, 7, 10]);
+        assert_eq!(intervals("C7(#9)"), vec![0, 3, 4, 7, 10]);
+        assert_eq!(intervals("C7b9"), vec![0, 1, 4, 7, 10]);
+        assert_eq!(intervals("C69"), vec![0, 2, 4, 7, 9]);
+    }
+
+    #[test]
+    fn test_parse_half_diminished() {
+        assert!(Chord::parse("Bm7-5").unwrap().is_half_diminished());
+        assert!(!Chord::parse("Bm7").unwrap().is_half_diminished());
+        assert_eq!(intervals("Bm7-5"), vec![0, 3, 6, 10]);
+        assert_eq!(intervals("Bm7b5"), vec![0, 3, 6, 10]);
+        assert_eq!(intervals("Bø"), vec![0, 3, 6, 10]);
+    }
+
+    #[test]
+    fn test_parse_bass() {
+        let chord = Chord::parse("F#m7/E").unwrap();
+        assert_eq!(chord.root, 6);
+        assert_eq!(chord.suffix, "m7");
+        assert_eq!(chord.bass, Some(4));
+
+        let chord = Chord::parse("GonB").unwrap();
+        assert_eq!(chord.root, 7);
+        assert_eq!(chord.suffix, "");
+        assert_eq!(chord.bass, Some(11));
+        assert_eq!(chord.bass_notation, BassNotation::On);
+        assert_eq!(chord.to_string(), "GonB");
+
+        // 6/9 is not a slash chord
+        let chord = Chord::parse("C6/9").unwrap();
+        assert_eq!(chord.bass, None);
+        assert_eq!(intervals("C6/9"), vec![0, 2, 4, 7, 9]);
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert!(Chord::parse("Hello").is_none());
+        assert!(Chord::parse("").is_none());
+        assert!(Chord::parse("|").is_none());
+    }
+
+    #[test]
+    fn test_transpose_and_display() {
+        let chord = Chord::parse("Bbm7/Ab").unwrap();
+        assert_eq!(chord.transpose(2).to_string(), "Cm7/Bb");
+        let chord = Chord::parse("F#m7-5").unwrap();
+        assert_eq!(chord.transpose(-1).to_string(), "Fm7-5");
+    }
+
+    #[test]
+    fn test_required_tones() {
+        let chord = Chord::parse("G7").unwrap();
+        let fifth = chord.tones().into_iter().find(|t| t.interval == 7).unwrap();
+        assert!(!fifth.required);
+
+        let chord = Chord::parse("Bm7-5").unwrap();
+        let fifth = chord.tones().into_iter().find(|t| t.interval == 6).unwrap();
+        assert!(fifth.required);
+    }
+}
@@ -0,0 +1,201 @@
+//! Instruments and tunings
+//!
+//! Strings are listed 1st string first (highest course first), matching the
+//! `frets` order of `ChordFingering` on the frontend. Open pitches are MIDI
+//! note numbers (E2 = 40).
+
+use serde::{Deserialize, Serialize};
+
+/// Instrument family, used to find the reference tuning
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InstrumentFamily {
+    Guitar,
+    Ukulele,
+    Bass,
+}
+
+impl InstrumentFamily {
+    /// Standard open pitches for this family, 1st string first
+    pub fn standard_strings(self) -> &'static [u8] {
+        match self {
+            // E4 B3 G3 D3 A2 E2
+            InstrumentFamily::Guitar => &[64, 59, 55, 50, 45, 40],
+            // A4 E4 C4 G4 (re-entrant)
+            InstrumentFamily::Ukulele => &[69, 64, 60, 67],
+            // G2 D2 A1 E1
+            InstrumentFamily::Bass => &[43, 38, 33, 28],
+        }
+    }
+}
+
+/// An instrument with a concrete tuning
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Instrument {
+    /// Tuning id, matching the frontend's `Tuning` values where they exist
+    pub id: String,
+    pub name: String,
+    pub family: InstrumentFamily,
+    /// Open MIDI pitch of each string, 1st string first
+    pub strings: Vec<u8>,
+    pub fret_count: u8,
+    /// Only the bass note of each chord is played (bass guitar)
+    pub root_only: bool,
+    /// Whether players of this instrument normally use a capo
+    pub uses_capo: bool,
+}
+
+impl Instrument {
+    fn preset(id: &str, name: &str, family: InstrumentFamily, strings: &[u8]) -> Self {
+        let (fret_count, root_only, uses_capo) = match family {
+            InstrumentFamily::Guitar => (22, false, true),
+            InstrumentFamily::Ukulele => (15, false, false),
+            InstrumentFamily::Bass => (20, true, false),
+        };
+        Self {
+            id: id.to_string(),
+            name: name.to_string(),
+            family,
+            strings: strings.to_vec(),
+            fret_count,
+            root_only,
+            uses_capo,
+        }
+    }
+
+    /// Standard 6-string guitar in E
+    pub fn standard_guitar() -> Self {
+        Self::preset(
+            "standard",
+            "Guitar (Standard)",
+            InstrumentFamily::Guitar,
+            InstrumentFamily::Guitar.standard_strings(),
+        )
+    }
+
+    /// All built-in instrument/tuning presets
+    pub fn presets() -> Vec<Self> {
+        use InstrumentFamily::*;
+        vec![
+            Self::standard_guitar(),
+            Self::preset(
+                "half-step-down",
+                "Guitar (Half-step down)",
+                Guitar,
+                &[63, 58, 54, 49, 44, 39],
+            ),
+            Self::preset(
+                "whole-step-down",
+                "Guitar (Whole-step down)",
+                Guitar,
+                &[62, 57, 53, 48, 43, 38],
+            ),
+            Self::preset(
+                "drop-d",
+                "Guitar (Drop D)",
+                Guitar,
+                &[64, 59, 55, 50, 45, 38],
+            ),
+            Self::preset(
+                "dadgad",
+                "Guitar (DADGAD)",
+                Guitar,
+                &[62, 57, 55, 50, 45, 38],
+            ),
+            Self::preset(
+                "open-g",
+                "Guitar (Open G)",
+                Guitar,
+                &[62, 59, 55, 50, 43, 38],
+            ),
+            Self::preset(
+                "open-d",
+                "Guitar (Open D)",
+                Guitar,
+                &[62, 57, 54, 50, 45, 38],
+            ),
+            Self::preset(
+                "ukulele",
+                "Ukulele (GCEA)",
+                Ukulele,
+                Ukulele.standard_strings(),
+            ),
+            Self::preset("bass", "Bass (4-string)", Bass, Bass.standard_strings()),
+        ]
+    }
+
+    /// Look up a preset by id
+    pub fn by_id(id: &str) -> Option<Self> {
+        Self::presets().into_iter().find(|i| i.id == id)
+    }
+
+    /// Semitone shift from the family's standard tuning, if every string moved by the same amount
+    ///
+    /// Half-step down is `Some(-1)`; drop D and open tunings are `None`.
+    pub fn uniform_offset(&self) -> Option<i32> {
+        let standard = self.family.standard_strings();
+        if standard.len() != self.strings.len() {
+            return None;
+        }
+        let offset = self.strings[0] as i32 - standard[0] as i32;
+        self.strings
+            .iter()
+            .zip(standard)
+            .all(|(&actual, &reference)| actual as i32 - reference as i32 == offset)
+            .then_some(offset)
+    }
+
+    /// Maximum number of strings that can be muted in a chord voicing
+    pub fn max_muted(&self) -> usize {
+        match self.family {
+            // Ukulele chords ring on all four strings
+            InstrumentFamily::Ukulele => 0,
+            _ => self.strings.len().saturating_sub(4),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_presets_have_unique_ids() {
+        let presets = Instrument::presets();
+        for (i, a) in presets.iter().enumerate() {
+            for b in &presets[i + 1..] {
+                assert_ne!(a.id, b.id);
+            }
+        }
+    }
+
+    #[test]
+    fn test_uniform_offset() {
+        assert_eq!(Instrument::standard_guitar().uniform_offset(), Some(0));
+        assert_eq!(
+            Instrument::by_id("half-step-down")
+                .unwrap()
+                .uniform_offset(),
+            Some(-1)
+        );
+        assert_eq!(
+            Instrument::by_id("whole-step-down")
+                .unwrap()
+                .uniform_offset(),
+            Some(-2)
+        );
+        assert_eq!(Instrument::by_id("drop-d").unwrap().uniform_offset(), None);
+        assert_eq!(
+            Instrument::by_id("ukulele").unwrap().uniform_offset(),
+            Some(0)
+        );
+    }
+
+    #[test]
+    fn test_ukulele_pitch_classes() {
+        let uke = Instrument::by_id("ukulele").unwrap();
+        let pitch_classes: Vec<u8> = uke.strings.iter().map(|p| p % 12).collect();
+        assert_eq!(pitch_classes, vec![9, 4, 0, 7]);
+        assert_eq!(uke.max_muted(), 0);
+    }
+}
@@ -0,0 +1,234 @@
+//! Keys and key detection
+
+use super::chord::{Chord, Quality};
+use super::{note_name, parse_note, pitch_class};
+use crate::parsers::FetchedChordSheet;
+use std::fmt;
+
+/// Major scale degrees (semitones above the tonic)
+pub const MAJOR_SCALE: [u8; 7] = [0, 2, 4, 5, 7, 9, 11];
+
+/// Natural minor scale degrees (semitones above the tonic)
+pub const MINOR_SCALE: [u8; 7] = [0, 2, 3, 5, 7, 8, 10];
+
+/// Triad quality on each degree of the major scale
+const MAJOR_TRIADS: [Quality; 7] = [
+    Quality::Major,
+    Quality::Minor,
+    Quality::Minor,
+    Quality::Major,
+    Quality::Major,
+    Quality::Minor,
+    Quality::Diminished,
+];
+
+/// Triad quality on each degree of the natural minor scale
+const MINOR_TRIADS: [Quality; 7] = [
+    Quality::Minor,
+    Quality::Diminished,
+    Quality::Major,
+    Quality::Minor,
+    Quality::Minor,
+    Quality::Major,
+    Quality::Major,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Key {
+    pub tonic: u8,
+    pub minor: bool,
+    pub prefer_flat: bool,
+}
+
+impl Key {
+    pub fn new(tonic: u8, minor: bool) -> Self {
+        // Flat keys: F Bb Eb Ab Db Gb major, and their relative minors
+        let relative_major = if minor { (tonic + 3) % 12 } else { tonic };
+        let prefer_flat = matches!(relative_major, 5 | 10 | 3 | 8 | 1 | 6);
+        Self {
+            tonic,
+            minor,
+            prefer_flat,
+        }
+    }
+
+    /// Parse "C", "Am", "Eb", "F#m", "Bbmin", "C major", "A minor"
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = text.trim();
+        let (tonic, len) = parse_note(text)?;
+        let rest = text[len..].trim().to_lowercase();
+        let minor = rest.starts_with("minor")
+            || rest.starts_with("min")
+            || (rest.starts_with('m') && !rest.starts_with("maj"));
+        let mut key = Self::new(tonic, minor);
+        if len > 1 {
+            key.prefer_flat = text[1..].starts_with('b') || text[1..].starts_with('♭');
+        }
+        Some(key)
+    }
+
+    pub fn scale(&self) -> &'static [u8; 7] {
+        if self.minor {
+            &MINOR_SCALE
+        } else {
+            &MAJOR_SCALE
+        }
+    }
+
+    /// Diatonic triad quality on a scale degree index (0-6)
+    pub fn triad(&self, degree: usize) -> Quality {
+        if self.minor {
+            MINOR_TRIADS[degree]
+        } else {
+            MAJOR_TRIADS[degree]
+        }
+    }
+
+    /// Scale degree index (0-6) of a pitch class, if it is diatonic
+    pub fn degree_of(&self, pc: u8) -> Option<usize> {
+        let interval = pitch_class(pc as i32 - self.tonic as i32);
+        self.scale().iter().position(|&d| d == interval)
+    }
+
+    /// Spell a pitch class in this key
+    pub fn spell(&self, pc: u8) -> &'static str {
+        note_name(pc, self.prefer_flat)
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}{}",
+            self.spell(self.tonic),
+            if self.minor { "m" } else { "" }
+        )
+    }
+}
+
+/// The key a sheet states, or the one its chords suggest when it states none
+/// or one that cannot be read (site text like "原曲キー" or "-2")
+pub fn sheet_key(sheet: &FetchedChordSheet) -> Option<Key> {
+    sheet
+        .key
+        .as_deref()
+        .and_then(Key::parse)
+        .or_else(|| detect_key(sheet))
+}
+
+/// Guess the key of a sheet from its chords
+///
+/// Every chord scores against all 24 keys (diatonic root and matching triad
+/// quality score highest); the first and last chords add a tonic bonus.
+pub fn detect_key(sheet: &FetchedChordSheet) -> Option<Key> {
+    let chords: Vec<Chord> = sheet
+        .sections
+        .iter()
+        .flat_map(|s| &s.lines)
+        .flat_map(|l| &l.chords)
+        .filter_map(|c| Chord::parse(&c.chord))
+        .collect();
+    if chords.is_empty() {
+        return None;
+    }
+
+    let mut best: Option<(i32, Key)> = None;
+    for minor in [false, true] {
+        for tonic in 0..12 {
+            let key = Key::new(tonic, minor);
+            let mut score = 0;
+            for chord in &chords {
+                score += match key.degree_of(chord.root) {
+                    Some(degree) if triad_matches(key.triad(degree), chord.quality) => 2,
+                    Some(_) => 1,
+                    None => -1,
+                };
+            }
+            for (chord, bonus) in [(&chords[0], 2), (&chords[chords.len() - 1], 3)] {
+                if chord.root == tonic && triad_matches(key.triad(0), chord.quality) {
+                    score += bonus;
+                }
+            }
+            // Ties go to the first candidate: major keys, then lower tonic
+            if best.is_none_or(|(best_score, _)| score > best_score) {
+                best = Some((score, key));
+            }
+        }
+    }
+    best.map(|(_, key)| key)
+}
+
+/// Whether a chord's quality fits a diatonic triad (sus/power chords fit anything but dim)
+fn triad_matches(diatonic: Quality, actual: Quality) -> bool {
+    match actual {
+        Quality::Sus2 | Quality::Sus4 | Quality::Power => diatonic != Quality::Diminished,
+        _ => diatonic == actual,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::{FetchedChord, FetchedLine, FetchedSection};
+
+    fn sheet_with(chords: &[&str]) -> FetchedChordSheet {
+        let mut sheet = FetchedChordSheet::new(String::new());
+        let mut section = FetchedSection::new("Main");
+        section.lines.push(FetchedLine::with_chords(
+            "",
+            chords
+                .iter()
+                .enumerate()
+                .map(|(i, c)| FetchedChord::new(c, i as i32 * 4))
+                .collect(),
+        ));
+        sheet.sections.push(section);
+        sheet
+    }
+
+    #[test]
+    fn test_parse_key() {
+        assert_eq!(Key::parse("C"), Some(Key::new(0, false)));
+        assert_eq!(Key::parse("Am"), Some(Key::new(9, true)));
+        assert_eq!(Key::parse("A minor"), Some(Key::new(9, true)));
+        assert_eq!(Key::parse("Cmaj"), Some(Key::new(0, false)));
+        assert_eq!(Key::parse("Eb").unwrap().to_string(), "Eb");
+        assert_eq!(Key::parse("F#m").unwrap().to_string(), "F#m");
+        assert!(Key::parse("Key").is_none());
+    }
+
+    #[test]
+    fn test_key_spelling() {
+        assert_eq!(Key::new(5, false).spell(10), "Bb");
+        assert_eq!(Key::new(7, false).spell(6), "F#");
+        assert_eq!(Key::new(2, true).spell(10), "Bb");
+    }
+
+    #[test]
+    fn test_detect_major_key() {
+        let sheet = sheet_with(&["G", "D", "Em", "C", "G", "D", "G"]);
+        assert_eq!(detect_key(&sheet), Some(Key::new(7, false)));
+    }
+
+    #[test]
+    fn test_detect_minor_key() {
+        let sheet = sheet_with(&["Am", "Dm", "E7", "Am", "F", "G", "Am"]);
+        assert_eq!(detect_key(&sheet), Some(Key::new(9, true)));
+    }
+
+    #[test]
+    fn test_sheet_key_falls_back_to_chords() {
+        let mut sheet = sheet_with(&["G", "D", "Em", "C", "G", "D", "G"]);
+        sheet.key = Some("Eb".to_string());
+        assert_eq!(sheet_key(&sheet), Some(Key::new(3, false)));
+        sheet.key = Some("原曲キー".to_string());
+        assert_eq!(sheet_key(&sheet), Some(Key::new(7, false)));
+    }
+
+    #[test]
+    fn test_detect_empty() {
+        let sheet = sheet_with(&[]);
+        assert_eq!(detect_key(&sheet), None);
+    }
+}
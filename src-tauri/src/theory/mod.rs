@@ -0,0 +1,161 @@
+//! Music theory primitives shared by the sheet transforms
+//!
+//! - `chord`: chord symbol parsing ("Am7", "D/F#", "GonB", "Bm7-5")
+//! - `instrument`: instruments and tunings (guitar, ukulele, bass)
+//! - `voicing`: fingering search on an instrument's fretboard
+//! - `key`: keys and key detection
+//! - `nashville`: Nashville number / Roman numeral conversion
+//!
+//! Pitch classes are `u8` values in `0..12` with C = 0.
+
+pub mod chord;
+pub mod instrument;
+pub mod key;
+pub mod nashville;
+pub mod voicing;
+
+use crate::parsers::FetchedChordSheet;
+use chord::Chord;
+
+/// Note names using sharps (index = pitch class)
+pub const NOTES_SHARP: [&str; 12] = [
+    "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+];
+
+/// Note names using flats (index = pitch class)
+pub const NOTES_FLAT: [&str; 12] = [
+    "C", "Db", "D", "Eb", "E", "F", "Gb", "G", "Ab", "A", "Bb", "B",
+];
+
+/// Parse a note name at the start of `text`
+/// Returns the pitch class and the number of bytes consumed.
+/// Accepts `#`, `b`, `♯` and `♭` accidentals.
+pub fn parse_note(text: &str) -> Option<(u8, usize)> {
+    let mut chars = text.chars();
+    let letter = chars.next()?;
+    let natural: i32 = match letter {
+        'C' => 0,
+        'D' => 2,
+        'E' => 4,
+        'F' => 5,
+        'G' => 7,
+        'A' => 9,
+        'B' => 11,
+        _ => return None,
+    };
+
+    let mut consumed = letter.len_utf8();
+    let offset = match chars.next() {
+        Some(c @ ('#' | '♯')) => {
+            consumed += c.len_utf8();
+            1
+        }
+        Some(c @ ('b' | '♭')) => {
+            consumed += c.len_utf8();
+            -1
+        }
+        _ => 0,
+    };
+
+    Some((pitch_class(natural + offset), consumed))
+}
+
+/// Wrap any semitone value into a pitch class
+pub fn pitch_class(semitones: i32) -> u8 {
+    semitones.rem_euclid(12) as u8
+}
+
+/// Spell a pitch class with sharps or flats
+pub fn note_name(pc: u8, prefer_flat: bool) -> &'static str {
+    let index = (pc % 12) as usize;
+    if prefer_flat {
+        NOTES_FLAT[index]
+    } else {
+        NOTES_SHARP[index]
+    }
+}
+
+/// Whether a note/chord spelling uses a flat accidental
+pub fn uses_flat(name: &str) -> bool {
+    name.get(1..)
+        .is_some_and(|rest| rest.starts_with('b') || rest.starts_with('♭'))
+}
+
+/// Transpose a single chord name, keeping the suffix as written
+/// Unparseable strings (e.g. "N.C.") are returned unchanged.
+pub fn transpose_chord_name(name: &str, semitones: i32) -> String {
+    if semitones.rem_euclid(12) == 0 {
+        return name.to_string();
+    }
+    match Chord::parse(name) {
+        Some(chord) => chord.transpose(semitones).to_string(),
+        None => name.to_string(),
+    }
+}
+
+/// Transpose every chord in a sheet, including the key
+pub fn transpose_sheet(sheet: &FetchedChordSheet, semitones: i32) -> FetchedChordSheet {
+    let mut result = sheet.clone();
+    for section in &mut result.sections {
+        for line in &mut section.lines {
+            for chord in &mut line.chords {
+                chord.chord = transpose_chord_name(&chord.chord, semitones);
+            }
+            // Tab that can't move without leaving the fretboard stays as written
+            if let Some(tab) = line.tab.take() {
+                line.tab = Some(tab.transpose(semitones).unwrap_or(tab));
+            }
+        }
+    }
+    result.key = sheet
+        .key
+        .as_deref()
+        .map(|key| transpose_chord_name(key, semitones));
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::line;
+    use crate::parsers::FetchedSection;
+
+    #[test]
+    fn test_parse_note() {
+        assert_eq!(parse_note("C"), Some((0, 1)));
+        assert_eq!(parse_note("F#m7"), Some((6, 2)));
+        assert_eq!(parse_note("Bb"), Some((10, 2)));
+        assert_eq!(parse_note("E♭"), Some((3, 4)));
+        assert_eq!(parse_note("Cb"), Some((11, 2)));
+        assert_eq!(parse_note("H"), None);
+    }
+
+    #[test]
+    fn test_transpose_chord_name() {
+        assert_eq!(transpose_chord_name("Am7", 2), "Bm7");
+        assert_eq!(transpose_chord_name("D/F#", -2), "C/E");
+        assert_eq!(transpose_chord_name("Bb", 2), "C");
+        assert_eq!(transpose_chord_name("Eb", 1), "E");
+        assert_eq!(transpose_chord_name("Ab", 1), "A");
+        assert_eq!(transpose_chord_name("Db", -1), "C");
+        assert_eq!(transpose_chord_name("Gb", 2), "Ab");
+        assert_eq!(transpose_chord_name("GonB", 2), "AonC#");
+        assert_eq!(transpose_chord_name("N.C.", 3), "N.C.");
+    }
+
+    #[test]
+    fn test_transpose_sheet() {
+        let mut sheet = FetchedChordSheet::new(String::new());
+        sheet.key = Some("G".to_string());
+        let mut section = FetchedSection::new("Aメロ");
+        section.lines.push(line("歌詞", &[("G", 0), ("Em7", 1)]));
+        sheet.sections.push(section);
+
+        let result = transpose_sheet(&sheet, 2);
+        assert_eq!(result.key, Some("A".to_string()));
+        let chords = &result.sections[0].lines[0].chords;
+        assert_eq!(chords[0].chord, "A");
+        assert_eq!(chords[1].chord, "F#m7");
+        assert_eq!(chords[1].position, 1);
+    }
+}
@@ -0,0 +1,606 @@
+//! Nashville number and Roman numeral notation
+//!
+//! Nashville: `1 4 5m7 b7 1/3` (suffix kept as written, slash bass as a degree)
+//! Roman: `I IV vi7 bVII I6 V7/V` (case shows quality, figures show inversions,
+//! `/` + numeral marks a secondary dominant, `/` + digit a non-chord bass)
+
+use super::chord::{Chord, Quality, Seventh};
+use super::key::Key;
+use super::pitch_class;
+use crate::parsers::FetchedChordSheet;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NumberStyle {
+    Nashville,
+    Roman,
+}
+
+const ROMAN: [&str; 7] = ["I", "II", "III", "IV", "V", "VI", "VII"];
+
+/// (accidental, degree index) for each interval above a major tonic
+const MAJOR_DEGREES: [(&str, usize); 12] = [
+    ("", 0),
+    ("b", 1),
+    ("", 1),
+    ("b", 2),
+    ("", 2),
+    ("", 3),
+    ("#", 3),
+    ("", 4),
+    ("b", 5),
+    ("", 5),
+    ("b", 6),
+    ("", 6),
+];
+
+/// (accidental, degree index) for each interval above a minor tonic (natural minor)
+const MINOR_DEGREES: [(&str, usize); 12] = [
+    ("", 0),
+    ("b", 1),
+    ("", 1),
+    ("", 2),
+    ("#", 2),
+    ("", 3),
+    ("#", 3),
+    ("", 4),
+    ("", 5),
+    ("#", 5),
+    ("", 6),
+    ("#", 6),
+];
+
+/// Degree name (accidental, 0-based degree index) for an interval above the tonic
+fn degree_name(interval: u8, key: &Key) -> (&'static str, usize) {
+    let table = if key.minor {
+        &MINOR_DEGREES
+    } else {
+        &MAJOR_DEGREES
+    };
+    table[(interval % 12) as usize]
+}
+
+fn interval_from(key: &Key, pc: u8) -> u8 {
+    pitch_class(pc as i32 - key.tonic as i32)
+}
+
+/// Convert one chord to Nashville or Roman notation
+pub fn to_number(chord: &Chord, key: &Key, style: NumberStyle) -> String {
+    match style {
+        NumberStyle::Nashville => to_nashville(chord, key),
+        NumberStyle::Roman => to_roman(chord, key),
+    }
+}
+
+fn to_nashville(chord: &Chord, key: &Key) -> String {
+    let (accidental, index) = degree_name(interval_from(key, chord.root), key);
+    let mut result = format!("{}{}{}", accidental, index + 1, chord.suffix);
+    if let Some(bass) = chord.bass {
+        let (accidental, index) = degree_name(interval_from(key, bass), key);
+        result.push_str(&format!("/{}{}", accidental, index + 1));
+    }
+    result
+}
+
+fn to_roman(chord: &Chord, key: &Key) -> String {
+    let interval = interval_from(key, chord.root);
+
+    // Secondary dominant: a non-diatonic major/dominant chord resolving down a fifth
+    // onto a diatonic, non-diminished degree other than the tonic
+    if let Some(target) = secondary_target(chord, key) {
+        let (accidental, index) = degree_name(interval_from(key, target), key);
+        let target_numeral = numeral(index, key.triad(index));
+        let seventh = if chord.seventh == Some(Seventh::Minor) {
+            "7"
+        } else {
+            ""
+        };
+        return format!("V{}/{}{}", seventh, accidental, target_numeral);
+    }
+
+    let (accidental, index) = degree_name(interval, key);
+    let mut result = format!("{}{}", accidental, numeral(index, chord.quality));
+    result.push_str(&roman_suffix(chord));
+
+    if let Some(bass) = chord.bass {
+        match inversion_figure(chord, bass) {
+            Some(figure) => {
+                // Seventh-chord figures (65, 43, 42) replace the trailing 7
+                if chord.seventh.is_some() && result.ends_with('7') {
+                    result.pop();
+                }
+                result.push_str(figure);
+            }
+            None => {
+                let (accidental, index) = degree_name(interval_from(key, bass), key);
+                result.push_str(&format!("/{}{}", accidental, index + 1));
+            }
+        }
+    }
+    result
+}
+
+fn numeral(index: usize, quality: Quality) -> String {
+    let base = ROMAN[index];
+    match quality {
+        Quality::Minor => base.to_lowercase(),
+        Quality::Diminished => format!("{}°", base.to_lowercase()),
+        Quality::Augmented => format!("{}+", base),
+        _ => base.to_string(),
+    }
+}
+
+/// Suffix after the numeral: sevenths, sus, tensions
+fn roman_suffix(chord: &Chord) -> String {
+    let mut suffix = String::new();
+    let half_diminished = chord.is_half_diminished();
+    if half_diminished {
+        // "viiø7" replaces the minor numeral's plain 7 and flat five
+        suffix.push_str("ø7");
+    } else {
+        match chord.seventh {
+            Some(Seventh::Minor) | Some(Seventh::Diminished) => suffix.push('7'),
+            Some(Seventh::Major) => suffix.push_str("maj7"),
+            None => {}
+        }
+    }
+    match chord.quality {
+        Quality::Sus2 => suffix.push_str("sus2"),
+        Quality::Sus4 => suffix.push_str("sus4"),
+        Quality::Power => suffix.push('5'),
+        _ => {}
+    }
+    if chord.sixth {
+        // A bare "6" would read as a first inversion
+        suffix.push_str("add6");
+    }
+    if let Some(extension) = chord.extension {
+        suffix.push_str(&format!("({})", extension));
+    }
+    for degree in &chord.added {
+        suffix.push_str(&format!("add{}", degree));
+    }
+    for &(degree, shift) in &chord.alterations {
+        if half_diminished && degree == 5 {
+            continue;
+        }
+        suffix.push_str(&format!("{}{}", if shift < 0 { "b" } else { "#" }, degree));
+    }
+    suffix
+}
+
+/// Figured-bass inversion for a chord tone in the bass
+fn inversion_figure(chord: &Chord, bass: u8) -> Option<&'static str> {
+    let interval = pitch_class(bass as i32 - chord.root as i32);
+    // Sus and power chords have no third to invert on
+    let third = match chord.quality {
+        Quality::Major | Quality::Augmented => 4,
+        Quality::Minor | Quality::Diminished => 3,
+        _ => return None,
+    };
+    let fifth = match chord.quality {
+        Quality::Diminished => 6,
+        Quality::Augmented => 8,
+        _ if chord.alterations.contains(&(5, -1)) => 6,
+        _ => 7,
+    };
+    let seventh = match chord.seventh {
+        Some(Seventh::Minor) => Some(10),
+        Some(Seventh::Major) => Some(11),
+        Some(Seventh::Diminished) => Some(9),
+        None => None,
+    };
+
+    let has_seventh = seventh.is_some();
+    if interval == third {
+        Some(if has_seventh { "65" } else { "6" })
+    } else if interval == fifth {
+        Some(if has_seventh { "43" } else { "64" })
+    } else if Some(interval) == seventh {
+        Some("42")
+    } else {
+        None
+    }
+}
+
+fn secondary_target(chord: &Chord, key: &Key) -> Option<u8> {
+    let dominant_quality = chord.quality == Quality::Major
+        && matches!(chord.seventh, None | Some(Seventh::Minor))
+        && chord.bass.is_none()
+        && !chord.has_tensions();
+    if !dominant_quality {
+        return None;
+    }
+    let degree = key.degree_of(chord.root);
+    // Already diatonic (V, IV, I in major): not secondary
+    if let Some(degree) = degree {
+        let diatonic_major = key.triad(degree) == Quality::Major;
+        let diatonic_seventh = key.scale()[(degree + 6) % 7];
+        let seventh_fits = chord.seventh.is_none()
+            || pitch_class(key.tonic as i32 + diatonic_seventh as i32)
+                == pitch_class(chord.root as i32 + 10);
+        if diatonic_major && seventh_fits {
+            return None;
+        }
+    } else {
+        return None;
+    }
+
+    let target = pitch_class(chord.root as i32 - 7);
+    let target_degree = key.degree_of(target)?;
+    if target_degree == 0 || key.triad(target_degree) == Quality::Diminished {
+        return None;
+    }
+    Some(target)
+}
+
+/// Parse a Nashville or Roman number back into a chord name in `key`
+pub fn from_number(text: &str, key: &Key, style: NumberStyle) -> Option<String> {
+    match style {
+        NumberStyle::Nashville => from_nashville(text, key),
+        NumberStyle::Roman => from_roman(text, key),
+    }
+}
+
+/// Read an optional accidental and a degree; returns (interval, rest)
+fn take_degree<'a>(text: &'a str, key: &Key, roman: bool) -> Option<(u8, bool, &'a str)> {
+    let (shift, text) = match text.chars().next()? {
+        'b' | '♭' => (-1, &text[text.chars().next()?.len_utf8()..]),
+        '#' | '♯' => (1, &text[text.chars().next()?.len_utf8()..]),
+        _ => (0, text),
+    };
+
+    let (index, upper, rest) = if roman {
+        let len = text
+            .find(|c: char| !matches!(c, 'I' | 'V' | 'i' | 'v'))
+            .unwrap_or(text.len());
+        let numeral = &text[..len];
+        let index = ROMAN.iter().position(|r| r.eq_ignore_ascii_case(numeral))?;
+        let upper = numeral.chars().all(|c| c.is_ascii_uppercase());
+        (index, upper, &text[len..])
+    } else {
+        let digit = text.chars().next()?.to_digit(10)? as usize;
+        if !(1..=7).contains(&digit) {
+            return None;
+        }
+        (digit - 1, true, &text[1..])
+    };
+
+    let interval = pitch_class(key.scale()[index] as i32 + shift);
+    Some((interval, upper, rest))
+}
+
+fn spell_in(key: &Key, interval: u8, flat: bool) -> &'static str {
+    let pc = pitch_class(key.tonic as i32 + interval as i32);
+    super::note_name(pc, key.prefer_flat || flat)
+}
+
+fn from_nashville(text: &str, key: &Key) -> Option<String> {
+    let text = text.trim();
+    let (main, bass) = match text.rfind('/') {
+        Some(index) => (&text[..index], Some(&text[index + 1..])),
+        None => (text, None),
+    };
+    let flat = main.starts_with('b');
+    let (interval, _, suffix) = take_degree(main, key, false)?;
+    let mut result = format!("{}{}", spell_in(key, interval, flat), suffix);
+    if let Some(bass) = bass {
+        let (bass_interval, _, rest) = take_degree(bass, key, false)?;
+        if !rest.is_empty() {
+            return None;
+        }
+        result.push('/');
+        result.push_str(spell_in(key, bass_interval, flat || bass.starts_with('b')));
+    }
+    Some(result)
+}
+
+fn from_roman(text: &str, key: &Key) -> Option<String> {
+    let text = text.trim();
+
+    // Secondary dominant: V/x or V7/x
+    if let Some((dominant, target)) = text.split_once('/') {
+        if target
+            .trim_start_matches(['b', '#'])
+            .starts_with(['I', 'V', 'i', 'v'])
+        {
+            let (target_interval, _, _) = take_degree(target, key, true)?;
+            let root = pitch_class(target_interval as i32 + 7);
+            let (interval, _, suffix) = take_degree(dominant, key, true)?;
+            if interval != 7 {
+                return None;
+            }
+            return Some(format!("{}{}", spell_in(key, root, false), suffix));
+        }
+    }
+
+    let (main, bass_degree) = match text.rfind('/') {
+        Some(index) => (&text[..index], Some(&text[index + 1..])),
+        None => (text, None),
+    };
+    let flat = main.starts_with('b');
+    let (interval, upper, rest) = take_degree(main, key, true)?;
+
+    let mut suffix = String::new();
+    let mut rest = rest;
+    let mut minor = !upper;
+    let mut seventh_from_figure = false;
+    let mut bass_interval: Option<i32> = None;
+
+    if let Some(r) = rest.strip_prefix('°') {
+        rest = r;
+        suffix.push_str("dim");
+        minor = false;
+    } else if let Some(r) = rest.strip_prefix('+') {
+        rest = r;
+        suffix.push_str("aug");
+    }
+    if let Some(r) = rest.strip_prefix('ø') {
+        rest = r.strip_prefix('7').unwrap_or(r);
+        minor = false;
+        suffix = "m7-5".to_string();
+    }
+    if minor {
+        suffix.insert(0, 'm');
+    }
+
+    // Inversion figures (must be checked longest first)
+    let is_dim = suffix.starts_with("dim");
+    let third: i32 = if is_dim || minor || suffix.starts_with("m7-5") {
+        3
+    } else {
+        4
+    };
+    let fifth: i32 = if is_dim || suffix.starts_with("m7-5") {
+        6
+    } else if suffix.starts_with("aug") {
+        8
+    } else {
+        7
+    };
+    let figures = [
+        ("65", third, true),
+        ("43", fifth, true),
+        ("42", -1, true),
+        ("64", fifth, false),
+        ("6", third, false),
+    ];
+    // "add6" is an added sixth, not a first-inversion figure
+    if !rest.ends_with("add6") {
+        for (figure, bass, seventh) in figures {
+            if let Some(tail) = rest.strip_suffix(figure) {
+                rest = tail;
+                seventh_from_figure = seventh;
+                bass_interval = Some(bass);
+                break;
+            }
+        }
+    }
+
+    let mut tail = rest.to_string();
+    if seventh_from_figure && !suffix.contains('7') && !tail.contains('7') {
+        // "IVmaj65" drops the 7 after "maj"; "V65" drops a plain 7
+        if tail.ends_with("maj") {
+            tail.push('7');
+        } else {
+            tail.insert(0, '7');
+        }
+    }
+    let tail = tail.replace("add6", "6").replace(['(', ')'], "");
+    suffix.push_str(&tail);
+
+    // A 42 figure puts the seventh in the bass
+    let bass_interval = bass_interval.map(|b| {
+        if b < 0 {
+            if suffix.contains("maj7") {
+                11
+            } else if suffix.starts_with("dim7") {
+                9
+            } else {
+                10
+            }
+        } else {
+            b
+        }
+    });
+
+    let root_name = spell_in(key, interval, flat);
+    let mut result = format!("{}{}", root_name, suffix);
+    if let Some(b) = bass_interval {
+        result.push('/');
+        result.push_str(spell_in(key, pitch_class(interval as i32 + b), flat));
+    } else if let Some(bass) = bass_degree {
+        let (b, _, rest) = take_degree(bass, key, false)?;
+        if !rest.is_empty() {
+            return None;
+        }
+        result.push('/');
+        result.push_str(spell_in(key, b, flat || bass.starts_with('b')));
+    }
+    Some(result)
+}
+
+/// A sheet rendered in numbers
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NumberedSheet {
+    pub key: String,
+    pub style: NumberStyle,
+    pub sheet: FetchedChordSheet,
+}
+
+/// Convert every chord of a sheet to numbers; unparseable chords are kept as-is
+pub fn sheet_to_numbers(sheet: &FetchedChordSheet, key: &Key, style: NumberStyle) -> NumberedSheet {
+    let mut result = sheet.clone();
+    for chord in result
+        .sections
+        .iter_mut()
+        .flat_map(|s| &mut s.lines)
+        .flat_map(|l| &mut l.chords)
+    {
+        if let Some(parsed) = Chord::parse(&chord.chord) {
+            chord.chord = to_number(&parsed, key, style);
+        }
+    }
+    NumberedSheet {
+        key: key.to_string(),
+        style,
+        sheet: result,
+    }
+}
+
+/// Convert a numbered sheet back into letter chords in any key
+pub fn sheet_from_numbers(
+    sheet: &FetchedChordSheet,
+    key: &Key,
+    style: NumberStyle,
+) -> FetchedChordSheet {
+    let mut result = sheet.clone();
+    for chord in result
+        .sections
+        .iter_mut()
+        .flat_map(|s| &mut s.lines)
+        .flat_map(|l| &mut l.chords)
+    {
+        if let Some(letter) = from_number(&chord.chord, key, style) {
+            chord.chord = letter;
+        }
+    }
+    result.key = Some(key.to_string());
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn n(chord: &str, key: &str) -> String {
+        to_number(
+            &Chord::parse(chord).unwrap(),
+            &Key::parse(key).unwrap(),
+            NumberStyle::Nashville,
+        )
+    }
+
+    fn r(chord: &str, key: &str) -> String {
+        to_number(
+            &Chord::parse(chord).unwrap(),
+            &Key::parse(key).unwrap(),
+            NumberStyle::Roman,
+        )
+    }
+
+    #[test]
+    fn test_nashville_diatonic() {
+        assert_eq!(n("C", "C"), "1");
+        assert_eq!(n("F", "C"), "4");
+        assert_eq!(n("G7", "C"), "57");
+        assert_eq!(n("Am7", "C"), "6m7");
+        assert_eq!(n("F#m", "D"), "3m");
+    }
+
+    #[test]
+    fn test_nashville_borrowed_and_slash() {
+        assert_eq!(n("Bb", "C"), "b7");
+        assert_eq!(n("Ab", "C"), "b6");
+        assert_eq!(n("Fm", "C"), "4m");
+        assert_eq!(n("F#m7-5", "C"), "#4m7-5");
+        assert_eq!(n("C/E", "C"), "1/3");
+        assert_eq!(n("D/F#", "G"), "5/7");
+    }
+
+    #[test]
+    fn test_roman_diatonic() {
+        assert_eq!(r("C", "C"), "I");
+        assert_eq!(r("Dm", "C"), "ii");
+        assert_eq!(r("Am", "C"), "vi");
+        assert_eq!(r("Bdim", "C"), "vii°");
+        assert_eq!(r("G7", "C"), "V7");
+        assert_eq!(r("FM7", "C"), "IVmaj7");
+        assert_eq!(r("Bm7-5", "C"), "viiø7");
+    }
+
+    #[test]
+    fn test_roman_borrowed() {
+        assert_eq!(r("Bb", "C"), "bVII");
+        assert_eq!(r("Ab", "C"), "bVI");
+        assert_eq!(r("Fm", "C"), "iv");
+        assert_eq!(r("Eb", "C"), "bIII");
+    }
+
+    #[test]
+    fn test_roman_inversions() {
+        assert_eq!(r("C/E", "C"), "I6");
+        assert_eq!(r("C/G", "C"), "I64");
+        assert_eq!(r("G7/B", "C"), "V65");
+        assert_eq!(r("G7/D", "C"), "V43");
+        assert_eq!(r("G7/F", "C"), "V42");
+        assert_eq!(r("C/D", "C"), "I/2");
+        assert_eq!(r("C6", "C"), "Iadd6");
+    }
+
+    #[test]
+    fn test_roman_secondary_dominants() {
+        assert_eq!(r("D7", "C"), "V7/V");
+        assert_eq!(r("D", "C"), "V/V");
+        assert_eq!(r("E7", "C"), "V7/vi");
+        assert_eq!(r("A7", "C"), "V7/ii");
+        assert_eq!(r("C7", "C"), "V7/IV");
+        // Diatonic dominant is not secondary
+        assert_eq!(r("G", "C"), "V");
+    }
+
+    #[test]
+    fn test_minor_key() {
+        assert_eq!(r("Am", "Am"), "i");
+        assert_eq!(r("C", "Am"), "III");
+        assert_eq!(r("E7", "Am"), "V7");
+        assert_eq!(n("G", "Am"), "7");
+        assert_eq!(n("E7", "Am"), "57");
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let chords = [
+            "C", "Dm7", "Em", "F", "G7", "Am", "Bm7-5", "Bb", "Fm", "C/E", "G7/B", "G/D", "D7",
+            "E7", "Gsus4", "Cadd9", "FM7", "C6", "C/D",
+        ];
+        for style in [NumberStyle::Nashville, NumberStyle::Roman] {
+            let key = Key::parse("C").unwrap();
+            for chord in chords {
+                let number = to_number(&Chord::parse(chord).unwrap(), &key, style);
+                let back = from_number(&number, &key, style).unwrap();
+                let expected = Chord::parse(chord).unwrap();
+                let actual = Chord::parse(&back).unwrap();
+                assert_eq!(
+                    (actual.root, actual.bass, actual.tones()),
+                    (expected.root, expected.bass, expected.tones()),
+                    "{:?} {} -> {} -> {}",
+                    style,
+                    chord,
+                    number,
+                    back
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_from_numbers_in_other_key() {
+        let key = Key::parse("Eb").unwrap();
+        assert_eq!(
+            from_number("4", &key, NumberStyle::Nashville).unwrap(),
+            "Ab"
+        );
+        assert_eq!(
+            from_number("6m7", &key, NumberStyle::Nashville).unwrap(),
+            "Cm7"
+        );
+        assert_eq!(
+            from_number("5/7", &key, NumberStyle::Nashville).unwrap(),
+            "Bb/D"
+        );
+        assert_eq!(from_number("V7/V", &key, NumberStyle::Roman).unwrap(), "F7");
+        assert_eq!(from_number("ii", &key, NumberStyle::Roman).unwrap(), "Fm");
+    }
+}
@@ -0,0 +1,355 @@
+//! Chord voicing search on a fretted instrument
+//!
+//! Voicings are found by scanning 4-fret windows up the neck and keeping
+//! every combination that covers the chord's required tones with the right
+//! bass note. Fingers, barre and a difficulty score are derived from the
+//! fret pattern.
+
+use super::chord::Chord;
+use super::instrument::{Instrument, InstrumentFamily};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// Frets a hand can cover without shifting (window = base..base+SPAN)
+const MAX_SPAN: u8 = 3;
+
+/// Highest window start considered for chord shapes
+const MAX_BASE_FRET: u8 = 12;
+
+/// Coarse difficulty, matching the frontend's `ChordDifficulty`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VoicingDifficulty {
+    Easy,
+    Medium,
+    Hard,
+}
+
+/// One way to play a chord
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Voicing {
+    /// Fret per string, 1st string first; None = muted, 0 = open
+    pub frets: Vec<Option<u8>>,
+    /// Lowest fretted fret (1 when only open strings are used)
+    pub base_fret: u8,
+    pub barre_at: Option<u8>,
+    /// Fretting fingers needed (a barre counts as one)
+    pub fingers: u8,
+    pub difficulty: VoicingDifficulty,
+    /// Relative effort; lower is easier
+    pub score: u32,
+}
+
+impl Voicing {
+    /// Build a voicing from a fret pattern; None if no hand can play it
+    pub fn from_frets(frets: Vec<Option<u8>>) -> Option<Self> {
+        let fretted: Vec<(usize, u8)> = frets
+            .iter()
+            .enumerate()
+            .filter_map(|(i, f)| f.filter(|&f| f > 0).map(|f| (i, f)))
+            .collect();
+
+        let min_fret = fretted.iter().map(|&(_, f)| f).min().unwrap_or(0);
+        let max_fret = fretted.iter().map(|&(_, f)| f).max().unwrap_or(0);
+        let span = max_fret - min_fret;
+        if span > MAX_SPAN {
+            return None;
+        }
+
+        let (fingers, barre_at) = if fretted.len() <= 4 {
+            (fretted.len() as u8, None)
+        } else {
+            // Barre the lowest fret across the strings it appears on
+            let at_min: Vec<usize> = fretted
+                .iter()
+                .filter(|&&(_, f)| f == min_fret)
+                .map(|&(i, _)| i)
+                .collect();
+            let first = *at_min.first()?;
+            let last = *at_min.last()?;
+            if frets[first..=last].contains(&Some(0)) {
+                return None;
+            }
+            let above: Vec<(usize, u8)> = fretted
+                .iter()
+                .copied()
+                .filter(|&(_, f)| f > min_fret)
+                .collect();
+            (1 + fingers_for(&above), Some(min_fret))
+        };
+        if fingers > 4 {
+            return None;
+        }
+
+        let muted = frets.iter().filter(|f| f.is_none()).count() as u32;
+        let open = frets.iter().filter(|f| **f == Some(0)).count() as u32;
+        let position = min_fret.saturating_sub(1) as u32;
+
+        let mut score = fingers as u32 * 10 + span as u32 * 5 + position * 2 + muted * 6;
+        if barre_at.is_some() {
+            score += 30;
+        }
+        score = score.saturating_sub(open * 2);
+
+        let difficulty = match barre_at {
+            Some(_) if fingers >= 4 => VoicingDifficulty::Hard,
+            Some(_) => VoicingDifficulty::Medium,
+            None if fingers <= 3 && max_fret <= 4 => VoicingDifficulty::Easy,
+            None => VoicingDifficulty::Medium,
+        };
+
+        Some(Self {
+            frets,
+            base_fret: min_fret.max(1),
+            barre_at,
+            fingers,
+            difficulty,
+            score,
+        })
+    }
+
+    pub fn is_barre(&self) -> bool {
+        self.barre_at.is_some()
+    }
+
+    /// Open-position shape: rings at least one open string and stays within the first 4 frets
+    pub fn is_open(&self) -> bool {
+        self.frets.contains(&Some(0)) && self.frets.iter().flatten().all(|&f| f <= 4)
+    }
+}
+
+/// Fingers for notes above a barre; 3+ adjacent strings on one fret take a single finger
+fn fingers_for(notes: &[(usize, u8)]) -> u8 {
+    let mut fingers = 0u8;
+    let mut i = 0;
+    while i < notes.len() {
+        let (string, fret) = notes[i];
+        let mut run = 1;
+        while i + run < notes.len() && notes[i + run].1 == fret && notes[i + run].0 == string + run
+        {
+            run += 1;
+        }
+        fingers += if run >= 3 { 1 } else { run as u8 };
+        i += run;
+    }
+    fingers
+}
+
+/// Find playable voicings for a chord, easiest first
+pub fn find_voicings(chord: &Chord, instrument: &Instrument, limit: usize) -> Vec<Voicing> {
+    if instrument.root_only {
+        return find_bass_positions(chord.bass_note(), instrument, limit);
+    }
+
+    let string_count = instrument.strings.len();
+    let mut required: Vec<u8> = Vec::new();
+    let mut allowed: u16 = 0;
+    for tone in chord.tones() {
+        let pc = (chord.root + tone.interval) % 12;
+        allowed |= 1 << pc;
+        if tone.required {
+            required.push(pc);
+        }
+    }
+    // Drop the least essential tones when the chord has more notes than strings
+    required.truncate(string_count);
+    let bass = chord.bass_note();
+    allowed |= 1 << bass;
+
+    // Re-entrant ukulele tuning has no fixed lowest string
+    let check_bass = instrument.family != InstrumentFamily::Ukulele;
+    let min_sounding = string_count.saturating_sub(instrument.max_muted()).max(1);
+
+    let mut seen: HashSet<Vec<Option<u8>>> = HashSet::new();
+    let mut voicings: Vec<Voicing> = Vec::new();
+    let max_base = MAX_BASE_FRET.min(instrument.fret_count.saturating_sub(MAX_SPAN));
+
+    for base in 1..=max_base {
+        let candidates: Vec<Vec<Option<u8>>> = instrument
+            .strings
+            .iter()
+            .map(|&open| {
+                let mut options: Vec<Option<u8>> = Vec::new();
+                if allowed & (1 << (open % 12)) != 0 {
+                    options.push(Some(0));
+                }
+                for fret in base..=base + MAX_SPAN {
+                    if allowed & (1 << ((open + fret) % 12)) != 0 {
+                        options.push(Some(fret));
+                    }
+                }
+                options.push(None);
+                options
+            })
+            .collect();
+
+        let mut current: Vec<Option<u8>> = vec![None; string_count];
+        search(
+            instrument,
+            &candidates,
+            string_count,
+            &mut current,
+            &mut |frets| {
+                let sounding: Vec<(u8, u8)> = frets
+                    .iter()
+                    .zip(&instrument.strings)
+                    .filter_map(|(f, &open)| f.map(|f| (open + f, (open + f) % 12)))
+                    .collect();
+                if sounding.len() < min_sounding {
+                    return;
+                }
+                if !required
+                    .iter()
+                    .all(|pc| sounding.iter().any(|&(_, s)| s == *pc))
+                {
+                    return;
+                }
+                if check_bass {
+                    let lowest = sounding.iter().min_by_key(|&&(pitch, _)| pitch);
+                    if lowest.map(|&(_, pc)| pc) != Some(bass) {
+                        return;
+                    }
+                }
+                if seen.insert(frets.to_vec()) {
+                    if let Some(voicing) = Voicing::from_frets(frets.to_vec()) {
+                        voicings.push(voicing);
+                    }
+                }
+            },
+        );
+    }
+
+    voicings.sort_by(|a, b| a.score.cmp(&b.score).then(a.base_fret.cmp(&b.base_fret)));
+    voicings.truncate(limit);
+    voicings
+}
+
+/// Depth-first over strings from the lowest-pitched course upwards.
+/// Mutes are only allowed contiguously from the bass side.
+fn search(
+    instrument: &Instrument,
+    candidates: &[Vec<Option<u8>>],
+    remaining: usize,
+    current: &mut Vec<Option<u8>>,
+    emit: &mut dyn FnMut(&[Option<u8>]),
+) {
+    if remaining == 0 {
+        emit(current);
+        return;
+    }
+    let index = remaining - 1;
+    let string_count = candidates.len();
+    let muted_so_far = string_count - remaining;
+    let bass_side_all_muted = current[remaining..].iter().all(|f| f.is_none());
+
+    for option in &candidates[index] {
+        if option.is_none() && !(bass_side_all_muted && muted_so_far < instrument.max_muted()) {
+            continue;
+        }
+        current[index] = *option;
+        search(instrument, candidates, remaining - 1, current, emit);
+    }
+    current[index] = None;
+}
+
+/// Bass: every position of the note within the first 12 frets, lowest first
+fn find_bass_positions(note: u8, instrument: &Instrument, limit: usize) -> Vec<Voicing> {
+    let mut positions: Vec<(u8, usize)> = Vec::new();
+    for (index, &open) in instrument.strings.iter().enumerate() {
+        for fret in 0..=instrument.fret_count.min(12) {
+            if (open + fret) % 12 == note {
+                positions.push((open + fret, index));
+            }
+        }
+    }
+    positions.sort();
+
+    positions
+        .into_iter()
+        .filter_map(|(pitch, index)| {
+            let open = instrument.strings[index];
+            let mut frets = vec![None; instrument.strings.len()];
+            frets[index] = Some(pitch - open);
+            Voicing::from_frets(frets)
+        })
+        .take(limit)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn voicings(name: &str, instrument: &Instrument) -> Vec<Voicing> {
+        find_voicings(&Chord::parse(name).unwrap(), instrument, 5)
+    }
+
+    #[test]
+    fn test_open_c_is_easiest() {
+        let result = voicings("C", &Instrument::standard_guitar());
+        assert_eq!(
+            result[0].frets,
+            vec![Some(0), Some(1), Some(0), Some(2), Some(3), None]
+        );
+        assert_eq!(result[0].difficulty, VoicingDifficulty::Easy);
+        assert!(result[0].is_open());
+        assert!(!result[0].is_barre());
+    }
+
+    #[test]
+    fn test_f_requires_barre() {
+        let chord = Chord::parse("F").unwrap();
+        let result = find_voicings(&chord, &Instrument::standard_guitar(), 20);
+        assert!(!result.is_empty());
+        let full = result
+            .iter()
+            .find(|v| v.frets.iter().all(|f| f.is_some()))
+            .expect("full barre voicing");
+        assert_eq!(full.barre_at, Some(1));
+    }
+
+    #[test]
+    fn test_slash_chord_bass() {
+        let guitar = Instrument::standard_guitar();
+        let chord = Chord::parse("D/F#").unwrap();
+        for voicing in find_voicings(&chord, &guitar, 10) {
+            let lowest = voicing
+                .frets
+                .iter()
+                .zip(&guitar.strings)
+                .filter_map(|(f, open)| f.map(|f| open + f))
+                .min()
+                .unwrap();
+            assert_eq!(lowest % 12, 6);
+        }
+    }
+
+    #[test]
+    fn test_drop_d_uses_low_string() {
+        let drop_d = Instrument::by_id("drop-d").unwrap();
+        let result = voicings("D", &drop_d);
+        assert_eq!(result[0].frets[5], Some(0));
+    }
+
+    #[test]
+    fn test_ukulele_c() {
+        let uke = Instrument::by_id("ukulele").unwrap();
+        let result = voicings("C", &uke);
+        assert_eq!(result[0].frets, vec![Some(3), Some(0), Some(0), Some(0)]);
+    }
+
+    #[test]
+    fn test_bass_root_only() {
+        let bass = Instrument::by_id("bass").unwrap();
+        let result = voicings("Am7/G", &bass);
+        assert_eq!(result[0].frets, vec![None, None, None, Some(3)]);
+    }
+
+    #[test]
+    fn test_fingers_for_ring_barre() {
+        // A-shape: three strings on the same fret take one finger
+        assert_eq!(fingers_for(&[(1, 5), (2, 5), (3, 5)]), 1);
+        // E-shape: two strings on one fret still need two fingers
+        assert_eq!(fingers_for(&[(2, 2), (3, 3), (4, 3)]), 3);
+    }
+}
@@ -157,15 +157,19 @@ export function AddSongModal({ isOpen, onClose, onSave, onSaveAndEdit }: AddSong
 
         const songInput: CreateSongInput = {
           title: sheet.title || '無題',
+          subtitle: sheet.subtitle || undefined,
+          tieIn: sheet.tie_in || undefined,
           artistName: sheet.artist || undefined,
           originalKey: sheet.key || undefined,
           capo: sheet.capo || 0,
           sourceUrl: sheet.source_url,
+          fetchedSections: sheet.sections,
           sections: sheet.sections.map((s) => ({
             name: s.name,
             lines: s.lines.map((l) => ({
               lyrics: l.lyrics,
               chords: l.chords.map((c) => ({ chord: c.chord, position: c.position })),
+              tab: l.tab ? l.tab.lines.join('\n') : undefined,
             })),
           })),
         };
@@ -238,10 +242,14 @@ export function AddSongModal({ isOpen, onClose, onSave, onSaveAndEdit }: AddSong
     if ((activeTab === 'search' || activeTab === 'url' || activeTab === 'chordwiki') && preview) {
       songInput = {
         title: preview.title || '無題',
+        subtitle: preview.subtitle || undefined,
+        tieIn: preview.tie_in || undefined,
         artistName: preview.artist || undefined,
         originalKey: preview.key || undefined,
         capo: preview.capo || 0,
+        difficulty: preview.source_difficulty ?? preview.difficulty?.level,
         sourceUrl: preview.source_url,
+        fetchedSections: preview.sections,
         sections: preview.sections.map((s): CreateSectionInput => ({
           name: s.name,
           lines: s.lines.map((l) => ({
@@ -250,6 +258,7 @@ export function AddSongModal({ isOpen, onClose, onSave, onSaveAndEdit }: AddSong
               chord: c.chord,
               position: c.position,
             })),
+            tab: l.tab ? l.tab.lines.join('\n') : undefined,
           })),
         })),
       };
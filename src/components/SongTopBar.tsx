@@ -279,6 +279,14 @@ export function SongTopBar({
               {songData.title}
             </h1>
           )}
+          {(songData.subtitle || songData.tieIn) && (
+            <p
+              className="text-text-muted truncate"
+              style={{ fontSize: `${12 * scale}px` }}
+            >
+              {[songData.subtitle, songData.tieIn].filter(Boolean).join(' / ')}
+            </p>
+          )}
           {artist && (
             <p
               className="text-text-secondary truncate"
@@ -164,6 +164,7 @@ function initializeEditState(song: SongWithDetails): {
       chords: line.chords,
       memo: undefined,
       measures: line.measures ?? 4,
+      tab: line.tab,
     })),
   }));
 
@@ -484,6 +485,7 @@ export const SongView = forwardRef<HTMLElement, SongViewProps>(function SongView
             lyrics: line.lyrics,
             chords: line.chords,
             measures: line.measures ?? 4,
+            tab: line.tab ?? null,
           })),
         })),
       };
@@ -1085,15 +1087,21 @@ export const SongView = forwardRef<HTMLElement, SongViewProps>(function SongView
                           }
                         }}
                       >
-                        <PlayableChordLine
-                          lyrics={line.lyrics}
-                          chords={line.chords}
-                          transpose={effectiveTranspose}
-                          viewMode={viewMode}
-                          onChordClick={onChordClick}
-                          onPlayFromLine={!isPlaying && onPlayFromLine ? () => onPlayFromLine(String(line.id)) : undefined}
-                          scale={scale}
-                        />
+                        {line.tab ? (
+                          <pre className="font-mono text-xs text-text-secondary overflow-x-auto leading-snug">
+                            {line.tab}
+                          </pre>
+                        ) : (
+                          <PlayableChordLine
+                            lyrics={line.lyrics}
+                            chords={line.chords}
+                            transpose={effectiveTranspose}
+                            viewMode={viewMode}
+                            onChordClick={onChordClick}
+                            onPlayFromLine={!isPlaying && onPlayFromLine ? () => onPlayFromLine(String(line.id)) : undefined}
+                            scale={scale}
+                          />
+                        )}
                       </div>
                     ))}
                   </div>
@@ -16,6 +16,7 @@ interface EditableLine {
   chords: ExtendedChordPosition[];
   memo?: string;  // 行レベルのメモ/注釈
   measures?: number;  // 小節数（デフォルト: 4）
+  tab?: string | null;  // タブ譜の行（編集せずそのまま保存する）
 }
 
 // Drag state for chord position adjustment (mouse-based for smooth sliding)
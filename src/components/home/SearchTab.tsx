@@ -138,15 +138,20 @@ export function SearchTab() {
 
         const songInput: CreateSongInput = {
           title: sheet.title || '無題',
+          subtitle: sheet.subtitle || undefined,
+          tieIn: sheet.tie_in || undefined,
           artistName: sheet.artist || undefined,
           originalKey: sheet.key || undefined,
           capo: sheet.capo || 0,
+          difficulty: sheet.source_difficulty ?? sheet.difficulty?.level,
           sourceUrl: sheet.source_url,
+          fetchedSections: sheet.sections,
           sections: sheet.sections.map((s) => ({
             name: s.name,
             lines: s.lines.map((l) => ({
               lyrics: l.lyrics,
               chords: l.chords.map((c) => ({ chord: c.chord, position: c.position })),
+              tab: l.tab ? l.tab.lines.join('\n') : undefined,
             })),
           })),
         };
@@ -171,10 +176,14 @@ export function SearchTab() {
 
     const songInput: CreateSongInput = {
       title: preview.title || '無題',
+      subtitle: preview.subtitle || undefined,
+      tieIn: preview.tie_in || undefined,
       artistName: preview.artist || undefined,
       originalKey: preview.key || undefined,
       capo: preview.capo || 0,
+      difficulty: preview.source_difficulty ?? preview.difficulty?.level,
       sourceUrl: preview.source_url,
+      fetchedSections: preview.sections,
       sections: preview.sections.map((s): CreateSectionInput => ({
         name: s.name,
         lines: s.lines.map((l) => ({
@@ -183,6 +192,7 @@ export function SearchTab() {
             chord: c.chord,
             position: c.position,
           })),
+          tab: l.tab ? l.tab.lines.join('\n') : undefined,
         })),
       })),
     };
@@ -175,7 +175,7 @@ export { isSupabaseEnabled } from '@/lib/supabase';
  * These are synchronous and don't depend on backend
  */
 export function isSupportedUrl(url: string): boolean {
-  const supportedDomains = ['ufret.jp', 'j-total.net', 'gakufu.gakki.me'];
+  const supportedDomains = ['ufret.jp', 'j-total.net', 'gakufu.gakki.me', 'ultimate-guitar.com'];
   return supportedDomains.some((domain) => url.includes(domain));
 }
 
@@ -188,5 +188,6 @@ export function getSiteName(url: string): string {
   if (url.includes('chordwiki.org')) return 'ChordWiki';
   if (url.includes('j-total.net')) return 'J-Total';
   if (url.includes('gakufu.gakki.me')) return '楽器.me';
+  if (url.includes('ultimate-guitar.com')) return 'Ultimate Guitar';
   return 'Unknown';
 }
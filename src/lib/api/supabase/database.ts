@@ -125,6 +125,7 @@ export async function getSongById(id: UUID): Promise<SongWithDetails | null> {
         chords: lineRow.chords_json as ExtendedChordPosition[],
         orderIndex: lineRow.order_index,
         measures: lineRow.measures,
+        tab: lineRow.tab ?? null,
       }));
 
     return { section, lines };
@@ -162,6 +163,8 @@ export async function getSongById(id: UUID): Promise<SongWithDetails | null> {
   const song: Song = {
     id: songData.id,
     title: songData.title,
+    subtitle: songData.subtitle ?? null,
+    tieIn: songData.tie_in ?? null,
     artistId: songData.artist_id,
     originalKey: songData.original_key,
     bpm: songData.bpm,
@@ -242,6 +245,8 @@ export async function saveSong(input: CreateSongInput): Promise<UUID> {
     id: songId,
     user_id: userId,
     title: input.title,
+    subtitle: input.subtitle ?? null,
+    tie_in: input.tieIn ?? null,
     artist_id: artistId,
     original_key: input.originalKey ?? null,
     bpm: input.bpm ?? null,
@@ -281,6 +286,7 @@ export async function saveSong(input: CreateSongInput): Promise<UUID> {
       chords_json: lineInput.chords,
       order_index: lIdx,
       measures: 4,
+      tab: lineInput.tab ?? null,
     }))
   );
 
@@ -375,6 +381,8 @@ export async function updateSong(id: UUID, input: UpdateSongInput): Promise<void
   };
 
   if (input.title !== undefined) updates.title = input.title;
+  if (input.subtitle !== undefined) updates.subtitle = input.subtitle || null;
+  if (input.tieIn !== undefined) updates.tie_in = input.tieIn || null;
   if (artistId !== undefined) updates.artist_id = artistId;
   if (input.originalKey !== undefined) updates.original_key = input.originalKey;
   if (input.bpm !== undefined) updates.bpm = input.bpm;
@@ -432,6 +440,7 @@ export async function updateSong(id: UUID, input: UpdateSongInput): Promise<void
         chords_json: lineInput.chords,
         order_index: lIdx,
         measures: lineInput.measures ?? 4,
+        tab: lineInput.tab ?? null,
       }))
     );
 
@@ -3,6 +3,7 @@
  * SQLite-based local database via Tauri SQL plugin
  */
 
+import { invoke } from '@tauri-apps/api/core';
 import Database from '@tauri-apps/plugin-sql';
 import type {
   UUID,
@@ -33,9 +34,12 @@ import type {
   Difficulty,
   Tuning,
   UpdateSongInput,
+  UpdateSectionInput,
+  RevisionSource,
   ChordPreference,
   ChordFingering,
 } from '@/types/database';
+import type { FetchedSection as SourceSection } from '@/lib/scraper';
 import type { DatabaseAPI } from '../types';
 
 // ============================================
@@ -54,6 +58,8 @@ CREATE TABLE IF NOT EXISTS artists (
 CREATE TABLE IF NOT EXISTS songs (
     id TEXT PRIMARY KEY,
     title TEXT NOT NULL,
+    subtitle TEXT,
+    tie_in TEXT,
     artist_id TEXT REFERENCES artists(id) ON DELETE SET NULL,
     original_key TEXT,
     bpm INTEGER,
@@ -103,6 +109,7 @@ CREATE TABLE IF NOT EXISTS playlists (
     id TEXT PRIMARY KEY,
     name TEXT NOT NULL,
     description TEXT,
+    performance_date TEXT DEFAULT NULL,
     created_at TEXT NOT NULL DEFAULT (datetime('now')),
     updated_at TEXT NOT NULL DEFAULT (datetime('now'))
 );
@@ -111,6 +118,12 @@ CREATE TABLE IF NOT EXISTS playlist_songs (
     playlist_id TEXT NOT NULL REFERENCES playlists(id) ON DELETE CASCADE,
     song_id TEXT NOT NULL REFERENCES songs(id) ON DELETE CASCADE,
     order_index INTEGER NOT NULL,
+    key_override TEXT DEFAULT NULL,
+    capo_override INTEGER DEFAULT NULL,
+    bpm_override INTEGER DEFAULT NULL,
+    notes TEXT DEFAULT NULL,
+    set_break TEXT DEFAULT NULL,
+    break_minutes INTEGER DEFAULT NULL,
     PRIMARY KEY (playlist_id, song_id)
 );
 
@@ -136,6 +149,32 @@ CREATE TABLE IF NOT EXISTS annotations (
 );
 
 CREATE INDEX IF NOT EXISTS idx_annotations_line ON annotations(line_id);
+
+CREATE TABLE IF NOT EXISTS practice_sessions (
+    id TEXT PRIMARY KEY,
+    song_id TEXT NOT NULL REFERENCES songs(id) ON DELETE CASCADE,
+    started_at TEXT NOT NULL,
+    ended_at TEXT NOT NULL,
+    tempo INTEGER DEFAULT NULL,
+    rating INTEGER DEFAULT NULL CHECK (rating BETWEEN 1 AND 5),
+    notes TEXT DEFAULT NULL,
+    created_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+CREATE INDEX IF NOT EXISTS idx_practice_sessions_song ON practice_sessions(song_id, started_at);
+
+CREATE TABLE IF NOT EXISTS sync_log (
+    table_name TEXT NOT NULL,
+    row_key TEXT NOT NULL,
+    deleted INTEGER NOT NULL DEFAULT 0,
+    changed_at TEXT NOT NULL,
+    PRIMARY KEY (table_name, row_key)
+);
+
+CREATE TABLE IF NOT EXISTS sync_state (
+    key TEXT PRIMARY KEY,
+    value TEXT NOT NULL
+);
 `;
 
 // Migration statements for adding new columns to existing databases
@@ -149,8 +188,54 @@ const MIGRATION_STATEMENTS = [
   'ALTER TABLE sections ADD COLUMN playback_speed_override REAL DEFAULT NULL',
   // Line measures (from 003_line_measures.sql)
   'ALTER TABLE lines ADD COLUMN measures INTEGER DEFAULT 4',
+  // Setlist overrides (from 005_setlist_overrides.sql)
+  'ALTER TABLE playlist_songs ADD COLUMN key_override TEXT DEFAULT NULL',
+  'ALTER TABLE playlist_songs ADD COLUMN capo_override INTEGER DEFAULT NULL',
+  'ALTER TABLE playlist_songs ADD COLUMN bpm_override INTEGER DEFAULT NULL',
+  'ALTER TABLE playlist_songs ADD COLUMN notes TEXT DEFAULT NULL',
+  'ALTER TABLE playlist_songs ADD COLUMN set_break TEXT DEFAULT NULL',
+  'ALTER TABLE playlist_songs ADD COLUMN break_minutes INTEGER DEFAULT NULL',
+  // Performance dates (from 006_practice_log.sql)
+  'ALTER TABLE playlists ADD COLUMN performance_date TEXT DEFAULT NULL',
+  // Tab lines (from 007_line_tabs.sql)
+  'ALTER TABLE lines ADD COLUMN tab TEXT DEFAULT NULL',
+  // Subtitles and tie-ins (from 008_song_subtitles.sql)
+  'ALTER TABLE songs ADD COLUMN subtitle TEXT DEFAULT NULL',
+  'ALTER TABLE songs ADD COLUMN tie_in TEXT DEFAULT NULL',
+];
+
+// Sync log triggers (from 004_sync_log.sql): tables synced with Supabase and their key columns
+const SYNC_TABLES: [string, string[]][] = [
+  ['artists', ['id']],
+  ['songs', ['id']],
+  ['sections', ['id']],
+  ['lines', ['id']],
+  ['tags', ['id']],
+  ['song_tags', ['song_id', 'tag_id']],
+  ['playlists', ['id']],
+  ['playlist_songs', ['playlist_id', 'song_id']],
+  ['chord_preferences', ['id']],
 ];
 
+// Trigger bodies contain ';', so these run one by one instead of through SCHEMA_SQL
+const SYNC_TRIGGER_STATEMENTS = SYNC_TABLES.flatMap(([table, keys]) =>
+  (
+    [
+      ['insert', 'NEW', 0],
+      ['update', 'NEW', 0],
+      ['delete', 'OLD', 1],
+    ] as const
+  ).map(([op, ref, deleted]) => {
+    const key = `json_object(${keys.map((k) => `'${k}', ${ref}.${k}`).join(', ')})`;
+    return `CREATE TRIGGER IF NOT EXISTS sync_${table}_${op} AFTER ${op.toUpperCase()} ON ${table}
+WHEN NOT EXISTS (SELECT 1 FROM sync_state WHERE key = 'applying_remote')
+BEGIN
+    INSERT OR REPLACE INTO sync_log (table_name, row_key, deleted, changed_at)
+    VALUES ('${table}', ${key}, ${deleted}, strftime('%Y-%m-%dT%H:%M:%fZ', 'now'));
+END`;
+  })
+);
+
 export async function initDatabase(): Promise<void> {
   db = await Database.load('sqlite:cat4g.db');
 
@@ -172,6 +257,10 @@ export async function initDatabase(): Promise<void> {
       // Column already exists - ignore
     }
   }
+
+  for (const statement of SYNC_TRIGGER_STATEMENTS) {
+    await db.execute(statement);
+  }
 }
 
 export async function getDatabase(): Promise<Database> {
@@ -193,6 +282,8 @@ function toSong(row: SongRow): Song {
   return {
     id: row.id,
     title: row.title,
+    subtitle: row.subtitle ?? null,
+    tieIn: row.tie_in ?? null,
     artistId: row.artist_id,
     originalKey: row.original_key,
     bpm: row.bpm,
@@ -239,6 +330,7 @@ function toLine(row: LineRow): Line {
     chords: JSON.parse(row.chords_json) as ExtendedChordPosition[],
     orderIndex: row.order_index,
     measures: row.measures ?? 4,
+    tab: row.tab ?? null,
   };
 }
 
@@ -387,6 +479,41 @@ export async function searchSongs(query: string): Promise<SongListItem[]> {
   }));
 }
 
+// ============================================
+// Revisions (kept by the Rust backend)
+// ============================================
+
+/**
+ * Record a song's sections in its revision history. The history is kept
+ * beside the library, so a failure is logged rather than failing the save.
+ */
+async function saveRevision(
+  songId: UUID,
+  sections: SourceSection[],
+  source: RevisionSource
+): Promise<void> {
+  try {
+    await invoke('save_revision', { songId, sections, source });
+  } catch (e) {
+    console.error('Failed to save revision:', e);
+  }
+}
+
+/** Edited sections in the shape the backend keeps them */
+function toSourceSections(sections: UpdateSectionInput[]): SourceSection[] {
+  return sections.map((section) => ({
+    name: section.name,
+    repeat_count: section.repeatCount,
+    bpm_override: section.bpmOverride,
+    lines: section.lines.map((line) => ({
+      lyrics: line.lyrics,
+      chords: line.chords.map(({ chord, position }) => ({ chord, position })),
+      measures: line.measures,
+      tab: line.tab ? { lines: line.tab.split('\n'), notes: [] } : null,
+    })),
+  }));
+}
+
 // ============================================
 // Songs - Write Operations
 // ============================================
@@ -419,11 +546,13 @@ export async function saveSong(input: CreateSongInput): Promise<UUID> {
 
     // Insert song
     await database.execute(
-      `INSERT INTO songs (id, title, artist_id, original_key, bpm, time_signature, capo, difficulty, source_url, notes, created_at, updated_at)
-       VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)`,
+      `INSERT INTO songs (id, title, subtitle, tie_in, artist_id, original_key, bpm, time_signature, capo, difficulty, source_url, notes, created_at, updated_at)
+       VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)`,
       [
         songId,
         input.title,
+        input.subtitle ?? null,
+        input.tieIn ?? null,
         artistId,
         input.originalKey ?? null,
         input.bpm ?? null,
@@ -452,8 +581,8 @@ export async function saveSong(input: CreateSongInput): Promise<UUID> {
         const lineId = generateUUID();
 
         await database.execute(
-          'INSERT INTO lines (id, section_id, lyrics, chords_json, order_index, measures) VALUES (?, ?, ?, ?, ?, ?)',
-          [lineId, sectionId, lineInput.lyrics, JSON.stringify(lineInput.chords), lIdx, 4]
+          'INSERT INTO lines (id, section_id, lyrics, chords_json, order_index, measures, tab) VALUES (?, ?, ?, ?, ?, ?, ?)',
+          [lineId, sectionId, lineInput.lyrics, JSON.stringify(lineInput.chords), lIdx, 4, lineInput.tab ?? null]
         );
       }
     }
@@ -474,6 +603,11 @@ export async function saveSong(input: CreateSongInput): Promise<UUID> {
     throw e;
   }
 
+  // The sheet as fetched is what a later re-fetch merges against
+  if (input.fetchedSections) {
+    await saveRevision(songId, input.fetchedSections, 'import');
+  }
+
   return songId;
 }
 
@@ -533,6 +667,14 @@ export async function updateSong(id: UUID, input: UpdateSongInput): Promise<void
       fields.push('title = ?');
       values.push(input.title);
     }
+    if (input.subtitle !== undefined) {
+      fields.push('subtitle = ?');
+      values.push(input.subtitle || null);
+    }
+    if (input.tieIn !== undefined) {
+      fields.push('tie_in = ?');
+      values.push(input.tieIn || null);
+    }
     if (artistIdUpdated) {
       fields.push('artist_id = ?');
       values.push(artistId);
@@ -617,8 +759,16 @@ export async function updateSong(id: UUID, input: UpdateSongInput): Promise<void
           const lineId = lineInput.id ?? generateUUID();
 
           await database.execute(
-            'INSERT INTO lines (id, section_id, lyrics, chords_json, order_index, measures) VALUES (?, ?, ?, ?, ?, ?)',
-            [lineId, sectionId, lineInput.lyrics, JSON.stringify(lineInput.chords), lIdx, lineInput.measures ?? 4]
+            'INSERT INTO lines (id, section_id, lyrics, chords_json, order_index, measures, tab) VALUES (?, ?, ?, ?, ?, ?, ?)',
+            [
+              lineId,
+              sectionId,
+              lineInput.lyrics,
+              JSON.stringify(lineInput.chords),
+              lIdx,
+              lineInput.measures ?? 4,
+              lineInput.tab ?? null,
+            ]
           );
         }
       }
@@ -629,6 +779,10 @@ export async function updateSong(id: UUID, input: UpdateSongInput): Promise<void
     await database.execute('ROLLBACK');
     throw e;
   }
+
+  if (input.sections !== undefined) {
+    await saveRevision(id, toSourceSections(input.sections), input.revisionSource ?? 'manual_edit');
+  }
 }
 
 // ============================================
@@ -819,6 +973,11 @@ export async function getArtists(): Promise<Artist[]> {
   return rows.map(toArtist);
 }
 
+/** Names of the library's artists, for matching imported artist names */
+export async function getArtistNames(): Promise<string[]> {
+  return (await getArtists()).map((artist) => artist.name);
+}
+
 export async function getSongsByArtist(artistId: UUID): Promise<SongListItem[]> {
   const database = await getDatabase();
   const rows = await database.select<
@@ -7,10 +7,26 @@ import { invoke } from '@tauri-apps/api/core';
 import type { ScraperAPI } from '../types';
 import type {
   FetchedChordSheet,
+  FileImportReport,
+  ImportedSong,
+  SongFile,
   SupportedSite,
   UfretSearchResponse,
   UfretSearchResult,
 } from '@/lib/scraper';
+import { getArtistNames } from './database';
+
+/**
+ * Artist names already in the library, so the backend spells a fetched
+ * artist the way the library does; empty if the database is unavailable
+ */
+export async function knownArtists(): Promise<string[]> {
+  try {
+    return await getArtistNames();
+  } catch {
+    return [];
+  }
+}
 
 /**
  * Fetch and parse chord sheet from URL
@@ -19,7 +35,10 @@ import type {
  * @throws Error if URL is unsupported or parsing fails
  */
 export async function fetchChordSheet(url: string): Promise<FetchedChordSheet> {
-  return await invoke<FetchedChordSheet>('fetch_chord_sheet', { url });
+  return await invoke<FetchedChordSheet>('fetch_chord_sheet', {
+    url,
+    knownArtists: await knownArtists(),
+  });
 }
 
 /**
@@ -32,7 +51,11 @@ export async function parseChordSheetHtml(
   url: string,
   html: string
 ): Promise<FetchedChordSheet> {
-  return await invoke<FetchedChordSheet>('parse_chord_sheet', { url, html });
+  return await invoke<FetchedChordSheet>('parse_chord_sheet', {
+    url,
+    html,
+    knownArtists: await knownArtists(),
+  });
 }
 
 /**
@@ -68,6 +91,43 @@ export async function fetchArtistSongs(
   throw new Error('fetchArtistSongs is not yet implemented in Tauri backend');
 }
 
+// ============================================
+// File and iReal Pro Imports (desktop only)
+// ============================================
+
+/**
+ * Import the songs in a Guitar Pro, OpenLyrics, OpenSong or SongbookPro file
+ * @param path - File path
+ * @returns Every song in the file, and the archive songs that could not be read
+ */
+export async function importFile(path: string): Promise<SongFile> {
+  return await invoke<SongFile>('import_file', { path, knownArtists: await knownArtists() });
+}
+
+/**
+ * Import many song files at once
+ * @param paths - File paths
+ * @returns Each file's songs or error
+ */
+export async function importFiles(paths: string[]): Promise<FileImportReport[]> {
+  return await invoke<FileImportReport[]>('import_files', {
+    paths,
+    knownArtists: await knownArtists(),
+  });
+}
+
+/**
+ * Decode the songs in an irealb:// or irealbook:// link
+ * @param url - iReal Pro link
+ * @returns Songs in the link
+ */
+export async function importIrealUrl(url: string): Promise<ImportedSong[]> {
+  return await invoke<ImportedSong[]>('import_ireal_url', {
+    url,
+    knownArtists: await knownArtists(),
+  });
+}
+
 // ============================================
 // Utility Functions (re-exported for convenience)
 // ============================================
@@ -79,7 +139,7 @@ export async function fetchArtistSongs(
  */
 export function isSupportedUrl(url: string): boolean {
   // ChordWiki is excluded due to Cloudflare protection - use manual HTML input instead
-  const supportedDomains = ['ufret.jp', 'j-total.net', 'gakufu.gakki.me'];
+  const supportedDomains = ['ufret.jp', 'j-total.net', 'gakufu.gakki.me', 'ultimate-guitar.com'];
   return supportedDomains.some((domain) => url.includes(domain));
 }
 
@@ -100,6 +160,7 @@ export function getSiteName(url: string): string {
   if (url.includes('chordwiki.org')) return 'ChordWiki';
   if (url.includes('j-total.net')) return 'J-Total';
   if (url.includes('gakufu.gakki.me')) return '楽器.me';
+  if (url.includes('ultimate-guitar.com')) return 'Ultimate Guitar';
   return 'Unknown';
 }
 
@@ -1,23 +1,71 @@
 import { invoke } from '@tauri-apps/api/core';
+import { knownArtists } from '@/lib/api/tauri/scraper';
 
 // Types matching Rust backend structures
 export interface FetchedChordSheet {
   title: string | null;
+  // タイトルから分離したサブタイトル・タイアップ情報
+  subtitle?: string | null;
+  tie_in?: string | null;
   artist: string | null;
   key: string | null;
   capo: number | null;
   sections: FetchedSection[];
   source_url: string;
+  difficulty?: DifficultyRating | null;
+  // 取り込み元サイトが示す難易度・チューニング（Ultimate Guitar など）
+  source_difficulty?: DifficultyRating['level'] | null;
+  tuning?: string | null;
+}
+
+// 取り込み時に算出される難易度 (1-5)
+export interface DifficultyRating {
+  score: number;
+  level: 'beginner' | 'intermediate' | 'advanced';
+  reasons: DifficultyReason[];
+}
+
+export interface DifficultyReason {
+  factor: 'distinct_chords' | 'barre_chords' | 'change_density' | 'extensions' | 'tab_sections';
+  points: number;
+  detail: string;
 }
 
 export interface FetchedSection {
   name: string;
   lines: FetchedLine[];
+  repeat_count?: number;
+  bpm_override?: number;
+  time_signature?: string;
+}
+
+export type TabTechnique =
+  | 'hammer_on'
+  | 'pull_off'
+  | 'bend'
+  | 'release'
+  | 'slide_up'
+  | 'slide_down';
+
+export interface TabNote {
+  system: number;
+  string: number;
+  column: number;
+  fret: number;
+  technique: TabTechnique | null;
+}
+
+export interface TabBlock {
+  lines: string[];
+  notes: TabNote[];
 }
 
 export interface FetchedLine {
   lyrics: string;
   chords: FetchedChord[];
+  measures?: number;
+  // ソロ・リフのタブ譜（歌詞行の間に置かれた位置のまま）
+  tab?: TabBlock | null;
 }
 
 export interface FetchedChord {
@@ -25,6 +73,27 @@ export interface FetchedChord {
   position: number;
 }
 
+// ファイル・iReal Pro リンクから取り込んだ曲
+export interface ImportedSong {
+  sheet: FetchedChordSheet;
+  bpm?: number | null;
+  time_signature?: string | null;
+  difficulty?: DifficultyRating | null;
+}
+
+// 1ファイル分の取り込み結果（skipped は読めなかった曲とその理由）
+export interface SongFile {
+  songs: ImportedSong[];
+  skipped: string[];
+}
+
+export interface FileImportReport {
+  path: string;
+  songs: ImportedSong[];
+  skipped: string[];
+  error?: string | null;
+}
+
 export interface SupportedSite {
   name: string;
   domain: string;
@@ -60,7 +129,10 @@ export interface UfretSearchResponse {
  * @throws Error if URL is unsupported or parsing fails
  */
 export async function fetchChordSheet(url: string): Promise<FetchedChordSheet> {
-  return await invoke<FetchedChordSheet>('fetch_chord_sheet', { url });
+  return await invoke<FetchedChordSheet>('fetch_chord_sheet', {
+    url,
+    knownArtists: await knownArtists(),
+  });
 }
 
 /**
@@ -73,7 +145,11 @@ export async function parseChordSheetHtml(
   url: string,
   html: string
 ): Promise<FetchedChordSheet> {
-  return await invoke<FetchedChordSheet>('parse_chord_sheet', { url, html });
+  return await invoke<FetchedChordSheet>('parse_chord_sheet', {
+    url,
+    html,
+    knownArtists: await knownArtists(),
+  });
 }
 
 /**
@@ -91,7 +167,7 @@ export async function getSupportedSites(): Promise<SupportedSite[]> {
  */
 export function isSupportedUrl(url: string): boolean {
   // ChordWiki is excluded due to Cloudflare protection - use manual HTML input instead
-  const supportedDomains = ['ufret.jp', 'j-total.net', 'gakufu.gakki.me'];
+  const supportedDomains = ['ufret.jp', 'j-total.net', 'gakufu.gakki.me', 'ultimate-guitar.com'];
   return supportedDomains.some((domain) => url.includes(domain));
 }
 
@@ -112,5 +188,6 @@ export function getSiteName(url: string): string {
   if (url.includes('chordwiki.org')) return 'ChordWiki';
   if (url.includes('j-total.net')) return 'J-Total';
   if (url.includes('gakufu.gakki.me')) return '楽器.me';
+  if (url.includes('ultimate-guitar.com')) return 'Ultimate Guitar';
   return 'Unknown';
 }
@@ -3,6 +3,8 @@
  * Based on spec: specs/02_database.md, specs/03_frontend.md
  */
 
+import type { FetchedSection as SourceSection } from '@/lib/scraper';
+
 // ============================================
 // Basic Types
 // ============================================
@@ -52,6 +54,8 @@ export interface Artist {
 export interface Song {
   id: UUID;
   title: string;
+  subtitle: string | null;  // "Live ver." など、タイトルから分離した補足
+  tieIn: string | null;     // タイアップ（ドラマ・映画・CM など）
   artistId: UUID | null;
   originalKey: string | null;
   bpm: number | null;
@@ -88,6 +92,7 @@ export interface Line {
   chords: ExtendedChordPosition[];
   orderIndex: number;
   measures: number;  // 小節数（デフォルト: 4）
+  tab: string | null;  // タブ譜の行（改行区切り）。タブ譜の行は歌詞・コードを持たない
 }
 
 /** タグ */
@@ -168,6 +173,8 @@ export interface PlaylistWithSongs {
 /** 曲作成入力 */
 export interface CreateSongInput {
   title: string;
+  subtitle?: string;
+  tieIn?: string;
   artistName?: string;
   originalKey?: string;
   bpm?: number;
@@ -177,6 +184,8 @@ export interface CreateSongInput {
   sourceUrl?: string;
   notes?: string;
   sections: CreateSectionInput[];
+  /** 取得・取り込みしたままのセクション（再取得時のマージの基準として保存する） */
+  fetchedSections?: SourceSection[];
   tagIds?: UUID[];
 }
 
@@ -191,6 +200,7 @@ export interface CreateSectionInput {
 export interface CreateLineInput {
   lyrics: string;
   chords: ChordPosition[];
+  tab?: string;
 }
 
 /** プレイリスト作成入力 */
@@ -269,6 +279,8 @@ export interface ArtistRow {
 export interface SongRow {
   id: string;
   title: string;
+  subtitle: string | null;
+  tie_in: string | null;
   artist_id: string | null;
   original_key: string | null;
   bpm: number | null;
@@ -305,6 +317,7 @@ export interface LineRow {
   chords_json: string;
   order_index: number;
   measures: number;
+  tab: string | null;
 }
 
 /** DB行: tags テーブル */
@@ -399,6 +412,8 @@ export interface ExtendedChordPosition extends ChordPosition {
 /** 曲更新用入力 */
 export interface UpdateSongInput {
   title?: string;
+  subtitle?: string;
+  tieIn?: string;
   artistName?: string;
   originalKey?: string;   // "調" はこのフィールドに対応（例: "C", "Am", "G"）
   bpm?: number;
@@ -410,8 +425,13 @@ export interface UpdateSongInput {
   difficulty?: Difficulty;
   notes?: string;
   sections?: UpdateSectionInput[];
+  /** sections を変更した操作（履歴に残す種別。省略時は手動編集） */
+  revisionSource?: RevisionSource;
 }
 
+/** 履歴（リビジョン）の種別 */
+export type RevisionSource = 'manual_edit' | 'refetch' | 'transform' | 'import' | 'restore';
+
 /** セクション更新用入力 */
 export interface UpdateSectionInput {
   id?: UUID;              // 既存セクションの場合
@@ -437,6 +457,8 @@ export interface UpdateLineInput {
   dynamicsOverride?: Dynamics;
   /** 小節数（デフォルト: 4） */
   measures?: number;
+  /** タブ譜の行（改行区切り） */
+  tab?: string | null;
 }
 
 /** 注釈（アノテーション） */
@@ -68,6 +68,8 @@ export interface Database {
           id: string;
           user_id: string;
           title: string;
+          subtitle: string | null;
+          tie_in: string | null;
           artist_id: string | null;
           original_key: string | null;
           bpm: number | null;
@@ -84,6 +86,8 @@ export interface Database {
           id?: string;
           user_id: string;
           title: string;
+          subtitle?: string | null;
+          tie_in?: string | null;
           artist_id?: string | null;
           original_key?: string | null;
           bpm?: number | null;
@@ -100,6 +104,8 @@ export interface Database {
           id?: string;
           user_id?: string;
           title?: string;
+          subtitle?: string | null;
+          tie_in?: string | null;
           artist_id?: string | null;
           original_key?: string | null;
           bpm?: number | null;
@@ -185,6 +191,7 @@ export interface Database {
           chords_json: ChordPositionJson[];
           order_index: number;
           measures: number;
+          tab: string | null;
         };
         Insert: {
           id?: string;
@@ -194,6 +201,7 @@ export interface Database {
           chords_json?: ChordPositionJson[];
           order_index: number;
           measures?: number;
+          tab?: string | null;
         };
         Update: {
           id?: string;
@@ -203,6 +211,7 @@ export interface Database {
           chords_json?: ChordPositionJson[];
           order_index?: number;
           measures?: number;
+          tab?: string | null;
         };
         Relationships: [
           {
@@ -0,0 +1,54 @@
+-- ============================================================
+-- Sync Columns
+-- Columns the desktop sync (src-tauri/src/sync) needs on every table:
+--   updated_at  when the row was changed, on whichever device (last-writer-wins)
+--   deleted_at  tombstone, so deletions reach the other devices
+--   synced_at   when the server received the row (pull cursor)
+-- ============================================================
+
+-- デスクトップ版にのみあった曲の設定列
+ALTER TABLE songs ADD COLUMN IF NOT EXISTS transpose INTEGER DEFAULT 0;
+ALTER TABLE songs ADD COLUMN IF NOT EXISTS playback_speed REAL DEFAULT 1.0;
+ALTER TABLE songs ADD COLUMN IF NOT EXISTS tuning TEXT DEFAULT 'standard';
+
+-- synced_at は常にサーバー時刻（プル時のカーソル）
+CREATE OR REPLACE FUNCTION update_synced_at_column()
+RETURNS TRIGGER AS $$
+BEGIN
+    NEW.synced_at = NOW();
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+-- updated_at は端末側の変更時刻を保つ。送られてこなかった更新だけサーバー時刻にする
+CREATE OR REPLACE FUNCTION update_updated_at_column()
+RETURNS TRIGGER AS $$
+BEGIN
+    IF NEW.updated_at IS NOT DISTINCT FROM OLD.updated_at THEN
+        NEW.updated_at = NOW();
+    END IF;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+DO $$
+DECLARE
+    t TEXT;
+BEGIN
+    FOREACH t IN ARRAY ARRAY[
+        'artists', 'songs', 'sections', 'lines', 'tags', 'song_tags',
+        'playlists', 'playlist_songs', 'chord_preferences'
+    ]
+    LOOP
+        EXECUTE format('ALTER TABLE %I ADD COLUMN IF NOT EXISTS updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()', t);
+        EXECUTE format('ALTER TABLE %I ADD COLUMN IF NOT EXISTS deleted_at TIMESTAMPTZ', t);
+        EXECUTE format('ALTER TABLE %I ADD COLUMN IF NOT EXISTS synced_at TIMESTAMPTZ NOT NULL DEFAULT NOW()', t);
+        EXECUTE format('CREATE INDEX IF NOT EXISTS idx_%s_synced ON %I(user_id, synced_at)', t, t);
+        EXECUTE format('DROP TRIGGER IF EXISTS %s_synced_at ON %I', t, t);
+        EXECUTE format(
+            'CREATE TRIGGER %s_synced_at BEFORE INSERT OR UPDATE ON %I FOR EACH ROW EXECUTE FUNCTION update_synced_at_column()',
+            t, t
+        );
+    END LOOP;
+END;
+$$;
@@ -0,0 +1,15 @@
+-- ============================================================
+-- Setlist Overrides
+-- Per-entry performance settings and set breaks on playlist_songs,
+-- matching the desktop schema (src-tauri/migrations/005_setlist_overrides.sql)
+-- ============================================================
+
+ALTER TABLE playlist_songs ADD COLUMN IF NOT EXISTS key_override TEXT DEFAULT NULL;
+ALTER TABLE playlist_songs ADD COLUMN IF NOT EXISTS capo_override INTEGER DEFAULT NULL;
+ALTER TABLE playlist_songs ADD COLUMN IF NOT EXISTS bpm_override INTEGER DEFAULT NULL;
+ALTER TABLE playlist_songs ADD COLUMN IF NOT EXISTS notes TEXT DEFAULT NULL;
+ALTER TABLE playlist_songs ADD COLUMN IF NOT EXISTS set_break TEXT DEFAULT NULL;
+ALTER TABLE playlist_songs ADD COLUMN IF NOT EXISTS break_minutes INTEGER DEFAULT NULL;
+
+COMMENT ON COLUMN playlist_songs.key_override IS '本番での演奏キー（null = 曲のキー）';
+COMMENT ON COLUMN playlist_songs.set_break IS 'この曲から始まるセットの名前';
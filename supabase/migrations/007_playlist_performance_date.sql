@@ -0,0 +1,9 @@
+-- ============================================================
+-- Playlist Performance Date
+-- Date a setlist is played, used by the desktop practice scheduler
+-- (src-tauri/migrations/006_practice_log.sql)
+-- ============================================================
+
+ALTER TABLE playlists ADD COLUMN IF NOT EXISTS performance_date DATE DEFAULT NULL;
+
+COMMENT ON COLUMN playlists.performance_date IS '本番の日付';
@@ -0,0 +1,9 @@
+-- ============================================================
+-- Line Tabs
+-- Tablature kept as its own line among the lyric lines,
+-- matching the desktop schema (src-tauri/migrations/007_line_tabs.sql)
+-- ============================================================
+
+ALTER TABLE lines ADD COLUMN IF NOT EXISTS tab TEXT DEFAULT NULL;
+
+COMMENT ON COLUMN lines.tab IS 'タブ譜の行（改行区切り）';
@@ -0,0 +1,11 @@
+-- ============================================================
+-- Song Subtitles
+-- Text split off a title by normalization ("Live ver.", tie-ins),
+-- matching the desktop schema (src-tauri/migrations/008_song_subtitles.sql)
+-- ============================================================
+
+ALTER TABLE songs ADD COLUMN IF NOT EXISTS subtitle TEXT DEFAULT NULL;
+ALTER TABLE songs ADD COLUMN IF NOT EXISTS tie_in TEXT DEFAULT NULL;
+
+COMMENT ON COLUMN songs.subtitle IS 'サブタイトル（"Live ver." など）';
+COMMENT ON COLUMN songs.tie_in IS 'タイアップ（ドラマ・映画・CM など）';